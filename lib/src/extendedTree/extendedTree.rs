@@ -1,18 +1,33 @@
 use crate::storage::numeric_encoder::StrHash;
+use crate::store::StorageError;
 
-use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::rc::{Rc};
-use std::cell::{RefCell};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, RwLock};
 
 use super::{MultiTreeNode};
 
-#[derive(Debug, Clone)]
+// 编码好之后只会被读取，可以放在 Arc 中由多个加载线程共享
+#[derive(Debug)]
 pub struct MultiTree{
     root: StrHash,
-    hash_str_node: RefCell<HashMap<StrHash, Rc<MultiTreeNode>>>,   // StrHash -> 节点
-    parent_way: RefCell<Vec<Vec<StrHash>>>,
-    hash_parent_by_str: RefCell<HashMap<StrHash, Vec<Vec<StrHash>>>>
+    hash_str_node: RwLock<HashMap<StrHash, Arc<MultiTreeNode>>>,   // StrHash -> 节点
+    parent_way: RwLock<Vec<Vec<StrHash>>>,
+    hash_parent_by_str: RwLock<HashMap<StrHash, Vec<Vec<StrHash>>>>,
+    gap: AtomicU32,   // 编码时每个节点区间末尾预留的空闲编号个数，供之后增量插入子节点
+}
+
+// 与之前派生的 Clone 相同：节点本身是共享的，只复制索引
+impl Clone for MultiTree {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root,
+            hash_str_node: RwLock::new(self.hash_str_node.read().unwrap().clone()),
+            parent_way: RwLock::new(self.parent_way.read().unwrap().clone()),
+            hash_parent_by_str: RwLock::new(self.hash_parent_by_str.read().unwrap().clone()),
+            gap: AtomicU32::new(self.gap.load(Ordering::Relaxed)),
+        }
+    }
 }
 
 impl MultiTree {
@@ -20,17 +35,18 @@ impl MultiTree {
         let root_strhash = StrHash::new(data);
 
         let mut hash = HashMap::new();
-        hash.insert(root_strhash, Rc::new(MultiTreeNode::new(data)));
+        hash.insert(root_strhash, Arc::new(MultiTreeNode::new(data)));
 
         Self {
             root: root_strhash,
-            hash_str_node: RefCell::new(hash),
-            hash_parent_by_str: RefCell::new(HashMap::new()),
-            parent_way: RefCell::new(Vec::new()),
+            hash_str_node: RwLock::new(hash),
+            hash_parent_by_str: RwLock::new(HashMap::new()),
+            parent_way: RwLock::new(Vec::new()),
+            gap: AtomicU32::new(0),
         }
     }
 
-    pub fn is_root(&self, other: Rc<MultiTreeNode>) -> bool {
+    pub fn is_root(&self, other: Arc<MultiTreeNode>) -> bool {
         if self.root == other.get_data() {
             true
         } else {
@@ -38,22 +54,26 @@ impl MultiTree {
         }
     }
 
-    pub fn get_root(&self) -> Rc<MultiTreeNode> {
-        Rc::clone(self.hash_str_node.borrow().get(&self.root).unwrap())
+    pub fn get_root(&self) -> Arc<MultiTreeNode> {
+        Arc::clone(self.hash_str_node.read().unwrap().get(&self.root).unwrap())
     }
 
     // 1、先判断父节点先前是否存在，若不存在，则父节点的父节点是root，将其添加进root的孩子中
     // 2、父节点添加子节点时，不会重复添加子节点
     // 3、如果子节点在root的子节点中，应将其从其中去掉；并且子节点也应去掉root父节点
     // 4、最后在由树维护的节点hash中插入父节点与子节点
+    // 会形成环的边（见 creates_cycle）不插入并返回 false，否则编码时的深度优先遍历不会结束
     pub fn insert(&self, child_str: &str, parent_str: &str) -> bool {
+        if self.creates_cycle(child_str, parent_str) {
+            return false;
+        }
         let if_parent_exist = self.if_exist(parent_str);   // 1
         let mut parent_contain_root = false;
 
         let child = self.construct_node(child_str);
         let parent = self.construct_node(parent_str);
 
-        if let true = parent.add_child(Rc::clone(&child)) {   // 2
+        if let true = parent.add_child(Arc::clone(&child)) {   // 2
             // 3.循环遍历子节点的父节点，如果有root，则在root中将该子节点删除，也要删除对应的root父节点
             for interval in &*(child.get_interval_nodes()) {   
                 if let Ok(parent) = (*interval).get_parent() {
@@ -69,14 +89,14 @@ impl MultiTree {
             }
 
             if !if_parent_exist {   // 1
-                self.get_root().add_child(Rc::clone(&parent));
-                parent.add_parent(Rc::clone(&self.get_root()));
+                self.get_root().add_child(Arc::clone(&parent));
+                parent.add_parent(Arc::clone(&self.get_root()));
             }
 
-            child.add_parent(Rc::clone(&parent));
+            child.add_parent(Arc::clone(&parent));
 
-            self.hash_str_node.borrow_mut().insert(parent.get_data(), Rc::clone(&parent));
-            self.hash_str_node.borrow_mut().insert(child.get_data(), Rc::clone(&child));
+            self.hash_str_node.write().unwrap().insert(parent.get_data(), Arc::clone(&parent));
+            self.hash_str_node.write().unwrap().insert(child.get_data(), Arc::clone(&child));
 
             return true;
         }
@@ -84,15 +104,41 @@ impl MultiTree {
         false
     }
 
+    // 插入 child -> parent 这条边是否会形成环：child 与 parent 相同，或者 child 已经是 parent 的祖先
+    // 沿着父节点向上查找，不需要树已经编码
+    pub fn creates_cycle(&self, child_str: &str, parent_str: &str) -> bool {
+        let child = StrHash::new(child_str);
+        let parent = match self.get_node_by_strhash(StrHash::new(parent_str)) {
+            Ok(node) => node,
+            Err(()) => return child_str == parent_str,
+        };
+        let mut visited = HashSet::new();
+        let mut stack = vec![parent];
+        while let Some(node) = stack.pop() {
+            if node.get_data() == child {
+                return true;
+            }
+            if !visited.insert(node.get_data()) {
+                continue;
+            }
+            for interval in node.get_interval_nodes().iter() {
+                if let Ok(parent) = interval.get_parent() {
+                    stack.push(parent);
+                }
+            }
+        }
+        false
+    }
+
     // 根据str获得其后代节点的数量
     pub fn count_childs_by_str(&self, node_str: &str) -> Result<u32, ()> {
-        if let Some(link_node) = self.hash_str_node.borrow().get(&StrHash::new(node_str)) {
+        if let Some(link_node) = self.hash_str_node.read().unwrap().get(&StrHash::new(node_str)) {
             if link_node.get_childs().len() == 0 {
                 return Ok(0u32);
             }
 
-            let mut stack: Vec<Rc<MultiTreeNode>> = Vec::new();
-            stack.push(Rc::clone(&link_node));
+            let mut stack: Vec<Arc<MultiTreeNode>> = Vec::new();
+            stack.push(Arc::clone(&link_node));
 
             let mut start = 0u32;
 
@@ -100,7 +146,7 @@ impl MultiTree {
                 let node = stack.pop().unwrap();
 
                 for child in node.get_childs().iter().rev(){
-                    stack.push(Rc::clone(child));
+                    stack.push(Arc::clone(child));
                 }
 
                 start = start + 1u32;
@@ -114,7 +160,7 @@ impl MultiTree {
 
     // 根据str获得其父节点个数
     pub fn count_parents_by_str(&self, node_str: &str) -> Result<usize, ()> {
-        match self.hash_str_node.borrow().get(&StrHash::new(node_str)) {
+        match self.hash_str_node.read().unwrap().get(&StrHash::new(node_str)) {
             Some(node) => {Ok(node.get_interval_nodes().len())},
             None => Err(())
         }
@@ -122,42 +168,185 @@ impl MultiTree {
 
     // 判断树里是否存在某个节点
     pub fn if_exist(&self, value: &str) -> bool {
-        self.hash_str_node.borrow().contains_key(&StrHash::new(value))
+        self.hash_str_node.read().unwrap().contains_key(&StrHash::new(value))
     }
 
     // 根据 strhash 获得节点
-    pub fn get_node_by_strhash(&self, strhash: StrHash) -> Result<Rc<MultiTreeNode>,()> {
-        match self.hash_str_node.borrow().get(&strhash) {
-            Some(node) => Ok(Rc::clone(node)),
+    pub fn get_node_by_strhash(&self, strhash: StrHash) -> Result<Arc<MultiTreeNode>,()> {
+        match self.hash_str_node.read().unwrap().get(&strhash) {
+            Some(node) => Ok(Arc::clone(node)),
             None => Err(())
         }
     }
 
-    // 对树进行编码
-    pub fn encode(&self) {
-        self.initial_root();
+    // 使用区间编码判断 ancestor 是否是 descendant 的（真）祖先，树需要已经编码
+    // 多继承节点下的子孙只保留最后一次遍历得到的区间，所以区间不被包含时还要沿着 descendant 的各个父节点继续判断
+    pub fn is_ancestor(&self, ancestor: &str, descendant: &str) -> bool {
+        let ancestor = match self.get_node_by_strhash(StrHash::new(ancestor)) {
+            Ok(node) => node,
+            Err(()) => return false,
+        };
+        let descendant = match self.get_node_by_strhash(StrHash::new(descendant)) {
+            Ok(node) => node,
+            Err(()) => return false,
+        };
+        if ancestor.get_data() == descendant.get_data() {
+            return false;
+        }
+
+        let ancestor_intervals: Vec<(u32, u32)> = ancestor
+            .get_interval_nodes()
+            .iter()
+            .map(|interval| (interval.get_start(), interval.get_end()))
+            .collect();
+        let mut visited = HashSet::new();
+        let mut stack = vec![descendant];
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node.get_data()) {
+                continue;
+            }
+            for interval in node.get_interval_nodes().iter() {
+                if ancestor_intervals
+                    .iter()
+                    .any(|(start, end)| *start <= interval.get_start() && interval.get_end() <= *end)
+                {
+                    return true;
+                }
+                if let Ok(parent) = interval.get_parent() {
+                    stack.push(parent);
+                }
+            }
+        }
+        false
+    }
 
-        let root = self.get_root();
-        let root_parent = self.get_root().get_interval_nodes().get(0).unwrap().get_parent().unwrap();
+    // 节点的层号（根节点为 1），多继承节点取其各个区间中最小的层号
+    // 节点不存在或者树还没有编码时返回 None
+    pub fn depth_of(&self, node: &str) -> Option<u32> {
+        let node = self.get_node_by_strhash(StrHash::new(node)).ok()?;
+        let depth = node
+            .get_interval_nodes()
+            .iter()
+            .map(|interval| interval.get_layer())
+            .filter(|layer| *layer > 0)
+            .min()
+            .map(u32::from);
+        depth
+    }
+
+    // 对树进行编码，可以重复调用：root_parent 只添加一次，之前的路径会被清空
+    pub fn encode(&self) {
+        if self.get_root().count_parents() == 0 {
+            self.initial_root();
+        }
+        self.parent_way.write().unwrap().clear();
+        self.hash_parent_by_str.write().unwrap().clear();
 
         self.tao();
 
         self.parent_way_by_strhash();
     }
 
+    // 编码时在每个节点的区间末尾预留 gap 个空闲编号，之后 insert_and_reencode_subtree 可以把新的子节点放进去
+    // 预留的编号会随着深度累加，gap 需要保持较小以免超出 u32
+    // 序列化不保存 gap，from_bytes 得到的树再次整体编码时不再预留
+    pub fn encode_with_gap(&self, gap: u32) {
+        self.gap.store(gap, Ordering::Relaxed);
+        self.encode();
+    }
+
+    // 插入 child -> parent 这条边并更新区间编码，返回值与 insert 相同
+    // child 是新节点、parent 已经编码并且其区间中还有空闲编号时，只给 child 分配一段空闲编号（占一半，剩下的留给之后的兄弟节点），
+    // 其他节点的编码都不变；否则整棵树重新编码，之前保存的区间编码需要重新生成
+    // 编码之后的树只能用这个方法插入，insert 插入的节点没有编码
+    pub fn insert_and_reencode_subtree(&self, child_str: &str, parent_str: &str) -> bool {
+        let parent = self.get_node_by_strhash(StrHash::new(parent_str)).ok();
+        let free = match &parent {
+            Some(parent) if !self.if_exist(child_str) => self.free_interval(parent),
+            _ => None,
+        };
+        if !self.insert(child_str, parent_str) {
+            return false;
+        }
+        match (parent, free) {
+            (Some(parent), Some((start, end, layer))) => {
+                let child = self.get_node_by_strhash(StrHash::new(child_str)).unwrap();
+                for interval in child.get_interval_nodes().iter() {
+                    interval.set_start(start);
+                    interval.set_end(end);
+                    interval.set_layer(layer);
+                }
+                // 与 recursive 相同：路径以节点自身开头，不包含 root
+                let ways = self
+                    .hash_parent_by_str
+                    .read().unwrap()
+                    .get(&parent.get_data())
+                    .cloned()
+                    .unwrap_or_default();
+                for way in ways {
+                    let mut child_way = vec![child.get_data()];
+                    child_way.extend(way.into_iter().filter(|node| *node != self.root));
+                    self.parent_way.write().unwrap().push(child_way.clone());
+                    self.hash_parent_by_str
+                        .write().unwrap()
+                        .entry(child.get_data())
+                        .or_insert_with(Vec::new)
+                        .push(child_way);
+                }
+            }
+            _ => self.encode(),
+        }
+        true
+    }
+
+    // parent 的区间中最后一个子节点之后的空闲编号，分配前一半给新的子节点，返回 (start, end, layer)
+    // 多继承节点的子节点在最后一次遍历时编码，位于 start 最大的区间中
+    fn free_interval(&self, parent: &Arc<MultiTreeNode>) -> Option<(u32, u32, u16)> {
+        let (start, end, layer) = parent
+            .get_interval_nodes()
+            .iter()
+            .map(|interval| (interval.get_start(), interval.get_end(), interval.get_layer()))
+            .max_by_key(|(start, _, _)| *start)?;
+        if start == 0 {
+            return None; // 还没有编码
+        }
+        let used = parent
+            .get_childs()
+            .iter()
+            .flat_map(|child| {
+                child
+                    .get_interval_nodes()
+                    .iter()
+                    .filter(|interval| {
+                        interval
+                            .get_parent()
+                            .map_or(false, |p| p.get_data() == parent.get_data())
+                    })
+                    .map(|interval| interval.get_end())
+                    .collect::<Vec<_>>()
+            })
+            .max()
+            .unwrap_or(start);
+        if used >= end {
+            return None;
+        }
+        let child_start = used + 1;
+        Some((child_start, child_start + (end - child_start) / 2, layer + 1))
+    }
+
     // 其为私有方法，以保证插入过程可以正常进行下去
-    fn construct_node(&self, value: &str) -> Rc<MultiTreeNode> {
+    fn construct_node(&self, value: &str) -> Arc<MultiTreeNode> {
         if !self.if_exist(value) {
-            let treenode = Rc::new(MultiTreeNode::new(value));
-            self.hash_str_node.borrow_mut().insert(treenode.get_data(), treenode);
+            let treenode = Arc::new(MultiTreeNode::new(value));
+            self.hash_str_node.write().unwrap().insert(treenode.get_data(), treenode);
         }
 
-        Rc::clone(self.hash_str_node.borrow().get(&StrHash::new(value)).unwrap())
+        Arc::clone(self.hash_str_node.read().unwrap().get(&StrHash::new(value)).unwrap())
     }
 
     // 节点层号编码
     // 若子节点的父节点是多继承节点，则该子节点的层号跟随树中出现在最右侧的多继承父节点
-    fn generate_layer(&self, child: Rc<MultiTreeNode>, parent: Rc<MultiTreeNode>, depth: u16) {
+    fn generate_layer(&self, child: Arc<MultiTreeNode>, parent: Arc<MultiTreeNode>, depth: u16) {
         // 根据parent找到interval_node进行编码
         for interval in child.get_interval_nodes().iter() {
             if interval.get_parent().unwrap().get_data() == parent.get_data() {
@@ -170,19 +359,19 @@ impl MultiTree {
         }
 
         for child_node in child.get_childs().iter(){
-            self.generate_layer(Rc::clone(child_node),Rc::clone(&child) , depth+1);
+            self.generate_layer(Arc::clone(child_node),Arc::clone(&child) , depth+1);
         }
     }
 
     // TODO：计算某节点的后代节点数
     // 多继承节点的子节点会被重复计算
-    pub fn count_childs(&self, node: Rc<MultiTreeNode>) -> u32 {
+    pub fn count_childs(&self, node: Arc<MultiTreeNode>) -> u32 {
         if node.get_childs().len() == 0 {
             return 0;
         }
 
-        let mut stack: Vec<Rc<MultiTreeNode>> = Vec::new();
-        stack.push(Rc::clone(&node));
+        let mut stack: Vec<Arc<MultiTreeNode>> = Vec::new();
+        stack.push(Arc::clone(&node));
 
         let mut count: u32 = 0;
 
@@ -190,7 +379,7 @@ impl MultiTree {
             let node = stack.pop().unwrap();
 
             for child in node.get_childs().iter().rev(){
-                stack.push(Rc::clone(child));
+                stack.push(Arc::clone(child));
             }
 
             count = count + 1;
@@ -202,10 +391,10 @@ impl MultiTree {
     pub fn tao(&self) {
         let mut count = 0;
         let mut way:Vec<StrHash> = Vec::new();
-        self.recursive(self.get_root(), self.get_root().get_interval_nodes().borrow().get(0).unwrap().get_parent().unwrap(), count, 1u16, way);
+        self.recursive(self.get_root(), self.get_root().get_interval_nodes().get(0).unwrap().get_parent().unwrap(), count, 1u16, way);
     }
 
-    pub fn recursive(&self, current_node: Rc<MultiTreeNode>, parent: Rc<MultiTreeNode>, count: u32, layer: u16, parent_way: Vec<StrHash>) -> u32{
+    pub fn recursive(&self, current_node: Arc<MultiTreeNode>, parent: Arc<MultiTreeNode>, count: u32, layer: u16, parent_way: Vec<StrHash>) -> u32{
         let mut current = count + 1;   // 区间编码的左边界
 
         let mut way: Vec<StrHash> = Vec::new();
@@ -217,11 +406,12 @@ impl MultiTree {
             }
         }
 
-        self.parent_way.borrow_mut().push(way.clone());
+        self.parent_way.write().unwrap().push(way.clone());
  
         for child in current_node.get_childs().iter() {
-            current = self.recursive(Rc::clone(child), Rc::clone(&current_node), current, layer+1, way.clone());
+            current = self.recursive(Arc::clone(child), Arc::clone(&current_node), current, layer+1, way.clone());
         }
+        current += self.gap.load(Ordering::Relaxed);
 
         for interval in current_node.get_interval_nodes().iter() {
             if interval.get_parent().unwrap().get_data() == parent.get_data() {
@@ -236,17 +426,298 @@ impl MultiTree {
 
     // 形成节点到根节点的路径（从根节点自顶向下）
     pub fn parent_way_by_strhash(&self) {
-        for way in self.parent_way.borrow().iter() {      
-            (*self.hash_parent_by_str.borrow_mut().entry(*(way.get(0).unwrap())).or_insert(Vec::new())).push(way.clone());
+        for way in self.parent_way.read().unwrap().iter() {      
+            (*self.hash_parent_by_str.write().unwrap().entry(*(way.get(0).unwrap())).or_insert(Vec::new())).push(way.clone());
         }
     }
 
     pub fn get_parent_way_by_str(&self, strhash: StrHash) -> Vec<Vec<StrHash>> {
-        self.hash_parent_by_str.borrow().get(&strhash).unwrap().clone()
+        self.hash_parent_by_str.read().unwrap().get(&strhash).unwrap().clone()
     }
 
     pub fn initial_root(&self) {
         // 设置根节点的父节点以及其编码
         self.get_root().add_parent(self.construct_node("root_parent"));
     }
-}
\ No newline at end of file
+
+    // 序列化整棵树（包括已经计算好的区间编码），以便之后的加载复用同一套编号
+    // 格式：root | 节点数 | 每个节点(data | 子节点数 | 子节点... | 区间数 | (父节点 start end layer)...) | 路径数 | 每条路径(长度 | 节点...)
+    // 节点按 StrHash 排序，保证同一棵树的序列化结果稳定
+    // 个数超出 u32 时返回错误，而不是截断后写入无法还原的数据
+    pub fn to_bytes(&self) -> Result<Vec<u8>, StorageError> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.root.to_be_bytes());
+
+        let nodes = self.hash_str_node.read().unwrap();
+        let mut hashes: Vec<StrHash> = nodes.keys().copied().collect();
+        hashes.sort_unstable_by_key(|hash| hash.to_be_bytes());
+        write_len(&mut buffer, hashes.len(), "nodes")?;
+        for hash in hashes {
+            let node = nodes.get(&hash).unwrap();
+            buffer.extend_from_slice(&hash.to_be_bytes());
+
+            let childs = node.get_childs();
+            write_len(&mut buffer, childs.len(), "children")?;
+            for child in childs.iter() {
+                buffer.extend_from_slice(&child.get_data().to_be_bytes());
+            }
+
+            // 父节点已被释放的区间无法还原，直接跳过
+            let intervals: Vec<_> = node
+                .get_interval_nodes()
+                .iter()
+                .filter_map(|interval| interval.get_parent().ok().map(|parent| (parent.get_data(), Arc::clone(interval))))
+                .collect();
+            write_len(&mut buffer, intervals.len(), "intervals")?;
+            for (parent, interval) in intervals {
+                buffer.extend_from_slice(&parent.to_be_bytes());
+                buffer.extend_from_slice(&interval.get_start().to_be_bytes());
+                buffer.extend_from_slice(&interval.get_end().to_be_bytes());
+                buffer.extend_from_slice(&interval.get_layer().to_be_bytes());
+            }
+        }
+
+        let parent_way = self.parent_way.read().unwrap();
+        write_len(&mut buffer, parent_way.len(), "paths")?;
+        for way in parent_way.iter() {
+            write_len(&mut buffer, way.len(), "path nodes")?;
+            for node in way {
+                buffer.extend_from_slice(&node.to_be_bytes());
+            }
+        }
+        Ok(buffer)
+    }
+
+    // 从 to_bytes 的结果还原树，数据不完整或引用了不存在的节点时返回 Err
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, ()> {
+        let root = read_strhash(&mut bytes)?;
+
+        // 先创建所有节点，再连接子节点与父节点（区间中的父节点是弱引用，需要节点已经存在）
+        let mut links = Vec::new();
+        let mut hash_str_node = HashMap::new();
+        for _ in 0..read_u32(&mut bytes)? {
+            let data = read_strhash(&mut bytes)?;
+            let mut childs = Vec::new();
+            for _ in 0..read_u32(&mut bytes)? {
+                childs.push(read_strhash(&mut bytes)?);
+            }
+            let mut intervals = Vec::new();
+            for _ in 0..read_u32(&mut bytes)? {
+                let parent = read_strhash(&mut bytes)?;
+                let start = read_u32(&mut bytes)?;
+                let end = read_u32(&mut bytes)?;
+                let layer = u16::from_be_bytes(take_bytes(&mut bytes)?);
+                intervals.push((parent, start, end, layer));
+            }
+            hash_str_node.insert(data, Arc::new(MultiTreeNode::from_strhash(data)));
+            links.push((data, childs, intervals));
+        }
+        if !hash_str_node.contains_key(&root) {
+            return Err(());
+        }
+
+        for (data, childs, intervals) in links {
+            let node = hash_str_node.get(&data).unwrap();
+            for child in childs {
+                node.add_child(Arc::clone(hash_str_node.get(&child).ok_or(())?));
+            }
+            for (parent, start, end, layer) in intervals {
+                node.add_parent(Arc::clone(hash_str_node.get(&parent).ok_or(())?));
+                let interval_nodes = node.get_interval_nodes();
+                let interval = interval_nodes.last().unwrap();
+                interval.set_start(start);
+                interval.set_end(end);
+                interval.set_layer(layer);
+            }
+        }
+
+        let mut parent_way = Vec::new();
+        for _ in 0..read_u32(&mut bytes)? {
+            let mut way = Vec::new();
+            for _ in 0..read_u32(&mut bytes)? {
+                way.push(read_strhash(&mut bytes)?);
+            }
+            parent_way.push(way);
+        }
+        if !bytes.is_empty() {
+            return Err(());
+        }
+
+        let tree = Self {
+            root,
+            hash_str_node: RwLock::new(hash_str_node),
+            parent_way: RwLock::new(parent_way),
+            hash_parent_by_str: RwLock::new(HashMap::new()),
+            gap: AtomicU32::new(0),
+        };
+        tree.parent_way_by_strhash();
+        Ok(tree)
+    }
+}
+
+fn write_len(buffer: &mut Vec<u8>, len: usize, what: &str) -> Result<(), StorageError> {
+    let len = u32::try_from(len).map_err(|_| {
+        StorageError::Other(format!("The tree has {} {} but at most {} could be serialized", len, what, u32::MAX).into())
+    })?;
+    buffer.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn take_bytes<const N: usize>(bytes: &mut &[u8]) -> Result<[u8; N], ()> {
+    if bytes.len() < N {
+        return Err(());
+    }
+    let mut buffer = [0; N];
+    buffer.copy_from_slice(&bytes[..N]);
+    *bytes = &bytes[N..];
+    Ok(buffer)
+}
+
+fn read_u32(bytes: &mut &[u8]) -> Result<u32, ()> {
+    Ok(u32::from_be_bytes(take_bytes(bytes)?))
+}
+
+fn read_strhash(bytes: &mut &[u8]) -> Result<StrHash, ()> {
+    Ok(StrHash::from_be_bytes(take_bytes(bytes)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // root 下有 a、b，x 同时继承 a 和 b，c 继承 x
+    fn diamond() -> MultiTree {
+        let tree = MultiTree::new("root");
+        tree.insert("a", "root");
+        tree.insert("x", "a");
+        tree.insert("x", "b");
+        tree.insert("c", "x");
+        tree.encode();
+        tree
+    }
+
+    #[test]
+    fn test_is_ancestor_across_two_paths() {
+        let tree = diamond();
+        for ancestor in ["a", "b", "x", "root"] {
+            assert!(tree.is_ancestor(ancestor, "c"), "{} should be an ancestor of c", ancestor);
+        }
+        assert!(tree.is_ancestor("a", "x"));
+        assert!(tree.is_ancestor("b", "x"));
+        assert!(tree.is_ancestor("root", "b"));
+
+        assert!(!tree.is_ancestor("a", "b"));
+        assert!(!tree.is_ancestor("b", "a"));
+        assert!(!tree.is_ancestor("c", "x"));
+        assert!(!tree.is_ancestor("x", "x"));
+        assert!(!tree.is_ancestor("missing", "c"));
+        assert!(!tree.is_ancestor("a", "missing"));
+    }
+
+    #[test]
+    fn test_depth_of() {
+        let tree = diamond();
+        assert_eq!(tree.depth_of("root"), Some(1));
+        assert_eq!(tree.depth_of("a"), Some(2));
+        assert_eq!(tree.depth_of("b"), Some(2));
+        assert_eq!(tree.depth_of("x"), Some(3));
+        assert_eq!(tree.depth_of("c"), Some(4));
+        assert_eq!(tree.depth_of("missing"), None);
+
+        // 编码之前没有层号
+        let tree = MultiTree::new("root");
+        tree.insert("a", "root");
+        assert_eq!(tree.depth_of("a"), None);
+    }
+
+    #[test]
+    fn test_is_ancestor_after_serialization() {
+        let tree = MultiTree::from_bytes(&diamond().to_bytes().unwrap()).unwrap();
+        assert!(tree.is_ancestor("a", "c"));
+        assert!(tree.is_ancestor("b", "c"));
+        assert!(!tree.is_ancestor("c", "a"));
+    }
+
+    #[test]
+    fn test_insert_skips_cycles() {
+        let tree = MultiTree::new("root");
+        assert!(tree.insert("b", "a"));
+        assert!(tree.insert("c", "b"));
+        assert!(tree.creates_cycle("a", "c"));
+        assert!(tree.creates_cycle("a", "a"));
+        assert!(!tree.creates_cycle("c", "a"));
+        assert!(!tree.insert("a", "c"));
+        assert!(!tree.insert("b", "b"));
+
+        // 跳过成环的边之后编码可以正常结束
+        tree.encode();
+        assert!(tree.is_ancestor("a", "c"));
+        assert!(!tree.is_ancestor("c", "a"));
+        assert_eq!(tree.depth_of("c"), Some(4));
+    }
+
+    #[test]
+    fn test_insert_and_reencode_subtree() {
+        let tree = MultiTree::new("root");
+        tree.insert("a", "root");
+        tree.insert("b", "root");
+        tree.encode_with_gap(2);
+        let interval_of = |node: &str| {
+            let node = tree.get_node_by_strhash(StrHash::new(node)).unwrap();
+            let intervals = node.get_interval_nodes();
+            (intervals[0].get_start(), intervals[0].get_end())
+        };
+
+        let mut edges = vec![
+            ("a".to_owned(), "root".to_owned()),
+            ("b".to_owned(), "root".to_owned()),
+        ];
+        for i in 0..30 {
+            // 依次插入到 a、之前插入的某个节点以及 b 下面，预留的编号用完之后会整体重新编码
+            let parent = match i % 3 {
+                0 => "a".to_owned(),
+                1 => edges[2 + i / 2].0.clone(),
+                _ => "b".to_owned(),
+            };
+            let child = format!("n{}", i);
+            let b_before = interval_of("b");
+            assert!(tree.insert_and_reencode_subtree(&child, &parent));
+            if i == 0 {
+                // a 中有空闲编号，其他节点的编码不变
+                assert_eq!(interval_of("b"), b_before);
+            }
+            edges.push((child, parent));
+
+            // 与沿着父节点查找的结果比较
+            let is_ancestor = |ancestor: &str, descendant: &str| {
+                let mut stack = vec![descendant.to_owned()];
+                while let Some(node) = stack.pop() {
+                    for (child, parent) in &edges {
+                        if *child == node {
+                            if parent == ancestor {
+                                return true;
+                            }
+                            stack.push(parent.clone());
+                        }
+                    }
+                }
+                false
+            };
+            for (ancestor, _) in &edges {
+                for (descendant, _) in &edges {
+                    assert_eq!(
+                        tree.is_ancestor(ancestor, descendant),
+                        is_ancestor(ancestor, descendant),
+                        "{} {} after inserting n{}",
+                        ancestor,
+                        descendant,
+                        i
+                    );
+                }
+                assert!(tree.is_ancestor("root", ancestor));
+            }
+        }
+        assert_eq!(tree.depth_of("n0"), Some(3));
+        assert!(!tree.insert_and_reencode_subtree("a", "n0"));
+    }
+}
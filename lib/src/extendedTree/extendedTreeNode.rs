@@ -1,7 +1,5 @@
 use crate::storage::numeric_encoder::StrHash;
-use std::cell::{RefCell, RefMut, Ref};
-use std::fmt::Result;
-use std::rc::{Rc};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
 
 use super::IntervalNode;
 
@@ -10,17 +8,26 @@ use super::IntervalNode;
 // 在获取编码的时候,对其区间编码节点(interval_node)进行迭代
 #[derive(Debug)]
 pub struct MultiTreeNode {
-    childs: RefCell<Vec<Rc<MultiTreeNode>>>,
+    childs: RwLock<Vec<Arc<MultiTreeNode>>>,
     data: StrHash,
-    interval_node: RefCell<Vec<Rc<IntervalNode>>>,
+    interval_node: RwLock<Vec<Arc<IntervalNode>>>,
 }
 
 impl MultiTreeNode {
     pub fn new(data: &str) -> Self {
         Self {
-            childs: RefCell::new(Vec::new()),
+            childs: RwLock::new(Vec::new()),
             data: StrHash::new(data),
-            interval_node: RefCell::new(Vec::new()),
+            interval_node: RwLock::new(Vec::new()),
+        }
+    }
+
+    // 反序列化时只知道节点的 StrHash
+    pub fn from_strhash(data: StrHash) -> Self {
+        Self {
+            childs: RwLock::new(Vec::new()),
+            data,
+            interval_node: RwLock::new(Vec::new()),
         }
     }
 
@@ -29,9 +36,9 @@ impl MultiTreeNode {
     }
 
     // 先检查是否已添加该子节点，否则返回Err，表示添加不成功
-    pub fn add_child(&self, child_node: Rc<MultiTreeNode>) -> bool {
+    pub fn add_child(&self, child_node: Arc<MultiTreeNode>) -> bool {
         {
-            let vec = &*(self.childs.borrow());
+            let vec = &*(self.childs.read().unwrap());
             for child in vec {
                 if (*child).data == child_node.get_data() {
                     return false;
@@ -39,13 +46,13 @@ impl MultiTreeNode {
             }
         }
 
-        self.childs.borrow_mut().push(child_node);
+        self.childs.write().unwrap().push(child_node);
         true
     }
 
     // 找到某个child在childs中的下标以供进行删除
     pub fn find_child_index(&self, child_strhash: StrHash) -> usize {
-        for (index, node) in self.childs.borrow().iter().enumerate() {
+        for (index, node) in self.childs.read().unwrap().iter().enumerate() {
             if (*node).get_data() == child_strhash{
                 return index;
             }
@@ -56,12 +63,12 @@ impl MultiTreeNode {
     pub fn remove_child(&self, child_strhash: StrHash){
         let index = self.find_child_index(child_strhash);
         if  index != usize::MAX {
-            self.childs.borrow_mut().remove(index);
+            self.childs.write().unwrap().remove(index);
         }
     }
 
     pub fn find_parent_index(&self, parent_strhash: StrHash) -> usize {
-        for (index,node) in self.interval_node.borrow().iter().enumerate() {
+        for (index,node) in self.interval_node.read().unwrap().iter().enumerate() {
             if (*node).get_parent().unwrap().get_data() == parent_strhash {
                 return index;
             }
@@ -73,32 +80,32 @@ impl MultiTreeNode {
     pub fn remove_parent(&self, parent_strhash: StrHash) {
         let index = self.find_parent_index(parent_strhash);
         if index != usize::MAX {
-            self.interval_node.borrow_mut().remove(index);
+            self.interval_node.write().unwrap().remove(index);
         }
     }
 
     // 得到IntervalNode的vec列表
-    pub fn get_interval_nodes(&self) -> Ref<Vec<Rc<IntervalNode>>>{
-        self.interval_node.borrow()
+    pub fn get_interval_nodes(&self) -> RwLockReadGuard<'_, Vec<Arc<IntervalNode>>>{
+        self.interval_node.read().unwrap()
     }
 
     // 子节点列表（注意不可更改），要更改子节点的操作应该直接在结构体内部进行更改而不能在结构体外部更改
-    pub fn get_childs(&self) -> Ref<Vec<Rc<MultiTreeNode>>> {
-        self.childs.borrow()
+    pub fn get_childs(&self) -> RwLockReadGuard<'_, Vec<Arc<MultiTreeNode>>> {
+        self.childs.read().unwrap()
     }
 
     // 添加父节点
-    pub fn add_parent(&self, parent: Rc<MultiTreeNode>) {
-        self.interval_node.borrow_mut().push(Rc::new(IntervalNode::new(parent)));
+    pub fn add_parent(&self, parent: Arc<MultiTreeNode>) {
+        self.interval_node.write().unwrap().push(Arc::new(IntervalNode::new(parent)));
     }
 
     pub fn count_parents(&self) -> usize {
-        self.interval_node.borrow().len()
+        self.interval_node.read().unwrap().len()
     }
 
     // 判断该节点是否含有某父节点
-    pub fn if_exist_parent(&self, parent: Rc<MultiTreeNode>) -> bool {
-        for interval in self.interval_node.borrow().iter() {
+    pub fn if_exist_parent(&self, parent: Arc<MultiTreeNode>) -> bool {
+        for interval in self.interval_node.read().unwrap().iter() {
             match interval.get_parent() {
                 Ok(node) => {
                     if node.get_data() == parent.get_data() {
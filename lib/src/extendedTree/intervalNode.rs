@@ -1,59 +1,59 @@
-use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+use std::sync::{Arc, RwLock, Weak};
 
 use super::MultiTreeNode;
 
-// RefCell：内部可变性   Rc：引用计数
+// 原子类型和 RwLock：内部可变性   Arc：引用计数，编码好的树可以在加载线程之间共享
 // 实现一个节点可以有对应的多个区间编码以及父类
 #[derive(Debug)]
 pub struct IntervalNode {
-    start: RefCell<u32>, 
-    end: RefCell<u32>,
-    layer: RefCell<u16>,
+    start: AtomicU32,
+    end: AtomicU32,
+    layer: AtomicU16,
 
-    parent: RefCell<Weak<MultiTreeNode>>
+    parent: RwLock<Weak<MultiTreeNode>>
 }
 
 impl IntervalNode {
-    pub fn new(parent: Rc<MultiTreeNode>) -> Self {
+    pub fn new(parent: Arc<MultiTreeNode>) -> Self {
         Self {
-            start: RefCell::new(u32::default()),
-            end: RefCell::new(u32::default()),
-            layer: RefCell::new(u16::default()),
+            start: AtomicU32::default(),
+            end: AtomicU32::default(),
+            layer: AtomicU16::default(),
 
-            parent: RefCell::new(Rc::downgrade(&parent))
+            parent: RwLock::new(Arc::downgrade(&parent))
         }
     }
 
-    // 不知道能否保持一致性（直接返回Rc父节点）
-    pub fn get_parent(&self) -> Result<Rc<MultiTreeNode>,()>{
-        match self.parent.borrow_mut().upgrade(){
-            Some(value) => Ok(Rc::clone(&value)),
+    // 不知道能否保持一致性（直接返回Arc父节点）
+    pub fn get_parent(&self) -> Result<Arc<MultiTreeNode>,()>{
+        match self.parent.read().unwrap().upgrade(){
+            Some(value) => Ok(value),
             None => Err(())
         }
     }
 
     pub fn set_start(&self, start: u32) {
-        *self.start.borrow_mut() = start;
+        self.start.store(start, Ordering::Relaxed);
     }
 
     pub fn get_start(&self) -> u32 {
-        *(self.start.borrow())
+        self.start.load(Ordering::Relaxed)
     }
 
     pub fn set_end(&self, end: u32) {
-        *self.end.borrow_mut() = end;
+        self.end.store(end, Ordering::Relaxed);
     }
 
     pub fn get_end(&self) -> u32 {
-        *(self.end.borrow())
+        self.end.load(Ordering::Relaxed)
     }
 
     pub fn set_layer(&self, layer: u16) {
-        *self.layer.borrow_mut() = layer;
+        self.layer.store(layer, Ordering::Relaxed);
     }
 
     pub fn get_layer(&self) -> u16 {
-        *(self.layer.borrow())
+        self.layer.load(Ordering::Relaxed)
     }
 }
\ No newline at end of file
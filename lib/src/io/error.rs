@@ -1,4 +1,5 @@
 use oxiri::IriParseError;
+use rio_api::parser::ParseError as RioParseError;
 use rio_turtle::TurtleError;
 use rio_xml::RdfXmlError;
 use std::error::Error;
@@ -14,6 +15,17 @@ pub enum ParseError {
 }
 
 impl ParseError {
+    /// The position of the error in the parsed file, if known.
+    ///
+    /// Only syntax errors have a position, see [`SyntaxError::position`].
+    #[inline]
+    pub fn position(&self) -> Option<TextPosition> {
+        match self {
+            Self::Io(_) => None,
+            Self::Syntax(e) => e.position(),
+        }
+    }
+
     #[inline]
     pub(crate) fn invalid_base_iri(iri: &str, error: IriParseError) -> Self {
         Self::Syntax(SyntaxError {
@@ -112,6 +124,31 @@ enum SyntaxErrorKind {
     InvalidBaseIri { iri: String, error: IriParseError },
 }
 
+impl SyntaxError {
+    /// The position of the error in the parsed file, if known.
+    ///
+    /// It is provided by the N-Triples, N-Quads, Turtle and TriG parsers but not by the RDF/XML one.
+    #[inline]
+    pub fn position(&self) -> Option<TextPosition> {
+        match &self.inner {
+            SyntaxErrorKind::Turtle(e) => e.textual_position().map(|position| TextPosition {
+                line: position.line_number(),
+                byte_in_line: position.byte_number(),
+            }),
+            SyntaxErrorKind::RdfXml(_) | SyntaxErrorKind::InvalidBaseIri { .. } => None,
+        }
+    }
+}
+
+/// A position in a parsed file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextPosition {
+    /// The line number, starting from 1.
+    pub line: u64,
+    /// The byte offset in the line, starting from 1.
+    pub byte_in_line: u64,
+}
+
 impl fmt::Display for SyntaxError {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
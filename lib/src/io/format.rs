@@ -64,6 +64,7 @@ impl GraphFormat {
     ///
     /// It supports some media type aliases.
     /// For example, "application/xml" is going to return `GraphFormat::RdfXml` even if it is not its canonical media type.
+    /// Media type parameters are ignored and the comparison is case-insensitive.
     ///
     /// Example:
     /// ```
@@ -73,7 +74,7 @@ impl GraphFormat {
     /// ```
     #[inline]
     pub fn from_media_type(media_type: &str) -> Option<Self> {
-        match media_type.split(';').next()?.trim() {
+        match media_type.split(';').next()?.trim().to_ascii_lowercase().as_str() {
             "application/n-triples" | "text/plain" => Some(Self::NTriples),
             "text/turtle" | "application/turtle" | "application/x-turtle" => Some(Self::Turtle),
             "application/rdf+xml" | "application/xml" | "text/xml" => Some(Self::RdfXml),
@@ -83,7 +84,7 @@ impl GraphFormat {
 
     /// Looks for a known format from an extension.
     ///
-    /// It supports some aliases.
+    /// It supports some aliases, a leading dot and is case-insensitive.
     ///
     /// Example:
     /// ```
@@ -93,7 +94,7 @@ impl GraphFormat {
     /// ```
     #[inline]
     pub fn from_extension(extension: &str) -> Option<Self> {
-        match extension {
+        match extension.trim_start_matches('.').to_ascii_lowercase().as_str() {
             "nt" | "txt" => Some(Self::NTriples),
             "ttl" => Some(Self::Turtle),
             "rdf" | "xml" => Some(Self::RdfXml),
@@ -162,6 +163,7 @@ impl DatasetFormat {
     /// Looks for a known format from a media type.
     ///
     /// It supports some media type aliases.
+    /// Media type parameters are ignored and the comparison is case-insensitive.
     ///
     /// Example:
     /// ```
@@ -171,7 +173,7 @@ impl DatasetFormat {
     /// ```
     #[inline]
     pub fn from_media_type(media_type: &str) -> Option<Self> {
-        match media_type.split(';').next()?.trim() {
+        match media_type.split(';').next()?.trim().to_ascii_lowercase().as_str() {
             "application/n-quads" | "text/x-nquads" | "text/nquads" => Some(Self::NQuads),
             "application/trig" | "application/x-trig" => Some(Self::TriG),
             _ => None,
@@ -180,7 +182,7 @@ impl DatasetFormat {
 
     /// Looks for a known format from an extension.
     ///
-    /// It supports some aliases.
+    /// It supports some aliases, a leading dot and is case-insensitive.
     ///
     /// Example:
     /// ```
@@ -190,7 +192,7 @@ impl DatasetFormat {
     /// ```
     #[inline]
     pub fn from_extension(extension: &str) -> Option<Self> {
-        match extension {
+        match extension.trim_start_matches('.').to_ascii_lowercase().as_str() {
             "nq" | "txt" => Some(Self::NQuads),
             "trig" => Some(Self::TriG),
             _ => None,
@@ -224,3 +226,52 @@ impl TryFrom<GraphFormat> for DatasetFormat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph_format_media_types() {
+        for format in [GraphFormat::NTriples, GraphFormat::Turtle, GraphFormat::RdfXml] {
+            assert_eq!(GraphFormat::from_media_type(format.media_type()), Some(format));
+            assert_eq!(GraphFormat::from_extension(format.file_extension()), Some(format));
+        }
+        assert_eq!(
+            GraphFormat::from_media_type("text/turtle; charset=utf-8"),
+            Some(GraphFormat::Turtle)
+        );
+        assert_eq!(
+            GraphFormat::from_media_type(" Application/N-Triples ;charset=UTF-8"),
+            Some(GraphFormat::NTriples)
+        );
+        assert_eq!(
+            GraphFormat::from_media_type("application/rdf+xml"),
+            Some(GraphFormat::RdfXml)
+        );
+        assert_eq!(GraphFormat::from_media_type("application/n-quads"), None);
+        assert_eq!(GraphFormat::from_media_type(""), None);
+    }
+
+    #[test]
+    fn test_graph_format_extensions() {
+        assert_eq!(GraphFormat::from_extension("nt"), Some(GraphFormat::NTriples));
+        assert_eq!(GraphFormat::from_extension(".TTL"), Some(GraphFormat::Turtle));
+        assert_eq!(GraphFormat::from_extension("xml"), Some(GraphFormat::RdfXml));
+        assert_eq!(GraphFormat::from_extension("nq"), None);
+    }
+
+    #[test]
+    fn test_dataset_format_mappings() {
+        for format in [DatasetFormat::NQuads, DatasetFormat::TriG] {
+            assert_eq!(DatasetFormat::from_media_type(format.media_type()), Some(format));
+            assert_eq!(DatasetFormat::from_extension(format.file_extension()), Some(format));
+        }
+        assert_eq!(
+            DatasetFormat::from_media_type("application/TriG; charset=utf-8"),
+            Some(DatasetFormat::TriG)
+        );
+        assert_eq!(DatasetFormat::from_extension(".nq"), Some(DatasetFormat::NQuads));
+        assert_eq!(DatasetFormat::from_media_type("text/turtle"), None);
+    }
+}
@@ -1,6 +1,6 @@
 //! Utilities to read RDF graphs and datasets.
 
-pub use crate::io::error::{ParseError, SyntaxError};
+pub use crate::io::error::{ParseError, SyntaxError, TextPosition};
 use crate::io::{DatasetFormat, GraphFormat};
 use crate::model::*;
 use oxiri::{Iri, IriParseError};
@@ -9,7 +9,9 @@ use rio_api::parser::{QuadsParser, TriplesParser};
 use rio_turtle::{NQuadsParser, NTriplesParser, TriGParser, TurtleParser};
 use rio_xml::RdfXmlParser;
 use std::collections::HashMap;
-use std::io::BufRead;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
 
 /// Parsers for RDF graph serialization formats.
 ///
@@ -61,6 +63,26 @@ impl GraphParser {
     ///assert_eq!(triples[0].subject.to_string(), "<http://example.com/s>");
     /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
     /// ```
+    ///
+    /// Relative IRIs are resolved following [RFC 3986](https://www.ietf.org/rfc/rfc3986.html). The base IRI must be absolute.
+    ///
+    /// N-Triples only allows absolute IRIs: N-Triples documents are still parsed with the N-Triples parser
+    /// and a relative IRI is an error even if a base IRI is set.
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphParser};
+    /// use std::io::Cursor;
+    ///
+    /// let parser = GraphParser::from_format(GraphFormat::Turtle).with_base_iri("http://example.com/")?;
+    /// let triples = parser.read_triples(Cursor::new("<s> <p> <o> ."))?.collect::<Result<Vec<_>,_>>()?;
+    ///
+    ///assert_eq!(triples[0].subject.to_string(), "<http://example.com/s>");
+    ///assert!(GraphParser::from_format(GraphFormat::Turtle).with_base_iri("s").is_err());
+    ///
+    /// let parser = GraphParser::from_format(GraphFormat::NTriples).with_base_iri("http://example.com/")?;
+    ///assert!(parser.read_triples(Cursor::new("<s> <p> <o> ."))?.next().unwrap().is_err());
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
     #[inline]
     pub fn with_base_iri(mut self, base_iri: impl Into<String>) -> Result<Self, IriParseError> {
         self.base_iri = Some(Iri::parse(base_iri.into())?);
@@ -68,6 +90,10 @@ impl GraphParser {
     }
 
     /// Executes the parsing itself on a [`BufRead`](std::io::BufRead) implementation and returns an iterator of triples.
+    ///
+    /// Any [`BufRead`](std::io::BufRead) works, there is no need for a [`Cursor`](std::io::Cursor):
+    /// a `&[u8]` for in-memory data or a [`BufReader`] over any [`Read`](std::io::Read) like a [`File`].
+    /// The input is parsed while the iterator is consumed, it is never fully loaded in memory.
     #[allow(clippy::unnecessary_wraps)]
     pub fn read_triples<R: BufRead>(&self, reader: R) -> Result<TripleReader<R>, ParseError> {
         Ok(TripleReader {
@@ -84,6 +110,28 @@ impl GraphParser {
             buffer: Vec::new(),
         })
     }
+
+    /// Opens the file at `path` and parses it with [`read_triples`](Self::read_triples) through a [`BufReader`].
+    ///
+    /// ```
+    /// use oxigraph::io::{GraphFormat, GraphParser};
+    ///
+    /// let path = std::env::temp_dir().join("oxigraph-read-triples-from-path.nt");
+    /// std::fs::write(&path, "<http://example.com/s> <http://example.com/p> <http://example.com/o> .")?;
+    ///
+    /// let parser = GraphParser::from_format(GraphFormat::NTriples);
+    /// let triples = parser.read_triples_from_path(&path)?.collect::<Result<Vec<_>,_>>()?;
+    ///
+    /// assert_eq!(triples.len(), 1);
+    /// # std::fs::remove_file(&path)?;
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
+    pub fn read_triples_from_path(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<TripleReader<BufReader<File>>, ParseError> {
+        self.read_triples(BufReader::new(File::open(path)?))
+    }
 }
 
 /// An iterator yielding read triples.
@@ -219,6 +267,26 @@ impl DatasetParser {
     }
 
     /// Executes the parsing itself on a [`BufRead`](std::io::BufRead) implementation and returns an iterator of quads.
+    ///
+    /// The quads are streamed: the input is read lazily while iterating.
+    /// Lines without graph name are returned in the default graph.
+    ///
+    /// ```
+    /// use oxigraph::io::{DatasetFormat, DatasetParser};
+    /// use oxigraph::model::{GraphName, NamedNode};
+    /// use std::io::Cursor;
+    ///
+    /// let file = "<http://example.com/s> <http://example.com/p> <http://example.com/o> <http://example.com/g> .
+    /// <http://example.com/s> <http://example.com/p> <http://example.com/o> .";
+    ///
+    /// let parser = DatasetParser::from_format(DatasetFormat::NQuads);
+    /// let quads = parser.read_quads(Cursor::new(file))?.collect::<Result<Vec<_>,_>>()?;
+    ///
+    ///assert_eq!(quads.len(), 2);
+    ///assert_eq!(quads[0].graph_name, GraphName::from(NamedNode::new("http://example.com/g")?));
+    ///assert_eq!(quads[1].graph_name, GraphName::DefaultGraph);
+    /// # Result::<_,Box<dyn std::error::Error>>::Ok(())
+    /// ```
     #[allow(clippy::unnecessary_wraps)]
     pub fn read_quads<R: BufRead>(&self, reader: R) -> Result<QuadReader<R>, ParseError> {
         Ok(QuadReader {
@@ -378,3 +446,77 @@ impl<'a> RioMapper {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn first_error_position(file: &str) -> Option<TextPosition> {
+        GraphParser::from_format(GraphFormat::NTriples)
+            .read_triples(Cursor::new(file))
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap()
+            .position()
+    }
+
+    #[test]
+    fn test_ntriples_error_position() {
+        let file = "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n\
+                    <http://example.com/s> <http://example.com/p> \"o .\n\
+                    <http://example.com/s> <http://example.com/p> <http://example.com/o> .\n";
+        assert_eq!(
+            first_error_position(file),
+            Some(TextPosition {
+                line: 2,
+                byte_in_line: 51
+            })
+        );
+    }
+
+    #[test]
+    fn test_ntriples_error_position_on_unterminated_last_line() {
+        let file = "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n\
+                    <http://example.com/s> <http://example.com/p> <http://example.com/o> .\n\
+                    <http://example.com/s> <http://example.com/p> <http://example.com/o>";
+        assert_eq!(first_error_position(file).map(|p| p.line), Some(3));
+        assert_eq!(first_error_position(&format!("{}\n", file)).map(|p| p.line), Some(3));
+    }
+
+    #[test]
+    fn test_read_triples_from_any_buf_read() -> Result<(), Box<dyn std::error::Error>> {
+        let file = "<http://example.com/s> <http://example.com/p> <http://example.com/o> .\n\
+                    <http://example.com/s> <http://example.com/p> \"o\"@en .\n\
+                    _:b <http://example.com/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n";
+        let parser = GraphParser::from_format(GraphFormat::NTriples);
+        let from_bytes = parser
+            .read_triples(file.as_bytes())?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(from_bytes.len(), 3);
+
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}.nt", rand::random::<u128>()));
+        std::fs::write(&path, file)?;
+        let from_buf_reader = parser
+            .read_triples(BufReader::new(File::open(&path)?))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let from_path = parser
+            .read_triples_from_path(&path)?
+            .collect::<Result<Vec<_>, _>>()?;
+        std::fs::remove_file(&path)?;
+
+        // 空白节点的 id 在每次解析时随机生成，只比较其余的部分
+        let normalize = |triples: Vec<Triple>| {
+            triples
+                .into_iter()
+                .map(|t| match t.subject {
+                    Subject::BlankNode(_) => (None, t.predicate, t.object),
+                    subject => (Some(subject), t.predicate, t.object),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(normalize(from_buf_reader), normalize(from_bytes.clone()));
+        assert_eq!(normalize(from_path), normalize(from_bytes));
+        Ok(())
+    }
+}
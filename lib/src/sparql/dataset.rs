@@ -19,10 +19,15 @@ impl DatasetView {
         let dataset = EncodedDatasetSpec {
             default: dataset
                 .default_graph_graphs()
-                .map(|graphs| graphs.iter().map(|g| g.as_ref().into()).collect::<Vec<_>>()),
+                .map(|graphs| {
+                    graphs
+                        .iter()
+                        .map(|g| reader.encode_graph_name(g.as_ref()))
+                        .collect::<Vec<_>>()
+                }),
             named: dataset
                 .available_named_graphs()
-                .map(|graphs| graphs.iter().map(|g| g.as_ref().into()).collect::<Vec<_>>()),
+                .map(|graphs| graphs.iter().map(|g| reader.encode_term(g.as_ref())).collect::<Vec<_>>()),
         };
         Self {
             reader,
@@ -150,8 +155,9 @@ impl DatasetView {
 
     pub fn encode_term<'a>(&self, term: impl Into<TermRef<'a>>) -> EncodedTerm {
         let term = term.into();
-        let encoded = term.into();
-        insert_term(term, &encoded, &mut |key, value| {
+        let prefixes = self.reader.prefix_table();
+        let encoded = prefixes.encode_term(term);
+        insert_term(term, &encoded, prefixes, &mut |key, value| {
             self.insert_str(key, value);
             Ok(())
         })
@@ -159,6 +165,11 @@ impl DatasetView {
         encoded
     }
 
+    // 存储是否注册了 IRI 前缀，此时 IRI 需要通过 encode_term 编码
+    pub fn has_prefixes(&self) -> bool {
+        !self.reader.prefix_table().is_empty()
+    }
+
     pub fn insert_str(&self, key: &StrHash, value: &str) {
         if let Entry::Vacant(e) = self.extra.borrow_mut().entry(*key) {
             if !matches!(self.reader.contains_str(key), Ok(true)) {
@@ -180,6 +191,10 @@ impl StrLookup for DatasetView {
     fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
         Ok(self.extra.borrow().contains_key(key) || self.reader.contains_str(key)?)
     }
+
+    fn get_prefix(&self, prefix_id: u8) -> Result<Option<String>, StorageError> {
+        self.reader.get_prefix(prefix_id)
+    }
 }
 
 struct EncodedDatasetSpec {
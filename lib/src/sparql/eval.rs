@@ -1731,14 +1731,14 @@ impl SimpleEvaluator {
                 let dataset = self.dataset.clone();
                 Rc::new(move |tuple| {
                     let value = to_simple_string(&dataset, &lexical_form(tuple)?)?;
-                    let datatype = if let EncodedTerm::NamedNode { iri_id } = datatype(tuple)? {
-                        dataset.get_str(&iri_id).ok()?
-                    } else {
-                        None
-                    }?;
+                    let datatype = datatype(tuple)?;
+                    if !datatype.is_named_node() {
+                        return None;
+                    }
+                    let datatype = dataset.decode_named_node(&datatype).ok()?;
                     Some(dataset.encode_term(LiteralRef::new_typed_literal(
                         &value,
-                        NamedNodeRef::new_unchecked(&datatype),
+                        datatype.as_ref(),
                     )))
                 })
             }
@@ -2072,6 +2072,10 @@ fn to_bool(term: &EncodedTerm) -> Option<bool> {
 fn to_string_id(dataset: &DatasetView, term: &EncodedTerm) -> Option<SmallStringOrId> {
     match term {
         EncodedTerm::NamedNode { iri_id } => Some((*iri_id).into()),
+        EncodedTerm::PrefixedNamedNode { .. } => Some(build_string_id(
+            dataset,
+            dataset.decode_named_node(term).ok()?.as_str(),
+        )),
         EncodedTerm::DefaultGraph
         | EncodedTerm::NumericalBlankNode { .. }
         | EncodedTerm::SmallBlankNode { .. }
@@ -2346,6 +2350,7 @@ fn equals(a: &EncodedTerm, b: &EncodedTerm) -> Option<bool> {
     match a {
         EncodedTerm::DefaultGraph
         | EncodedTerm::NamedNode { .. }
+        | EncodedTerm::PrefixedNamedNode { .. }
         | EncodedTerm::NumericalBlankNode { .. }
         | EncodedTerm::SmallBlankNode { .. }
         | EncodedTerm::BigBlankNode { .. }
@@ -2544,6 +2549,14 @@ fn cmp_terms(dataset: &DatasetView, a: Option<&EncodedTerm>, b: Option<&EncodedT
                 EncodedTerm::NamedNode { iri_id: b } => {
                     compare_str_ids(dataset, a, b).unwrap_or(Ordering::Equal)
                 }
+                EncodedTerm::PrefixedNamedNode { .. } => {
+                    compare_named_nodes(dataset, &EncodedTerm::NamedNode { iri_id: *a }, b)
+                }
+                _ if b.is_blank_node() => Ordering::Greater,
+                _ => Ordering::Less,
+            },
+            EncodedTerm::PrefixedNamedNode { .. } => match b {
+                _ if b.is_named_node() => compare_named_nodes(dataset, a, b),
                 _ if b.is_blank_node() => Ordering::Greater,
                 _ => Ordering::Less,
             },
@@ -2788,6 +2801,14 @@ fn partial_cmp_literals(
     }
 }
 
+// 至少有一个是前缀压缩的 IRI 时，只能解码之后再比较
+fn compare_named_nodes(dataset: &DatasetView, a: &EncodedTerm, b: &EncodedTerm) -> Ordering {
+    match (dataset.decode_named_node(a), dataset.decode_named_node(b)) {
+        (Ok(a), Ok(b)) => a.as_str().cmp(b.as_str()),
+        _ => Ordering::Equal,
+    }
+}
+
 fn compare_str_ids(dataset: &DatasetView, a: &StrHash, b: &StrHash) -> Option<Ordering> {
     Some(dataset.get_str(a).ok()??.cmp(&dataset.get_str(b).ok()??))
 }
@@ -2804,6 +2825,7 @@ fn datatype(dataset: &DatasetView, value: &EncodedTerm) -> Option<EncodedTerm> {
     //TODO: optimize?
     match value {
         EncodedTerm::NamedNode { .. }
+        | EncodedTerm::PrefixedNamedNode { .. }
         | EncodedTerm::SmallBlankNode { .. }
         | EncodedTerm::BigBlankNode { .. }
         | EncodedTerm::NumericalBlankNode { .. }
@@ -2819,9 +2841,20 @@ fn datatype(dataset: &DatasetView, value: &EncodedTerm) -> Option<EncodedTerm> {
             Some(encode_named_node(dataset, rdf::LANG_STRING))
         }
         EncodedTerm::SmallTypedLiteral { datatype_id, .. }
-        | EncodedTerm::BigTypedLiteral { datatype_id, .. } => Some(EncodedTerm::NamedNode {
-            iri_id: *datatype_id,
-        }),
+        | EncodedTerm::BigTypedLiteral { datatype_id, .. } => {
+            let datatype = EncodedTerm::NamedNode {
+                iri_id: *datatype_id,
+            };
+            if dataset.has_prefixes() {
+                // 字面量中只保存了数据类型完整 IRI 的哈希，使用前缀时需要按前缀表重新编码
+                Some(build_named_node(
+                    dataset,
+                    dataset.decode_named_node(&datatype).ok()?.as_str(),
+                ))
+            } else {
+                Some(datatype)
+            }
+        }
         EncodedTerm::BooleanLiteral(..) => Some(encode_named_node(dataset, xsd::BOOLEAN)),
         EncodedTerm::FloatLiteral(..) => Some(encode_named_node(dataset, xsd::FLOAT)),
         EncodedTerm::DoubleLiteral(..) => Some(encode_named_node(dataset, xsd::DOUBLE)),
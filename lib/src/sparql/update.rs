@@ -194,7 +194,7 @@ impl<'a, 'b: 'a> SimpleUpdateEvaluator<'a, 'b> {
                 if self
                     .transaction
                     .reader()
-                    .contains_named_graph(&graph_name.as_ref().into())?
+                    .contains_named_graph_ref(graph_name.as_ref().into())?
                 {
                     Ok(self.transaction.clear_graph(graph_name.into())?)
                 } else if silent {
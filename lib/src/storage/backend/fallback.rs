@@ -5,6 +5,7 @@ use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::mem::transmute;
+use std::ops::Bound;
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, RwLock, RwLockWriteGuard};
 
@@ -13,16 +14,20 @@ pub struct ColumnFamilyDefinition {
     pub use_iter: bool,
     pub min_prefix_size: usize,
     pub unordered_writes: bool,
+    pub bloom_filter_bits: Option<f64>,
 }
 
+// 每个列族的数据放在 Arc 中：快照只复制这些 Arc，写入时再复制被修改的列族（写时复制）
+type Trees = HashMap<ColumnFamily, Arc<BTreeMap<Vec<u8>, Vec<u8>>>>;
+
 #[derive(Clone)]
-pub struct Db(Arc<RwLock<HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>);
+pub struct Db(Arc<RwLock<Trees>>);
 
 impl Db {
     pub fn new(column_families: Vec<ColumnFamilyDefinition>) -> Result<Self, StorageError> {
         let mut trees = HashMap::new();
         for cf in column_families {
-            trees.insert(ColumnFamily(cf.name), BTreeMap::default());
+            trees.insert(ColumnFamily(cf.name), Arc::default());
         }
         trees.entry(ColumnFamily("default")).or_default(); // We make sure that "default" key exists.
         Ok(Self(Arc::new(RwLock::new(trees))))
@@ -37,9 +42,10 @@ impl Db {
         }
     }
 
+    // 与当前的数据共享各列族，之后的写入会复制被修改的列族，对快照不可见
     #[must_use]
     pub fn snapshot(&self) -> Reader {
-        Reader(InnerReader::Simple(self.0.clone()))
+        Reader(InnerReader::Simple(self.0.read().unwrap().clone()))
     }
 
     pub fn transaction<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
@@ -48,6 +54,15 @@ impl Db {
     ) -> Result<T, E> {
         f(Transaction(Rc::new(RefCell::new(self.0.write().unwrap()))))
     }
+
+    // 内存实现中写事务互斥，不会出现冲突
+    pub fn transaction_with_retry<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        _max_retries: usize,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.transaction(f)
+    }
 }
 
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
@@ -56,10 +71,8 @@ pub struct ColumnFamily(&'static str);
 pub struct Reader(InnerReader);
 
 enum InnerReader {
-    Simple(Arc<RwLock<HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>),
-    Transaction(
-        Weak<RefCell<RwLockWriteGuard<'static, HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>>,
-    ),
+    Simple(Trees),
+    Transaction(Weak<RefCell<RwLockWriteGuard<'static, Trees>>>),
 }
 
 impl Reader {
@@ -70,8 +83,6 @@ impl Reader {
     ) -> Result<Option<Vec<u8>>, StorageError> {
         match &self.0 {
             InnerReader::Simple(reader) => Ok(reader
-                .read()
-                .unwrap()
                 .get(column_family)
                 .and_then(|cf| cf.get(key).cloned())),
             InnerReader::Transaction(reader) => {
@@ -96,8 +107,6 @@ impl Reader {
     ) -> Result<bool, StorageError> {
         match &self.0 {
             InnerReader::Simple(reader) => Ok(reader
-                .read()
-                .unwrap()
                 .get(column_family)
                 .map_or(false, |cf| cf.contains_key(key))),
             InnerReader::Transaction(reader) => {
@@ -125,8 +134,7 @@ impl Reader {
         prefix: &[u8],
     ) -> Result<Iter, StorageError> {
         let data: Vec<_> = match &self.0 {
-            InnerReader::Simple(reader) => {
-                let trees = reader.read().unwrap();
+            InnerReader::Simple(trees) => {
                 let tree = if let Some(tree) = trees.get(column_family) {
                     tree
                 } else {
@@ -175,11 +183,49 @@ impl Reader {
         Ok(Iter { iter, current })
     }
 
+    /// Iterates over the keys greater or equal to `start` and lower than `end`, or up to the last key if `end` is `None`.
+    pub fn scan_range(
+        &self,
+        column_family: &ColumnFamily,
+        start: &[u8],
+        end: Option<Vec<u8>>,
+    ) -> Result<Iter, StorageError> {
+        // 只复制 [start, end) 区间中的数据，end 不大于 start 时区间为空（BTreeMap::range 会 panic）
+        let range = |tree: &BTreeMap<Vec<u8>, Vec<u8>>| -> Vec<(Vec<u8>, Vec<u8>)> {
+            let end = match &end {
+                Some(end) if end.as_slice() <= start => return Vec::new(),
+                Some(end) => Bound::Excluded(end.as_slice()),
+                None => Bound::Unbounded,
+            };
+            tree.range::<[u8], _>((Bound::Included(start), end))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        };
+        let data = match &self.0 {
+            InnerReader::Simple(reader) => reader
+                .get(column_family)
+                .map_or_else(Vec::new, |tree| range(tree)),
+            InnerReader::Transaction(reader) => {
+                if let Some(reader) = reader.upgrade() {
+                    (*reader)
+                        .borrow()
+                        .get(column_family)
+                        .map_or_else(Vec::new, |tree| range(tree))
+                } else {
+                    return Err(StorageError::Other(
+                        "The transaction is already ended".into(),
+                    ));
+                }
+            }
+        };
+        let mut iter = data.into_iter();
+        let current = iter.next();
+        Ok(Iter { iter, current })
+    }
+
     pub fn len(&self, column_family: &ColumnFamily) -> Result<usize, StorageError> {
         match &self.0 {
             InnerReader::Simple(reader) => Ok(reader
-                .read()
-                .unwrap()
                 .get(column_family)
                 .map_or(0, |tree| tree.len())),
             InnerReader::Transaction(reader) => {
@@ -200,8 +246,6 @@ impl Reader {
     pub fn is_empty(&self, column_family: &ColumnFamily) -> Result<bool, StorageError> {
         match &self.0 {
             InnerReader::Simple(reader) => Ok(reader
-                .read()
-                .unwrap()
                 .get(column_family)
                 .map_or(true, |tree| tree.is_empty())),
             InnerReader::Transaction(reader) => {
@@ -220,9 +264,7 @@ impl Reader {
     }
 }
 
-pub struct Transaction<'a>(
-    Rc<RefCell<RwLockWriteGuard<'a, HashMap<ColumnFamily, BTreeMap<Vec<u8>, Vec<u8>>>>>>,
-);
+pub struct Transaction<'a>(Rc<RefCell<RwLockWriteGuard<'a, Trees>>>);
 
 impl Transaction<'_> {
     #[allow(unsafe_code)]
@@ -233,6 +275,11 @@ impl Transaction<'_> {
         })))
     }
 
+    /// The transaction holds the write lock of the whole database, so it already sees the latest data.
+    pub fn latest_reader(&self) -> Reader {
+        self.reader()
+    }
+
     pub fn contains_key_for_update(
         &self,
         column_family: &ColumnFamily,
@@ -250,10 +297,7 @@ impl Transaction<'_> {
         key: &[u8],
         value: &[u8],
     ) -> Result<(), StorageError> {
-        self.0
-            .borrow_mut()
-            .get_mut(column_family)
-            .unwrap()
+        Arc::make_mut(self.0.borrow_mut().get_mut(column_family).unwrap())
             .insert(key.into(), value.into());
         Ok(())
     }
@@ -267,11 +311,7 @@ impl Transaction<'_> {
     }
 
     pub fn remove(&mut self, column_family: &ColumnFamily, key: &[u8]) -> Result<(), StorageError> {
-        self.0
-            .borrow_mut()
-            .get_mut(column_family)
-            .unwrap()
-            .remove(key);
+        Arc::make_mut(self.0.borrow_mut().get_mut(column_family).unwrap()).remove(key);
         Ok(())
     }
 }
@@ -294,6 +334,19 @@ impl Iter {
         self.current = self.iter.next();
     }
 
+    /// Moves the iterator to the first key greater or equal to `key`.
+    ///
+    /// Only seeking forward is supported.
+    pub fn seek(&mut self, key: &[u8]) {
+        while self
+            .current
+            .as_ref()
+            .map_or(false, |(k, _)| k.as_slice() < key)
+        {
+            self.current = self.iter.next();
+        }
+    }
+
     pub fn status(&self) -> Result<(), StorageError> {
         Ok(())
     }
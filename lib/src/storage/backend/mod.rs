@@ -5,9 +5,46 @@
 pub use fallback::{ColumnFamily, ColumnFamilyDefinition, Db, Iter, Reader, Transaction};
 #[cfg(not(target_arch = "wasm32"))]
 pub use rocksdb::{
-    ColumnFamily, ColumnFamilyDefinition, Db, Iter, Reader, SstFileWriter, Transaction,
+    ColumnFamily, ColumnFamilyDefinition, Db, Iter, Reader, SharedSnapshot, SstFile,
+    SstFileWriter, StorageOptions, Transaction, WriteBatch,
 };
 
+/// The column families of the storage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum StorageColumnFamily {
+    Default,
+    Id2str,
+    Spog,
+    Posg,
+    Ospg,
+    Gspo,
+    Gpos,
+    Gosp,
+    Dspo,
+    Dpos,
+    Dosp,
+    Graphs,
+    Trees,
+}
+
+impl StorageColumnFamily {
+    pub const ALL: [Self; 13] = [
+        Self::Default,
+        Self::Id2str,
+        Self::Spog,
+        Self::Posg,
+        Self::Ospg,
+        Self::Gspo,
+        Self::Gpos,
+        Self::Gosp,
+        Self::Dspo,
+        Self::Dpos,
+        Self::Dosp,
+        Self::Graphs,
+        Self::Trees,
+    ];
+}
+
 #[cfg(target_arch = "wasm32")]
 mod fallback;
 #[cfg(not(target_arch = "wasm32"))]
@@ -2,7 +2,8 @@
 
 #![allow(unsafe_code, trivial_casts)]
 
-use crate::storage::error::StorageError;
+use crate::storage::error::{has_transient_source, StorageError, TransientError};
+use crate::storage::backend::StorageColumnFamily;
 use crate::store::CorruptionError;
 use lazy_static::lazy_static;
 use libc::{self, c_char, c_void, free};
@@ -15,14 +16,16 @@ use std::env::temp_dir;
 use std::error::Error;
 use std::ffi::{CStr, CString};
 use std::fmt;
-use std::fs::remove_dir_all;
+use std::fs::{remove_dir_all, remove_file};
 use std::io;
 use std::marker::PhantomData;
+use std::mem::take;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::rc::{Rc, Weak};
 use std::sync::Arc;
-use std::thread::{available_parallelism, yield_now};
+use std::thread::{available_parallelism, sleep, yield_now};
+use std::time::Duration;
 use std::{ptr, slice};
 
 // 自定义宏
@@ -76,6 +79,35 @@ pub struct ColumnFamilyDefinition {
     pub use_iter: bool,
     pub min_prefix_size: usize,
     pub unordered_writes: bool,
+    /// Bits per key of a whole-key Bloom filter on the SST files, `None` keeps the default table options.
+    ///
+    /// Column families with `use_iter: false` already get a 10 bits per key filter from the point lookup optimization.
+    pub bloom_filter_bits: Option<f64>,
+}
+
+/// Tuning of the RocksDB instance. `None` keeps the default computed from the machine resources.
+///
+/// The options are only read when the database is opened: changing them requires reopening it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StorageOptions {
+    /// Size in bytes of the LRU block cache shared by all the column families.
+    ///
+    /// If it is not set the point lookup column families like `id2str` keep their own cache.
+    pub block_cache_size: Option<usize>,
+    /// Bits per key of the whole-key Bloom filter of the `id2str` column family.
+    ///
+    /// If it is not set `id2str` keeps the 10 bits per key filter of the point lookup optimization.
+    pub id2str_bloom_filter_bits: Option<u32>,
+    /// Maximal number of concurrent flushes and compactions.
+    pub max_background_jobs: Option<i32>,
+    /// Size in bytes of a memtable before it is flushed to disk.
+    pub write_buffer_size: Option<usize>,
+    /// Maximal number of files kept open. It is capped by the number of file descriptors allowed to the process.
+    pub max_open_files: Option<i32>,
+    /// Size in bytes of the fixed prefix extractor of some column families, replacing the default one.
+    ///
+    /// `0` disables the prefix extractor. The sizes are checked against the key encoding when the storage is opened.
+    pub min_prefix_sizes: Vec<(StorageColumnFamily, usize)>,
 }
 
 // Arc原子引用计数，能够以线程安全的方式在线程间共享不可变数据
@@ -92,7 +124,7 @@ unsafe impl Send for Db {}
 unsafe impl Sync for Db {}
 
 struct DbHandler {
-    db: *mut rocksdb_transactiondb_t,
+    db: DbKind,
     options: *mut rocksdb_options_t,
     transaction_options: *mut rocksdb_transaction_options_t,
     transactiondb_options: *mut rocksdb_transactiondb_options_t,
@@ -110,6 +142,24 @@ struct DbHandler {
     in_memory: bool,
 }
 
+// 可读写的数据库使用 TransactionDB，只读的数据库使用普通的 DB
+#[derive(Clone, Copy)]
+enum DbKind {
+    ReadWrite(*mut rocksdb_transactiondb_t),
+    ReadOnly(*mut rocksdb_t),
+}
+
+impl DbHandler {
+    fn read_write_db(&self) -> Result<*mut rocksdb_transactiondb_t, StorageError> {
+        match self.db {
+            DbKind::ReadWrite(db) => Ok(db),
+            DbKind::ReadOnly(_) => Err(StorageError::Other(
+                "The database has been opened in read-only mode and is not writable".into(),
+            )),
+        }
+    }
+}
+
 // 自定义实现当 DbHandler 实例离开作用域时调用的 drop 方法
 impl Drop for DbHandler {
     fn drop(&mut self) {
@@ -117,7 +167,10 @@ impl Drop for DbHandler {
             for cf_handle in &self.cf_handles {
                 rocksdb_column_family_handle_destroy(*cf_handle);
             }
-            rocksdb_transactiondb_close(self.db);
+            match self.db {
+                DbKind::ReadWrite(db) => rocksdb_transactiondb_close(db),
+                DbKind::ReadOnly(db) => rocksdb_close(db),
+            }
             for cf_option in &self.cf_options {
                 rocksdb_options_destroy(*cf_option);
             }
@@ -150,21 +203,61 @@ impl Db {
             temp_dir()// 返回临时目录的路径
         }
         .join(format!("oxigraph-rocksdb-{}", random::<u128>()));
-        Ok(Self(Arc::new(Self::do_open(path, column_families, true)?)))
+        Ok(Self(Arc::new(Self::do_open(
+            path,
+            column_families,
+            &StorageOptions::default(),
+            true,
+            false,
+        )?)))
     }
 
     pub fn open(
         path: &Path,
         column_families: Vec<ColumnFamilyDefinition>,
     ) -> Result<Self, StorageError> {
-        Ok(Self(Arc::new(Self::do_open(path.to_owned(),column_families,false,)?)))            
+        Self::open_with_options(path, column_families, &StorageOptions::default())
+    }
+
+    pub fn open_with_options(
+        path: &Path,
+        column_families: Vec<ColumnFamilyDefinition>,
+        storage_options: &StorageOptions,
+    ) -> Result<Self, StorageError> {
+        Ok(Self(Arc::new(Self::do_open(
+            path.to_owned(),
+            column_families,
+            storage_options,
+            false,
+            false,
+        )?)))
+    }
+
+    // 以只读模式打开，不会获取数据库的写锁
+    pub fn open_read_only(
+        path: &Path,
+        column_families: Vec<ColumnFamilyDefinition>,
+    ) -> Result<Self, StorageError> {
+        Ok(Self(Arc::new(Self::do_open(
+            path.to_owned(),
+            column_families,
+            &StorageOptions::default(),
+            false,
+            true,
+        )?)))
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.0.db, DbKind::ReadOnly(_))
     }
 
     // TODO：创建返回了 DbHandler 实例，其中的细节还没看
     fn do_open(
         path: PathBuf,
         mut column_families: Vec<ColumnFamilyDefinition>,
+        storage_options: &StorageOptions,
         in_memory: bool,
+        read_only: bool,
     ) -> Result<DbHandler, StorageError> {
         let c_path = path_to_cstring(&path)?;
 
@@ -178,6 +271,12 @@ impl Db {
                 options,
                 available_parallelism()?.get().try_into().unwrap(),
             );
+            if let Some(max_background_jobs) = storage_options.max_background_jobs {
+                rocksdb_options_set_max_background_jobs(options, max_background_jobs);
+            }
+            if let Some(write_buffer_size) = storage_options.write_buffer_size {
+                rocksdb_options_set_write_buffer_size(options, write_buffer_size);
+            }
             if let Some(available_fd) = available_file_descriptors()? {
                 if available_fd < 96 {
                     rocksdb_options_destroy(options);
@@ -190,10 +289,15 @@ impl Db {
                     )
                     .into());
                 }
+                let max_open_files: i32 = (available_fd - 48).try_into().unwrap();
                 rocksdb_options_set_max_open_files(
                     options,
-                    (available_fd - 48).try_into().unwrap(),
+                    storage_options
+                        .max_open_files
+                        .map_or(max_open_files, |m| min(m, max_open_files)),
                 );
+            } else if let Some(max_open_files) = storage_options.max_open_files {
+                rocksdb_options_set_max_open_files(options, max_open_files);
             }
             rocksdb_options_set_info_log_level(options, 2); // We only log warnings
             rocksdb_options_set_max_log_file_size(options, 1024 * 1024); // Only 1MB log size
@@ -226,6 +330,13 @@ impl Db {
                 block_based_table_options,
                 16,
             );
+            // 所有列族共享同一个 cache，由各 table options 共享持有，列族的选项创建之后释放
+            let block_cache = storage_options
+                .block_cache_size
+                .map(|block_cache_size| rocksdb_cache_create_lru(block_cache_size));
+            if let Some(cache) = block_cache {
+                rocksdb_block_based_options_set_block_cache(block_based_table_options, cache);
+            }
             rocksdb_options_set_block_based_table_factory(options, block_based_table_options);
 
             let transactiondb_options = rocksdb_transactiondb_options_create();
@@ -240,6 +351,7 @@ impl Db {
                     use_iter: true,
                     min_prefix_size: 0,
                     unordered_writes: false,
+                    bloom_filter_bits: None,
                 })
             }
             let column_family_names = column_families.iter().map(|c| c.name).collect::<Vec<_>>();   // 获取一个闭包并创建一个迭代器，该迭代器在每个元素上调用该闭包（这个迭代器是cf的名字vec）
@@ -264,25 +376,86 @@ impl Db {
                     if cf.unordered_writes {
                         rocksdb_options_set_unordered_write(options, 1);
                     }
+                    // 点查询列族在设置了共享 cache 时也要替换 optimize_for_point_lookup 创建的 cache
+                    if cf.bloom_filter_bits.is_some() || (!cf.use_iter && block_cache.is_some()) {
+                        // table factory 会拷贝这些选项，filter policy 与 cache 由其共享持有，因此这里可以直接释放
+                        let table_options = rocksdb_block_based_options_create();
+                        assert!(
+                            !table_options.is_null(),
+                            "rocksdb_block_based_options_create returned null"
+                        );
+                        rocksdb_block_based_options_set_format_version(table_options, 5);
+                        rocksdb_block_based_options_set_index_block_restart_interval(
+                            table_options,
+                            16,
+                        );
+                        if !cf.use_iter {
+                            // 保留 optimize_for_point_lookup 设置的哈希索引与 block cache
+                            rocksdb_block_based_options_set_data_block_index_type(
+                                table_options,
+                                rocksdb_block_based_table_data_block_index_type_binary_search_and_hash
+                                    .try_into()
+                                    .unwrap(),
+                            );
+                            rocksdb_block_based_options_set_data_block_hash_ratio(table_options, 0.75);
+                            match block_cache {
+                                Some(cache) => {
+                                    rocksdb_block_based_options_set_block_cache(table_options, cache)
+                                }
+                                None => {
+                                    let cache = rocksdb_cache_create_lru(128 * 1024 * 1024);
+                                    rocksdb_block_based_options_set_block_cache(table_options, cache);
+                                    rocksdb_cache_destroy(cache);
+                                }
+                            }
+                        } else if let Some(cache) = block_cache {
+                            rocksdb_block_based_options_set_block_cache(table_options, cache);
+                        }
+                        // 没有设置 bloom_filter_bits 时使用 optimize_for_point_lookup 的默认值
+                        rocksdb_block_based_options_set_filter_policy(
+                            table_options,
+                            rocksdb_filterpolicy_create_bloom_full(cf.bloom_filter_bits.unwrap_or(10.)),
+                        );
+                        rocksdb_block_based_options_set_whole_key_filtering(table_options, 1);
+                        rocksdb_options_set_block_based_table_factory(options, table_options);
+                        rocksdb_block_based_options_destroy(table_options);
+                    }
                     options
                 })
                 .collect::<Vec<_>>();
+            if let Some(cache) = block_cache {
+                rocksdb_cache_destroy(cache);
+            }
 
             let mut cf_handles: Vec<*mut rocksdb_column_family_handle_t> =
                 vec![ptr::null_mut(); column_family_names.len()];
-            let db = ffi_result!(rocksdb_transactiondb_open_column_families_with_status(
-                options,
-                transactiondb_options,
-                c_path.as_ptr(),
-                c_column_families.len().try_into().unwrap(),
-                c_column_families
-                    .iter()
-                    .map(|cf| cf.as_ptr())
-                    .collect::<Vec<_>>()
-                    .as_ptr(),
-                cf_options.as_ptr() as *const *const rocksdb_options_t,
-                cf_handles.as_mut_ptr(),
-            ))
+            let c_column_family_names = c_column_families
+                .iter()
+                .map(|cf| cf.as_ptr())
+                .collect::<Vec<_>>();
+            let db = if read_only {
+                ffi_result!(rocksdb_open_for_read_only_column_families_with_status(
+                    options,
+                    c_path.as_ptr(),
+                    c_column_families.len().try_into().unwrap(),
+                    c_column_family_names.as_ptr(),
+                    cf_options.as_ptr() as *const *const rocksdb_options_t,
+                    cf_handles.as_mut_ptr(),
+                    0, // The writer process might have a live WAL
+                ))
+                .map(DbKind::ReadOnly)
+            } else {
+                ffi_result!(rocksdb_transactiondb_open_column_families_with_status(
+                    options,
+                    transactiondb_options,
+                    c_path.as_ptr(),
+                    c_column_families.len().try_into().unwrap(),
+                    c_column_family_names.as_ptr(),
+                    cf_options.as_ptr() as *const *const rocksdb_options_t,
+                    cf_handles.as_mut_ptr(),
+                ))
+                .map(DbKind::ReadWrite)
+            }
             .map_err(|e| {
                 for cf_option in &cf_options {
                     rocksdb_options_destroy(*cf_option);
@@ -292,7 +465,13 @@ impl Db {
                 rocksdb_block_based_options_destroy(block_based_table_options);
                 e
             })?;
-            assert!(!db.is_null(), "rocksdb_create returned null");
+            assert!(
+                match db {
+                    DbKind::ReadWrite(db) => !db.is_null(),
+                    DbKind::ReadOnly(db) => !db.is_null(),
+                },
+                "rocksdb_create returned null"
+            );
             for handle in &cf_handles {
                 assert!(
                     !handle.is_null(),
@@ -381,16 +560,19 @@ impl Db {
     #[must_use]
     pub fn snapshot(&self) -> Reader {
         unsafe {
-            let snapshot = rocksdb_transactiondb_create_snapshot(self.0.db);
+            let snapshot = match self.0.db {
+                DbKind::ReadWrite(db) => rocksdb_transactiondb_create_snapshot(db),
+                DbKind::ReadOnly(db) => rocksdb_create_snapshot(db),
+            };
             assert!(
                 !snapshot.is_null(),
-                "rocksdb_transactiondb_create_snapshot returned null"
+                "rocksdb_create_snapshot returned null"
             );
             let options = rocksdb_readoptions_create_copy(self.0.read_options);
             rocksdb_readoptions_set_snapshot(options, snapshot);
 
             Reader {
-                inner: InnerReader::Snapshot(Rc::new(InnerSnapshot {
+                inner: InnerReader::Snapshot(Arc::new(InnerSnapshot {
                     db: self.0.clone(),
                     snapshot,
                 })),
@@ -404,10 +586,31 @@ impl Db {
         &'b self,
         f: impl Fn(Transaction<'a>) -> Result<T, E>,
     ) -> Result<T, E> {
+        self.run_transaction(None, f)
+    }
+
+    /// Same as [`transaction`](Self::transaction) but gives up after `max_retries` conflicts
+    /// and waits exponentially longer after each of them.
+    pub fn transaction_with_retry<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        max_retries: usize,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.run_transaction(Some(max_retries), f)
+    }
+
+    // max_retries 为 None 时冲突后无限重试
+    fn run_transaction<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        max_retries: Option<usize>,
+        f: impl Fn(Transaction<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let db = self.0.read_write_db()?;
+        let mut retries = 0;
         loop {
             let transaction = unsafe {
                 let transaction = rocksdb_transaction_begin(    // 开启事务
-                    self.0.db,
+                    db,
                     self.0.write_options,
                     self.0.transaction_options,
                     ptr::null_mut(),
@@ -451,20 +654,18 @@ impl Db {
                         free(snapshot as *mut c_void);
                         r.map_err(StorageError::from)?; // We make sure to also run destructors if the commit fails
                     }
-                    // We look for the root error
-                    let mut error: &(dyn Error + 'static) = &e;
-                    while let Some(e) = error.source() {
-                        error = e;
-                    }
-                    let is_conflict_error =
-                        error.downcast_ref::<ErrorStatus>().map_or(false, |e| {
-                            e.0.code == rocksdb_status_code_t_rocksdb_status_code_busy
-                                || e.0.code == rocksdb_status_code_t_rocksdb_status_code_timed_out
-                                || e.0.code == rocksdb_status_code_t_rocksdb_status_code_try_again
-                        });
-                    if is_conflict_error {
-                        // We give a chance to the OS to do something else before retrying in order to help avoiding another conflict
-                        yield_now();
+                    // The conflicts are wrapped in a TransientError when converted to a StorageError
+                    if has_transient_source(&e) {
+                        match max_retries {
+                            // We give a chance to the OS to do something else before retrying in order to help avoiding another conflict
+                            None => yield_now(),
+                            // 指数退避：1ms、2ms、4ms……，最多等待约 1s
+                            Some(max_retries) if retries < max_retries => {
+                                sleep(Duration::from_millis(1 << min(retries, 10)))
+                            }
+                            Some(_) => return Err(e),
+                        }
+                        retries += 1;
                     } else {
                         // We raise the error
                         return Err(e);
@@ -481,13 +682,24 @@ impl Db {
         key: &[u8],
     ) -> Result<Option<PinnableSlice>, StorageError> {
         unsafe {
-            let slice = ffi_result!(rocksdb_transactiondb_get_pinned_cf_with_status(
-                self.0.db,
-                self.0.read_options,
-                column_family.0,
-                key.as_ptr() as *const c_char,
-                key.len()
-            ))?;
+            let slice = match self.0.db {
+                DbKind::ReadWrite(db) => {
+                    ffi_result!(rocksdb_transactiondb_get_pinned_cf_with_status(
+                        db,
+                        self.0.read_options,
+                        column_family.0,
+                        key.as_ptr() as *const c_char,
+                        key.len()
+                    ))
+                }
+                DbKind::ReadOnly(db) => ffi_result!(rocksdb_get_pinned_cf_with_status(
+                    db,
+                    self.0.read_options,
+                    column_family.0,
+                    key.as_ptr() as *const c_char,
+                    key.len()
+                )),
+            }?;
             Ok(if slice.is_null() {
                 None
             } else {
@@ -504,6 +716,65 @@ impl Db {
         Ok(self.get(column_family, key)?.is_some()) //TODO: optimize
     }
 
+    /// Looks up several keys of a column family at once, the results are in the order of the keys.
+    pub fn multi_get(
+        &self,
+        column_family: &ColumnFamily,
+        keys: &[&[u8]],
+    ) -> Result<Vec<Option<PinnableSlice>>, StorageError> {
+        let keys_list = keys
+            .iter()
+            .map(|k| k.as_ptr() as *const c_char)
+            .collect::<Vec<_>>();
+        let keys_list_sizes = keys.iter().map(|k| k.len()).collect::<Vec<_>>();
+        let mut values = vec![ptr::null_mut(); keys.len()];
+        let mut statuses = (0..keys.len())
+            .map(|_| rocksdb_status_t {
+                code: rocksdb_status_code_t_rocksdb_status_code_ok,
+                subcode: rocksdb_status_subcode_t_rocksdb_status_subcode_none,
+                severity: rocksdb_status_severity_t_rocksdb_status_severity_none,
+                string: ptr::null(),
+            })
+            .collect::<Vec<_>>();
+        unsafe {
+            match self.0.db {
+                DbKind::ReadWrite(db) => rocksdb_transactiondb_multi_get_pinned_cf_with_status(
+                    db,
+                    self.0.read_options,
+                    column_family.0,
+                    keys.len(),
+                    keys_list.as_ptr(),
+                    keys_list_sizes.as_ptr(),
+                    values.as_mut_ptr(),
+                    statuses.as_mut_ptr(),
+                ),
+                DbKind::ReadOnly(db) => rocksdb_multi_get_pinned_cf_with_status(
+                    db,
+                    self.0.read_options,
+                    column_family.0,
+                    keys.len(),
+                    keys_list.as_ptr(),
+                    keys_list_sizes.as_ptr(),
+                    values.as_mut_ptr(),
+                    statuses.as_mut_ptr(),
+                ),
+            }
+        }
+        // 先接管所有的 slice 与错误信息，保证出错时它们也会被释放
+        let values = values
+            .into_iter()
+            .map(|slice| (!slice.is_null()).then(|| PinnableSlice(slice)))
+            .collect::<Vec<_>>();
+        let mut statuses = statuses.into_iter().map(ErrorStatus).collect::<Vec<_>>();
+        if let Some(i) = statuses
+            .iter()
+            .position(|status| status.0.code != rocksdb_status_code_t_rocksdb_status_code_ok)
+        {
+            return Err(statuses.swap_remove(i).into());
+        }
+        Ok(values)
+    }
+
     // insert 函数，但是键和值是u8类型（1bytes）
     // 将（key,value）绑定到对应的 cf 上
     pub fn insert(
@@ -512,9 +783,10 @@ impl Db {
         key: &[u8],
         value: &[u8],
     ) -> Result<(), StorageError> {
+        let db = self.0.read_write_db()?;
         unsafe {
             ffi_result!(rocksdb_transactiondb_put_cf_with_status(
-                self.0.db,
+                db,
                 self.0.write_options,
                 column_family.0,
                 key.as_ptr() as *const c_char,
@@ -528,9 +800,10 @@ impl Db {
 
     // 将 immutable Memory Table 中的数据 flush 到 SST（Sorted String Table） 中
     pub fn flush(&self, column_family: &ColumnFamily) -> Result<(), StorageError> {
+        let db = self.0.read_write_db()?;
         unsafe {
             ffi_result!(rocksdb_transactiondb_flush_cf_with_status(
-                self.0.db,
+                db,
                 self.0.flush_options,
                 column_family.0,
             ))?;
@@ -541,15 +814,28 @@ impl Db {
     // 当 Level 0 层的 SST 文件个数超过阈值之后，就会通过 Compaction 策略将其放到 Level 1 层，以此类推，直到最底层
     #[allow(clippy::unnecessary_wraps)]
     pub fn compact(&self, column_family: &ColumnFamily) -> Result<(), StorageError> {
+        self.compact_range(column_family, None, None)
+    }
+
+    /// Compacts the keys of `column_family` between `start` and `end`, an unbounded side when `None`.
+    ///
+    /// RocksDB rewrites all the table files overlapping the range so keys slightly outside of it may be compacted too.
+    pub fn compact_range(
+        &self,
+        column_family: &ColumnFamily,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+    ) -> Result<(), StorageError> {
+        let db = self.0.read_write_db()?;
         unsafe {
             ffi_result!(rocksdb_transactiondb_compact_range_cf_opt_with_status(
-                self.0.db,
+                db,
                 column_family.0,
                 self.0.compaction_options,
-                ptr::null(),
-                0,
-                ptr::null(),
-                0,
+                start.map_or(ptr::null(), |k| k.as_ptr() as *const c_char),
+                start.map_or(0, <[u8]>::len),
+                end.map_or(ptr::null(), |k| k.as_ptr() as *const c_char),
+                end.map_or(0, <[u8]>::len),
             ))?;
         }
         Ok(())
@@ -574,8 +860,9 @@ impl Db {
     // TODO：这个方法是做什么呢
     pub fn insert_stt_files(
         &self,
-        ssts_for_cf: &[(&ColumnFamily, PathBuf)],
+        ssts_for_cf: &[(&ColumnFamily, SstFile)],
     ) -> Result<(), StorageError> {
+        let db = self.0.read_write_db()?;
         let mut paths_by_cf = HashMap::<_, Vec<_>>::new();
         for (cf, path) in ssts_for_cf {
             paths_by_cf
@@ -598,7 +885,7 @@ impl Db {
             .collect::<Vec<_>>();
         unsafe {
             ffi_result!(rocksdb_transactiondb_ingest_external_files_with_status(
-                self.0.db,
+                db,
                 args.as_ptr(),
                 args.len()
             ))?;
@@ -606,6 +893,28 @@ impl Db {
         Ok(())
     }
 
+    pub fn new_write_batch(&self) -> WriteBatch {
+        let batch = unsafe { rocksdb_writebatch_create() };
+        assert!(!batch.is_null(), "rocksdb_writebatch_create returned null");
+        WriteBatch(batch)
+    }
+
+    /// Applies the batch immediately, without taking the transaction locks.
+    ///
+    /// It is the only way to apply range deletions on a `TransactionDB`:
+    /// the caller must ensure no concurrent transaction writes the same keys.
+    pub fn write_batch(&self, batch: WriteBatch) -> Result<(), StorageError> {
+        let db = self.0.read_write_db()?;
+        unsafe {
+            ffi_result!(rocksdb_transactiondb_write_without_concurrency_control_with_status(
+                db,
+                self.0.write_options,
+                batch.0
+            ))?;
+        }
+        Ok(())
+    }
+
     pub fn backup(&self, target_directory: &Path) -> Result<(), StorageError> {
         if self.0.in_memory {
             return Err(StorageError::Other(
@@ -613,15 +922,78 @@ impl Db {
                     .into(),
             ));
         }
+        let db = self.0.read_write_db()?;
         let path = path_to_cstring(target_directory)?;
         unsafe {
             ffi_result!(rocksdb_transactiondb_create_checkpoint_with_status(
-                self.0.db,
+                db,
+                path.as_ptr()
+            ))?;
+        }
+        Ok(())
+    }
+
+    // 使用 RocksDB 的 BackupEngine，同一目录下的备份共享 SST 文件，返回新备份的编号
+    pub fn backup_incremental(&self, backup_directory: &Path) -> Result<u32, StorageError> {
+        if self.0.in_memory {
+            return Err(StorageError::Other(
+                "It is not possible to make an incremental backup of an in-memory database"
+                    .into(),
+            ));
+        }
+        let db = self.0.read_write_db()?;
+        let path = path_to_cstring(backup_directory)?;
+        unsafe {
+            Ok(ffi_result!(rocksdb_transactiondb_create_new_backup_with_status(
+                db,
                 path.as_ptr()
+            ))?)
+        }
+    }
+
+    // 将 backup_directory 中的一个备份恢复到 target_directory，目标数据库不能处于打开状态
+    pub fn restore_from_backup(
+        backup_directory: &Path,
+        target_directory: &Path,
+        backup_id: u32,
+    ) -> Result<(), StorageError> {
+        let backup_path = path_to_cstring(backup_directory)?;
+        let target_path = path_to_cstring(target_directory)?;
+        unsafe {
+            ffi_result!(rocksdb_restore_db_from_backup_with_status(
+                backup_path.as_ptr(),
+                target_path.as_ptr(),
+                backup_id
             ))?;
         }
         Ok(())
     }
+
+    /// Reads an integer property like `rocksdb.total-sst-files-size` of a column family.
+    ///
+    /// Returns `None` if RocksDB does not know the property.
+    pub fn property_int(
+        &self,
+        column_family: &ColumnFamily,
+        name: &str,
+    ) -> Result<Option<u64>, StorageError> {
+        let name = CString::new(name).map_err(|e| StorageError::Other(e.into()))?;
+        let mut value = 0;
+        let status = unsafe {
+            match self.0.db {
+                DbKind::ReadWrite(db) => rocksdb_transactiondb_property_int_cf(
+                    db,
+                    column_family.0,
+                    name.as_ptr(),
+                    &mut value,
+                ),
+                DbKind::ReadOnly(db) => {
+                    rocksdb_property_int_cf(db, column_family.0, name.as_ptr(), &mut value)
+                }
+            }
+        };
+        Ok(if status == 0 { Some(value) } else { None })
+    }
 }
 
 // It is fine to not keep a lifetime: there is no way to use this type without the database being still in scope.
@@ -641,7 +1013,7 @@ pub struct Reader {
 
 #[derive(Clone)]
 enum InnerReader {
-    Snapshot(Rc<InnerSnapshot>),
+    Snapshot(Arc<InnerSnapshot>),
     Transaction(Weak<*mut rocksdb_transaction_t>),
 }
 
@@ -651,9 +1023,19 @@ struct InnerSnapshot {
     snapshot: *const rocksdb_snapshot_t,
 }
 
+// RocksDB 的快照是不可变的，可以在多个线程中同时读取
+unsafe impl Send for InnerSnapshot {}
+
+unsafe impl Sync for InnerSnapshot {}
+
 impl Drop for InnerSnapshot {
     fn drop(&mut self) {
-        unsafe { rocksdb_transactiondb_release_snapshot(self.db.db, self.snapshot) }
+        unsafe {
+            match self.db.db {
+                DbKind::ReadWrite(db) => rocksdb_transactiondb_release_snapshot(db, self.snapshot),
+                DbKind::ReadOnly(db) => rocksdb_release_snapshot(db, self.snapshot),
+            }
+        }
     }
 }
 
@@ -672,7 +1054,33 @@ impl Drop for Reader {
     }
 }
 
+/// A snapshot of a [`Reader`] that can be sent to other threads to read the same state of the database.
+#[derive(Clone)]
+pub struct SharedSnapshot(Arc<InnerSnapshot>);
+
+impl SharedSnapshot {
+    #[must_use]
+    pub fn reader(&self) -> Reader {
+        unsafe {
+            let options = rocksdb_readoptions_create_copy(self.0.db.read_options);
+            rocksdb_readoptions_set_snapshot(options, self.0.snapshot);
+            Reader {
+                inner: InnerReader::Snapshot(Arc::clone(&self.0)),
+                options,
+            }
+        }
+    }
+}
+
 impl Reader {
+    /// Returns the snapshot read by this reader or `None` if it reads a transaction.
+    pub fn shared_snapshot(&self) -> Option<SharedSnapshot> {
+        match &self.inner {
+            InnerReader::Snapshot(inner) => Some(SharedSnapshot(Arc::clone(inner))),
+            InnerReader::Transaction(_) => None,
+        }
+    }
+
     // 对 InnerReader 分开讨论，分为 Snapshot 和 Transaction
     pub fn get(
         &self,
@@ -681,15 +1089,24 @@ impl Reader {
     ) -> Result<Option<PinnableSlice>, StorageError> {
         unsafe {
             let slice = match &self.inner {
-                InnerReader::Snapshot(inner) => {
-                    ffi_result!(rocksdb_transactiondb_get_pinned_cf_with_status(
-                        inner.db.db,
+                InnerReader::Snapshot(inner) => match inner.db.db {
+                    DbKind::ReadWrite(db) => {
+                        ffi_result!(rocksdb_transactiondb_get_pinned_cf_with_status(
+                            db,
+                            self.options,
+                            column_family.0,
+                            key.as_ptr() as *const c_char,
+                            key.len()
+                        ))
+                    }
+                    DbKind::ReadOnly(db) => ffi_result!(rocksdb_get_pinned_cf_with_status(
+                        db,
                         self.options,
                         column_family.0,
                         key.as_ptr() as *const c_char,
                         key.len()
-                    ))
-                }
+                    )),
+                },
                 InnerReader::Transaction(inner) => {
                     if let Some(inner) = inner.upgrade() {   // upgrade将Weak指针升级到Rc，如果成功，则延迟丢弃内部值；如果内部值已经被丢弃，则返回 None
                         ffi_result!(rocksdb_transaction_get_pinned_cf_with_status(
@@ -751,7 +1168,18 @@ impl Reader {
                 None
             }
         };
+        self.scan_range(column_family, prefix, upper_bound)
+    }
 
+    /// Iterates over the keys greater or equal to `start` and lower than `end`, or up to the last key if `end` is `None`.
+    pub fn scan_range(
+        &self,
+        column_family: &ColumnFamily,
+        start: &[u8],
+        end: Option<Vec<u8>>,
+    ) -> Result<Iter, StorageError> {
+        // end 需要和迭代器活得一样久，由 Iter 持有
+        let upper_bound = end;
         unsafe {
             let options = rocksdb_readoptions_create_copy(self.options);
             assert!(
@@ -767,9 +1195,12 @@ impl Reader {
                 );
             }
             let iter = match &self.inner {
-                InnerReader::Snapshot(inner) => {
-                    rocksdb_transactiondb_create_iterator_cf(inner.db.db, options, column_family.0)
-                }
+                InnerReader::Snapshot(inner) => match inner.db.db {
+                    DbKind::ReadWrite(db) => {
+                        rocksdb_transactiondb_create_iterator_cf(db, options, column_family.0)
+                    }
+                    DbKind::ReadOnly(db) => rocksdb_create_iterator_cf(db, options, column_family.0),
+                },
                 InnerReader::Transaction(inner) => {
                     if let Some(inner) = inner.upgrade() {
                         rocksdb_transaction_create_iterator_cf(*inner, options, column_family.0)
@@ -781,10 +1212,10 @@ impl Reader {
                 }
             };
             assert!(!iter.is_null(), "rocksdb_create_iterator returned null");
-            if prefix.is_empty() {
+            if start.is_empty() {
                 rocksdb_iter_seek_to_first(iter);
             } else {
-                rocksdb_iter_seek(iter, prefix.as_ptr() as *const c_char, prefix.len());
+                rocksdb_iter_seek(iter, start.as_ptr() as *const c_char, start.len());
             }
             let is_currently_valid = rocksdb_iter_valid(iter) != 0;
             Ok(Iter {
@@ -833,6 +1264,17 @@ impl Transaction<'_> {
         }
     }
 
+    /// Returns a reader that sees the latest committed data and the writes of this transaction,
+    /// and not the snapshot taken when the transaction started.
+    pub fn latest_reader(&self) -> Reader {
+        let options = unsafe { rocksdb_readoptions_create_copy(self.read_options) };
+        unsafe { rocksdb_readoptions_set_snapshot(options, ptr::null()) };
+        Reader {
+            inner: InnerReader::Transaction(Rc::downgrade(&self.transaction)),
+            options,
+        }
+    }
+
     pub fn get_for_update(
         &self,
         column_family: &ColumnFamily,
@@ -1033,6 +1475,16 @@ impl Iter {
         }
     }
 
+    /// Moves the iterator to the first key greater or equal to `key`.
+    ///
+    /// The upper bound of the scanned prefix still applies.
+    pub fn seek(&mut self, key: &[u8]) {
+        unsafe {
+            rocksdb_iter_seek(self.iter, key.as_ptr() as *const c_char, key.len());
+            self.is_currently_valid = rocksdb_iter_valid(self.iter) != 0;
+        }
+    }
+
     pub fn key(&self) -> Option<&[u8]> {
         if self.is_valid() {
             unsafe {
@@ -1044,6 +1496,18 @@ impl Iter {
             None
         }
     }
+
+    pub fn value(&self) -> Option<&[u8]> {
+        if self.is_valid() {
+            unsafe {
+                let mut len = 0;
+                let val = rocksdb_iter_value(self.iter, &mut len);
+                Some(slice::from_raw_parts(val as *const u8, len))
+            }
+        } else {
+            None
+        }
+    }
 }
 
 
@@ -1058,6 +1522,10 @@ impl Drop for SstFileWriter {
         unsafe {
             rocksdb_sstfilewriter_destroy(self.writer);
         }
+        // 没有 finish 的文件不会被导入，直接删除；finish 之后路径交给 SstFile 管理
+        if !self.path.as_os_str().is_empty() {
+            let _ = remove_file(&self.path);
+        }
     }
 }
 
@@ -1085,11 +1553,82 @@ impl SstFileWriter {
         self.insert(key, value)
     }
 
-    pub fn finish(self) -> Result<PathBuf, StorageError> {
+    pub fn finish(mut self) -> Result<SstFile, StorageError> {
         unsafe {
             ffi_result!(rocksdb_sstfilewriter_finish_with_status(self.writer))?;
         }
-        Ok(self.path.clone())
+        Ok(SstFile(take(&mut self.path)))
+    }
+}
+
+/// A finished SST file waiting to be ingested with [`Db::insert_stt_files`].
+///
+/// The file is removed when dropped: the ingestion copies it into the database,
+/// and if the ingestion fails or is never reached (an error or a panic while building the other files) it does not leak on disk.
+pub struct SstFile(PathBuf);
+
+impl Deref for SstFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for SstFile {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.0);
+    }
+}
+
+/// A batch of deletions applied atomically with [`Db::write_batch`], outside of any transaction.
+pub struct WriteBatch(*mut rocksdb_writebatch_t);
+
+impl Drop for WriteBatch {
+    fn drop(&mut self) {
+        unsafe {
+            rocksdb_writebatch_destroy(self.0);
+        }
+    }
+}
+
+impl WriteBatch {
+    pub fn insert(&mut self, column_family: &ColumnFamily, key: &[u8], value: &[u8]) {
+        unsafe {
+            rocksdb_writebatch_put_cf(
+                self.0,
+                column_family.0,
+                key.as_ptr() as *const c_char,
+                key.len(),
+                value.as_ptr() as *const c_char,
+                value.len(),
+            );
+        }
+    }
+
+    pub fn remove(&mut self, column_family: &ColumnFamily, key: &[u8]) {
+        unsafe {
+            rocksdb_writebatch_delete_cf(
+                self.0,
+                column_family.0,
+                key.as_ptr() as *const c_char,
+                key.len(),
+            );
+        }
+    }
+
+    /// Removes all the keys in `start..end`.
+    pub fn remove_range(&mut self, column_family: &ColumnFamily, start: &[u8], end: &[u8]) {
+        unsafe {
+            rocksdb_writebatch_delete_range_cf(
+                self.0,
+                column_family.0,
+                start.as_ptr() as *const c_char,
+                start.len(),
+                end.as_ptr() as *const c_char,
+                end.len(),
+            );
+        }
     }
 }
 
@@ -1155,6 +1694,11 @@ impl From<ErrorStatus> for StorageError {
             Self::Io(io::Error::new(kind, status))
         } else if status.0.code == rocksdb_status_code_t_rocksdb_status_code_corruption {
             Self::Corruption(CorruptionError::new(status))
+        } else if status.0.code == rocksdb_status_code_t_rocksdb_status_code_busy
+            || status.0.code == rocksdb_status_code_t_rocksdb_status_code_timed_out
+            || status.0.code == rocksdb_status_code_t_rocksdb_status_code_try_again
+        {
+            Self::Other(Box::new(TransientError::new(status)))
         } else {
             Self::Other(Box::new(status))
         }
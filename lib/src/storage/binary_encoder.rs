@@ -1,8 +1,9 @@
-use crate::storage::numeric_encoder::{EncodedQuad, EncodedTerm, EncodedTriple, StrHash};
+use crate::model::NamedNodeRef;
+use crate::storage::numeric_encoder::{EncodedQuad, EncodedTerm, EncodedTriple, PrefixTable, StrHash, XSD_STRING_ID};
 use crate::storage::small_string::SmallString;
 use crate::storage::StorageError;
 use crate::store::CorruptionError;
-use crate::extendedTree::{MultiTree, MultiTreeNode, extendedTreeNode};
+use crate::extendedTree::{IntervalNode, MultiTree, MultiTreeNode, extendedTreeNode};
 use crate::extendedTree::vocab::{rdf, rdfs, owl, lubm};
 use crate::xsd::*;
 use std::collections::HashMap;
@@ -10,15 +11,44 @@ use std::io::{Cursor, Read};
 use std::mem::size_of;
 use std::rc::Rc;
 
+use std::sync::Arc;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 
+/// Cumulative capacity in bytes of the keys and values built by [`encode_term_triple`] and the `oxiuse` encoders.
+///
+/// It is a one-shot measurement of the size of the encoded data, not of the live memory: the buffers are never subtracted when freed.
+/// It is shared by all the storages of the process, use [`reset_atom_bytes`] before the measured load.
 pub static ATOM_BYTES: AtomicUsize = AtomicUsize::new(0);
 
+/// The current value of [`ATOM_BYTES`].
+pub fn atom_bytes() -> usize {
+    ATOM_BYTES.load(Ordering::SeqCst)
+}
+
+/// Sets [`ATOM_BYTES`] back to zero and returns the bytes counted since the previous reset.
+pub fn reset_atom_bytes() -> usize {
+    ATOM_BYTES.swap(0, Ordering::SeqCst)
+}
+
 #[cfg(not(target_arch = "wasm32"))]
-pub const LATEST_STORAGE_VERSION: u64 = 1;
+pub const LATEST_STORAGE_VERSION: u64 = 2;
 pub const WRITTEN_TERM_MAX_SIZE: usize = size_of::<u8>() + 2 * size_of::<StrHash>();
-pub const INTERVAL_ENCODING_MAX_SIZE: usize = size_of::<u8>() * 19;
+// 一个区间：start(u32) + end(u32) + layer(u16)
+const INTERVAL_SIZE: usize = 2 * size_of::<u32>() + size_of::<u16>();
+/// The maximal number of intervals of a single class, the count is encoded on one byte.
+pub const MAX_ENCODED_INTERVALS: usize = u8::MAX as usize;
+// 所有的区间编码都是：类型 + 一个或两个区间列表，每个区间列表是区间数(u8) + 每个区间(start, end, layer)
+// subClassOf、subPropertyOf：类型 + 子节点在该父节点下的区间列表 + 父节点的所有区间
+// domain、range、type：类型 + 宾语的所有区间
+// 单继承时 subClassOf、subPropertyOf 编码的长度
+pub const HIERARCHY_INTERVAL_ENCODING_SIZE: usize =
+    size_of::<u8>() + 2 * (size_of::<u8>() + INTERVAL_SIZE);
+// 版本 1 中 subClassOf、subPropertyOf 编码里子节点的一个区间：start(u32) + end(u32)，没有 layer
+const LEGACY_CHILD_INTERVAL_SIZE: usize = 2 * size_of::<u32>();
+// 两个区间列表都是最长的时候
+pub const INTERVAL_ENCODING_MAX_SIZE: usize =
+    size_of::<u8>() + 2 * (size_of::<u8>() + MAX_ENCODED_INTERVALS * INTERVAL_SIZE);
 
 // Encoded term type blocks
 // 1-7: usual named nodes (except prefixes c.f. later)
@@ -58,11 +88,66 @@ const TYPE_DURATION_LITERAL: u8 = 42;
 const TYPE_YEAR_MONTH_DURATION_LITERAL: u8 = 43;
 const TYPE_DAY_TIME_DURATION_LITERAL: u8 = 44;
 const TYPE_TRIPLE: u8 = 48;
+// 自定义前缀：类型字节为 128 + 前缀编号
+const TYPE_CUSTOM_PREFIXED_NAMED_NODE_START: u8 = 128;
+pub const MAX_CUSTOM_PREFIXES: usize = 128;
+/// The default maximal nesting of quoted triples accepted by [`TermReader::read_term`].
+pub const DEFAULT_MAX_TRIPLE_NESTING: usize = 128;
 
 const TYPE_CLASS: u8 = 50;
 const TYPE_PROPERTY: u8 = 51;
 
-#[derive(Clone, Copy)]
+/// A kind of RDF term, selected by the type byte that starts the encoding of the term.
+///
+/// Used by [`StorageReader::quads_with_object_of_type`](super::StorageReader::quads_with_object_of_type).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ObjectKind {
+    /// IRIs, including the ones compressed with a registered prefix.
+    NamedNode,
+    BlankNode,
+    /// Simple literals and `xsd:string` literals.
+    String,
+    /// Language-tagged strings.
+    LangString,
+    /// `xsd:boolean` literals.
+    Boolean,
+    /// `xsd:float`, `xsd:double`, `xsd:integer` and `xsd:decimal` literals.
+    Numeric,
+    /// `xsd:dateTime`, `xsd:time`, `xsd:date` and the Gregorian (`xsd:gYear`...) literals.
+    DateTime,
+    /// `xsd:duration`, `xsd:yearMonthDuration` and `xsd:dayTimeDuration` literals.
+    Duration,
+    /// Literals with any other datatype.
+    OtherTypedLiteral,
+    /// Quoted triples.
+    Triple,
+}
+
+impl ObjectKind {
+    // 每一类 term 的类型字节区间（包含两端），命名节点的自定义前缀占用了 128 之后的所有类型字节
+    pub(crate) fn type_byte_ranges(self) -> &'static [(u8, u8)] {
+        match self {
+            Self::NamedNode => &[
+                (TYPE_NAMED_NODE_ID, TYPE_NAMED_NODE_ID),
+                (TYPE_CUSTOM_PREFIXED_NAMED_NODE_START, u8::MAX),
+            ],
+            Self::BlankNode => &[(TYPE_NUMERICAL_BLANK_NODE_ID, TYPE_BIG_BLANK_NODE_ID)],
+            Self::String => &[(TYPE_SMALL_STRING_LITERAL, TYPE_BIG_STRING_LITERAL)],
+            Self::LangString => &[(
+                TYPE_SMALL_SMALL_LANG_STRING_LITERAL,
+                TYPE_BIG_BIG_LANG_STRING_LITERAL,
+            )],
+            Self::Boolean => &[(TYPE_BOOLEAN_LITERAL_TRUE, TYPE_BOOLEAN_LITERAL_FALSE)],
+            Self::Numeric => &[(TYPE_FLOAT_LITERAL, TYPE_DECIMAL_LITERAL)],
+            Self::DateTime => &[(TYPE_DATE_TIME_LITERAL, TYPE_G_MONTH_LITERAL)],
+            Self::Duration => &[(TYPE_DURATION_LITERAL, TYPE_DAY_TIME_DURATION_LITERAL)],
+            Self::OtherTypedLiteral => &[(TYPE_SMALL_TYPED_LITERAL, TYPE_BIG_TYPED_LITERAL)],
+            Self::Triple => &[(TYPE_TRIPLE, TYPE_TRIPLE)],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum QuadEncoding {
     Spog,
     Posg,
@@ -76,22 +161,134 @@ pub enum QuadEncoding {
 }
 
 impl QuadEncoding {
-    // 将 buffer 包装进 Cursor里，然后可以调用cursor中的方法进行快速处理
+    // 将 buffer 解码成 EncodedQuad
     pub fn decode(self, buffer: &[u8]) -> Result<EncodedQuad, StorageError> {
-        // 标准库在通常用作buffer(缓冲区)的各种类型上实现了一些 I/O traits，例如 Cursor<Vec<u8>> and Cursor<&[u8]>
-        // Cursor 实现了 Read trait
-        let mut cursor = Cursor::new(&buffer);   // 创建一个新的cursor来包装所提供的底层内存缓冲区
-        
+        Ok(self.decode_with_len(buffer)?.0)
+    }
+
+    // 直接在借用的切片上读取（&[u8] 实现了 Read，每次读取都会前移切片），不需要 Cursor
+    // 同时返回 term 部分所占的字节数，调用者可以据此检查 key 末尾是否还有多余的字节
+    pub fn decode_with_len(self, buffer: &[u8]) -> Result<(EncodedQuad, usize), StorageError> {
+        let mut rest = buffer;
+        let quad = match self {
+            QuadEncoding::Spog => rest.read_spog_quad(),
+            QuadEncoding::Posg => rest.read_posg_quad(),
+            QuadEncoding::Ospg => rest.read_ospg_quad(),
+            QuadEncoding::Gspo => rest.read_gspo_quad(),
+            QuadEncoding::Gpos => rest.read_gpos_quad(),
+            QuadEncoding::Gosp => rest.read_gosp_quad(),
+            QuadEncoding::Dspo => rest.read_dspo_quad(),
+            QuadEncoding::Dpos => rest.read_dpos_quad(),
+            QuadEncoding::Dosp => rest.read_dosp_quad(),
+        }?;
+        Ok((quad, buffer.len() - rest.len()))
+    }
+
+    /// Decodes a key of the default graph indexes written by the `IntervalKey` load mode:
+    /// the interval encoding of the triple followed by its terms, and returns the decoded interval encoding.
+    ///
+    /// The interval encoding is absent if the subject or the object is not in the trees:
+    /// the key then starts with a term type byte, which is never one of the interval encoding type bytes.
+    /// A `TYPE_CLASS` header has one interval list for `rdf:type`, `rdfs:domain` and `rdfs:range`
+    /// and two for the class hierarchy predicates, so the predicate tells which length is the right one.
+    /// `prefixes` is the prefix table the store encodes its IRIs with, it is used to recognize these predicates.
+    pub fn decode_interval_key(self, buffer: &[u8], prefixes: &PrefixTable) -> Result<(EncodedQuad, Option<IntervalValue>), StorageError> {
+        if !matches!(self, QuadEncoding::Dspo | QuadEncoding::Dpos | QuadEncoding::Dosp) {
+            return Err(CorruptionError::msg(format!("The {:?} index has no interval encoding in its keys", self)).into());
+        }
+        let header_lens = match buffer.first() {
+            Some(&TYPE_PROPERTY) => vec![interval_header_len(buffer, 2)],
+            Some(&TYPE_CLASS) => vec![interval_header_len(buffer, 1), interval_header_len(buffer, 2)],
+            _ => vec![Some(0)],
+        };
+        let mut found = None;
+        for header_len in header_lens.into_iter().flatten() {
+            // 只接受 term 正好占满 key 剩余部分、并且谓语与区间编码的形状一致的解析结果
+            let quad = match self.decode_with_len(&buffer[header_len..]) {
+                Ok((quad, len)) if header_len + len == buffer.len() => quad,
+                _ => continue,
+            };
+            let value = decode_interval_value(&buffer[..header_len])?;
+            let matches = match &value {
+                None => true,
+                Some(IntervalValue::Hierarchy { .. }) => is_hierarchy_predicate(&quad.predicate, prefixes),
+                Some(IntervalValue::Class(_)) => !is_hierarchy_predicate(&quad.predicate, prefixes),
+            };
+            if matches {
+                if found.is_some() {
+                    return Err(CorruptionError::msg(format!(
+                        "The {:?} interval key {} could be decoded in several ways",
+                        self,
+                        hex::encode(buffer)
+                    ))
+                    .into());
+                }
+                found = Some((quad, value));
+            }
+        }
+        found.ok_or_else(|| {
+            CorruptionError::msg(format!("Invalid {:?} interval key {}", self, hex::encode(buffer))).into()
+        })
+    }
+
+    /// Decodes a key of the default graph indexes written by the `IntervalKey` load mode of the storage version 1
+    /// and returns whether it starts with an interval encoding, which is dropped.
+    ///
+    /// In this version the `rdfs:subClassOf` and `rdfs:subPropertyOf` encodings had no interval counts:
+    /// the intervals of the subject under the object without their layer, then the first interval of the object.
+    /// It is only used to migrate these stores, the interval encodings are then computed again from the saved trees.
+    pub fn decode_legacy_interval_key(self, buffer: &[u8], prefixes: &PrefixTable) -> Result<(EncodedQuad, bool), StorageError> {
+        // (区间编码的长度, 是否为 subClassOf、subPropertyOf 的编码)，子节点的区间数未知，逐个尝试
+        let mut header_lens = Vec::new();
+        match buffer.first() {
+            Some(&kind) if matches!(kind, TYPE_CLASS | TYPE_PROPERTY) => {
+                let mut len = 1 + INTERVAL_SIZE;
+                while len <= buffer.len() {
+                    header_lens.push((len, Some(true)));
+                    len += LEGACY_CHILD_INTERVAL_SIZE;
+                }
+                if let (TYPE_CLASS, Some(count)) = (kind, buffer.get(1)) {
+                    header_lens.push((2 + usize::from(*count) * INTERVAL_SIZE, Some(false)));
+                }
+            }
+            _ => header_lens.push((0, None)),
+        }
+        let mut found = None;
+        for (header_len, hierarchy) in header_lens {
+            let quad = match buffer.get(header_len..).map(|terms| self.decode_with_len(terms)) {
+                Some(Ok((quad, len))) if header_len + len == buffer.len() => quad,
+                _ => continue,
+            };
+            if hierarchy.map_or(true, |hierarchy| hierarchy == is_hierarchy_predicate(&quad.predicate, prefixes)) {
+                if found.is_some() {
+                    return Err(CorruptionError::msg(format!(
+                        "The {:?} interval key {} could be decoded in several ways",
+                        self,
+                        hex::encode(buffer)
+                    ))
+                    .into());
+                }
+                found = Some((quad, header_len > 0));
+            }
+        }
+        found.ok_or_else(|| {
+            CorruptionError::msg(format!("Invalid {:?} interval key {}", self, hex::encode(buffer))).into()
+        })
+    }
+
+    // 将 EncodedQuad 按照该索引的顺序写入 sink，与 decode 对应
+    // 默认图的三个索引不写入图名
+    pub fn encode_quad(self, quad: &EncodedQuad, sink: &mut Vec<u8>) {
         match self {
-            QuadEncoding::Spog => cursor.read_spog_quad(),
-            QuadEncoding::Posg => cursor.read_posg_quad(),
-            QuadEncoding::Ospg => cursor.read_ospg_quad(),
-            QuadEncoding::Gspo => cursor.read_gspo_quad(),
-            QuadEncoding::Gpos => cursor.read_gpos_quad(),
-            QuadEncoding::Gosp => cursor.read_gosp_quad(),
-            QuadEncoding::Dspo => cursor.read_dspo_quad(),
-            QuadEncoding::Dpos => cursor.read_dpos_quad(),
-            QuadEncoding::Dosp => cursor.read_dosp_quad(),
+            QuadEncoding::Spog => write_spog_quad(sink, quad),
+            QuadEncoding::Posg => write_posg_quad(sink, quad),
+            QuadEncoding::Ospg => write_ospg_quad(sink, quad),
+            QuadEncoding::Gspo => write_gspo_quad(sink, quad),
+            QuadEncoding::Gpos => write_gpos_quad(sink, quad),
+            QuadEncoding::Gosp => write_gosp_quad(sink, quad),
+            QuadEncoding::Dspo => write_spo_quad(sink, quad),
+            QuadEncoding::Dpos => write_pos_quad(sink, quad),
+            QuadEncoding::Dosp => write_osp_quad(sink, quad),
         }
     }
 }
@@ -102,7 +299,12 @@ pub fn decode_term(buffer: &[u8]) -> Result<EncodedTerm, StorageError> {
 }
 
 pub trait TermReader {
-    fn read_term(&mut self) -> Result<EncodedTerm, StorageError>;
+    fn read_term(&mut self) -> Result<EncodedTerm, StorageError> {
+        self.read_term_with_max_nesting(DEFAULT_MAX_TRIPLE_NESTING)
+    }
+
+    /// Reads a term, failing with a [`CorruptionError`] if quoted triples are nested more than `max_nesting` times.
+    fn read_term_with_max_nesting(&mut self, max_nesting: usize) -> Result<EncodedTerm, StorageError>;
 
     fn read_spog_quad(&mut self) -> Result<EncodedQuad, StorageError> {
         let subject = self.read_term()?;
@@ -226,216 +428,319 @@ pub trait TermReader {
 // Readers 由一种必需的方法 read() 定义。对 read() 的每次调用都会尝试将字节从此源拉入提供的buffer
 // 为实现了 Read trait 的类型实现 TermReader，在其内部对 read_term() 方法进行实现
 impl<R: Read> TermReader for R {
-    // self 是装配了buffer字节数组的cursor
-    fn read_term(&mut self) -> Result<EncodedTerm, StorageError> {
-        let mut type_buffer = [0];  
-        self.read_exact(&mut type_buffer)?;  // 该函数读取所需的字节数以完全填充指定的缓冲区 buff（在这里是类型buffer，只占一个字节）
-        
-        match type_buffer[0] {
-            TYPE_NAMED_NODE_ID => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::NamedNode {
-                    iri_id: StrHash::from_be_bytes(buffer),
-                })
-            }
-            TYPE_NUMERICAL_BLANK_NODE_ID => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::NumericalBlankNode {
-                    id: u128::from_be_bytes(buffer),
-                })
-            }
-            TYPE_SMALL_BLANK_NODE_ID => {  // inner: [u8; 16]
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::SmallBlankNode(
-                    SmallString::from_be_bytes(buffer).map_err(CorruptionError::new)?,
-                ))
-            }
-            TYPE_BIG_BLANK_NODE_ID => { // StrHash
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::BigBlankNode {
-                    id_id: StrHash::from_be_bytes(buffer),
-                })
-            }
-            TYPE_SMALL_SMALL_LANG_STRING_LITERAL => { // language解析在前
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::SmallSmallLangStringLiteral {
-                    value: SmallString::from_be_bytes(value_buffer)
-                        .map_err(CorruptionError::new)?,
-                    language: SmallString::from_be_bytes(language_buffer)
-                        .map_err(CorruptionError::new)?,
-                })
-            }
-            TYPE_SMALL_BIG_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::SmallBigLangStringLiteral {
-                    value: SmallString::from_be_bytes(value_buffer)
-                        .map_err(CorruptionError::new)?,
-                    language_id: StrHash::from_be_bytes(language_buffer),
-                })
-            }
-            TYPE_BIG_SMALL_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::BigSmallLangStringLiteral {
-                    value_id: StrHash::from_be_bytes(value_buffer),
-                    language: SmallString::from_be_bytes(language_buffer)
-                        .map_err(CorruptionError::new)?,
-                })
-            }
-            TYPE_BIG_BIG_LANG_STRING_LITERAL => {
-                let mut language_buffer = [0; 16];
-                self.read_exact(&mut language_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::BigBigLangStringLiteral {
-                    value_id: StrHash::from_be_bytes(value_buffer),
-                    language_id: StrHash::from_be_bytes(language_buffer),
-                })
-            }
-            TYPE_SMALL_TYPED_LITERAL => {
-                let mut datatype_buffer = [0; 16]; // NamedNodeRef
-                self.read_exact(&mut datatype_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::SmallTypedLiteral {
-                    datatype_id: StrHash::from_be_bytes(datatype_buffer),
-                    value: SmallString::from_be_bytes(value_buffer)
-                        .map_err(CorruptionError::new)?,
-                })
-            }
-            TYPE_BIG_TYPED_LITERAL => {
-                let mut datatype_buffer = [0; 16];
-                self.read_exact(&mut datatype_buffer)?;
-                let mut value_buffer = [0; 16];
-                self.read_exact(&mut value_buffer)?;
-                Ok(EncodedTerm::BigTypedLiteral {
-                    datatype_id: StrHash::from_be_bytes(datatype_buffer),
-                    value_id: StrHash::from_be_bytes(value_buffer),
-                })
-            }
-            TYPE_SMALL_STRING_LITERAL => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::SmallStringLiteral(
-                    SmallString::from_be_bytes(buffer).map_err(CorruptionError::new)?,
+    fn read_term_with_max_nesting(&mut self, max_nesting: usize) -> Result<EncodedTerm, StorageError> {
+        read_term_with_depth(self, 0, max_nesting)
+    }
+}
+
+// reader 是装配了buffer字节数组的cursor
+// depth 为当前所在的 TYPE_TRIPLE 嵌套层数，损坏的数据可能含有大量嵌套的 TYPE_TRIPLE 字节，不加限制会导致栈溢出
+// 日期时间类型的编码：16 个字节的秒数（Decimal）和 2 个字节的时区偏移（单位为分钟，0xFFFF 表示没有时区）
+// 秒数的任意 16 个字节都是合法的 Decimal，时区偏移最多为 ±14 小时
+fn check_timestamp_bytes(bytes: &[u8; 18]) -> Result<(), StorageError> {
+    let timezone_offset = [bytes[16], bytes[17]];
+    if timezone_offset != [u8::MAX; 2] && i16::from_be_bytes(timezone_offset).abs() > 14 * 60 {
+        return Err(CorruptionError::msg(format!(
+            "Invalid timezone offset encoding: {} minutes",
+            i16::from_be_bytes(timezone_offset)
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+fn read_term_with_depth<R: Read + ?Sized>(
+    reader: &mut R,
+    depth: usize,
+    max_nesting: usize,
+) -> Result<EncodedTerm, StorageError> {
+    let mut type_buffer = [0];  
+    reader.read_exact(&mut type_buffer)?;  // 该函数读取所需的字节数以完全填充指定的缓冲区 buff（在这里是类型buffer，只占一个字节）
+    
+    match type_buffer[0] {
+        TYPE_NAMED_NODE_ID => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::NamedNode {
+                iri_id: StrHash::from_be_bytes(buffer),
+            })
+        }
+        TYPE_NUMERICAL_BLANK_NODE_ID => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::NumericalBlankNode {
+                id: u128::from_be_bytes(buffer),
+            })
+        }
+        TYPE_SMALL_BLANK_NODE_ID => {  // inner: [u8; 16]
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::SmallBlankNode(
+                SmallString::from_be_bytes(buffer).map_err(CorruptionError::new)?,
+            ))
+        }
+        TYPE_BIG_BLANK_NODE_ID => { // StrHash
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::BigBlankNode {
+                id_id: StrHash::from_be_bytes(buffer),
+            })
+        }
+        TYPE_SMALL_SMALL_LANG_STRING_LITERAL => { // language解析在前
+            let mut language_buffer = [0; 16];
+            reader.read_exact(&mut language_buffer)?;
+            let mut value_buffer = [0; 16];
+            reader.read_exact(&mut value_buffer)?;
+            Ok(EncodedTerm::SmallSmallLangStringLiteral {
+                value: SmallString::from_be_bytes(value_buffer)
+                    .map_err(CorruptionError::new)?,
+                language: SmallString::from_be_bytes(language_buffer)
+                    .map_err(CorruptionError::new)?,
+            })
+        }
+        TYPE_SMALL_BIG_LANG_STRING_LITERAL => {
+            let mut language_buffer = [0; 16];
+            reader.read_exact(&mut language_buffer)?;
+            let mut value_buffer = [0; 16];
+            reader.read_exact(&mut value_buffer)?;
+            Ok(EncodedTerm::SmallBigLangStringLiteral {
+                value: SmallString::from_be_bytes(value_buffer)
+                    .map_err(CorruptionError::new)?,
+                language_id: StrHash::from_be_bytes(language_buffer),
+            })
+        }
+        TYPE_BIG_SMALL_LANG_STRING_LITERAL => {
+            let mut language_buffer = [0; 16];
+            reader.read_exact(&mut language_buffer)?;
+            let mut value_buffer = [0; 16];
+            reader.read_exact(&mut value_buffer)?;
+            Ok(EncodedTerm::BigSmallLangStringLiteral {
+                value_id: StrHash::from_be_bytes(value_buffer),
+                language: SmallString::from_be_bytes(language_buffer)
+                    .map_err(CorruptionError::new)?,
+            })
+        }
+        TYPE_BIG_BIG_LANG_STRING_LITERAL => {
+            let mut language_buffer = [0; 16];
+            reader.read_exact(&mut language_buffer)?;
+            let mut value_buffer = [0; 16];
+            reader.read_exact(&mut value_buffer)?;
+            Ok(EncodedTerm::BigBigLangStringLiteral {
+                value_id: StrHash::from_be_bytes(value_buffer),
+                language_id: StrHash::from_be_bytes(language_buffer),
+            })
+        }
+        TYPE_SMALL_TYPED_LITERAL => {
+            let mut datatype_buffer = [0; 16]; // NamedNodeRef
+            reader.read_exact(&mut datatype_buffer)?;
+            let mut value_buffer = [0; 16];
+            reader.read_exact(&mut value_buffer)?;
+            Ok(EncodedTerm::SmallTypedLiteral {
+                datatype_id: StrHash::from_be_bytes(datatype_buffer),
+                value: SmallString::from_be_bytes(value_buffer)
+                    .map_err(CorruptionError::new)?,
+            })
+        }
+        TYPE_BIG_TYPED_LITERAL => {
+            let mut datatype_buffer = [0; 16];
+            reader.read_exact(&mut datatype_buffer)?;
+            let mut value_buffer = [0; 16];
+            reader.read_exact(&mut value_buffer)?;
+            Ok(EncodedTerm::BigTypedLiteral {
+                datatype_id: StrHash::from_be_bytes(datatype_buffer),
+                value_id: StrHash::from_be_bytes(value_buffer),
+            })
+        }
+        TYPE_SMALL_STRING_LITERAL => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::SmallStringLiteral(
+                SmallString::from_be_bytes(buffer).map_err(CorruptionError::new)?,
+            ))
+        }
+        TYPE_BIG_STRING_LITERAL => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::BigStringLiteral {
+                value_id: StrHash::from_be_bytes(buffer),
+            })
+        }
+        TYPE_BOOLEAN_LITERAL_TRUE => Ok(EncodedTerm::BooleanLiteral(true)),
+        TYPE_BOOLEAN_LITERAL_FALSE => Ok(EncodedTerm::BooleanLiteral(false)),
+        TYPE_FLOAT_LITERAL => {
+            let mut buffer = [0; 4];   // 32位
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::FloatLiteral(Float::from_be_bytes(buffer)))
+        }
+        TYPE_DOUBLE_LITERAL => {
+            let mut buffer = [0; 8];  // 64位
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::DoubleLiteral(Double::from_be_bytes(buffer)))
+        }
+        TYPE_INTEGER_LITERAL => {
+            let mut buffer = [0; 8]; // i64
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::IntegerLiteral(i64::from_be_bytes(buffer)))
+        }
+        TYPE_DECIMAL_LITERAL => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::DecimalLiteral(Decimal::from_be_bytes(buffer)))
+        }
+        TYPE_DATE_TIME_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::DateTimeLiteral(DateTime::from_be_bytes(
+                buffer,
+            )))
+        }
+        TYPE_TIME_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::TimeLiteral(Time::from_be_bytes(buffer)))
+        }
+        TYPE_DATE_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::DateLiteral(Date::from_be_bytes(buffer)))
+        }
+        TYPE_G_YEAR_MONTH_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::GYearMonthLiteral(GYearMonth::from_be_bytes(
+                buffer,
+            )))
+        }
+        TYPE_G_YEAR_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::GYearLiteral(GYear::from_be_bytes(buffer)))
+        }
+        TYPE_G_MONTH_DAY_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::GMonthDayLiteral(GMonthDay::from_be_bytes(
+                buffer,
+            )))
+        }
+        TYPE_G_DAY_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::GDayLiteral(GDay::from_be_bytes(buffer)))
+        }
+        TYPE_G_MONTH_LITERAL => {
+            let mut buffer = [0; 18];
+            reader.read_exact(&mut buffer)?;
+            check_timestamp_bytes(&buffer)?;
+            Ok(EncodedTerm::GMonthLiteral(GMonth::from_be_bytes(buffer)))
+        }
+        TYPE_DURATION_LITERAL => {
+            let mut buffer = [0; 24];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::DurationLiteral(Duration::from_be_bytes(
+                buffer,
+            )))
+        }
+        TYPE_YEAR_MONTH_DURATION_LITERAL => {
+            let mut buffer = [0; 8];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::YearMonthDurationLiteral(
+                YearMonthDuration::from_be_bytes(buffer),
+            ))
+        }
+        TYPE_DAY_TIME_DURATION_LITERAL => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::DayTimeDurationLiteral(
+                DayTimeDuration::from_be_bytes(buffer),
+            ))
+        }
+        TYPE_TRIPLE => {
+            if depth >= max_nesting {
+                return Err(CorruptionError::msg(format!(
+                    "quoted triples are nested more than {} times",
+                    max_nesting
                 ))
+                .into());
             }
-            TYPE_BIG_STRING_LITERAL => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::BigStringLiteral {
-                    value_id: StrHash::from_be_bytes(buffer),
-                })
-            }
-            TYPE_BOOLEAN_LITERAL_TRUE => Ok(EncodedTerm::BooleanLiteral(true)),
-            TYPE_BOOLEAN_LITERAL_FALSE => Ok(EncodedTerm::BooleanLiteral(false)),
-            TYPE_FLOAT_LITERAL => {
-                let mut buffer = [0; 4];   // 32位
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::FloatLiteral(Float::from_be_bytes(buffer)))
-            }
-            TYPE_DOUBLE_LITERAL => {
-                let mut buffer = [0; 8];  // 64位
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::DoubleLiteral(Double::from_be_bytes(buffer)))
-            }
-            TYPE_INTEGER_LITERAL => {
-                let mut buffer = [0; 8]; // i64
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::IntegerLiteral(i64::from_be_bytes(buffer)))
-            }
-            TYPE_DECIMAL_LITERAL => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::DecimalLiteral(Decimal::from_be_bytes(buffer)))
-            }
-            TYPE_DATE_TIME_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::DateTimeLiteral(DateTime::from_be_bytes(
-                    buffer,
-                )))
-            }
-            TYPE_TIME_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::TimeLiteral(Time::from_be_bytes(buffer)))
-            }
-            TYPE_DATE_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::DateLiteral(Date::from_be_bytes(buffer)))
-            }
-            TYPE_G_YEAR_MONTH_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::GYearMonthLiteral(GYearMonth::from_be_bytes(
-                    buffer,
-                )))
-            }
-            TYPE_G_YEAR_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::GYearLiteral(GYear::from_be_bytes(buffer)))
-            }
-            TYPE_G_MONTH_DAY_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::GMonthDayLiteral(GMonthDay::from_be_bytes(
-                    buffer,
-                )))
-            }
-            TYPE_G_DAY_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::GDayLiteral(GDay::from_be_bytes(buffer)))
-            }
-            TYPE_G_MONTH_LITERAL => {
-                let mut buffer = [0; 18];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::GMonthLiteral(GMonth::from_be_bytes(buffer)))
-            }
-            TYPE_DURATION_LITERAL => {
-                let mut buffer = [0; 24];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::DurationLiteral(Duration::from_be_bytes(
-                    buffer,
-                )))
-            }
-            TYPE_YEAR_MONTH_DURATION_LITERAL => {
-                let mut buffer = [0; 8];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::YearMonthDurationLiteral(
-                    YearMonthDuration::from_be_bytes(buffer),
+            Ok(EncodedTerm::Triple(Rc::new(EncodedTriple {
+                subject: read_term_with_depth(reader, depth + 1, max_nesting)?,
+                predicate: read_term_with_depth(reader, depth + 1, max_nesting)?,
+                object: read_term_with_depth(reader, depth + 1, max_nesting)?,
+            })))
+        }
+        type_id if type_id >= TYPE_CUSTOM_PREFIXED_NAMED_NODE_START => {
+            let mut buffer = [0; 16];
+            reader.read_exact(&mut buffer)?;
+            Ok(EncodedTerm::PrefixedNamedNode {
+                prefix_id: type_id - TYPE_CUSTOM_PREFIXED_NAMED_NODE_START,
+                suffix_id: StrHash::from_be_bytes(buffer),
+            })
+        }
+        _ => Err(CorruptionError::msg("the term buffer has an invalid type id").into()),
+    }
+}
+
+/// Returns the type byte of the term encoded at the start of `buffer`, or `None` if `buffer` is empty.
+pub fn peek_term_type(buffer: &[u8]) -> Option<u8> {
+    buffer.first().copied()
+}
+
+/// Returns the type byte and the length in bytes of the term encoded at the start of `buffer`, without decoding it.
+///
+/// It allows to skip over the terms of a multi-term key.
+/// Quoted triples are walked to compute their length, nested at most [`DEFAULT_MAX_TRIPLE_NESTING`] times.
+pub fn read_term_header(buffer: &[u8]) -> Result<(u8, usize), StorageError> {
+    let type_id = peek_term_type(buffer).ok_or_else(|| CorruptionError::msg("the term buffer is empty"))?;
+    Ok((type_id, encoded_term_len(buffer, 0)?))
+}
+
+// 根据类型字节计算 term 编码的字节数（包含类型字节），与 read_term_with_depth 中读取的长度一致
+fn encoded_term_len(buffer: &[u8], depth: usize) -> Result<usize, StorageError> {
+    let type_id = peek_term_type(buffer).ok_or_else(|| CorruptionError::msg("the term buffer is too short"))?;
+    let len = match type_id {
+        TYPE_BOOLEAN_LITERAL_TRUE | TYPE_BOOLEAN_LITERAL_FALSE => 1,
+        TYPE_FLOAT_LITERAL => 1 + 4,
+        TYPE_DOUBLE_LITERAL | TYPE_INTEGER_LITERAL | TYPE_YEAR_MONTH_DURATION_LITERAL => 1 + 8,
+        TYPE_NAMED_NODE_ID
+        | TYPE_NUMERICAL_BLANK_NODE_ID
+        | TYPE_SMALL_BLANK_NODE_ID
+        | TYPE_BIG_BLANK_NODE_ID
+        | TYPE_SMALL_STRING_LITERAL
+        | TYPE_BIG_STRING_LITERAL
+        | TYPE_DECIMAL_LITERAL
+        | TYPE_DAY_TIME_DURATION_LITERAL => 1 + 16,
+        TYPE_DATE_TIME_LITERAL..=TYPE_G_MONTH_LITERAL => 1 + 18,
+        TYPE_DURATION_LITERAL => 1 + 24,
+        TYPE_SMALL_SMALL_LANG_STRING_LITERAL..=TYPE_BIG_TYPED_LITERAL => 1 + 32,
+        TYPE_TRIPLE => {
+            if depth >= DEFAULT_MAX_TRIPLE_NESTING {
+                return Err(CorruptionError::msg(format!(
+                    "quoted triples are nested more than {} times",
+                    DEFAULT_MAX_TRIPLE_NESTING
                 ))
+                .into());
             }
-            TYPE_DAY_TIME_DURATION_LITERAL => {
-                let mut buffer = [0; 16];
-                self.read_exact(&mut buffer)?;
-                Ok(EncodedTerm::DayTimeDurationLiteral(
-                    DayTimeDuration::from_be_bytes(buffer),
-                ))
+            let mut len = 1;
+            for _ in 0..3 {
+                len += encoded_term_len(&buffer[len..], depth + 1)?;
             }
-            TYPE_TRIPLE => Ok(EncodedTerm::Triple(Rc::new(EncodedTriple {
-                subject: self.read_term()?,
-                predicate: self.read_term()?,
-                object: self.read_term()?,
-            }))),
-            _ => Err(CorruptionError::msg("the term buffer has an invalid type id").into()),
+            len
         }
+        type_id if type_id >= TYPE_CUSTOM_PREFIXED_NAMED_NODE_START => 1 + 16,
+        _ => return Err(CorruptionError::msg("the term buffer has an invalid type id").into()),
+    };
+    if len > buffer.len() {
+        return Err(CorruptionError::msg("the term buffer is too short").into());
     }
+    Ok(len)
 }
 
 pub fn write_spog_quad(sink: &mut Vec<u8>, quad: &EncodedQuad) {
@@ -526,9 +831,9 @@ pub fn encode_term_triple(t1: &EncodedTerm, t2: &EncodedTerm, t3: &EncodedTerm)
 
 
 // ############################## 将区间编码加在value中 ##############################
-pub fn encode_term_triple_oxiuse_value_spo(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> (Vec<u8>, Vec<u8>) {
+pub fn encode_term_triple_oxiuse_value_spo(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<(Vec<u8>, Vec<u8>), StorageError> {
     let mut key_vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
-    let value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
+    let value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
     // 编码 key
     write_term(&mut key_vec, map.get("s").unwrap());
     write_term(&mut key_vec, map.get("p").unwrap());
@@ -538,12 +843,12 @@ pub fn encode_term_triple_oxiuse_value_spo(map: HashMap<&str, &EncodedTerm>, tre
     ATOM_BYTES.fetch_add(value_vec.capacity(), Ordering::SeqCst);
     // println!("语义数据库占用内存空间: {}", ATOM_BYTES.load(Ordering::SeqCst));
 
-    (key_vec , value_vec)
+    Ok((key_vec , value_vec))
 }
 
-pub fn encode_term_triple_oxiuse_value_pos(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> (Vec<u8>, Vec<u8>) {
+pub fn encode_term_triple_oxiuse_value_pos(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<(Vec<u8>, Vec<u8>), StorageError> {
     let mut key_vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
-    let value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
+    let value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
 
     // 编码 key
     write_term(&mut key_vec, map.get("p").unwrap());
@@ -554,12 +859,12 @@ pub fn encode_term_triple_oxiuse_value_pos(map: HashMap<&str, &EncodedTerm>, tre
     ATOM_BYTES.fetch_add(value_vec.capacity(), Ordering::SeqCst);
     // println!("语义数据库占用内存空间: {}", ATOM_BYTES.load(Ordering::SeqCst));
 
-    (key_vec , value_vec)
+    Ok((key_vec , value_vec))
 }
 
-pub fn encode_term_triple_oxiuse_value_osp(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> (Vec<u8>, Vec<u8>) {
+pub fn encode_term_triple_oxiuse_value_osp(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<(Vec<u8>, Vec<u8>), StorageError> {
     let mut key_vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
-    let value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
+    let value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
 
     // 编码 key
     write_term(&mut key_vec, map.get("o").unwrap());
@@ -569,13 +874,13 @@ pub fn encode_term_triple_oxiuse_value_osp(map: HashMap<&str, &EncodedTerm>, tre
     ATOM_BYTES.fetch_add(key_vec.capacity(), Ordering::SeqCst);
     ATOM_BYTES.fetch_add(value_vec.capacity(), Ordering::SeqCst);
 
-    (key_vec , value_vec)
+    Ok((key_vec , value_vec))
 }
 
 
-pub fn encode_term_triple_oxiuse_key_spo(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> Vec<u8> {
+pub fn encode_term_triple_oxiuse_key_spo(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<Vec<u8>, StorageError> {
     let mut key_vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
-    let mut value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
+    let mut value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
 
     key_vec.append(&mut value_vec);
 
@@ -584,12 +889,12 @@ pub fn encode_term_triple_oxiuse_key_spo(map: HashMap<&str, &EncodedTerm>, trees
     write_term(&mut key_vec, map.get("p").unwrap());
     write_term(&mut key_vec, map.get("o").unwrap());
 
-    key_vec
+    Ok(key_vec)
 }
 
-pub fn encode_term_triple_oxiuse_key_pos(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> Vec<u8> {
+pub fn encode_term_triple_oxiuse_key_pos(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<Vec<u8>, StorageError> {
     let mut key_vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
-    let mut value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
+    let mut value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
 
     key_vec.append(&mut value_vec);
 
@@ -598,12 +903,12 @@ pub fn encode_term_triple_oxiuse_key_pos(map: HashMap<&str, &EncodedTerm>, trees
     write_term(&mut key_vec, map.get("o").unwrap());
     write_term(&mut key_vec, map.get("s").unwrap());
 
-    key_vec
+    Ok(key_vec)
 }
 
-pub fn encode_term_triple_oxiuse_key_osp(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> Vec<u8> {
+pub fn encode_term_triple_oxiuse_key_osp(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<Vec<u8>, StorageError> {
     let mut key_vec = Vec::with_capacity(3 * WRITTEN_TERM_MAX_SIZE);
-    let mut value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
+    let mut value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
 
     key_vec.append(&mut value_vec);
 
@@ -612,15 +917,13 @@ pub fn encode_term_triple_oxiuse_key_osp(map: HashMap<&str, &EncodedTerm>, trees
     write_term(&mut key_vec, map.get("s").unwrap());
     write_term(&mut key_vec, map.get("p").unwrap());
 
-    key_vec
+    Ok(key_vec)
 }
 
 // ############################## 将区间编码加在key中 ##############################
-pub fn encode_term_triple_oxiuse_key(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> Vec<u8>{
+pub fn encode_term_triple_oxiuse_key(map: HashMap<&str, &EncodedTerm>, trees: &(MultiTree, MultiTree), iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>) -> Result<Vec<u8>, StorageError> {
     let mut key_vec = Vec::with_capacity(5 * WRITTEN_TERM_MAX_SIZE);
-    let mut value_vec = encoded_interval_encoding(map.clone(), trees);   // 获得区间编码，有可能是空的
-
-    println!("{:?}", value_vec);
+    let mut value_vec = encoded_interval_encoding(map.clone(), trees, iri_hash)?;   // 获得区间编码，有可能是空的
 
     // 编码 key
     write_term(&mut key_vec, map.get("s").unwrap());
@@ -628,15 +931,23 @@ pub fn encode_term_triple_oxiuse_key(map: HashMap<&str, &EncodedTerm>, trees: (M
     write_term(&mut key_vec, map.get("o").unwrap());
 
     key_vec.append(&mut value_vec);
-    key_vec
+    Ok(key_vec)
 }
 
 // TODO:区间编码的方案在这，然后将编码的vec返回
-fn encoded_interval_encoding(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree, MultiTree)) -> Vec<u8> { 
-    let mut value_vec = Vec::with_capacity(INTERVAL_ENCODING_MAX_SIZE);   // 这个大小可能得改
-
-    let classTree = trees.0;
-    let propertyTree = trees.1;
+// 树的状态不足以完成编码时（父节点没有区间、区间数超出一个字节）返回错误，而不是让加载线程 panic
+// 主语或宾语不在树中时返回空的编码
+// 树和谓语都按完整 IRI 的 StrHash 比较，iri_hash 给出命名节点（包括使用前缀编码的）完整 IRI 的 StrHash
+fn encoded_interval_encoding(
+    map: HashMap<&str, &EncodedTerm>,
+    trees: &(MultiTree, MultiTree),
+    iri_hash: &impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError>,
+) -> Result<Vec<u8>, StorageError> {
+    // 大多数节点只有一个父节点，多继承时再扩容
+    let mut value_vec = Vec::with_capacity(HIERARCHY_INTERVAL_ENCODING_SIZE);
+
+    let classTree = &trees.0;
+    let propertyTree = &trees.1;
 
     let sub_class_of = StrHash::new(rdfs::SUB_CLASS_OF);
     let sub_property_of = StrHash::new(rdfs::SUB_PROPERTY_OF);
@@ -645,119 +956,157 @@ fn encoded_interval_encoding(map: HashMap<&str, &EncodedTerm>, trees: (MultiTree
     let rdf_type = StrHash::new(rdf::TYPE);
     let sub_organization_of = StrHash::new(lubm::SUB_ORGANIZATION);
 
-    match map.get("p").unwrap() {
-        EncodedTerm::NamedNode { iri_id } => {
-            if *iri_id == sub_class_of || *iri_id == sub_organization_of{   // 子父类的情况，需要先得到子父类（父节点编码的是第一个区间编码）
-                // 先得到主语和宾语
-                let s = {
-                    if let EncodedTerm::NamedNode { iri_id } = map.get("s").unwrap() {
-                        classTree.get_node_by_strhash(*iri_id)
-                    } else {
-                        Err(())
-                    }
-                };
-
-                let o = {
-                    if let EncodedTerm::NamedNode { iri_id } = map.get("o").unwrap() {
-                        classTree.get_node_by_strhash(*iri_id)
-                    } else {
-                        Err(())
-                    }
-                };
-
-                match s {
-                    Ok(child) => {
-                        match o {
-                            Ok(parent) => {
-                                value_vec.push(TYPE_CLASS);
-
-                                for interval in child.get_interval_nodes().iter() {
-                                    if interval.get_parent().unwrap().get_data() == parent.get_data() {
-                                        value_vec.extend_from_slice(&interval.get_start().to_be_bytes());
-                                        value_vec.extend_from_slice(&interval.get_end().to_be_bytes());
-                                    }
-                                }
-
-                                value_vec.extend_from_slice(&parent.get_interval_nodes().get(0).unwrap().get_start().to_be_bytes());
-                                value_vec.extend_from_slice(&parent.get_interval_nodes().get(0).unwrap().get_end().to_be_bytes());
-                                value_vec.extend_from_slice(&parent.get_interval_nodes().get(0).unwrap().get_layer().to_be_bytes());
-                            },
-                            _ => return value_vec
-                        }   
-                    },
-                    _ => return value_vec
-                };
-            } else if *iri_id == sub_property_of {   // 子父属性
-                // 先得到主语和宾语
-                let s = {
-                    if let EncodedTerm::NamedNode { iri_id } = map.get("s").unwrap() {
-                        propertyTree.get_node_by_strhash(*iri_id)
-                    } else {
-                        Err(())
-                    }
-                };
-
-                let o = {
-                    if let EncodedTerm::NamedNode { iri_id } = map.get("o").unwrap() {
-                        propertyTree.get_node_by_strhash(*iri_id)
-                    } else {
-                        Err(())
-                    }
-                };
-
-                match s {
-                    Ok(child) => {
-                        match o {
-                            Ok(parent) => {
-                                value_vec.push(TYPE_PROPERTY);
-
-                                for interval in child.get_interval_nodes().iter() {
-                                    if interval.get_parent().unwrap().get_data() == parent.get_data() {
-                                        value_vec.extend_from_slice(&interval.get_start().to_be_bytes());
-                                        value_vec.extend_from_slice(&interval.get_end().to_be_bytes());
-                                    }
-                                }
-
-                                value_vec.extend_from_slice(&parent.get_interval_nodes().get(0).unwrap().get_start().to_be_bytes());
-                                value_vec.extend_from_slice(&parent.get_interval_nodes().get(0).unwrap().get_end().to_be_bytes());
-                                value_vec.extend_from_slice(&parent.get_interval_nodes().get(0).unwrap().get_layer().to_be_bytes());
-                            },
-                            _ => return value_vec
-                        }   
-                    },
-                    _ => return value_vec
-                };
-            } else if (*iri_id == domain) || (*iri_id == range) || (*iri_id == rdf_type){   // domain、range、type
-                
-                let o = {
-                    if let EncodedTerm::NamedNode { iri_id } = map.get("o").unwrap() {
-                        classTree.get_node_by_strhash(*iri_id)
-                    } else {
-                        Err(())
-                    }
-                };
-
-                match o {
-                    Ok(node) => {
-                        value_vec.push(TYPE_CLASS);
-                        let count = node.get_interval_nodes().len() as u8;
-                        value_vec.extend_from_slice(&count.to_be_bytes());
-
-                        for interval in node.get_interval_nodes().iter() {
-                            value_vec.extend_from_slice(&interval.get_start().to_be_bytes());
-                            value_vec.extend_from_slice(&interval.get_end().to_be_bytes());
-                            value_vec.extend_from_slice(&interval.get_layer().to_be_bytes());
-                        }
-                    },
-                    _ => return value_vec
-                }
+    let tree_node = |tree: &MultiTree, term: &str| -> Result<_, StorageError> {
+        Ok(iri_hash(*map.get(term).unwrap())?.and_then(|iri_id| tree.get_node_by_strhash(iri_id).ok()))
+    };
+
+    if let Some(iri_id) = iri_hash(*map.get("p").unwrap())? {
+        // 子父类、子父属性：子节点在该父节点下的区间，以及父节点的所有区间
+        let hierarchy = if iri_id == sub_class_of || iri_id == sub_organization_of {
+            Some((TYPE_CLASS, classTree))
+        } else if iri_id == sub_property_of {
+            Some((TYPE_PROPERTY, propertyTree))
+        } else {
+            None
+        };
+        if let Some((kind, tree)) = hierarchy {
+            if let (Some(child), Some(parent)) = (tree_node(tree, "s")?, tree_node(tree, "o")?) {
+                ensure_has_interval(&parent)?;
+                value_vec.push(kind);
+                let child_intervals: Vec<_> = child
+                    .get_interval_nodes()
+                    .iter()
+                    .filter(|interval| interval.get_parent().map_or(false, |p| p.get_data() == parent.get_data()))
+                    .cloned()
+                    .collect();
+                write_interval_list(&mut value_vec, &child, &child_intervals)?;
+                write_interval_list(&mut value_vec, &parent, &parent.get_interval_nodes())?;
             }
-        },
-        _ => {}
+        } else if (iri_id == domain) || (iri_id == range) || (iri_id == rdf_type) {   // domain、range、type：宾语的所有区间
+            if let Some(node) = tree_node(classTree, "o")? {
+                value_vec.push(TYPE_CLASS);
+                write_interval_list(&mut value_vec, &node, &node.get_interval_nodes())?;
+            }
+        }
+    }
+
+    debug_assert!(value_vec.len() <= INTERVAL_ENCODING_MAX_SIZE);
+    Ok(value_vec)
+}
+
+// 父节点没有区间说明树还没有编码（或已损坏）
+fn ensure_has_interval(node: &MultiTreeNode) -> Result<(), StorageError> {
+    if node.get_interval_nodes().is_empty() {
+        return Err(StorageError::Other(
+            format!("The tree node {:?} has no interval, has the tree been encoded?", node.get_data()).into(),
+        ));
+    }
+    Ok(())
+}
+
+// 区间列表：区间数（一个字节）+ 每个区间的 start、end、layer
+fn write_interval_list(
+    sink: &mut Vec<u8>,
+    node: &MultiTreeNode,
+    intervals: &[Arc<IntervalNode>],
+) -> Result<(), StorageError> {
+    let count = u8::try_from(intervals.len()).map_err(|_| {
+        StorageError::Other(
+            format!(
+                "The class {:?} has {} intervals but at most {} could be encoded",
+                node.get_data(),
+                intervals.len(),
+                u8::MAX
+            )
+            .into(),
+        )
+    })?;
+    sink.push(count);
+    sink.reserve(intervals.len() * INTERVAL_SIZE);
+    for interval in intervals {
+        sink.extend_from_slice(&interval.get_start().to_be_bytes());
+        sink.extend_from_slice(&interval.get_end().to_be_bytes());
+        sink.extend_from_slice(&interval.get_layer().to_be_bytes());
+    }
+    Ok(())
+}
+
+/// An interval label of a tree node: `(start, end, layer)`.
+pub type Interval = (u32, u32, u16);
+
+/// The decoded interval encoding of a triple, see [`decode_interval_value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntervalValue {
+    /// `rdfs:subClassOf` or `rdfs:subPropertyOf`:
+    /// the intervals of the subject under the object and all the intervals of the object.
+    Hierarchy {
+        child: Vec<Interval>,
+        parent: Vec<Interval>,
+    },
+    /// `rdf:type`, `rdfs:domain` or `rdfs:range`: all the intervals of the object class.
+    Class(Vec<Interval>),
+}
+
+// 解析 encoded_interval_encoding 写入的区间编码，value 为空（主语或宾语不在树中）时返回 None
+// 区间列表的长度由区间数决定，所以一个还是两个区间列表不会有歧义
+pub fn decode_interval_value(mut value: &[u8]) -> Result<Option<IntervalValue>, CorruptionError> {
+    let (&kind, rest) = match value.split_first() {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+    if !matches!(kind, TYPE_CLASS | TYPE_PROPERTY) {
+        return Err(CorruptionError::msg(format!("Invalid interval encoding type {}", kind)));
+    }
+    value = rest;
+    let mut lists = Vec::with_capacity(2);
+    while !value.is_empty() {
+        let count = usize::from(value[0]);
+        let end = 1 + count * INTERVAL_SIZE;
+        if value.len() < end || lists.len() == 2 {
+            return Err(CorruptionError::msg(format!(
+                "Invalid interval encoding: {} trailing bytes",
+                value.len()
+            )));
+        }
+        lists.push(
+            value[1..end]
+                .chunks_exact(INTERVAL_SIZE)
+                .map(|interval| {
+                    let mut start = [0; 4];
+                    start.copy_from_slice(&interval[0..4]);
+                    let mut end = [0; 4];
+                    end.copy_from_slice(&interval[4..8]);
+                    let mut layer = [0; 2];
+                    layer.copy_from_slice(&interval[8..10]);
+                    (u32::from_be_bytes(start), u32::from_be_bytes(end), u16::from_be_bytes(layer))
+                })
+                .collect::<Vec<_>>(),
+        );
+        value = &value[end..];
+    }
+    let parent = lists.pop();
+    match (kind, lists.pop(), parent) {
+        (_, Some(child), Some(parent)) => Ok(Some(IntervalValue::Hierarchy { child, parent })),
+        (TYPE_CLASS, None, Some(intervals)) => Ok(Some(IntervalValue::Class(intervals))),
+        _ => Err(CorruptionError::msg("Invalid interval encoding: missing interval list")),
     }
+}
 
+// 区间编码头部的长度：类型字节 + lists 个区间列表，buffer 不够长时返回 None
+fn interval_header_len(buffer: &[u8], lists: usize) -> Option<usize> {
+    let mut len = 1;
+    for _ in 0..lists {
+        len += 1 + usize::from(*buffer.get(len)?) * INTERVAL_SIZE;
+    }
+    (len <= buffer.len()).then_some(len)
+}
 
-    value_vec
+// 子类、子属性等谓语的区间编码有两个区间列表
+// 前缀表的编码是唯一的，所以用它编码这些谓语之后直接比较
+fn is_hierarchy_predicate(predicate: &EncodedTerm, prefixes: &PrefixTable) -> bool {
+    [rdfs::SUB_CLASS_OF, lubm::SUB_ORGANIZATION, rdfs::SUB_PROPERTY_OF]
+        .into_iter()
+        .any(|iri| *predicate == prefixes.encode_named_node(NamedNodeRef::new_unchecked(iri)))
 }
 
 pub fn encode_term_quad(
@@ -775,6 +1124,10 @@ pub fn encode_term_quad(
 }
 
 // 将传入的 term 类型 id 以及 term 的字节序列放入 buffer 中
+/// Appends the encoding of `term` to `sink`: its type byte followed by its fixed width payload.
+///
+/// All the numeric payloads (hashes, floats, integers, decimals, timestamps, timezone offsets and durations)
+/// are written big-endian so that a store written on one architecture can be read on any other.
 pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
     match term {
         EncodedTerm::DefaultGraph => (),
@@ -782,6 +1135,14 @@ pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
             sink.push(TYPE_NAMED_NODE_ID);
             sink.extend_from_slice(&iri_id.to_be_bytes());
         }
+        EncodedTerm::PrefixedNamedNode {
+            prefix_id,
+            suffix_id,
+        } => {
+            debug_assert!(usize::from(*prefix_id) < MAX_CUSTOM_PREFIXES);
+            sink.push(TYPE_CUSTOM_PREFIXED_NAMED_NODE_START + prefix_id);
+            sink.extend_from_slice(&suffix_id.to_be_bytes());
+        }
         EncodedTerm::NumericalBlankNode { id } => {
             sink.push(TYPE_NUMERICAL_BLANK_NODE_ID);
             sink.extend_from_slice(&id.to_be_bytes())
@@ -825,6 +1186,18 @@ pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
             sink.extend_from_slice(&language_id.to_be_bytes());
             sink.extend_from_slice(&value_id.to_be_bytes());
         }
+        // xsd:string 类型的字面量与普通字符串相同（RDF 1.1），按普通字符串写入
+        EncodedTerm::SmallTypedLiteral { value, datatype_id } if *datatype_id == *XSD_STRING_ID => {
+            sink.push(TYPE_SMALL_STRING_LITERAL);
+            sink.extend_from_slice(&value.to_be_bytes());
+        }
+        EncodedTerm::BigTypedLiteral {
+            value_id,
+            datatype_id,
+        } if *datatype_id == *XSD_STRING_ID => {
+            sink.push(TYPE_BIG_STRING_LITERAL);
+            sink.extend_from_slice(&value_id.to_be_bytes());
+        }
         EncodedTerm::SmallTypedLiteral { value, datatype_id } => {
             sink.push(TYPE_SMALL_TYPED_LITERAL);
             sink.extend_from_slice(&datatype_id.to_be_bytes());
@@ -912,10 +1285,11 @@ pub fn write_term(sink: &mut Vec<u8>, term: &EncodedTerm) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::TermRef;
+    use crate::model::{NamedNodeRef, TermRef};
     use crate::storage::numeric_encoder::*;
     use std::cell::RefCell;
     use std::collections::HashMap;
+    use std::str::FromStr;
 
     #[derive(Default)]
     struct MemoryStrStore {
@@ -932,9 +1306,14 @@ mod tests {
         }
     }
 
+    // 没有注册前缀时命名节点的编码就是完整 IRI 的 StrHash
+    fn unprefixed_iri_hash(term: &EncodedTerm) -> Result<Option<StrHash>, StorageError> {
+        iri_hash(term, &MemoryStrStore::default())
+    }
+
     impl MemoryStrStore {
         fn insert_term(&self, term: TermRef<'_>, encoded: &EncodedTerm) {
-            insert_term(term, encoded, &mut |h, v| {
+            insert_term(term, encoded, &PrefixTable::default(), &mut |h, v| {
                 self.insert_str(h, v);
                 Ok(())
             })
@@ -949,6 +1328,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_xsd_string_literal_is_plain_string() {
+        use crate::model::vocab::xsd;
+        use crate::model::*;
+
+        for value in ["foo", "a value too long to be inlined"] {
+            let typed = Literal::new_typed_literal(value, xsd::STRING);
+            let plain = Literal::new_simple_literal(value);
+            assert_eq!(EncodedTerm::from(typed.as_ref()), EncodedTerm::from(plain.as_ref()));
+        }
+
+        // 即使直接构造了带 xsd:string 类型的编码，写入的 key 也与普通字符串相同
+        let small = SmallString::try_from("foo").unwrap();
+        assert_eq!(
+            encode_term(&EncodedTerm::SmallTypedLiteral {
+                value: small,
+                datatype_id: StrHash::new(xsd::STRING.as_str()),
+            }),
+            encode_term(&EncodedTerm::SmallStringLiteral(small))
+        );
+        let value_id = StrHash::new("a value too long to be inlined");
+        assert_eq!(
+            encode_term(&EncodedTerm::BigTypedLiteral {
+                value_id,
+                datatype_id: StrHash::new(xsd::STRING.as_str()),
+            }),
+            encode_term(&EncodedTerm::BigStringLiteral { value_id })
+        );
+        assert_ne!(
+            encode_term(&EncodedTerm::SmallTypedLiteral {
+                value: small,
+                datatype_id: StrHash::new(xsd::TOKEN.as_str()),
+            }),
+            encode_term(&EncodedTerm::SmallStringLiteral(small))
+        );
+    }
+
     #[test]
     fn test_encoding() {
         use crate::model::vocab::xsd;
@@ -1020,4 +1436,546 @@ mod tests {
             assert_eq!(encoded, Cursor::new(&buffer).read_term().unwrap());
         }
     }
+
+
+    #[test]
+    fn test_decode_with_len() {
+        let quad = EncodedQuad::new(
+            EncodedTerm::NamedNode { iri_id: StrHash::new("http://example.com/s") },
+            EncodedTerm::NamedNode { iri_id: StrHash::new("http://example.com/p") },
+            EncodedTerm::IntegerLiteral(1),
+            EncodedTerm::NamedNode { iri_id: StrHash::new("http://example.com/g") },
+        );
+        let key = encode_term_quad(&quad.subject, &quad.predicate, &quad.object, &quad.graph_name);
+        let (decoded, len) = QuadEncoding::Spog.decode_with_len(&key).unwrap();
+        assert_eq!(decoded, quad);
+        assert_eq!(len, key.len());
+
+        // IntervalKey 模式的 key 以区间编码开头，term 部分只占 key 的一部分
+        let classes = MultiTree::new(owl::OWL_CLASS);
+        classes.insert("http://example.com/x", owl::OWL_CLASS);
+        classes.encode();
+        let trees = (classes, MultiTree::new(rdf::PROPERTY));
+        let subject = EncodedTerm::NamedNode { iri_id: StrHash::new("http://example.com/s") };
+        let predicate = EncodedTerm::NamedNode { iri_id: StrHash::new(rdf::TYPE) };
+        let object = EncodedTerm::NamedNode { iri_id: StrHash::new("http://example.com/x") };
+        let mut map = HashMap::new();
+        map.insert("s", &subject);
+        map.insert("p", &predicate);
+        map.insert("o", &object);
+        let key = encode_term_triple_oxiuse_key_spo(map, &trees, &unprefixed_iri_hash).unwrap();
+        let header_len = key.len() - encode_term_triple(&subject, &predicate, &object).len();
+        assert!(header_len > 0);
+        let (decoded, len) = QuadEncoding::Dspo.decode_with_len(&key[header_len..]).unwrap();
+        assert_eq!(decoded, EncodedQuad::new(subject, predicate, object, EncodedTerm::DefaultGraph));
+        assert_eq!(header_len + len, key.len());
+        assert!(len < key.len());
+        assert_eq!(QuadEncoding::Dspo.decode_interval_key(&key, &PrefixTable::default()).unwrap().0, decoded);
+    }
+
+
+    #[test]
+    fn test_prefixed_named_node_round_trip() {
+        for prefix_id in [0, 1, 127] {
+            let term = EncodedTerm::PrefixedNamedNode {
+                prefix_id,
+                suffix_id: StrHash::new("s"),
+            };
+            let mut buffer = Vec::new();
+            write_term(&mut buffer, &term);
+            assert_eq!(buffer.len(), WRITTEN_TERM_MAX_SIZE - 16);
+            assert_eq!(buffer[0], 128 + prefix_id);
+            assert_eq!(term, decode_term(&buffer).unwrap());
+        }
+    }
+
+
+    fn interval_encoding_of(
+        s: &str,
+        p: &str,
+        o: &str,
+        trees: &(MultiTree, MultiTree),
+    ) -> Result<Vec<u8>, StorageError> {
+        let s = EncodedTerm::NamedNode { iri_id: StrHash::new(s) };
+        let p = EncodedTerm::NamedNode { iri_id: StrHash::new(p) };
+        let o = EncodedTerm::NamedNode { iri_id: StrHash::new(o) };
+        let mut map = HashMap::new();
+        map.insert("s", &s);
+        map.insert("p", &p);
+        map.insert("o", &o);
+        encoded_interval_encoding(map, trees, &unprefixed_iri_hash)
+    }
+
+    #[test]
+    fn test_interval_encoding_parent_without_interval() {
+        // 树没有编码，根节点还没有区间
+        let classes = MultiTree::new(owl::OWL_CLASS);
+        classes.insert("http://example.com/a", owl::OWL_CLASS);
+        let trees = (classes, MultiTree::new(rdf::PROPERTY));
+        let error = interval_encoding_of("http://example.com/a", rdfs::SUB_CLASS_OF, owl::OWL_CLASS, &trees).unwrap_err();
+        assert!(error.to_string().contains("has no interval"));
+    }
+
+    #[test]
+    fn test_interval_encoding_too_many_intervals() {
+        let classes = MultiTree::new(owl::OWL_CLASS);
+        for i in 0..=u8::MAX as usize {
+            classes.insert("http://example.com/x", &format!("http://example.com/p{}", i));
+        }
+        classes.encode();
+        let trees = (classes, MultiTree::new(rdf::PROPERTY));
+        let error = interval_encoding_of("http://example.com/s", rdf::TYPE, "http://example.com/x", &trees).unwrap_err();
+        assert!(error.to_string().contains("256 intervals"));
+    }
+
+    #[test]
+    fn test_longest_interval_encoding_fits() {
+        // 区间数最多的节点给出最长的合法编码
+        let classes = MultiTree::new(owl::OWL_CLASS);
+        for i in 0..MAX_ENCODED_INTERVALS {
+            classes.insert("http://example.com/x", &format!("http://example.com/p{}", i));
+        }
+        classes.insert("http://example.com/y", "http://example.com/x");
+        classes.encode();
+        let trees = (classes, MultiTree::new(rdf::PROPERTY));
+
+        let value = interval_encoding_of("http://example.com/s", rdf::TYPE, "http://example.com/x", &trees).unwrap();
+        assert_eq!(value[..2], [TYPE_CLASS, u8::MAX]);
+        assert_eq!(value.len(), 2 + MAX_ENCODED_INTERVALS * INTERVAL_SIZE);
+
+        // 父节点的所有区间都会被写入
+        let value = interval_encoding_of("http://example.com/y", rdfs::SUB_CLASS_OF, "http://example.com/x", &trees).unwrap();
+        assert_eq!(value.len(), 3 + (1 + MAX_ENCODED_INTERVALS) * INTERVAL_SIZE);
+        assert!(value.len() <= INTERVAL_ENCODING_MAX_SIZE);
+        match decode_interval_value(&value).unwrap() {
+            Some(IntervalValue::Hierarchy { child, parent }) => {
+                assert_eq!(child.len(), 1);
+                assert_eq!(parent.len(), MAX_ENCODED_INTERVALS);
+            }
+            value => panic!("unexpected value {:?}", value),
+        }
+    }
+
+    // 两个父节点的类：x 同时继承 a 和 b，y 继承 x
+    fn two_parents_tree() -> (MultiTree, MultiTree) {
+        let classes = MultiTree::new(owl::OWL_CLASS);
+        classes.insert("http://example.com/a", owl::OWL_CLASS);
+        classes.insert("http://example.com/x", "http://example.com/a");
+        classes.insert("http://example.com/x", "http://example.com/b");
+        classes.insert("http://example.com/y", "http://example.com/x");
+        classes.encode();
+        (classes, MultiTree::new(rdf::PROPERTY))
+    }
+
+    fn tree_intervals(tree: &MultiTree, node: &str) -> Vec<Interval> {
+        tree.get_node_by_strhash(StrHash::new(node))
+            .unwrap()
+            .get_interval_nodes()
+            .iter()
+            .map(|interval| (interval.get_start(), interval.get_end(), interval.get_layer()))
+            .collect()
+    }
+
+    #[test]
+    fn test_interval_encoding_two_parents_subclass() {
+        let trees = two_parents_tree();
+        let x_intervals = tree_intervals(&trees.0, "http://example.com/x");
+        assert_eq!(x_intervals.len(), 2);
+
+        // x 在每个父节点下的区间各不相同，且被父节点的区间包含
+        for (index, parent) in ["http://example.com/a", "http://example.com/b"].into_iter().enumerate() {
+            let value = interval_encoding_of("http://example.com/x", rdfs::SUB_CLASS_OF, parent, &trees).unwrap();
+            assert_eq!(value.len(), HIERARCHY_INTERVAL_ENCODING_SIZE);
+            match decode_interval_value(&value).unwrap() {
+                Some(IntervalValue::Hierarchy { child, parent }) => {
+                    assert_eq!(child, vec![x_intervals[index]]);
+                    assert_eq!(parent.len(), 1);
+                    assert!(parent[0].0 < child[0].0 && child[0].1 <= parent[0].1);
+                }
+                value => panic!("unexpected value {:?}", value),
+            }
+        }
+
+        // 父节点有两个区间时全部写入，子节点写入树中父区间属于 x 的区间
+        let x = trees.0.get_node_by_strhash(StrHash::new("http://example.com/x")).unwrap();
+        let y_intervals = trees
+            .0
+            .get_node_by_strhash(StrHash::new("http://example.com/y"))
+            .unwrap()
+            .get_interval_nodes()
+            .iter()
+            .filter(|interval| interval.get_parent().map_or(false, |p| p.get_data() == x.get_data()))
+            .map(|interval| (interval.get_start(), interval.get_end(), interval.get_layer()))
+            .collect::<Vec<_>>();
+        assert!(!y_intervals.is_empty());
+        let value = interval_encoding_of("http://example.com/y", rdfs::SUB_CLASS_OF, "http://example.com/x", &trees).unwrap();
+        match decode_interval_value(&value).unwrap() {
+            Some(IntervalValue::Hierarchy { child, parent }) => {
+                assert_eq!(child, y_intervals);
+                assert_eq!(parent, x_intervals);
+                for (start, end, _) in child {
+                    assert!(parent.iter().any(|p| p.0 < start && end <= p.1));
+                }
+            }
+            value => panic!("unexpected value {:?}", value),
+        }
+    }
+
+    #[test]
+    fn test_decode_interval_key() {
+        let trees = two_parents_tree();
+        let term = |iri: &str| EncodedTerm::NamedNode { iri_id: StrHash::new(iri) };
+        for (s, p, o) in [
+            ("http://example.com/s", rdf::TYPE, "http://example.com/x"),
+            ("http://example.com/y", rdfs::SUB_CLASS_OF, "http://example.com/x"),
+            ("http://example.com/x", rdfs::SUB_CLASS_OF, "http://example.com/b"),
+            ("http://example.com/s", "http://example.com/p", "http://example.com/o"),
+        ] {
+            let (s, p, o) = (term(s), term(p), term(o));
+            let mut map = HashMap::new();
+            map.insert("s", &s);
+            map.insert("p", &p);
+            map.insert("o", &o);
+            let value = encoded_interval_encoding(map.clone(), &trees, &unprefixed_iri_hash).unwrap();
+            let expected_quad = EncodedQuad::new(s.clone(), p.clone(), o.clone(), EncodedTerm::DefaultGraph);
+            for (encoding, key) in [
+                (QuadEncoding::Dspo, encode_term_triple_oxiuse_key_spo(map.clone(), &trees, &unprefixed_iri_hash).unwrap()),
+                (QuadEncoding::Dpos, encode_term_triple_oxiuse_key_pos(map.clone(), &trees, &unprefixed_iri_hash).unwrap()),
+                (QuadEncoding::Dosp, encode_term_triple_oxiuse_key_osp(map.clone(), &trees, &unprefixed_iri_hash).unwrap()),
+            ] {
+                let (quad, interval) = encoding.decode_interval_key(&key, &PrefixTable::default()).unwrap();
+                assert_eq!(quad, expected_quad);
+                assert_eq!(interval, decode_interval_value(&value).unwrap());
+            }
+        }
+        assert!(QuadEncoding::Spog.decode_interval_key(&[], &PrefixTable::default()).is_err());
+        assert!(QuadEncoding::Dspo.decode_interval_key(&[TYPE_CLASS, 1], &PrefixTable::default()).is_err());
+    }
+
+    #[test]
+    fn test_interval_encoding_with_prefixes() {
+        let trees = two_parents_tree();
+        // 数据的命名空间和 rdfs 的命名空间都注册为前缀
+        let prefixes = PrefixTable::new(vec!["http://example.com/".into(), "http://www.w3.org/2000/01/rdf-schema#".into()]);
+        let store = MemoryStrStore::default();
+        let term = |iri: &str| {
+            let node = NamedNodeRef::new_unchecked(iri);
+            let encoded = prefixes.encode_named_node(node);
+            insert_term(node.into(), &encoded, &prefixes, &mut |key, value| {
+                store.insert_str(key, value);
+                Ok(())
+            })
+            .unwrap();
+            encoded
+        };
+        let lookup = |term: &EncodedTerm| match term {
+            EncodedTerm::PrefixedNamedNode { prefix_id, suffix_id } => Ok(Some(StrHash::new(&format!(
+                "{}{}",
+                prefixes.get(*prefix_id).unwrap(),
+                store.get_str(suffix_id)?.unwrap()
+            )))),
+            term => unprefixed_iri_hash(term),
+        };
+        for (s, p, o) in [
+            ("http://example.com/y", rdfs::SUB_CLASS_OF, "http://example.com/x"),
+            ("http://example.com/s", rdfs::DOMAIN, "http://example.com/x"),
+        ] {
+            let (prefixed_s, prefixed_p, prefixed_o) = (term(s), term(p), term(o));
+            assert!(matches!(prefixed_p, EncodedTerm::PrefixedNamedNode { .. }));
+            let mut map = HashMap::new();
+            map.insert("s", &prefixed_s);
+            map.insert("p", &prefixed_p);
+            map.insert("o", &prefixed_o);
+            let value = encoded_interval_encoding(map.clone(), &trees, &lookup).unwrap();
+            assert!(!value.is_empty());
+            assert_eq!(value, interval_encoding_of(s, p, o, &trees).unwrap());
+
+            let key = encode_term_triple_oxiuse_key_spo(map, &trees, &lookup).unwrap();
+            let (quad, interval) = QuadEncoding::Dspo.decode_interval_key(&key, &prefixes).unwrap();
+            assert_eq!(quad, EncodedQuad::new(prefixed_s, prefixed_p, prefixed_o, EncodedTerm::DefaultGraph));
+            assert_eq!(interval, decode_interval_value(&value).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_interval_encoding_two_parents_type() {
+        let trees = two_parents_tree();
+        let x_intervals = tree_intervals(&trees.0, "http://example.com/x");
+        for predicate in [rdf::TYPE, rdfs::DOMAIN, rdfs::RANGE] {
+            let value = interval_encoding_of("http://example.com/s", predicate, "http://example.com/x", &trees).unwrap();
+            assert_eq!(value.len(), 2 + 2 * INTERVAL_SIZE);
+            assert_eq!(decode_interval_value(&value).unwrap(), Some(IntervalValue::Class(x_intervals.clone())));
+        }
+
+        // 不在树中的宾语没有编码
+        let value = interval_encoding_of("http://example.com/s", rdf::TYPE, "http://example.com/unknown", &trees).unwrap();
+        assert_eq!(decode_interval_value(&value).unwrap(), None);
+    }
+
+    #[test]
+    fn test_decode_malformed_interval_value() {
+        // 类型错误、区间列表被截断、多余的字节、只有类型
+        assert!(decode_interval_value(&[TYPE_NAMED_NODE_ID, 0]).is_err());
+        assert!(decode_interval_value(&[TYPE_CLASS, 1, 0, 0]).is_err());
+        assert!(decode_interval_value(&[TYPE_CLASS, 0, 0, 0]).is_err());
+        assert!(decode_interval_value(&[TYPE_CLASS]).is_err());
+        // 属性只有层级编码
+        assert!(decode_interval_value(&[TYPE_PROPERTY, 0]).is_err());
+        assert_eq!(
+            decode_interval_value(&[TYPE_PROPERTY, 0, 0]).unwrap(),
+            Some(IntervalValue::Hierarchy { child: Vec::new(), parent: Vec::new() })
+        );
+    }
+
+
+    #[test]
+    fn test_read_term_nesting_limit() {
+        // 大量叠加的 TYPE_TRIPLE 字节不能导致栈溢出
+        assert!(decode_term(&vec![TYPE_TRIPLE; 1_000_000]).is_err());
+
+        let leaf = EncodedTerm::NamedNode {
+            iri_id: StrHash::new("http://example.com/a"),
+        };
+        let nested = |depth: usize| {
+            (0..depth).fold(leaf.clone(), |term, _| {
+                EncodedTerm::Triple(Rc::new(EncodedTriple {
+                    subject: term,
+                    predicate: leaf.clone(),
+                    object: leaf.clone(),
+                }))
+            })
+        };
+        let deepest = nested(DEFAULT_MAX_TRIPLE_NESTING);
+        assert_eq!(decode_term(&encode_term(&deepest)).unwrap(), deepest);
+        assert!(decode_term(&encode_term(&nested(DEFAULT_MAX_TRIPLE_NESTING + 1))).is_err());
+
+        let buffer = encode_term(&nested(3));
+        assert!(Cursor::new(&buffer).read_term_with_max_nesting(2).is_err());
+        assert!(Cursor::new(&buffer).read_term_with_max_nesting(3).is_ok());
+    }
+
+    #[test]
+    fn test_read_term_header() {
+        use crate::model::vocab::xsd;
+        use crate::model::*;
+
+        let terms: Vec<Term> = vec![
+            NamedNode::new_unchecked("http://foo.com").into(),
+            BlankNode::default().into(),
+            Literal::new_simple_literal("literal").into(),
+            Literal::new_language_tagged_literal_unchecked("foo-fr", "fr").into(),
+            Literal::from(true).into(),
+            Literal::from(1.2_f32).into(),
+            Literal::from(1.2).into(),
+            Literal::from(1).into(),
+            Literal::new_typed_literal("-1.32", xsd::DECIMAL).into(),
+            Literal::new_typed_literal("2020-01-01T01:01:01Z", xsd::DATE_TIME).into(),
+            Literal::new_typed_literal("PT1S", xsd::DURATION).into(),
+            Literal::new_typed_literal("PT1S", xsd::DAY_TIME_DURATION).into(),
+            Literal::new_typed_literal("P1Y", xsd::YEAR_MONTH_DURATION).into(),
+            Literal::new_typed_literal("-foo", NamedNode::new_unchecked("http://foo.com")).into(),
+            Triple::new(
+                NamedNode::new_unchecked("http://foo.com"),
+                NamedNode::new_unchecked("http://bar.com"),
+                Triple::new(
+                    BlankNode::default(),
+                    NamedNode::new_unchecked("http://bar.com"),
+                    Literal::from(1.2),
+                ),
+            )
+            .into(),
+        ];
+        for term in &terms {
+            let buffer = encode_term(&term.as_ref().into());
+            assert_eq!(read_term_header(&buffer).unwrap(), (buffer[0], buffer.len()), "{}", term);
+            assert!(read_term_header(&buffer[..buffer.len() - 1]).is_err(), "{}", term);
+        }
+        let prefixed = EncodedTerm::PrefixedNamedNode {
+            prefix_id: 3,
+            suffix_id: StrHash::new("foo"),
+        };
+        assert_eq!(read_term_header(&encode_term(&prefixed)).unwrap(), (TYPE_CUSTOM_PREFIXED_NAMED_NODE_START + 3, 17));
+
+        // 跳过多个 term 组成的 key 中的前两个 term
+        let key = terms.iter().map(|term| encode_term(&term.as_ref().into())).collect::<Vec<_>>().concat();
+        let (_, first_len) = read_term_header(&key).unwrap();
+        let (_, second_len) = read_term_header(&key[first_len..]).unwrap();
+        assert_eq!(peek_term_type(&key[first_len + second_len..]), Some(TYPE_SMALL_STRING_LITERAL));
+
+        assert_eq!(peek_term_type(&[]), None);
+        assert!(read_term_header(&[]).is_err());
+        assert!(read_term_header(&[0]).is_err());
+        assert!(read_term_header(&vec![TYPE_TRIPLE; 1_000_000]).is_err());
+    }
+
+    #[test]
+    fn test_read_malformed_fixed_width_literals() {
+        let date_time = |seconds: i128, timezone_offset: [u8; 2]| {
+            let mut buffer = vec![TYPE_DATE_TIME_LITERAL];
+            buffer.extend_from_slice(&seconds.to_be_bytes());
+            buffer.extend_from_slice(&timezone_offset);
+            buffer
+        };
+        assert!(decode_term(&date_time(0, 841_i16.to_be_bytes())).is_err());
+        assert!(decode_term(&date_time(0, i16::MIN.to_be_bytes())).is_err());
+        for t in [TYPE_TIME_LITERAL, TYPE_DATE_LITERAL, TYPE_G_YEAR_LITERAL, TYPE_G_MONTH_LITERAL] {
+            let mut buffer = date_time(0, 900_i16.to_be_bytes());
+            buffer[0] = t;
+            assert!(decode_term(&buffer).is_err());
+        }
+
+        // 合法的编码仍然可以读出并格式化
+        for term in [
+            EncodedTerm::DecimalLiteral(Decimal::from_be_bytes(i128::MAX.to_be_bytes())),
+            // Decimal 的运算可以得到 i128::MIN，写入后必须可以读出
+            EncodedTerm::DecimalLiteral(Decimal::from_be_bytes(i128::MIN.to_be_bytes())),
+            EncodedTerm::DayTimeDurationLiteral(DayTimeDuration::from_be_bytes(i128::MIN.to_be_bytes())),
+            EncodedTerm::DateTimeLiteral(DateTime::from_str("2022-01-01T12:00:00-14:00").unwrap()),
+            EncodedTerm::DateLiteral(Date::from_str("-0042-01-01Z").unwrap()),
+            EncodedTerm::DurationLiteral(Duration::from_str("-P1Y2DT3.5S").unwrap()),
+        ] {
+            let decoded = decode_term(&encode_term(&term)).unwrap();
+            assert_eq!(decoded, term);
+            if let EncodedTerm::DecimalLiteral(value) = decoded {
+                assert!(!value.to_string().is_empty());
+            }
+        }
+        assert!(decode_term(&date_time(0, 840_i16.to_be_bytes())).is_ok());
+        assert!(decode_term(&date_time(i128::MAX, [u8::MAX; 2])).is_ok());
+        assert!(decode_term(&date_time(i128::MIN, [u8::MAX; 2])).is_ok());
+    }
+
+    #[test]
+    fn test_numeric_encoding_is_big_endian() {
+        use crate::model::vocab::xsd;
+        use crate::model::*;
+
+        let encoded = |literal: Literal| {
+            let term = EncodedTerm::from(literal.as_ref());
+            let buffer = encode_term(&term);
+            assert_eq!(decode_term(&buffer).unwrap(), term);
+            buffer
+        };
+        let expected = |type_id: u8, parts: &[&[u8]]| {
+            let mut buffer = vec![type_id];
+            for part in parts {
+                buffer.extend_from_slice(part);
+            }
+            buffer
+        };
+        let one_second = 1_000_000_000_000_000_000_i128;
+
+        assert_eq!(
+            encoded(Literal::new_typed_literal("1.5", xsd::FLOAT)),
+            [TYPE_FLOAT_LITERAL, 0x3F, 0xC0, 0, 0]
+        );
+        assert_eq!(
+            encoded(Literal::from(1.5)),
+            [TYPE_DOUBLE_LITERAL, 0x3F, 0xF8, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            encoded(Literal::from(258)),
+            [TYPE_INTEGER_LITERAL, 0, 0, 0, 0, 0, 0, 1, 2]
+        );
+        assert_eq!(
+            encoded(Literal::from(-1)),
+            [TYPE_INTEGER_LITERAL, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+        assert_eq!(
+            encoded(Literal::new_typed_literal("-1.32", xsd::DECIMAL)),
+            expected(TYPE_DECIMAL_LITERAL, &[&(-132 * one_second / 100).to_be_bytes()])
+        );
+        assert_eq!(
+            encoded(Literal::new_typed_literal("P1Y", xsd::YEAR_MONTH_DURATION)),
+            [TYPE_YEAR_MONTH_DURATION_LITERAL, 0, 0, 0, 0, 0, 0, 0, 12]
+        );
+        assert_eq!(
+            encoded(Literal::new_typed_literal("PT1.5S", xsd::DAY_TIME_DURATION)),
+            expected(TYPE_DAY_TIME_DURATION_LITERAL, &[&(3 * one_second / 2).to_be_bytes()])
+        );
+        assert_eq!(
+            encoded(Literal::new_typed_literal("P1MT1S", xsd::DURATION)),
+            expected(TYPE_DURATION_LITERAL, &[&1_i64.to_be_bytes(), &one_second.to_be_bytes()])
+        );
+
+        // 时间类型：16 字节的大端秒数（定点小数）加 2 字节的大端时区偏移（分钟），没有时区时为 0xFFFF
+        let timestamp = |buffer: &[u8]| {
+            assert_eq!(buffer.len(), 1 + 18);
+            let mut value = [0; 16];
+            value.copy_from_slice(&buffer[1..17]);
+            (i128::from_be_bytes(value), [buffer[17], buffer[18]])
+        };
+        for (type_id, datatype, first, second) in [
+            (TYPE_DATE_TIME_LITERAL, xsd::DATE_TIME, "2020-01-01T00:00:01+01:00", "2020-01-01T00:00:02+01:00"),
+            (TYPE_TIME_LITERAL, xsd::TIME, "00:00:01+01:00", "00:00:02+01:00"),
+            (TYPE_DATE_LITERAL, xsd::DATE, "2020-01-01+01:00", "2020-01-02+01:00"),
+            (TYPE_G_YEAR_MONTH_LITERAL, xsd::G_YEAR_MONTH, "2020-01+01:00", "2020-02+01:00"),
+            (TYPE_G_YEAR_LITERAL, xsd::G_YEAR, "2020+01:00", "2021+01:00"),
+            (TYPE_G_MONTH_DAY_LITERAL, xsd::G_MONTH_DAY, "--01-01+01:00", "--01-02+01:00"),
+            (TYPE_G_DAY_LITERAL, xsd::G_DAY, "---01+01:00", "---02+01:00"),
+            (TYPE_G_MONTH_LITERAL, xsd::G_MONTH, "--01+01:00", "--02+01:00"),
+        ] {
+            let first = encoded(Literal::new_typed_literal(first, datatype));
+            let second = encoded(Literal::new_typed_literal(second, datatype));
+            assert_eq!(first[0], type_id);
+            assert_eq!(second[0], type_id);
+            let (first_value, first_offset) = timestamp(&first);
+            let (second_value, second_offset) = timestamp(&second);
+            assert!(first_value < second_value);
+            assert_eq!((second_value - first_value) % one_second, 0);
+            assert_eq!(first_offset, [0, 60]);
+            assert_eq!(second_offset, [0, 60]);
+        }
+        let (value, offset) = timestamp(&encoded(Literal::new_typed_literal("00:00:01", xsd::TIME)));
+        let (next, _) = timestamp(&encoded(Literal::new_typed_literal("00:00:02", xsd::TIME)));
+        assert_eq!(next - value, one_second);
+        assert_eq!(offset, [u8::MAX; 2]);
+        let (value, offset) = timestamp(&encoded(Literal::new_typed_literal("00:00:00-01:00", xsd::TIME)));
+        let (utc, _) = timestamp(&encoded(Literal::new_typed_literal("01:00:00Z", xsd::TIME)));
+        assert_eq!(value, utc);
+        assert_eq!(offset, (-60_i16).to_be_bytes());
+    }
+
+    #[test]
+    fn test_quad_encoding_round_trip() {
+        let term = |name: &str| EncodedTerm::NamedNode {
+            iri_id: StrHash::new(&format!("http://example.com/{}", name)),
+        };
+        let named = EncodedQuad::new(term("s"), term("p"), EncodedTerm::IntegerLiteral(1), term("g"));
+        let default = EncodedQuad::new(term("s"), term("p"), EncodedTerm::IntegerLiteral(1), EncodedTerm::DefaultGraph);
+        for (encoding, quad) in [
+            (QuadEncoding::Spog, &named),
+            (QuadEncoding::Posg, &named),
+            (QuadEncoding::Ospg, &named),
+            (QuadEncoding::Gspo, &named),
+            (QuadEncoding::Gpos, &named),
+            (QuadEncoding::Gosp, &named),
+            (QuadEncoding::Dspo, &default),
+            (QuadEncoding::Dpos, &default),
+            (QuadEncoding::Dosp, &default),
+        ] {
+            let mut buffer = Vec::new();
+            encoding.encode_quad(quad, &mut buffer);
+            assert_eq!(encoding.decode_with_len(&buffer).unwrap(), (quad.clone(), buffer.len()));
+        }
+    }
+
+    #[test]
+    fn test_atom_bytes() {
+        // 计数器是全局的，其他测试可能同时在编码，所以只测量本测试前后的差值，并且不重置计数器
+        let terms = (0..100_000)
+            .map(|i| {
+                EncodedTerm::from(NamedNodeRef::new_unchecked(&format!(
+                    "http://example.com/{}",
+                    i
+                )))
+            })
+            .collect::<Vec<_>>();
+        let before = atom_bytes();
+        for term in &terms {
+            encode_term_triple(term, term, term);
+        }
+        let expected = terms.len() * 3 * WRITTEN_TERM_MAX_SIZE;
+        assert!(atom_bytes() - before >= expected);
+    }
 }
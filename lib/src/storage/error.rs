@@ -37,6 +37,24 @@ impl Error for StorageError {
     }
 }
 
+impl StorageError {
+    /// Returns `true` if the operation failed because of a temporary state and may succeed if it is retried.
+    ///
+    /// It is the case of the conflicts between concurrent transactions, of the lock timeouts
+    /// and of the interrupted or timed out I/O operations.
+    /// Corruptions and the other I/O errors are permanent: retrying is useless.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            Self::Corruption(_) => false,
+            Self::Other(e) => has_transient_source(e.as_ref()),
+        }
+    }
+}
+
 impl From<io::Error> for StorageError {
     #[inline]
     fn from(error: io::Error) -> Self {
@@ -119,6 +137,49 @@ impl From<CorruptionError> for io::Error {
     }
 }
 
+/// A backend error caused by a temporary state, like a conflict with a concurrent transaction.
+///
+/// It is wrapped by the backend when the error is converted to a [`StorageError`], see [`StorageError::is_transient`].
+#[derive(Debug)]
+pub(crate) struct TransientError {
+    inner: Box<dyn Error + Send + Sync + 'static>,
+}
+
+impl TransientError {
+    #[inline]
+    pub(crate) fn new(error: impl Into<Box<dyn Error + Send + Sync + 'static>>) -> Self {
+        Self {
+            inner: error.into(),
+        }
+    }
+}
+
+impl fmt::Display for TransientError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl Error for TransientError {
+    #[inline]
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.inner.as_ref())
+    }
+}
+
+// 沿着 source 链查找 TransientError，调用者的错误类型可能包装了 StorageError
+pub(crate) fn has_transient_source(error: &(dyn Error + 'static)) -> bool {
+    let mut error = Some(error);
+    while let Some(e) = error {
+        if e.is::<TransientError>() {
+            return true;
+        }
+        error = e.source();
+    }
+    false
+}
+
 /// An error raised while loading a file into a [`Store`](crate::store::Store).
 #[derive(Debug)]
 pub enum LoaderError {
@@ -224,3 +285,35 @@ impl From<SerializerError> for io::Error {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        for kind in [
+            io::ErrorKind::Interrupted,
+            io::ErrorKind::WouldBlock,
+            io::ErrorKind::TimedOut,
+        ] {
+            assert!(StorageError::from(io::Error::new(kind, "oops")).is_transient());
+        }
+        for kind in [
+            io::ErrorKind::NotFound,
+            io::ErrorKind::PermissionDenied,
+            io::ErrorKind::Other,
+        ] {
+            assert!(!StorageError::from(io::Error::new(kind, "oops")).is_transient());
+        }
+        assert!(!StorageError::from(CorruptionError::msg("oops")).is_transient());
+        assert!(!StorageError::Other("oops".into()).is_transient());
+
+        let conflict = StorageError::Other(Box::new(TransientError::new("Resource busy")));
+        assert!(conflict.is_transient());
+        assert_eq!(conflict.to_string(), "Resource busy");
+        // 调用者的错误包装了 StorageError
+        let wrapped = LoaderError::Storage(conflict);
+        assert!(has_transient_source(&wrapped));
+    }
+}
@@ -1,31 +1,51 @@
-use crate::model::{GraphNameRef, NamedOrBlankNodeRef, Quad, QuadRef, TermRef};
+use crate::model::{
+    GraphName, GraphNameRef, NamedNode, NamedNodeRef, NamedOrBlankNode, NamedOrBlankNodeRef, Quad,
+    QuadRef, Subject, SubjectRef, Term, TermRef, TripleRef,
+};
+use crate::io::{DatasetFormat, DatasetSerializer, GraphFormat, GraphSerializer};
 use crate::storage::backend::{Reader, Transaction};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage::binary_encoder::LATEST_STORAGE_VERSION;
 use crate::storage::binary_encoder::{
     decode_term, encode_term, encode_term_pair, encode_term_quad, encode_term_triple,
     write_gosp_quad, write_gpos_quad, write_gspo_quad, write_osp_quad, write_ospg_quad,
-    write_pos_quad, write_posg_quad, write_spo_quad, write_spog_quad, write_term, QuadEncoding,
-    WRITTEN_TERM_MAX_SIZE,ATOM_BYTES
+    write_pos_quad, write_posg_quad, write_spo_quad, write_spog_quad, write_term,
+    decode_interval_value, IntervalValue, TermReader, MAX_CUSTOM_PREFIXES, WRITTEN_TERM_MAX_SIZE,ATOM_BYTES
 };
+pub use crate::storage::binary_encoder::{atom_bytes, reset_atom_bytes, ObjectKind, QuadEncoding};
 pub use crate::storage::error::{CorruptionError, LoaderError, SerializerError, StorageError};
 use crate::storage::numeric_encoder::{
-    insert_term, Decoder, EncodedQuad, EncodedTerm, StrHash, StrLookup,
+    for_each_str_hash, insert_term, iri_hash, Decoder, EncodedQuad, EncodedTerm, PrefixTable, StrHash,
+    StrLookup,
 };
 
 use backend::{ColumnFamily, ColumnFamilyDefinition, Db, Iter};
+pub use backend::StorageColumnFamily;
+#[cfg(not(target_arch = "wasm32"))]
+use backend::{SstFile, WriteBatch};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::model::{BlankNode, Triple};
+#[cfg(not(target_arch = "wasm32"))]
+pub use backend::StorageOptions;
+use std::cell::Cell;
+#[cfg(not(target_arch = "wasm32"))]
+use std::cmp::Reverse;
 use std::cmp::{max, min};
-use std::collections::VecDeque;
 #[cfg(not(target_arch = "wasm32"))]
-use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::{BTreeMap, BinaryHeap};
 use std::error::Error;
 #[cfg(not(target_arch = "wasm32"))]
-use std::mem::take;
+use std::mem::{size_of, take};
+use std::iter::{from_fn, once, Take};
 use std::ops::Mul;
+use std::path::Path;
 #[cfg(not(target_arch = "wasm32"))]
-use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
 #[cfg(not(target_arch = "wasm32"))]
 use std::thread::spawn;
 use std::thread::JoinHandle;
@@ -34,7 +54,7 @@ use sysinfo::{System, SystemExt};
 use crate::extendedTree::vocab::{owl, rdf, rdfs, lubm};
 use crate::extendedTree::{MultiTree};
 use std::fs::File;
-use std::io::{self, BufRead, Read};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 
 use self::binary_encoder::{encode_term_triple_oxiuse_value_spo, encode_term_triple_oxiuse_value_osp, encode_term_triple_oxiuse_value_pos, encode_term_triple_oxiuse_key_spo, encode_term_triple_oxiuse_key_pos, encode_term_triple_oxiuse_key_osp};
 
@@ -56,10 +76,108 @@ const DSPO_CF: &str = "dspo";
 const DPOS_CF: &str = "dpos";
 const DOSP_CF: &str = "dosp";
 const GRAPHS_CF: &str = "graphs";
+const TREES_CF: &str = "trees";
 const DEFAULT_CF: &str = "default";
+// default_cf 中前缀表的 key 为 PREFIX_KEY + 前缀编号，编号从 0 开始连续分配
+const PREFIX_KEY: &[u8] = b"oxprefix";
+// trees_cf 中 Class 树与属性树的 key
+const CLASS_TREE_KEY: &[u8] = b"class";
+const PROPERTY_TREE_KEY: &[u8] = b"property";
+// trees_cf 中构造这两棵树的文件路径
+const TREE_PATH_KEY: &[u8] = b"path";
+// default_cf 中记录默认图索引布局（TripleEncodingMode）的 key，与 oxversion 放在一起
+const TRIPLE_ENCODING_KEY: &[u8] = b"oxencoding";
 #[cfg(not(target_arch = "wasm32"))]
 const DEFAULT_BULK_LOAD_BATCH_SIZE: usize = 1_000_000;
 const MAX_BULK_LOAD_BATCH_SIZE: usize = 100_000_000;
+#[cfg(not(target_arch = "wasm32"))]
+const DEFAULT_ID2STR_SPILL_SIZE: usize = 64_000_000;
+
+// StorageColumnFamily 定义在 backend 中（StorageOptions 会用到），名字与 key 编码相关的方法在这里
+impl StorageColumnFamily {
+    /// The name of the underlying RocksDB column family.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Default => DEFAULT_CF,
+            Self::Id2str => ID2STR_CF,
+            Self::Spog => SPOG_CF,
+            Self::Posg => POSG_CF,
+            Self::Ospg => OSPG_CF,
+            Self::Gspo => GSPO_CF,
+            Self::Gpos => GPOS_CF,
+            Self::Gosp => GOSP_CF,
+            Self::Dspo => DSPO_CF,
+            Self::Dpos => DPOS_CF,
+            Self::Dosp => DOSP_CF,
+            Self::Graphs => GRAPHS_CF,
+            Self::Trees => TREES_CF,
+        }
+    }
+
+    /// The largest size of the fixed prefix extractor that is consistent with the key encoding.
+    ///
+    /// The prefix must not be longer than the shortest possible first term of the keys:
+    /// a named node, a blank node or a quoted triple is written on at least 17 bytes (a type byte and a 16 bytes id),
+    /// but a small literal may only be its type byte.
+    /// `None` if the column family is only read with point lookups and has no prefix extractor.
+    pub const fn max_prefix_size(self) -> Option<usize> {
+        match self {
+            Self::Default | Self::Id2str | Self::Trees => None,
+            Self::Ospg | Self::Dosp => Some(1),
+            Self::Spog
+            | Self::Posg
+            | Self::Gspo
+            | Self::Gpos
+            | Self::Gosp
+            | Self::Dspo
+            | Self::Dpos
+            | Self::Graphs => Some(17),
+        }
+    }
+
+    /// The column family storing the given quad index.
+    pub const fn of_index(index: QuadEncoding) -> Self {
+        match index {
+            QuadEncoding::Spog => Self::Spog,
+            QuadEncoding::Posg => Self::Posg,
+            QuadEncoding::Ospg => Self::Ospg,
+            QuadEncoding::Gspo => Self::Gspo,
+            QuadEncoding::Gpos => Self::Gpos,
+            QuadEncoding::Gosp => Self::Gosp,
+            QuadEncoding::Dspo => Self::Dspo,
+            QuadEncoding::Dpos => Self::Dpos,
+            QuadEncoding::Dosp => Self::Dosp,
+        }
+    }
+}
+
+/// How [`StorageReader::quads_for_pattern`] answers a pattern, returned by [`StorageReader::plan_pattern`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PatternPlan {
+    /// The scanned column family.
+    /// If the graph name is not bound it is the named graph index and its default graph counterpart is scanned too.
+    pub chosen_cf: &'static str,
+    /// The length of the encoded bound terms used as the scan prefix.
+    pub prefix_len_bytes: usize,
+    /// The number of matching keys, counted up to [`PatternPlan::MAX_ESTIMATED_ROWS`].
+    pub estimated_rows: usize,
+}
+
+impl PatternPlan {
+    /// Above this number of keys the estimation stops counting.
+    pub const MAX_ESTIMATED_ROWS: usize = 10_000;
+}
+
+/// Per predicate statistics returned by [`StorageReader::predicate_stats`].
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct PredicateStats {
+    /// The number of quads with this predicate.
+    pub triple_count: usize,
+    /// The number of distinct objects of this predicate.
+    pub distinct_objects: usize,
+    /// The number of distinct subjects of this predicate.
+    pub distinct_subjects: usize,
+}
 
 /// Low level storage primitives
 // columnfamily可以起到隔离数据的作用。下面除了九张表存储三元组（四元组）之外，还包括id2str映射表
@@ -79,19 +197,186 @@ pub struct Storage {
     dpos_cf: ColumnFamily,
     dosp_cf: ColumnFamily,
     graphs_cf: ColumnFamily,
+    trees_cf: ColumnFamily,
+    change_listener: Arc<RwLock<Option<Box<ChangeListener>>>>,
+    detect_hash_collisions: Arc<AtomicBool>,
+    // id2str 的 key 使用的盐，0 表示不加盐，保存在 default_cf 的 oxsalt 中
+    str_salt: u128,
+    // 已注册的 IRI 前缀，打开存储、注册前缀和清空存储时从 default_cf 中重新读取
+    prefixes: Arc<RwLock<Arc<PrefixTable>>>,
+    // 默认图索引的 key 是否以区间编码开头（IntervalKey 布局），读取时需要先跳过区间编码
+    interval_keys: Arc<AtomicBool>,
+}
+
+type ChangeListener = dyn Fn(&ChangeEvent) + Send + Sync;
+
+/// A state change made by a [`StorageWriter`], reported to the listener set with [`Storage::set_change_listener`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub quad_or_graph: QuadOrGraph,
+    /// Always `true`: events are emitted once the writes are staged in the transaction, before it is committed.
+    /// A transaction that is rolled back or retried after a conflict may thus report changes that are never persisted.
+    pub pre_commit: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Insert,
+    Remove,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuadOrGraph {
+    Quad(Quad),
+    Graph(NamedOrBlankNode),
 }
 
 // 有column family、flash、compaction 对 rocksDB封装的底层操作
 impl Storage {
     // 创建Storage
     pub fn new() -> Result<Self, StorageError> {
+        Self::new_in_memory()
+    }
+
+    /// Creates a storage that only lives in memory, the same as [`new`](Self::new).
+    ///
+    /// No file is written on disk: RocksDB runs on an in-memory environment without write-ahead log,
+    /// and all the data is lost when the last clone of the storage is dropped.
+    /// [`backup`](Self::backup) is not supported on such a storage,
+    /// the data has to be copied to an opened one, e.g. with [`merge_from`](Self::merge_from), or serialized to be kept.
+    pub fn new_in_memory() -> Result<Self, StorageError> {
         Self::setup(Db::new(Self::initial_column_families())?)
     }
 
     // 打开给定路径的数据库
     #[cfg(not(target_arch = "wasm32"))]
     pub fn open(path: &Path) -> Result<Self, StorageError> {
-        Self::setup(Db::open(path, Self::initial_column_families())?)
+        Self::open_with_options(path, &StorageOptions::default())
+    }
+
+    /// Opens the storage with a custom tuning of the backend.
+    ///
+    /// The options only apply for the lifetime of the returned storage: reopening it with [`open`](Self::open) restores the defaults.
+    /// Changing [`StorageOptions::min_prefix_sizes`] does not require rewriting the data,
+    /// RocksDB ignores the prefix filters of the files written with another prefix size.
+    /// A prefix size larger than [`StorageColumnFamily::max_prefix_size`] is rejected.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_with_options(path: &Path, options: &StorageOptions) -> Result<Self, StorageError> {
+        Self::setup(Db::open_with_options(
+            path,
+            Self::column_families_with_options(options)?,
+            options,
+        )?)
+    }
+
+    // 用 options 中的 min_prefix_size 和 id2str 的 Bloom filter 覆盖默认值，前缀不能超过 key 中第一个词项的最短编码长度
+    #[cfg(not(target_arch = "wasm32"))]
+    fn column_families_with_options(
+        options: &StorageOptions,
+    ) -> Result<Vec<ColumnFamilyDefinition>, StorageError> {
+        let mut column_families = Self::initial_column_families();
+        for (column_family, min_prefix_size) in &options.min_prefix_sizes {
+            let max_prefix_size = column_family.max_prefix_size().ok_or_else(|| {
+                StorageError::Other(
+                    format!(
+                        "The {} column family is only used for point lookups and has no prefix extractor",
+                        column_family.name()
+                    )
+                    .into(),
+                )
+            })?;
+            if *min_prefix_size > max_prefix_size {
+                return Err(StorageError::Other(
+                    format!(
+                        "The prefix size of the {} column family must be at most {} bytes, the shortest encoding of the first term of its keys, found {}",
+                        column_family.name(),
+                        max_prefix_size,
+                        min_prefix_size
+                    )
+                    .into(),
+                ));
+            }
+            if let Some(definition) = column_families
+                .iter_mut()
+                .find(|definition| definition.name == column_family.name())
+            {
+                definition.min_prefix_size = *min_prefix_size;
+            }
+        }
+        if let Some(bits) = options.id2str_bloom_filter_bits {
+            if let Some(definition) = column_families
+                .iter_mut()
+                .find(|definition| definition.name == ID2STR_CF)
+            {
+                definition.bloom_filter_bits = Some(f64::from(bits));
+            }
+        }
+        Ok(column_families)
+    }
+
+    /// Opens the storage with `id2str` keys salted with `salt`, see [`StrHash::salted`].
+    ///
+    /// The salt is persisted next to the storage version on the first opening, when no string is stored yet,
+    /// and later openings with [`open`](Self::open) use it. Opening with another salt fails:
+    /// mixing salts in the same store corrupts reads because strings written with one salt are not found with the other.
+    /// The salt `0` means no salt.
+    ///
+    /// Only the `id2str` keys are salted, the quad indexes still reference strings by their unsalted [`StrHash`]
+    /// so their keys stay the same for every salt and remain predictable from the stored strings.
+    /// The salt does not protect against hash prediction.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_with_salt(path: &Path, salt: u128) -> Result<Self, StorageError> {
+        let mut storage = Self::open(path)?;
+        if storage.str_salt != salt {
+            if storage.str_salt != 0 || !storage.is_id2str_empty()? {
+                return Err(StorageError::Other(
+                    format!(
+                        "The store {} has been written with another string hash salt, opening it with a different salt would corrupt reads",
+                        path.display()
+                    )
+                    .into(),
+                ));
+            }
+            storage.save_str_salt(salt)?;
+            storage.str_salt = salt;
+        }
+        Ok(storage)
+    }
+
+    /// Opens the storage and checks that the redundant quad indexes have the same number of entries.
+    ///
+    /// A bulk load interrupted while ingesting its SST files may leave some indexes with the new quads and others without them.
+    /// The check only counts the keys of each index, it is much cheaper than [`StorageReader::validate`].
+    /// On a mismatch, `mode` tells if the opening fails or if [`repair`](Self::repair) is run first.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_with_recovery(path: &Path, mode: RecoveryMode) -> Result<Self, StorageError> {
+        let storage = Self::open(path)?;
+        if let Some(mismatch) = storage.snapshot().index_size_mismatch()? {
+            match mode {
+                RecoveryMode::Refuse => {
+                    return Err(CorruptionError::msg(format!(
+                        "The quad indexes of {} do not have the same number of entries ({}), a bulk load has probably been interrupted. Open it with RecoveryMode::Repair to rebuild the missing entries",
+                        path.display(),
+                        mismatch
+                    ))
+                    .into())
+                }
+                RecoveryMode::Repair => {
+                    storage.repair()?;
+                }
+            }
+        }
+        Ok(storage)
+    }
+
+    /// Opens the database at the given path in read-only mode.
+    ///
+    /// Several read-only handles can coexist with a read-write one on the same directory.
+    /// No migration is applied and all writes fail with a [`StorageError`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open_read_only(path: &Path) -> Result<Self, StorageError> {
+        Self::setup(Db::open_read_only(path, Self::initial_column_families())?)
     }
 
     // 初始化列族参数，用此来创建Db实例
@@ -102,66 +387,84 @@ impl Storage {
                 use_iter: false,
                 min_prefix_size: 0,
                 unordered_writes: true,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: SPOG_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: POSG_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: OSPG_CF,
                 use_iter: true,
                 min_prefix_size: 0, // There are small literals...
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: GSPO_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: GPOS_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: GOSP_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: DSPO_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: DPOS_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: DOSP_CF,
                 use_iter: true,
                 min_prefix_size: 0, // There are small literals...
                 unordered_writes: false,
+                bloom_filter_bits: None,
             },
             ColumnFamilyDefinition {
                 name: GRAPHS_CF,
                 use_iter: true,
                 min_prefix_size: 17, // named or blank node start
                 unordered_writes: false,
+                bloom_filter_bits: None,
+            },
+            ColumnFamilyDefinition {
+                name: TREES_CF,
+                use_iter: false,
+                min_prefix_size: 0,
+                unordered_writes: false,
+                bloom_filter_bits: None,
             },
         ]
     }
@@ -170,7 +473,7 @@ impl Storage {
     // 接着再使用db实例以及这些cf创建Storage实例
     // 装配 columnfamily
     fn setup(db: Db) -> Result<Self, StorageError> {
-        let this = Self {
+        let mut this = Self {
             default_cf: db.column_family(DEFAULT_CF).unwrap(),   
             id2str_cf: db.column_family(ID2STR_CF).unwrap(),
             spog_cf: db.column_family(SPOG_CF).unwrap(),
@@ -183,10 +486,26 @@ impl Storage {
             dpos_cf: db.column_family(DPOS_CF).unwrap(),
             dosp_cf: db.column_family(DOSP_CF).unwrap(),
             graphs_cf: db.column_family(GRAPHS_CF).unwrap(),
+            trees_cf: db.column_family(TREES_CF).unwrap(),
+            change_listener: Arc::default(),
+            detect_hash_collisions: Arc::default(),
+            str_salt: 0,
+            prefixes: Arc::default(),
+            interval_keys: Arc::default(),
             db,
         };
+        this.str_salt = this.load_str_salt()?;
+        this.reload_prefixes()?;
         #[cfg(not(target_arch = "wasm32"))]
-        this.migrate()?;
+        this.interval_keys.store(
+            this.triple_encoding_mode()? == Some(TripleEncodingMode::IntervalKey),
+            Ordering::Relaxed,
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        if !this.db.is_read_only() {
+            // 只读模式下无法写入迁移结果
+            this.migrate()?;
+        }
         Ok(this)
     }
 
@@ -218,6 +537,12 @@ impl Storage {
             version = 1;
             self.update_version(version)?;
         }
+        if version == 1 {
+            // We migrate to v2: the hierarchy interval encodings get their interval counts
+            self.migrate_interval_encodings()?;
+            version = 2;
+            self.update_version(version)?;
+        }
 
         match version {
             _ if version < LATEST_STORAGE_VERSION => Err(CorruptionError::msg(format!(
@@ -232,6 +557,51 @@ impl Storage {
         }
     }
 
+    // 版本 1 的 subClassOf、subPropertyOf 区间编码没有区间数，无法与其他区间编码统一解析
+    // 用保存的树重新计算默认图三个索引中的区间编码（key 的前缀或者 value），在一个 WriteBatch 中替换
+    #[cfg(not(target_arch = "wasm32"))]
+    fn migrate_interval_encodings(&self) -> Result<(), StorageError> {
+        // 使用前缀编码的 IRI 需要读取后缀才能在树中查找
+        let reader = self.snapshot();
+        let iri_hash = |term: &EncodedTerm| iri_hash(term, &reader);
+        let mut batch = self.db.new_write_batch();
+        let mut trees = None;
+        for (encoding, column_family) in [
+            (QuadEncoding::Dspo, &self.dspo_cf),
+            (QuadEncoding::Dpos, &self.dpos_cf),
+            (QuadEncoding::Dosp, &self.dosp_cf),
+        ] {
+            let mut iter = reader.reader.iter(column_family)?;
+            while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                let (quad, in_key) = encoding.decode_legacy_interval_key(key, reader.prefix_table())?;
+                if in_key || !value.is_empty() {
+                    if trees.is_none() {
+                        trees = Some(self.interval_trees()?.ok_or_else(|| {
+                            CorruptionError::msg(
+                                "The interval encodings of this store can not be migrated without the saved interval trees, please dump the store dataset and load it again",
+                            )
+                        })?);
+                    }
+                    let trees = trees.as_ref().unwrap();
+                    let map = triple_term_map(&quad);
+                    let (new_key, new_value) = match (in_key, encoding) {
+                        (true, QuadEncoding::Dspo) => (encode_term_triple_oxiuse_key_spo(map, trees, &iri_hash)?, Vec::new()),
+                        (true, QuadEncoding::Dpos) => (encode_term_triple_oxiuse_key_pos(map, trees, &iri_hash)?, Vec::new()),
+                        (true, _) => (encode_term_triple_oxiuse_key_osp(map, trees, &iri_hash)?, Vec::new()),
+                        (false, QuadEncoding::Dspo) => encode_term_triple_oxiuse_value_spo(map, trees, &iri_hash)?,
+                        (false, QuadEncoding::Dpos) => encode_term_triple_oxiuse_value_pos(map, trees, &iri_hash)?,
+                        (false, _) => encode_term_triple_oxiuse_value_osp(map, trees, &iri_hash)?,
+                    };
+                    batch.remove(column_family, key);
+                    batch.insert(column_family, &new_key, &new_value);
+                }
+                iter.next();
+            }
+            iter.status()?;
+        }
+        self.db.write_batch(batch)
+    }
+
     // 读取当前的 oxversion（若不存在则写入 LATEST_STORAGE_VERSION）
     #[cfg(not(target_arch = "wasm32"))]
     fn ensure_version(&self) -> Result<u64, StorageError> {
@@ -247,6 +617,44 @@ impl Storage {
         )
     }
 
+    // 读取 default_cf 中的 oxsalt，不存在时表示不加盐
+    fn load_str_salt(&self) -> Result<u128, StorageError> {
+        Ok(if let Some(salt) = self.db.get(&self.default_cf, b"oxsalt")? {
+            u128::from_be_bytes(<[u8; 16]>::try_from(&*salt).map_err(|_| {
+                CorruptionError::msg("The string hash salt stored in oxsalt should be 16 bytes long")
+            })?)
+        } else {
+            0
+        })
+    }
+
+    // 与 oxversion 一样写入 default_cf 并刷盘
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_str_salt(&self, salt: u128) -> Result<(), StorageError> {
+        self.db
+            .insert(&self.default_cf, b"oxsalt", &salt.to_be_bytes())?;
+        self.db.flush(&self.default_cf)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn is_id2str_empty(&self) -> Result<bool, StorageError> {
+        let reader = self.db.snapshot();
+        let iter = reader.iter(&self.id2str_cf)?;
+        let is_empty = iter.key().is_none();
+        iter.status()?;
+        Ok(is_empty)
+    }
+
+    /// Returns the salt applied to the `id2str` keys of this storage, `0` if there is none.
+    pub fn str_salt(&self) -> u128 {
+        self.str_salt
+    }
+
+    // id2str 中 StrHash 对应的 key
+    fn id2str_key(&self, key: &StrHash) -> [u8; 16] {
+        key.salted(self.str_salt).to_be_bytes()
+    }
+
     // 更新 version
     #[cfg(not(target_arch = "wasm32"))]
     fn update_version(&self, version: u64) -> Result<(), StorageError> {
@@ -255,11 +663,67 @@ impl Storage {
         self.db.flush(&self.default_cf)
     }
 
+    /// Registers an IRI prefix used to compress named nodes and returns its id.
+    ///
+    /// Registering the same prefix twice returns the same id. At most 128 prefixes could be registered.
+    /// Every named node is then encoded with the longest registered prefix it starts with, see [`PrefixTable`].
+    /// So that an IRI never has two encodings, the prefixes can only be registered while the storage is empty
+    /// and no other transaction is writing to it.
+    pub fn register_prefix(&self, prefix: &str) -> Result<u8, StorageError> {
+        let id = self.transaction(|mut writer| writer.register_prefix(prefix))?;
+        self.reload_prefixes()?;
+        Ok(id)
+    }
+
+    // 前缀表的 key 为 PREFIX_KEY + 编号，编号从 0 开始连续分配
+    fn load_prefixes(&self, reader: &Reader) -> Result<Vec<String>, StorageError> {
+        let mut prefixes = Vec::new();
+        while prefixes.len() < MAX_CUSTOM_PREFIXES {
+            match reader.get(&self.default_cf, &prefix_key(prefixes.len().try_into().unwrap()))? {
+                Some(prefix) => prefixes
+                    .push(String::from_utf8(prefix.into()).map_err(CorruptionError::new)?),
+                None => break,
+            }
+        }
+        Ok(prefixes)
+    }
+
+    fn reload_prefixes(&self) -> Result<(), StorageError> {
+        let prefixes = PrefixTable::new(self.load_prefixes(&self.db.snapshot())?);
+        *self.prefixes.write().unwrap() = Arc::new(prefixes);
+        Ok(())
+    }
+
+    fn prefix_table(&self) -> Arc<PrefixTable> {
+        Arc::clone(&self.prefixes.read().unwrap())
+    }
+
     // 创建当前Storage(db)的快照，并返回StorageReader【当前的Storage+一个只读视图（Reader）】
+    /// Returns a reader on a point-in-time view of the storage.
+    ///
+    /// All the reads done with the same reader observe the same state,
+    /// even if writes are committed in the meantime. Call `snapshot` again to see them.
+    ///
+    /// ```
+    /// use oxigraph::model::*;
+    /// use oxigraph::storage::Storage;
+    ///
+    /// let storage = Storage::new()?;
+    /// let ex = NamedNodeRef::new("http://example.com")?;
+    /// let reader = storage.snapshot();
+    /// storage.transaction(|mut writer| writer.insert(QuadRef::new(ex, ex, ex, GraphNameRef::DefaultGraph)))?;
+    ///
+    /// // The write is not visible from the reader created before it
+    /// assert_eq!(reader.len()?, 0);
+    /// assert_eq!(reader.quads_for_pattern(None, None, None, None).count(), 0);
+    /// assert_eq!(storage.snapshot().len()?, 1);
+    /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
+    /// ```
     pub fn snapshot(&self) -> StorageReader {
         StorageReader {
             reader: self.db.snapshot(),
             storage: self.clone(),
+            prefixes: self.prefix_table(),
         }
     }
 
@@ -273,1947 +737,7986 @@ impl Storage {
                 buffer: Vec::new(),
                 transaction,
                 storage: self,
+                prefixes: self.prefix_table(),
+            })
+        })
+    }
+
+    /// Same as [`transaction`](Self::transaction) but only retries `max_retries` times on write conflicts,
+    /// with an exponential backoff between the attempts, before returning the conflict error.
+    ///
+    /// The closure may be called several times: it must not have side effects outside of the given writer.
+    pub fn transaction_with_retry<'a, 'b: 'a, T, E: Error + 'static + From<StorageError>>(
+        &'b self,
+        max_retries: usize,
+        f: impl Fn(StorageWriter<'a>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        self.db.transaction_with_retry(max_retries, |transaction| {
+            f(StorageWriter {
+                buffer: Vec::new(),
+                transaction,
+                storage: self,
+                prefixes: self.prefix_table(),
             })
         })
     }
 
+    // 根据 StorageColumnFamily 获得对应的 cf
+    fn column_family(&self, which: StorageColumnFamily) -> &ColumnFamily {
+        match which {
+            StorageColumnFamily::Default => &self.default_cf,
+            StorageColumnFamily::Id2str => &self.id2str_cf,
+            StorageColumnFamily::Spog => &self.spog_cf,
+            StorageColumnFamily::Posg => &self.posg_cf,
+            StorageColumnFamily::Ospg => &self.ospg_cf,
+            StorageColumnFamily::Gspo => &self.gspo_cf,
+            StorageColumnFamily::Gpos => &self.gpos_cf,
+            StorageColumnFamily::Gosp => &self.gosp_cf,
+            StorageColumnFamily::Dspo => &self.dspo_cf,
+            StorageColumnFamily::Dpos => &self.dpos_cf,
+            StorageColumnFamily::Dosp => &self.dosp_cf,
+            StorageColumnFamily::Graphs => &self.graphs_cf,
+            StorageColumnFamily::Trees => &self.trees_cf,
+        }
+    }
+
+    /// Sets the callback called by [`StorageWriter`] for each quad or named graph actually inserted or removed.
+    ///
+    /// It is shared by all the clones of this storage and replaces any previously set listener.
+    pub fn set_change_listener(&self, listener: Box<ChangeListener>) {
+        *self.change_listener.write().unwrap() = Some(listener);
+    }
+
+    /// Enables checking, when a string is written to `id2str` under an already stored [`StrHash`],
+    /// that the stored string is the same and failing with a [`CorruptionError`] otherwise.
+    ///
+    /// It costs a read per already stored string so it is disabled by default.
+    pub fn set_hash_collision_detection(&self, enabled: bool) {
+        self.detect_hash_collisions.store(enabled, Ordering::Relaxed);
+    }
+
+    fn detects_hash_collisions(&self) -> bool {
+        self.detect_hash_collisions.load(Ordering::Relaxed)
+    }
+
+    fn has_change_listener(&self) -> bool {
+        self.change_listener.read().unwrap().is_some()
+    }
+
+    fn notify_change(&self, kind: ChangeKind, quad_or_graph: impl FnOnce() -> QuadOrGraph) {
+        if let Some(listener) = &*self.change_listener.read().unwrap() {
+            listener(&ChangeEvent {
+                kind,
+                quad_or_graph: quad_or_graph(),
+                pre_commit: true,
+            });
+        }
+    }
+
     // 最终数据的持久化都是保存在SST中，而SST则是由Memtable刷新到磁盘生成的，这就是Flush过程
     // 也使用了 rocksdb.rs 中提供的 API
     #[cfg(not(target_arch = "wasm32"))]
     pub fn flush(&self) -> Result<(), StorageError> {
-        self.db.flush(&self.default_cf)?;
-        self.db.flush(&self.gpos_cf)?;
-        self.db.flush(&self.gpos_cf)?;
-        self.db.flush(&self.gosp_cf)?;
-        self.db.flush(&self.spog_cf)?;
-        self.db.flush(&self.posg_cf)?;
-        self.db.flush(&self.ospg_cf)?;
-        self.db.flush(&self.dspo_cf)?;
-        self.db.flush(&self.dpos_cf)?;
-        self.db.flush(&self.dosp_cf)?;
-        self.db.flush(&self.id2str_cf)
+        for which in StorageColumnFamily::ALL {
+            self.flush_cf(which)?;
+        }
+        Ok(())
+    }
+
+    /// Flushes a single column family, e.g. the index that has just been written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn flush_cf(&self, which: StorageColumnFamily) -> Result<(), StorageError> {
+        self.db.flush(self.column_family(which))
     }
 
     // 使用了 rocksdb.rs 中提供了API
     #[cfg(not(target_arch = "wasm32"))]
     pub fn compact(&self) -> Result<(), StorageError> {
-        self.db.compact(&self.default_cf)?;
-        self.db.compact(&self.gpos_cf)?;
-        self.db.compact(&self.gpos_cf)?;
-        self.db.compact(&self.gosp_cf)?;
-        self.db.compact(&self.spog_cf)?;
-        self.db.compact(&self.posg_cf)?;
-        self.db.compact(&self.ospg_cf)?;
-        self.db.compact(&self.dspo_cf)?;
-        self.db.compact(&self.dpos_cf)?;
-        self.db.compact(&self.dosp_cf)?;
-        self.db.compact(&self.id2str_cf)
+        for which in StorageColumnFamily::ALL {
+            self.db.compact(self.column_family(which))?;
+        }
+        Ok(())
+    }
+
+    /// Compacts the quads of a single graph, e.g. to reclaim the space of a graph that has just been cleared.
+    ///
+    /// In the `gspo`, `gpos` and `gosp` indexes the graph is the key prefix so only its range is compacted.
+    /// In the `spog`, `posg` and `ospg` indexes the graph is the last term of the key and its quads are spread over
+    /// the whole index, and the default graph is the whole of `dspo`, `dpos` and `dosp`.
+    /// These indexes are fully compacted, which rewrites them entirely, only if `full_indexes` is `true`:
+    /// otherwise nothing is compacted for the default graph.
+    /// The `id2str` strings that are no longer used are not removed, see [`StorageWriter::collect_unused_strings`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compact_graph(&self, graph: &EncodedTerm, full_indexes: bool) -> Result<(), StorageError> {
+        if graph.is_default_graph() {
+            if full_indexes {
+                for column_family in [&self.dspo_cf, &self.dpos_cf, &self.dosp_cf] {
+                    self.db.compact(column_family)?;
+                }
+            }
+            return Ok(());
+        }
+        let start = encode_term(graph);
+        let end = next_prefix(&start);
+        for column_family in [&self.gspo_cf, &self.gpos_cf, &self.gosp_cf] {
+            self.db
+                .compact_range(column_family, Some(&start), end.as_deref())?;
+        }
+        if full_indexes {
+            for column_family in [&self.spog_cf, &self.posg_cf, &self.ospg_cf] {
+                self.db.compact(column_family)?;
+            }
+        }
+        Ok(())
     }
 
+    /// Writes a point-in-time consistent copy of the storage into `target_directory`, even under concurrent writes.
+    ///
+    /// It is a RocksDB checkpoint: the table files are hard linked and the write-ahead log is copied,
+    /// so a transaction is either fully in the copy or not at all. There is no need to [`flush`](Self::flush) first.
+    /// `target_directory` must not exist yet.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn backup(&self, target_directory: &Path) -> Result<(), StorageError> {
         self.db.backup(target_directory)
     }
-}
-#[derive(Clone)]
-
-pub struct StorageReader {
-    reader: Reader,
-    storage: Storage,   // 内
-}
 
-impl StorageReader {
-    // 三元组的个数？
-    pub fn len(&self) -> Result<usize, StorageError> {
-        Ok(self.reader.len(&self.storage.gspo_cf)? + self.reader.len(&self.storage.dspo_cf)?)
+    /// Adds a new backup of the storage to the backup set in `backup_directory`, creating it if needed.
+    ///
+    /// The backups of the same directory share their table files so only the files created since the previous backup are copied.
+    /// The memtables are flushed first. Use [`restore_from_backup`](Self::restore_from_backup) to get a database back.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn backup_incremental(&self, backup_directory: &Path) -> Result<BackupInfo, StorageError> {
+        let size_before = directory_size(backup_directory)?;
+        let backup_id = self.db.backup_incremental(backup_directory)?;
+        Ok(BackupInfo {
+            backup_id,
+            bytes_written: directory_size(backup_directory)?.saturating_sub(size_before),
+        })
     }
 
-    pub fn is_empty(&self) -> Result<bool, StorageError> {
-        Ok(self.reader.is_empty(&self.storage.gspo_cf)?
-            && self.reader.is_empty(&self.storage.dspo_cf)?)
+    /// Restores the backup `backup_id` of the backup set in `backup_directory` into a database in `target_directory`.
+    ///
+    /// The target must not be opened. The restored database can then be opened with [`open`](Self::open).
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn restore_from_backup(
+        backup_directory: &Path,
+        target_directory: &Path,
+        backup_id: u32,
+    ) -> Result<(), StorageError> {
+        Db::restore_from_backup(backup_directory, target_directory, backup_id)
     }
 
-    pub fn contains(&self, quad: &EncodedQuad) -> Result<bool, StorageError> {
-        let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
-        if quad.graph_name.is_default_graph() {
-            write_spo_quad(&mut buffer, quad);
-            Ok(self.reader.contains_key(&self.storage.dspo_cf, &buffer)?)
-        } else {
-            write_gspo_quad(&mut buffer, quad);
-            Ok(self.reader.contains_key(&self.storage.gspo_cf, &buffer)?)
-        }
+    // wasm32 下的内存后端没有需要刷新到磁盘或者合并的数据，直接返回成功
+    #[cfg(target_arch = "wasm32")]
+    pub fn flush(&self) -> Result<(), StorageError> {
+        Ok(())
     }
 
-    // TODO：方法的含义是啥（在查询的时候用吗，生成迭代?）
-    pub fn quads_for_pattern(
-        &self,
-        subject: Option<&EncodedTerm>,
-        predicate: Option<&EncodedTerm>,
-        object: Option<&EncodedTerm>,
-        graph_name: Option<&EncodedTerm>,
-    ) -> ChainedDecodingQuadIterator {
-        match subject {    // 先匹配s，再p，再o，再g（这四个EncodedTerm都有可能是空的）
-            Some(subject) => match predicate {
-                Some(predicate) => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => self.quads_for_subject_predicate_object_graph( // spog
-                            subject, predicate, object, graph_name,
-                        ),
-                        None => self.quads_for_subject_predicate_object(subject, predicate, object),  // spo
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => {
-                            self.quads_for_subject_predicate_graph(subject, predicate, graph_name) // spg
-                        }
-                        None => self.quads_for_subject_predicate(subject, predicate),   // sp
-                    },
-                },
-                None => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => {
-                            self.quads_for_subject_object_graph(subject, object, graph_name)
-                        }
-                        None => self.quads_for_subject_object(subject, object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => self.quads_for_subject_graph(subject, graph_name),
-                        None => self.quads_for_subject(subject),
-                    },
-                },
-            },
-            None => match predicate {
-                Some(predicate) => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => {
-                            self.quads_for_predicate_object_graph(predicate, object, graph_name)
-                        }
-                        None => self.quads_for_predicate_object(predicate, object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => self.quads_for_predicate_graph(predicate, graph_name),
-                        None => self.quads_for_predicate(predicate),     // TODO：s o g是空的
-                    },
-                },
-                None => match object {
-                    Some(object) => match graph_name {
-                        Some(graph_name) => self.quads_for_object_graph(object, graph_name),
-                        None => self.quads_for_object(object),
-                    },
-                    None => match graph_name {
-                        Some(graph_name) => self.quads_for_graph(graph_name),
-                        None => self.quads(),
-                    },
-                },
-            },
-        }
+    #[cfg(target_arch = "wasm32")]
+    pub fn compact(&self) -> Result<(), StorageError> {
+        Ok(())
     }
 
-    // 针对所有的元组
-    // 下面的方法应该是给定 s p o g 其中的零个或多个创建迭代器
-    // 使用 pair 方法创建，对dspo、gspo分别创建一个迭代器
-    pub fn quads(&self) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(self.dspo_quads(&[]), self.gspo_quads(&[]))
+    #[cfg(target_arch = "wasm32")]
+    pub fn compact_graph(&self, _graph: &EncodedTerm, _full_indexes: bool) -> Result<(), StorageError> {
+        Ok(())
     }
 
-    fn quads_in_named_graph(&self) -> DecodingQuadIterator {
-        self.gspo_quads(&[])
+    // 内存后端无法备份到目录中
+    #[cfg(target_arch = "wasm32")]
+    pub fn backup(&self, _target_directory: &Path) -> Result<(), StorageError> {
+        Err(StorageError::Other(
+            "backups are not supported by the in-memory storage used on wasm32".into(),
+        ))
     }
 
-    // 下面的方法是在九个存储三元组、四元组的表中，给定匹配的模式查询（参照quads_for_pattern方法）
-    // 都是使用pair方法创建
-    fn quads_for_subject(&self, subject: &EncodedTerm) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dspo_quads(&encode_term(subject)),
-            self.spog_quads(&encode_term(subject)),
-        )
+    /// Imports all the quads and named graphs of another storage snapshot with a bulk load.
+    ///
+    /// The strings are decoded from `other` and encoded again for this storage.
+    /// Returns the number of quads that were not already in this storage.
+    /// Like the other bulk loads, it is not atomic and concurrent writes make the returned count approximate.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn merge_from(&self, other: &StorageReader) -> Result<u64, StorageError> {
+        let len_before = self.snapshot().len()?;
+        StorageBulkLoader::new(self.clone()).load::<StorageError, StorageError, _>(
+            other
+                .quads()
+                .map(|quad| other.decode_quad(&quad?)),
+        )?;
+        // 没有四元组的命名图不会经过 bulk load，需要单独写入
+        for graph_name in other.decoded_named_graphs() {
+            let graph_name = graph_name?;
+            self.transaction(|mut writer| writer.insert_named_graph(graph_name.as_ref()))?;
+        }
+        let added = self.snapshot().len()?.saturating_sub(len_before);
+        Ok(added.try_into().unwrap())
     }
 
-    fn quads_for_subject_predicate(
-        &self,
-        subject: &EncodedTerm,
-        predicate: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dspo_quads(&encode_term_pair(subject, predicate)),
-            self.spog_quads(&encode_term_pair(subject, predicate)),
-        )
+    /// Rebuilds the index entries missing from one of the redundant quad indexes.
+    ///
+    /// Every quad found in any of the `dspo`/`dpos`/`dosp` or `spog`/`posg`/`ospg`/`gspo`/`gpos`/`gosp` indexes
+    /// is written back into the other indexes of its group and its graph name into `graphs`.
+    /// The rebuilt entries have empty values: interval encodings of the value load modes are not re-derived.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn repair(&self) -> Result<RepairReport, StorageError> {
+        let reader = self.snapshot();
+        let mut report = RepairReport::default();
+        self.repair_indexes(
+            &reader,
+            &[
+                (StorageColumnFamily::Dspo, QuadEncoding::Dspo, write_spo_quad),
+                (StorageColumnFamily::Dpos, QuadEncoding::Dpos, write_pos_quad),
+                (StorageColumnFamily::Dosp, QuadEncoding::Dosp, write_osp_quad),
+            ],
+            &mut report,
+        )?;
+        self.repair_indexes(
+            &reader,
+            &[
+                (StorageColumnFamily::Spog, QuadEncoding::Spog, write_spog_quad),
+                (StorageColumnFamily::Posg, QuadEncoding::Posg, write_posg_quad),
+                (StorageColumnFamily::Ospg, QuadEncoding::Ospg, write_ospg_quad),
+                (StorageColumnFamily::Gspo, QuadEncoding::Gspo, write_gspo_quad),
+                (StorageColumnFamily::Gpos, QuadEncoding::Gpos, write_gpos_quad),
+                (StorageColumnFamily::Gosp, QuadEncoding::Gosp, write_gosp_quad),
+            ],
+            &mut report,
+        )?;
+        Ok(report)
     }
 
-    fn quads_for_subject_predicate_object(
+    // 遍历快照中的每个索引，把缺失的排列直接写入数据库；检查使用数据库的最新状态，因此同一条目只会被补一次
+    #[cfg(not(target_arch = "wasm32"))]
+    fn repair_indexes(
         &self,
-        subject: &EncodedTerm,
-        predicate: &EncodedTerm,
-        object: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dspo_quads(&encode_term_triple(subject, predicate, object)),
-            self.spog_quads(&encode_term_triple(subject, predicate, object)),
-        )
+        reader: &StorageReader,
+        indexes: &[(StorageColumnFamily, QuadEncoding, fn(&mut Vec<u8>, &EncodedQuad))],
+        report: &mut RepairReport,
+    ) -> Result<(), StorageError> {
+        let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+        for (source, encoding, _) in indexes {
+            for quad in reader.index_quads(*encoding, &[]) {
+                let quad = quad?;
+                for (target, _, write) in indexes {
+                    if target == source {
+                        continue;
+                    }
+                    buffer.clear();
+                    write(&mut buffer, &quad);
+                    self.repair_entry(*target, &buffer, report)?;
+                }
+                if !quad.graph_name.is_default_graph() {
+                    self.repair_entry(
+                        StorageColumnFamily::Graphs,
+                        &encode_term(&quad.graph_name),
+                        report,
+                    )?;
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn quads_for_subject_object(
+    #[cfg(not(target_arch = "wasm32"))]
+    fn repair_entry(
         &self,
-        subject: &EncodedTerm,
-        object: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dosp_quads(&encode_term_pair(object, subject)),
-            self.ospg_quads(&encode_term_pair(object, subject)),
-        )
-    }
-
-    // TODO：这个方法有用
-    fn quads_for_predicate(&self, predicate: &EncodedTerm) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dpos_quads(&encode_term(predicate)),
-            self.posg_quads(&encode_term(predicate)),
-        )
+        which: StorageColumnFamily,
+        key: &[u8],
+        report: &mut RepairReport,
+    ) -> Result<(), StorageError> {
+        let column_family = self.column_family(which);
+        if !self.db.contains_key(column_family, key)? {
+            self.db.insert(column_family, key, &[])?;
+            *report.added.entry(which.name()).or_default() += 1;
+        }
+        Ok(())
     }
 
-    fn quads_for_predicate_object(
-        &self,
-        predicate: &EncodedTerm,
-        object: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dpos_quads(&encode_term_pair(predicate, object)),
-            self.posg_quads(&encode_term_pair(predicate, object)),
-        )
+    /// Removes everything from the storage, leaving it in the same state as a freshly created one.
+    ///
+    /// All the column families are emptied with range deletions in a single write batch,
+    /// which is much faster than [`StorageWriter::clear`] on big stores.
+    /// It is not done in a transaction: concurrent writes may be kept or lost.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn clear(&self) -> Result<(), StorageError> {
+        let reader = self.db.snapshot();
+        let mut batch = self.db.new_write_batch();
+        // 也清除了 default_cf 中的 oxversion 和前缀以及保存的树，之后重新写入 oxversion
+        for which in StorageColumnFamily::ALL {
+            remove_prefix(&mut batch, &reader, self.column_family(which), &[])?;
+        }
+        self.db.write_batch(batch)?;
+        self.update_version(LATEST_STORAGE_VERSION)?;
+        self.reload_prefixes()?;
+        self.interval_keys.store(false, Ordering::Relaxed);
+        // 清空后保留当前的盐，否则之后写入的字符串会与内存中的盐不一致
+        if self.str_salt != 0 {
+            self.save_str_salt(self.str_salt)?;
+        }
+        Ok(())
     }
 
-    fn quads_for_object(&self, object: &EncodedTerm) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::pair(
-            self.dosp_quads(&encode_term(object)),
-            self.ospg_quads(&encode_term(object)),
-        )
+    /// Removes all the quads of a graph with range deletions in a single write batch.
+    ///
+    /// It is much faster than [`StorageWriter::clear_graph`] on big graphs, which still removes the quads
+    /// one by one inside its transaction. Like [`clear`](Self::clear), it bypasses the transactions:
+    /// it takes no lock, so quads inserted concurrently by a transaction may be kept or lost, and it can not be rolled back.
+    /// The change listener is not notified of the removed quads.
+    /// It is meant for bulk maintenance when no other writer is running.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn clear_graph_by_range(&self, graph_name: GraphNameRef<'_>) -> Result<(), StorageError> {
+        let graph_name = self.prefix_table().encode_graph_name(graph_name);
+        let mut batch = self.db.new_write_batch();
+        self.remove_graph_quads_by_range(&mut batch, &graph_name)?;
+        self.db.write_batch(batch)
     }
 
-    // 加上图之后创建的 ChainedDecodingQuadIterator 就不一样了（使用new方法）
-    // 给点图，返回该图上所有元组的迭代器
-    fn quads_for_graph(&self, graph_name: &EncodedTerm) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dspo_quads(&Vec::default())
-        } else {
-            self.gspo_quads(&encode_term(graph_name))
-        })
+    /// Removes a named graph and all its quads with range deletions in a single write batch.
+    ///
+    /// Returns `false` if the graph did not exist.
+    /// Like [`clear_graph_by_range`](Self::clear_graph_by_range), it bypasses the transactions
+    /// and does not notify the change listener, neither of the quads nor of the graph.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn remove_named_graph_by_range(
+        &self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<bool, StorageError> {
+        let graph_name = self.prefix_table().encode_term(graph_name);
+        let key = encode_term(&graph_name);
+        if !self.db.contains_key(&self.graphs_cf, &key)? {
+            return Ok(false);
+        }
+        let mut batch = self.db.new_write_batch();
+        self.remove_graph_quads_by_range(&mut batch, &graph_name)?;
+        batch.remove(&self.graphs_cf, &key);
+        self.db.write_batch(batch)?;
+        Ok(true)
     }
 
-    fn quads_for_subject_graph(
+    // 命名图的 gspo、gpos、gosp 索引以图名开头，可以按前缀整段删除；spog、posg、ospg 中图名在最后，只能逐条删除
+    #[cfg(not(target_arch = "wasm32"))]
+    fn remove_graph_quads_by_range(
         &self,
-        subject: &EncodedTerm,
+        batch: &mut WriteBatch,
         graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dspo_quads(&encode_term(subject))
-        } else {
-            self.gspo_quads(&encode_term_pair(graph_name, subject))
-        })
+    ) -> Result<(), StorageError> {
+        let reader = self.snapshot();
+        if graph_name.is_default_graph() {
+            for column_family in [&self.dspo_cf, &self.dpos_cf, &self.dosp_cf] {
+                remove_prefix(batch, &reader.reader, column_family, &[])?;
+            }
+            return Ok(());
+        }
+        let mut buffer = Vec::new();
+        for quad in reader.quads_for_graph(graph_name) {
+            let quad = quad?;
+            for (column_family, write) in [
+                (&self.spog_cf, write_spog_quad as fn(&mut Vec<u8>, &EncodedQuad)),
+                (&self.posg_cf, write_posg_quad),
+                (&self.ospg_cf, write_ospg_quad),
+            ] {
+                buffer.clear();
+                write(&mut buffer, &quad);
+                batch.remove(column_family, &buffer);
+            }
+        }
+        let prefix = encode_term(graph_name);
+        for column_family in [&self.gspo_cf, &self.gpos_cf, &self.gosp_cf] {
+            remove_prefix(batch, &reader.reader, column_family, &prefix)?;
+        }
+        Ok(())
     }
 
-    fn quads_for_subject_predicate_graph(
-        &self,
-        subject: &EncodedTerm,
-        predicate: &EncodedTerm,
-        graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dspo_quads(&encode_term_pair(subject, predicate))
-        } else {
-            self.gspo_quads(&encode_term_triple(graph_name, subject, predicate))
-        })
+    /// Returns the size in bytes of the SST files of each column family, keyed by column family name.
+    ///
+    /// Data still in the memtables is not counted, call [`flush`](Self::flush) first to include it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn disk_usage(&self) -> Result<BTreeMap<&'static str, u64>, StorageError> {
+        StorageColumnFamily::ALL
+            .into_iter()
+            .map(|which| {
+                let size = self
+                    .db
+                    .property_int(self.column_family(which), "rocksdb.total-sst-files-size")?
+                    .unwrap_or(0);
+                Ok((which.name(), size))
+            })
+            .collect()
     }
 
-    fn quads_for_subject_predicate_object_graph(
-        &self,
-        subject: &EncodedTerm,
-        predicate: &EncodedTerm,
-        object: &EncodedTerm,
-        graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {  // 如果是默认图
-            self.dspo_quads(&encode_term_triple(subject, predicate, object))   // 传入dspo_quads()的是包含spo的buffer编码字节序列
-        } else {
-            self.gspo_quads(&encode_term_quad(graph_name, subject, predicate, object))
-        })
+    /// Reports the storage version, the size of each column family and the default graph layout.
+    ///
+    /// It only reads the `default` column family and the RocksDB properties, so it is cheap and works on a store
+    /// that [`StorageReader::validate`] reports as corrupted. A value that can not be read is left to `None`
+    /// and the failure is described in [`StorageDiagnostics::errors`] instead of failing the whole report.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn diagnostics(&self) -> StorageDiagnostics {
+        let mut diagnostics = StorageDiagnostics::default();
+        match self.db.get(&self.default_cf, b"oxversion") {
+            Ok(Some(version)) => match <[u8; 8]>::try_from(&*version) {
+                Ok(version) => diagnostics.version = Some(u64::from_be_bytes(version)),
+                Err(_) => diagnostics
+                    .errors
+                    .push(format!("The oxversion value should be 8 bytes long, found {}", version.len())),
+            },
+            Ok(None) => (),
+            Err(e) => diagnostics.errors.push(format!("Failed to read oxversion: {}", e)),
+        }
+        for which in StorageColumnFamily::ALL {
+            let mut property = |name: &str| {
+                self.db
+                    .property_int(self.column_family(which), name)
+                    .unwrap_or_else(|e| {
+                        diagnostics
+                            .errors
+                            .push(format!("Failed to read {} of {}: {}", name, which.name(), e));
+                        None
+                    })
+            };
+            let column_family = ColumnFamilyDiagnostics {
+                estimated_keys: property("rocksdb.estimate-num-keys"),
+                sst_bytes: property("rocksdb.total-sst-files-size"),
+            };
+            diagnostics.column_families.insert(which.name(), column_family);
+        }
+        match self.triple_encoding_mode() {
+            Ok(mode) => diagnostics.triple_encoding_mode = mode,
+            Err(e) => diagnostics.errors.push(e.to_string()),
+        }
+        diagnostics
     }
 
-    fn quads_for_subject_object_graph(
-        &self,
-        subject: &EncodedTerm,
-        object: &EncodedTerm,
-        graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dosp_quads(&encode_term_pair(object, subject))
-        } else {
-            self.gosp_quads(&encode_term_triple(graph_name, object, subject))
+    /// Returns the class and property trees saved by a previous interval bulk load, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn interval_trees(&self) -> Result<Option<(MultiTree, MultiTree)>, StorageError> {
+        let classes = self.db.get(&self.trees_cf, CLASS_TREE_KEY)?;
+        let properties = self.db.get(&self.trees_cf, PROPERTY_TREE_KEY)?;
+        Ok(match (classes, properties) {
+            (Some(classes), Some(properties)) => Some((
+                MultiTree::from_bytes(&classes)
+                    .map_err(|()| CorruptionError::msg("Invalid class tree encoding"))?,
+                MultiTree::from_bytes(&properties)
+                    .map_err(|()| CorruptionError::msg("Invalid property tree encoding"))?,
+            )),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    CorruptionError::msg("Only one of the class and property trees is stored").into(),
+                )
+            }
         })
     }
 
-    fn quads_for_predicate_graph(
-        &self,
-        predicate: &EncodedTerm,
-        graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dpos_quads(&encode_term(predicate))
-        } else {
-            self.gpos_quads(&encode_term_pair(graph_name, predicate))
-        })
+    /// Saves the encoded class and property trees so that later interval bulk loads reuse the same numbering.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_interval_trees(&self, trees: &(MultiTree, MultiTree)) -> Result<(), StorageError> {
+        self.db.insert(&self.trees_cf, CLASS_TREE_KEY, &trees.0.to_bytes()?)?;
+        self.db.insert(&self.trees_cf, PROPERTY_TREE_KEY, &trees.1.to_bytes()?)?;
+        self.db.flush(&self.trees_cf)
     }
 
-    fn quads_for_predicate_object_graph(
-        &self,
-        predicate: &EncodedTerm,
-        object: &EncodedTerm,
-        graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dpos_quads(&encode_term_pair(predicate, object))
-        } else {
-            self.gpos_quads(&encode_term_triple(graph_name, predicate, object))
-        })
+    /// Returns the path of the file the saved interval trees have been built from, if it has been recorded.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn interval_tree_path(&self) -> Result<Option<String>, StorageError> {
+        self.db
+            .get(&self.trees_cf, TREE_PATH_KEY)?
+            .map(|path| {
+                String::from_utf8(path.to_vec())
+                    .map_err(|_| CorruptionError::msg("Invalid interval tree path encoding").into())
+            })
+            .transpose()
     }
 
-    fn quads_for_object_graph(
-        &self,
-        object: &EncodedTerm,
-        graph_name: &EncodedTerm,
-    ) -> ChainedDecodingQuadIterator {
-        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
-            self.dosp_quads(&encode_term(object))
-        } else {
-            self.gosp_quads(&encode_term_pair(graph_name, object))
+    /// Records the path of the file the saved interval trees have been built from.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_interval_tree_path(&self, path: &str) -> Result<(), StorageError> {
+        self.db.insert(&self.trees_cf, TREE_PATH_KEY, path.as_bytes())?;
+        self.db.flush(&self.trees_cf)
+    }
+
+    /// Returns the layout of the default graph indexes recorded by the first bulk load, if any.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn triple_encoding_mode(&self) -> Result<Option<TripleEncodingMode>, StorageError> {
+        Ok(match self.db.get(&self.default_cf, TRIPLE_ENCODING_KEY)? {
+            Some(value) => Some(match &*value {
+                [byte] => TripleEncodingMode::from_byte(*byte).ok_or_else(|| {
+                    CorruptionError::msg(format!("Unknown triple encoding mode {} stored in oxencoding", byte))
+                })?,
+                _ => return Err(CorruptionError::msg("The triple encoding mode stored in oxencoding should be a single byte").into()),
+            }),
+            None => None,
         })
     }
 
-    pub fn named_graphs(&self) -> DecodingGraphIterator {
-        DecodingGraphIterator {
-            iter: self.reader.iter(&self.storage.graphs_cf).unwrap(), //TODO: propagate error?
+    // 第一次加载时记录布局，之后的加载必须使用相同的布局
+    // 没有记录但默认图已经有三元组时（事务写入或旧版本的加载），这些三元组是 Plain 布局
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ensure_triple_encoding_mode(&self, mode: TripleEncodingMode) -> Result<(), StorageError> {
+        let existing = match self.triple_encoding_mode()? {
+            Some(existing) => existing,
+            None => {
+                let reader = self.db.snapshot();
+                let iter = reader.iter(&self.dspo_cf)?;
+                let is_empty = iter.key().is_none();
+                iter.status()?;
+                if is_empty {
+                    self.db.insert(&self.default_cf, TRIPLE_ENCODING_KEY, &[mode.to_byte()])?;
+                    self.interval_keys
+                        .store(mode == TripleEncodingMode::IntervalKey, Ordering::Relaxed);
+                    return self.db.flush(&self.default_cf);
+                }
+                TripleEncodingMode::Plain
+            }
+        };
+        if existing == mode {
+            Ok(())
+        } else {
+            Err(StorageError::Other(
+                format!(
+                    "The default graph of this store is encoded with the {:?} layout, loading with the {:?} layout would make its triples unreadable",
+                    existing, mode
+                )
+                .into(),
+            ))
         }
     }
+}
+/// A backup created by [`Storage::backup_incremental`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// The id of the backup in its backup set, to give to [`Storage::restore_from_backup`].
+    pub backup_id: u32,
+    /// Number of bytes added to the backup directory by this backup.
+    pub bytes_written: u64,
+}
 
-    pub fn contains_named_graph(&self, graph_name: &EncodedTerm) -> Result<bool, StorageError> {
-        self.reader
-            .contains_key(&self.storage.graphs_cf, &encode_term(graph_name))
+// 目录中所有文件的总大小，目录不存在时为 0
+#[cfg(not(target_arch = "wasm32"))]
+fn directory_size(path: &Path) -> Result<u64, StorageError> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            directory_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
     }
+    Ok(size)
+}
 
+/// What [`Storage::open_with_recovery`] does if the quad indexes do not have the same number of entries.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryMode {
+    /// Fails with a [`CorruptionError`] listing the size of each index.
+    Refuse,
+    /// Runs [`Storage::repair`] before returning the storage.
+    Repair,
+}
 
+/// The report returned by [`Storage::diagnostics`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StorageDiagnostics {
+    /// The storage version stored in `oxversion`, `None` if it is missing or invalid.
+    pub version: Option<u64>,
+    /// The size of each column family, keyed by column family name.
+    pub column_families: BTreeMap<&'static str, ColumnFamilyDiagnostics>,
+    /// The default graph layout recorded by the first bulk load, see [`Storage::triple_encoding_mode`].
+    pub triple_encoding_mode: Option<TripleEncodingMode>,
+    /// The reads that failed while building the report.
+    pub errors: Vec<String>,
+}
 
-    // 调用self.inner_quads，生成迭代器，在 validate方法里会调用到
-    fn spog_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.spog_cf, prefix, QuadEncoding::Spog)
-    }
+/// The size of a column family in a [`StorageDiagnostics`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnFamilyDiagnostics {
+    /// The RocksDB estimate of the number of keys, memtables included.
+    pub estimated_keys: Option<u64>,
+    /// The size in bytes of the SST files, as in [`Storage::disk_usage`].
+    pub sst_bytes: Option<u64>,
+}
 
-    fn posg_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.posg_cf, prefix, QuadEncoding::Posg)
-    }
+/// The index entries added by [`Storage::repair`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    /// Number of added entries, keyed by column family name. Column families without additions are absent.
+    pub added: BTreeMap<&'static str, u64>,
+}
 
-    fn ospg_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.ospg_cf, prefix, QuadEncoding::Ospg)
+#[cfg(not(target_arch = "wasm32"))]
+impl RepairReport {
+    /// Returns `true` if the storage was already consistent.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
     }
+}
 
-    fn gspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.gspo_cf, prefix, QuadEncoding::Gspo)
-    }
+#[derive(Clone)]
 
-    fn gpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.gpos_cf, prefix, QuadEncoding::Gpos)
-    }
+pub struct StorageReader {
+    reader: Reader,
+    storage: Storage,   // 内
+    prefixes: Arc<PrefixTable>,
+}
 
-    fn gosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.gosp_cf, prefix, QuadEncoding::Gosp)
+impl StorageReader {
+    // 三元组的个数？
+    pub fn len(&self) -> Result<usize, StorageError> {
+        Ok(self.reader.len(&self.storage.gspo_cf)? + self.reader.len(&self.storage.dspo_cf)?)
     }
 
-    fn dspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {    // prefix 实际上就是包含s p o的buffer编码字节序列
-        self.inner_quads(&self.storage.dspo_cf, prefix, QuadEncoding::Dspo)
+    /// Number of quads in the given graph. A graph that does not exist is empty.
+    pub fn len_for_graph(&self, graph: GraphNameRef<'_>) -> Result<usize, StorageError> {
+        if graph.is_default_graph() {
+            Ok(self.reader.len(&self.storage.dspo_cf)?)
+        } else {
+            self.prefix_len(&self.storage.gspo_cf, &encode_term(&self.encode_graph_name(graph)))
+        }
     }
 
-    fn dpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.dpos_cf, prefix, QuadEncoding::Dpos)
+    /// Number of quads in all the named graphs, i.e. excluding the default graph.
+    pub fn named_graph_len(&self) -> Result<usize, StorageError> {
+        Ok(self.reader.len(&self.storage.gspo_cf)?)
     }
 
-    fn dosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
-        self.inner_quads(&self.storage.dosp_cf, prefix, QuadEncoding::Dosp)
+    // 只遍历 key 计数，不解码四元组
+    fn prefix_len(&self, column_family: &ColumnFamily, prefix: &[u8]) -> Result<usize, StorageError> {
+        self.prefix_len_up_to(column_family, prefix, usize::MAX)
     }
 
-    fn inner_quads(
+    // 计数到 limit 为止
+    fn prefix_len_up_to(
         &self,
         column_family: &ColumnFamily,
-        prefix: &[u8],   // spog的字节编码序列buffer（不定顺序）
-        encoding: QuadEncoding,
-    ) -> DecodingQuadIterator {
-        DecodingQuadIterator {
-            iter: self.reader.scan_prefix(column_family, prefix).unwrap(), // TODO: propagate error?
-            encoding,
+        prefix: &[u8],
+        limit: usize,
+    ) -> Result<usize, StorageError> {
+        let mut count = 0;
+        let mut iter = self.reader.scan_prefix(column_family, prefix)?;
+        while count < limit && iter.key().is_some() {
+            count += 1;
+            iter.next();
         }
+        iter.status()?;
+        Ok(count)
     }
 
-    // 根据 StrHash 编码获得其对应存储的字符串
-    #[cfg(not(target_arch = "wasm32"))]
-    pub fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
-        Ok(self
-            .storage
-            .db
-            .get(&self.storage.id2str_cf, &key.to_be_bytes())?
-            .map(|v| String::from_utf8(v.into()))
-            .transpose()
-            .map_err(CorruptionError::new)?)
+    pub fn is_empty(&self) -> Result<bool, StorageError> {
+        Ok(self.reader.is_empty(&self.storage.gspo_cf)?
+            && self.reader.is_empty(&self.storage.dspo_cf)?)
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
-        Ok(self
+    /// Returns `true` if the named graph contains no quad, whether it is registered in `graphs` or not.
+    ///
+    /// Only the first key of the graph prefix in `gspo` is probed, the quads are not counted.
+    pub fn graph_is_empty(&self, graph: NamedOrBlankNodeRef<'_>) -> Result<bool, StorageError> {
+        let iter = self
             .reader
-            .get(&self.storage.id2str_cf, &key.to_be_bytes())?
-            .map(|v| String::from_utf8(v.into()))
-            .transpose()
-            .map_err(CorruptionError::new)?)
+            .scan_prefix(&self.storage.gspo_cf, &encode_term(&self.encode_term(graph)))?;
+        let is_empty = iter.key().is_none();
+        iter.status()?;
+        Ok(is_empty)
     }
 
-    #[cfg(not(target_arch = "wasm32"))]
-    pub fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
-        self.storage
-            .db
-            .contains_key(&self.storage.id2str_cf, &key.to_be_bytes())
+    /// Same as [`contains`](Self::contains) but encodes the quad itself.
+    pub fn contains_quad(&self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
+        self.contains(&self.encode_quad(quad))
     }
 
-    #[cfg(target_arch = "wasm32")]
-    pub fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
-        self.reader
-            .contains_key(&self.storage.id2str_cf, &key.to_be_bytes())
+    pub fn contains(&self, quad: &EncodedQuad) -> Result<bool, StorageError> {
+        let mut buffer = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+        if quad.graph_name.is_default_graph() && self.storage.interval_keys.load(Ordering::Relaxed) {
+            // key 以区间编码开头，无法直接查找，只能按模式扫描
+            Ok(self
+                .quads_for_pattern(
+                    Some(&quad.subject),
+                    Some(&quad.predicate),
+                    Some(&quad.object),
+                    Some(&quad.graph_name),
+                )
+                .next()
+                .transpose()?
+                .is_some())
+        } else if quad.graph_name.is_default_graph() {
+            write_spo_quad(&mut buffer, quad);
+            Ok(self.reader.contains_key(&self.storage.dspo_cf, &buffer)?)
+        } else {
+            write_gspo_quad(&mut buffer, quad);
+            Ok(self.reader.contains_key(&self.storage.gspo_cf, &buffer)?)
+        }
     }
 
-    /// Validates that all the storage invariants held in the data
-    // 验证存储的数据是否一致（spo、pos、osp中的元组数量是否一致，四元组也同样）
-    #[cfg(not(target_arch = "wasm32"))]
-    pub fn validate(&self) -> Result<(), StorageError> {
-        // triples
-        let dspo_size = self.dspo_quads(&[]).count();
-        if dspo_size != self.dpos_quads(&[]).count() || dspo_size != self.dosp_quads(&[]).count() {
-            return Err(CorruptionError::new(
-                "Not the same number of triples in dspo, dpos and dosp",
-            )
-            .into());
-        }
-        for spo in self.dspo_quads(&[]) {
-            let spo = spo?;
-            self.decode_quad(&spo)?; // We ensure that the quad is readable
-            if !self.storage.db.contains_key(
-                &self.storage.dpos_cf,
-                &encode_term_triple(&spo.predicate, &spo.object, &spo.subject),
-            )? {
-                return Err(CorruptionError::new("Quad in dspo and not in dpos").into());
-            }
-            if !self.storage.db.contains_key(
-                &self.storage.dosp_cf,
-                &encode_term_triple(&spo.object, &spo.subject, &spo.predicate),
-            )? {
-                return Err(CorruptionError::new("Quad in dspo and not in dpos").into());
-            }
-        }
+    /// Returns the proper ancestors of a class or a property, following `rdfs:subClassOf` and `rdfs:subPropertyOf` transitively.
+    ///
+    /// The closure is computed from the interval encodings stored by [`LoadMode::IntervalValue`]
+    /// and the ancestors are returned from the root of the hierarchy downwards.
+    /// Terms that have not been loaded in this mode have no ancestors.
+    pub fn ancestors_of(&self, term: &EncodedTerm) -> Result<Vec<EncodedTerm>, StorageError> {
+        // 祖先的区间包含 term 的区间
+        self.hierarchy_closure(term, |term, other| other.0 < term.0 && term.1 <= other.1)
+    }
 
-        // quads
-        let gspo_size = self.gspo_quads(&[]).count();
-        if gspo_size != self.gpos_quads(&[]).count()
-            || gspo_size != self.gosp_quads(&[]).count()
-            || gspo_size != self.spog_quads(&[]).count()
-            || gspo_size != self.posg_quads(&[]).count()
-            || gspo_size != self.ospg_quads(&[]).count()
-        {
-            return Err(CorruptionError::new(
-                "Not the same number of triples in dspo, dpos and dosp",
-            )
-            .into());
+    /// Returns the proper descendants of a class or a property, following `rdfs:subClassOf` and `rdfs:subPropertyOf` transitively.
+    ///
+    /// Same as [`ancestors_of`](Self::ancestors_of) but in the other direction, in depth-first order.
+    pub fn descendants_of(&self, term: &EncodedTerm) -> Result<Vec<EncodedTerm>, StorageError> {
+        // 后代的区间被 term 的区间包含
+        self.hierarchy_closure(term, |term, other| term.0 < other.0 && other.1 <= term.1)
+    }
+
+    fn hierarchy_closure(
+        &self,
+        term: &EncodedTerm,
+        related: impl Fn((u32, u32), (u32, u32)) -> bool,
+    ) -> Result<Vec<EncodedTerm>, StorageError> {
+        // Class 树与属性树的区间编号是独立的，只能在同一棵树内比较
+        for predicates in [&[rdfs::SUB_CLASS_OF, lubm::SUB_ORGANIZATION][..], &[rdfs::SUB_PROPERTY_OF]] {
+            let intervals = self.hierarchy_intervals(predicates)?;
+            let term_intervals = match intervals.get(term) {
+                Some(term_intervals) => term_intervals,
+                None => continue,
+            };
+            let mut result = intervals
+                .iter()
+                .filter(|(other, _)| *other != term)
+                .filter_map(|(other, other_intervals)| {
+                    other_intervals
+                        .iter()
+                        .filter(|o| term_intervals.iter().any(|t| related(*t, **o)))
+                        .map(|o| o.0)
+                        .min()
+                        .map(|start| (start, other.clone()))
+                })
+                .collect::<Vec<_>>();
+            result.sort_unstable_by_key(|(start, _)| *start);
+            return Ok(result.into_iter().map(|(_, term)| term).collect());
         }
-        for gspo in self.gspo_quads(&[]) {
-            let gspo = gspo?;
-            self.decode_quad(&gspo)?; // We ensure that the quad is readable
-            if !self.storage.db.contains_key(
-                &self.storage.gpos_cf,
-                &encode_term_quad(
-                    &gspo.graph_name,
-                    &gspo.predicate,
-                    &gspo.object,
-                    &gspo.subject,
-                ),
-            )? {
-                return Err(CorruptionError::new("Quad in gspo and not in gpos").into());
-            }
-            if !self.storage.db.contains_key(
-                &self.storage.gosp_cf,
-                &encode_term_quad(
-                    &gspo.graph_name,
-                    &gspo.object,
-                    &gspo.subject,
-                    &gspo.predicate,
-                ),
-            )? {
-                return Err(CorruptionError::new("Quad in gspo and not in gosp").into());
-            }
-            if !self.storage.db.contains_key(
-                &self.storage.spog_cf,
-                &encode_term_quad(
-                    &gspo.subject,
-                    &gspo.predicate,
-                    &gspo.object,
-                    &gspo.graph_name,
-                ),
-            )? {
-                return Err(CorruptionError::new("Quad in gspo and not in spog").into());
-            }
-            if !self.storage.db.contains_key(
-                &self.storage.posg_cf,
-                &encode_term_quad(
-                    &gspo.predicate,
-                    &gspo.object,
-                    &gspo.subject,
-                    &gspo.graph_name,
-                ),
-            )? {
-                return Err(CorruptionError::new("Quad in gspo and not in posg").into());
-            }
-            if !self.storage.db.contains_key(
-                &self.storage.ospg_cf,
-                &encode_term_quad(
-                    &gspo.object,
-                    &gspo.subject,
-                    &gspo.predicate,
-                    &gspo.graph_name,
-                ),
-            )? {
-                return Err(CorruptionError::new("Quad in gspo and not in ospg").into());
-            }
-            if !self
-                .storage
-                .db
-                .contains_key(&self.storage.graphs_cf, &encode_term(&gspo.graph_name))?
-            {
-                return Err(
-                    CorruptionError::new("Quad graph name in gspo and not in graphs").into(),
-                );
+        Ok(Vec::new())
+    }
+
+    // 读取给定谓词的三元组 value 中的区间编码：主语得到其在宾语下的区间，宾语得到其所有区间
+    fn hierarchy_intervals(
+        &self,
+        predicates: &[&str],
+    ) -> Result<HashMap<EncodedTerm, Vec<(u32, u32)>>, StorageError> {
+        let mut intervals = HashMap::<EncodedTerm, Vec<(u32, u32)>>::new();
+        for predicate in predicates {
+            // 谓语也可能使用注册的前缀编码
+            let predicate = self.prefixes.encode_named_node(NamedNodeRef::new_unchecked(predicate));
+            let mut iter = self
+                .reader
+                .scan_prefix(&self.storage.dpos_cf, &encode_term(&predicate))?;
+            while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                if let Some(IntervalValue::Hierarchy { child, parent }) = decode_interval_value(value)? {
+                    let quad = QuadEncoding::Dpos.decode(key)?;
+                    for (term, term_intervals) in [(quad.subject, child), (quad.object, parent)] {
+                        let known_intervals = intervals.entry(term).or_default();
+                        for (start, end, _) in term_intervals {
+                            if !known_intervals.contains(&(start, end)) {
+                                known_intervals.push((start, end));
+                            }
+                        }
+                    }
+                }
+                iter.next();
             }
+            iter.status()?;
         }
-        Ok(())
+        Ok(intervals)
     }
-}
-
-
-// ##########################################################################
-// 在查询时若没有指定图，则使用 pair()新建 dspo、gspo两个迭代器
-// 若指定了图，则只使用 new()新建对应图上的迭代器
-#[derive(Clone)]
-pub struct ChainedDecodingQuadIterator {
-    first: DecodingQuadIterator,
-    second: Option<DecodingQuadIterator>,
-}
-
 
-impl ChainedDecodingQuadIterator {
-    fn new(first: DecodingQuadIterator) -> Self {
-        Self {
-            first,
-            second: None,
+    // TODO：方法的含义是啥（在查询的时候用吗，生成迭代?）
+    pub fn quads_for_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> ChainedDecodingQuadIterator {
+        match subject {    // 先匹配s，再p，再o，再g（这四个EncodedTerm都有可能是空的）
+            Some(subject) => match predicate {
+                Some(predicate) => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => self.quads_for_subject_predicate_object_graph( // spog
+                            subject, predicate, object, graph_name,
+                        ),
+                        None => self.quads_for_subject_predicate_object(subject, predicate, object),  // spo
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => {
+                            self.quads_for_subject_predicate_graph(subject, predicate, graph_name) // spg
+                        }
+                        None => self.quads_for_subject_predicate(subject, predicate),   // sp
+                    },
+                },
+                None => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => {
+                            self.quads_for_subject_object_graph(subject, object, graph_name)
+                        }
+                        None => self.quads_for_subject_object(subject, object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => self.quads_for_subject_graph(subject, graph_name),
+                        None => self.quads_for_subject(subject),
+                    },
+                },
+            },
+            None => match predicate {
+                Some(predicate) => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => {
+                            self.quads_for_predicate_object_graph(predicate, object, graph_name)
+                        }
+                        None => self.quads_for_predicate_object(predicate, object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => self.quads_for_predicate_graph(predicate, graph_name),
+                        None => self.quads_for_predicate(predicate),     // TODO：s o g是空的
+                    },
+                },
+                None => match object {
+                    Some(object) => match graph_name {
+                        Some(graph_name) => self.quads_for_object_graph(object, graph_name),
+                        None => self.quads_for_object(object),
+                    },
+                    None => match graph_name {
+                        Some(graph_name) => self.quads_for_graph(graph_name),
+                        None => self.quads(),
+                    },
+                },
+            },
         }
     }
 
-    fn pair(first: DecodingQuadIterator, second: DecodingQuadIterator) -> Self {
-        Self {
-            first,
-            second: Some(second),
-        }
+    /// Returns the page of at most `limit` quads starting after the first `offset` results of [`quads_for_pattern`](Self::quads_for_pattern).
+    ///
+    /// The quads are in the same order as [`quads_for_pattern`](Self::quads_for_pattern):
+    /// the default graph index is scanned first, then the named graph index, each in the byte order of its keys.
+    /// The order only depends on the content of the storage, so paging on the same [`Storage::snapshot`] has no gaps nor duplicates.
+    /// The first `offset` keys are skipped without being decoded.
+    pub fn quads_for_pattern_paged(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+        offset: usize,
+        limit: usize,
+    ) -> Take<ChainedDecodingQuadIterator> {
+        let mut iter = self.quads_for_pattern(subject, predicate, object, graph_name);
+        iter.skip_keys(offset);
+        iter.take(limit)
     }
-}
 
-impl Iterator for ChainedDecodingQuadIterator {
-    type Item = Result<EncodedQuad, StorageError>; // 被迭代的元素类型
+    /// Returns the default graph triples matching the pattern together with the value stored with their key.
+    ///
+    /// The values are empty except for the triples written by [`StorageBulkLoader::load_oxiuse_value`],
+    /// where they hold the interval encoding of the triple. Each `dspo`/`dpos`/`dosp` index stores its own value,
+    /// the returned one is the value of the index scanned for the pattern, as in [`quads_for_pattern`](Self::quads_for_pattern).
+    pub fn triples_for_pattern_with_values(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+    ) -> DecodingQuadValueIterator {
+        let inner = match (subject, predicate, object) {
+            (Some(subject), Some(predicate), Some(object)) => {
+                self.dspo_quads(&encode_term_triple(subject, predicate, object))
+            }
+            (Some(subject), Some(predicate), None) => {
+                self.dspo_quads(&encode_term_pair(subject, predicate))
+            }
+            (Some(subject), None, Some(object)) => {
+                self.dosp_quads(&encode_term_pair(object, subject))
+            }
+            (Some(subject), None, None) => self.dspo_quads(&encode_term(subject)),
+            (None, Some(predicate), Some(object)) => {
+                self.dpos_quads(&encode_term_pair(predicate, object))
+            }
+            (None, Some(predicate), None) => self.dpos_quads(&encode_term(predicate)),
+            (None, None, Some(object)) => self.dosp_quads(&encode_term(object)),
+            (None, None, None) => self.dspo_quads(&[]),
+        };
+        DecodingQuadValueIterator { inner }
+    }
 
-    fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {   // 推进迭代器并返回下一个值
-        if let Some(result) = self.first.next() {
-            Some(result)
-        } else if let Some(second) = self.second.as_mut() {
-            second.next()
+    /// Returns the index [`quads_for_pattern`](Self::quads_for_pattern) would scan for this pattern,
+    /// the length of the scan prefix and the number of matching keys.
+    ///
+    /// The keys are counted without decoding them and the count stops at [`PatternPlan::MAX_ESTIMATED_ROWS`]
+    /// so it is cheap enough to order the patterns of a join.
+    pub fn plan_pattern(
+        &self,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> Result<PatternPlan, StorageError> {
+        // 与 quads_for_pattern 的选择相同：宾语绑定而谓语未绑定时用 osp，谓语绑定而主语未绑定时用 pos，其余用 spo
+        let (named_index, graph_index, default_index) = if object.is_some() && predicate.is_none() {
+            (QuadEncoding::Ospg, QuadEncoding::Gosp, QuadEncoding::Dosp)
+        } else if predicate.is_some() && subject.is_none() {
+            (QuadEncoding::Posg, QuadEncoding::Gpos, QuadEncoding::Dpos)
         } else {
-            None
+            (QuadEncoding::Spog, QuadEncoding::Gspo, QuadEncoding::Dspo)
+        };
+        let index = match graph_name {
+            Some(graph_name) if graph_name.is_default_graph() => default_index,
+            Some(_) => graph_index,
+            None => named_index,
+        };
+        let prefix = Self::index_prefix(index, subject, predicate, object, graph_name)?;
+        let mut estimated_rows = self.prefix_len_up_to(
+            self.storage.column_family(StorageColumnFamily::of_index(index)),
+            &prefix,
+            PatternPlan::MAX_ESTIMATED_ROWS,
+        )?;
+        if graph_name.is_none() {
+            estimated_rows += self.prefix_len_up_to(
+                self.storage.column_family(StorageColumnFamily::of_index(default_index)),
+                &prefix,
+                PatternPlan::MAX_ESTIMATED_ROWS - estimated_rows,
+            )?;
         }
+        Ok(PatternPlan {
+            chosen_cf: StorageColumnFamily::of_index(index).name(),
+            prefix_len_bytes: prefix.len(),
+            estimated_rows,
+        })
     }
-}
-
-// ----------------------------------------------------------
-#[derive(Clone)]
-pub struct DecodingQuadIterator {
-    iter: Iter,
-    encoding: QuadEncoding,   // 三元组和四元组的九种序列（gspo...）枚举
-}
-
-impl Iterator for DecodingQuadIterator {
-    type Item = Result<EncodedQuad, StorageError>;
 
-    fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {   // 推进迭代器并返回下一个值
-        if let Err(e) = self.iter.status() {
-            return Some(Err(e));
+    /// Same as [`quads_for_pattern`](Self::quads_for_pattern) but scans the given `index`.
+    ///
+    /// The bound positions of the pattern must be a prefix of the index key, otherwise an error is returned.
+    /// If the graph name is not bound, the named graph index is paired with its default graph counterpart.
+    pub fn quads_for_pattern_in(
+        &self,
+        index: QuadEncoding,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> Result<ChainedDecodingQuadIterator, StorageError> {
+        let default_index = match index {
+            QuadEncoding::Spog | QuadEncoding::Gspo | QuadEncoding::Dspo => QuadEncoding::Dspo,
+            QuadEncoding::Posg | QuadEncoding::Gpos | QuadEncoding::Dpos => QuadEncoding::Dpos,
+            QuadEncoding::Ospg | QuadEncoding::Gosp | QuadEncoding::Dosp => QuadEncoding::Dosp,
+        };
+        // 默认图中的四元组只在 d* 索引中，这些索引的 key 中没有图，所以按照 d* 索引的顺序检查绑定的词项
+        if graph_name.map_or(false, EncodedTerm::is_default_graph) {
+            let prefix = Self::index_prefix(default_index, subject, predicate, object, None)?;
+            return Ok(ChainedDecodingQuadIterator::new(
+                self.index_quads(default_index, &prefix),
+            ));
         }
-        let term = self.encoding.decode(self.iter.key()?);
-        self.iter.next();
-        Some(term)
+        let prefix = Self::index_prefix(index, subject, predicate, object, graph_name)?;
+        if index == default_index {
+            return if graph_name.is_none() {
+                Ok(ChainedDecodingQuadIterator::new(
+                    self.index_quads(default_index, &prefix),
+                ))
+            } else {
+                Err(StorageError::Other(
+                    format!(
+                        "The {:?} index only contains the default graph and is not able to answer a pattern on a named graph",
+                        index
+                    )
+                    .into(),
+                ))
+            };
+        }
+        Ok(match graph_name {
+            Some(_) => ChainedDecodingQuadIterator::new(self.index_quads(index, &prefix)),
+            None => ChainedDecodingQuadIterator::pair(
+                self.index_quads(default_index, &prefix),
+                self.index_quads(index, &prefix),
+            ),
+        })
     }
-}
-
-pub struct DecodingGraphIterator {
-    iter: Iter,
-}
-
-impl Iterator for DecodingGraphIterator {
-    type Item = Result<EncodedTerm, StorageError>;   // 进行迭代的元素
 
-    fn next(&mut self) -> Option<Result<EncodedTerm, StorageError>> {
-        if let Err(e) = self.iter.status() {
-            return Some(Err(e));
+    // 将绑定的词项按照索引中的顺序编码为扫描前缀，绑定的词项不是索引 key 的前缀时返回错误
+    fn index_prefix(
+        index: QuadEncoding,
+        subject: Option<&EncodedTerm>,
+        predicate: Option<&EncodedTerm>,
+        object: Option<&EncodedTerm>,
+        graph_name: Option<&EncodedTerm>,
+    ) -> Result<Vec<u8>, StorageError> {
+        // 按照索引的顺序排列 s p o g
+        let terms = match index {
+            QuadEncoding::Spog => vec![subject, predicate, object, graph_name],
+            QuadEncoding::Posg => vec![predicate, object, subject, graph_name],
+            QuadEncoding::Ospg => vec![object, subject, predicate, graph_name],
+            QuadEncoding::Gspo => vec![graph_name, subject, predicate, object],
+            QuadEncoding::Gpos => vec![graph_name, predicate, object, subject],
+            QuadEncoding::Gosp => vec![graph_name, object, subject, predicate],
+            QuadEncoding::Dspo => vec![subject, predicate, object],
+            QuadEncoding::Dpos => vec![predicate, object, subject],
+            QuadEncoding::Dosp => vec![object, subject, predicate],
+        };
+        let bound = terms.iter().take_while(|t| t.is_some()).count();
+        if terms[bound..].iter().any(Option::is_some) {
+            return Err(StorageError::Other(
+                format!(
+                    "The {:?} index is not able to answer this pattern: the bound terms are not a prefix of its keys",
+                    index
+                )
+                .into(),
+            ));
         }
-        let term = decode_term(self.iter.key()?);   // 将内存里的 buffer 解码成 EncodedTerm
-        self.iter.next();
-        Some(term)
+        let mut prefix = Vec::with_capacity(4 * WRITTEN_TERM_MAX_SIZE);
+        for term in terms.into_iter().flatten() {
+            write_term(&mut prefix, term); // The default graph is written as an empty sequence
+        }
+        Ok(prefix)
     }
-}
 
-impl StrLookup for StorageReader {
-    fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
-        self.get_str(key)
+    // 针对所有的元组
+    // 下面的方法应该是给定 s p o g 其中的零个或多个创建迭代器
+    // 使用 pair 方法创建，对dspo、gspo分别创建一个迭代器
+    pub fn quads(&self) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(self.dspo_quads(&[]), self.gspo_quads(&[]))
     }
 
-    fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
-        self.contains_str(key)
+    /// Same as [`quads`](Self::quads) but decodes the quads using the strings of this snapshot.
+    ///
+    /// The quads are decoded one at a time. A quad that fails to be read or decoded yields an `Err` item
+    /// and the iteration can go on with the next quads.
+    pub fn iter(&self) -> impl Iterator<Item = Result<Quad, StorageError>> + '_ {
+        self.quads().map(move |quad| self.decode_quad(&quad?))
     }
-}
-
-pub struct StorageWriter<'a> {
-    buffer: Vec<u8>,
-    transaction: Transaction<'a>,
-    storage: &'a Storage,
-}
 
-impl<'a> StorageWriter<'a> {
-    pub fn reader(&self) -> StorageReader {
-        StorageReader {
-            reader: self.transaction.reader(),
-            storage: self.storage.clone(),
+    /// Serializes all the quads of this snapshot, the default graph first.
+    ///
+    /// The quads are decoded and written one at a time through a buffer that is flushed to `writer` by chunks,
+    /// so the dataset is never materialized in memory.
+    pub fn dump_dataset<W: Write>(&self, writer: W, format: DatasetFormat) -> Result<(), SerializerError> {
+        let mut writer = DatasetSerializer::from_format(format).quad_writer(BufWriter::new(writer))?;
+        for quad in self.quads() {
+            writer.write(&self.decode_quad(&quad?)?)?;
         }
+        writer.finish()?;
+        Ok(())
     }
 
-    // 重点看了一下insert方法
-    // 元组插入使用的是 Transaction 里的insert方法
-    // 而Term的插入使用的是Db中的插入方法
-    pub fn insert(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
-        let encoded = quad.into();   // type: EncodedQuad
-        self.buffer.clear();
+    /// Serializes the triples of a single graph, dropping the graph name.
+    ///
+    /// A registered named graph without quads writes nothing, a named graph that does not exist is an error.
+    pub fn dump_graph<W: Write>(
+        &self,
+        graph: GraphNameRef<'_>,
+        writer: W,
+        format: GraphFormat,
+    ) -> Result<(), SerializerError> {
+        let encoded_graph = self.encode_graph_name(graph);
+        if !graph.is_default_graph() && !self.contains_named_graph(&encoded_graph)? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("The named graph {} does not exist", graph),
+            )
+            .into());
+        }
+        let mut writer = GraphSerializer::from_format(format).triple_writer(BufWriter::new(writer))?;
+        for quad in self.quads_for_graph(&encoded_graph) {
+            writer.write(self.decode_quad(&quad?)?.as_ref())?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn quads_in_named_graph(&self) -> DecodingQuadIterator {
+        self.gspo_quads(&[])
+    }
+
+    // 下面的方法是在九个存储三元组、四元组的表中，给定匹配的模式查询（参照quads_for_pattern方法）
+    // 都是使用pair方法创建
+    fn quads_for_subject(&self, subject: &EncodedTerm) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dspo_quads(&encode_term(subject)),
+            self.spog_quads(&encode_term(subject)),
+        )
+    }
+
+    fn quads_for_subject_predicate(
+        &self,
+        subject: &EncodedTerm,
+        predicate: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dspo_quads(&encode_term_pair(subject, predicate)),
+            self.spog_quads(&encode_term_pair(subject, predicate)),
+        )
+    }
+
+    fn quads_for_subject_predicate_object(
+        &self,
+        subject: &EncodedTerm,
+        predicate: &EncodedTerm,
+        object: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dspo_quads(&encode_term_triple(subject, predicate, object)),
+            self.spog_quads(&encode_term_triple(subject, predicate, object)),
+        )
+    }
+
+    fn quads_for_subject_object(
+        &self,
+        subject: &EncodedTerm,
+        object: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dosp_quads(&encode_term_pair(object, subject)),
+            self.ospg_quads(&encode_term_pair(object, subject)),
+        )
+    }
+
+    // TODO：这个方法有用
+    fn quads_for_predicate(&self, predicate: &EncodedTerm) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dpos_quads(&encode_term(predicate)),
+            self.posg_quads(&encode_term(predicate)),
+        )
+    }
+
+    fn quads_for_predicate_object(
+        &self,
+        predicate: &EncodedTerm,
+        object: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dpos_quads(&encode_term_pair(predicate, object)),
+            self.posg_quads(&encode_term_pair(predicate, object)),
+        )
+    }
+
+    fn quads_for_object(&self, object: &EncodedTerm) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::pair(
+            self.dosp_quads(&encode_term(object)),
+            self.ospg_quads(&encode_term(object)),
+        )
+    }
+
+    /// Returns the quads whose object is of the given [`ObjectKind`], in `graph_name` or in all graphs if it is `None`.
+    ///
+    /// The object type byte is the first byte of the `dosp` and `ospg` keys (after the graph name in `gosp`),
+    /// so only the matching key ranges are scanned.
+    /// The default graph indexes written by an interval key bulk load start with the intervals and are not supported.
+    pub fn quads_with_object_of_type(
+        &self,
+        kind: ObjectKind,
+        graph_name: Option<&EncodedTerm>,
+    ) -> ChainedDecodingQuadIterator {
+        let indexes = match graph_name {
+            None => vec![(QuadEncoding::Dosp, Vec::new()), (QuadEncoding::Ospg, Vec::new())],
+            Some(graph_name) if graph_name.is_default_graph() => {
+                vec![(QuadEncoding::Dosp, Vec::new())]
+            }
+            Some(graph_name) => vec![(QuadEncoding::Gosp, encode_term(graph_name))],
+        };
+        let mut iters = Vec::new();
+        for (index, prefix) in indexes {
+            for (first_type, last_type) in kind.type_byte_ranges() {
+                let mut start = prefix.clone();
+                start.push(*first_type);
+                let mut last = prefix.clone();
+                last.push(*last_type);
+                iters.push(self.inner_quads_in_range(index, &start, next_prefix(&last)));
+            }
+        }
+        ChainedDecodingQuadIterator::chain(iters)
+    }
+
+    // 与 quads_with_object_of_type 相同，但按照主语的类型字节扫描 dspo 与 spog
+    fn quads_with_subject_of_type(&self, kind: ObjectKind) -> ChainedDecodingQuadIterator {
+        let mut iters = Vec::new();
+        for index in [QuadEncoding::Dspo, QuadEncoding::Spog] {
+            for (first_type, last_type) in kind.type_byte_ranges() {
+                iters.push(self.inner_quads_in_range(index, &[*first_type], next_prefix(&[*last_type])));
+            }
+        }
+        ChainedDecodingQuadIterator::chain(iters)
+    }
+
+    // 加上图之后创建的 ChainedDecodingQuadIterator 就不一样了（使用new方法）
+    // 给点图，返回该图上所有元组的迭代器
+    // 默认图只扫描 d* 索引，命名图的前缀由 named_graph_prefix 生成，不会出现空前缀
+    fn quads_for_graph(&self, graph_name: &EncodedTerm) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dspo_quads(&Vec::default())
+        } else {
+            self.gspo_quads(&named_graph_prefix(graph_name, &[]))
+        })
+    }
+
+    fn quads_for_subject_graph(
+        &self,
+        subject: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dspo_quads(&encode_term(subject))
+        } else {
+            self.gspo_quads(&named_graph_prefix(graph_name, &[subject]))
+        })
+    }
+
+    fn quads_for_subject_predicate_graph(
+        &self,
+        subject: &EncodedTerm,
+        predicate: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dspo_quads(&encode_term_pair(subject, predicate))
+        } else {
+            self.gspo_quads(&named_graph_prefix(graph_name, &[subject, predicate]))
+        })
+    }
+
+    fn quads_for_subject_predicate_object_graph(
+        &self,
+        subject: &EncodedTerm,
+        predicate: &EncodedTerm,
+        object: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {  // 如果是默认图
+            self.dspo_quads(&encode_term_triple(subject, predicate, object))   // 传入dspo_quads()的是包含spo的buffer编码字节序列
+        } else {
+            self.gspo_quads(&named_graph_prefix(graph_name, &[subject, predicate, object]))
+        })
+    }
+
+    fn quads_for_subject_object_graph(
+        &self,
+        subject: &EncodedTerm,
+        object: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dosp_quads(&encode_term_pair(object, subject))
+        } else {
+            self.gosp_quads(&named_graph_prefix(graph_name, &[object, subject]))
+        })
+    }
+
+    // 命名图使用 (graph, predicate) 两项前缀扫描 gpos，而不是只按图扫描后再过滤谓语
+    fn quads_for_predicate_graph(
+        &self,
+        predicate: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dpos_quads(&encode_term(predicate))
+        } else {
+            self.gpos_quads(&named_graph_prefix(graph_name, &[predicate]))
+        })
+    }
+
+    fn quads_for_predicate_object_graph(
+        &self,
+        predicate: &EncodedTerm,
+        object: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dpos_quads(&encode_term_pair(predicate, object))
+        } else {
+            self.gpos_quads(&named_graph_prefix(graph_name, &[predicate, object]))
+        })
+    }
+
+    fn quads_for_object_graph(
+        &self,
+        object: &EncodedTerm,
+        graph_name: &EncodedTerm,
+    ) -> ChainedDecodingQuadIterator {
+        ChainedDecodingQuadIterator::new(if graph_name.is_default_graph() {
+            self.dosp_quads(&encode_term(object))
+        } else {
+            self.gosp_quads(&named_graph_prefix(graph_name, &[object]))
+        })
+    }
+
+    pub fn named_graphs(&self) -> DecodingGraphIterator {
+        match self.reader.iter(&self.storage.graphs_cf) {
+            Ok(iter) => DecodingGraphIterator {
+                iter: Some(iter),
+                error: None,
+            },
+            Err(error) => DecodingGraphIterator {
+                iter: None,
+                error: Some(error),
+            },
+        }
+    }
+
+    /// Same as [`named_graphs`](Self::named_graphs) but decodes the graph names using the strings of this snapshot.
+    ///
+    /// A graph name whose string is missing from the storage yields a [`CorruptionError`].
+    pub fn decoded_named_graphs(
+        &self,
+    ) -> impl Iterator<Item = Result<NamedOrBlankNode, StorageError>> + '_ {
+        self.named_graphs()
+            .map(move |graph_name| self.decode_named_or_blank_node(&graph_name?))
+    }
+
+    /// Returns the IRIs used in the storage that start with `prefix`, in the order of their [`StrHash`].
+    ///
+    /// The indexes only contain the hashes of the IRIs so this is a full scan of the values of `id2str`:
+    /// its cost grows with the number of stored strings, not with the number of matches, and it is not meant for latency-sensitive paths.
+    /// A matching string is returned only if it is a valid IRI used as a named node by a quad or a named graph,
+    /// literals and blank node ids with the same text are skipped.
+    /// An IRI compressed with a prefix registered by [`Storage::register_prefix`] only has its suffix in `id2str`:
+    /// it is rebuilt with each registered prefix and kept if this prefix is the one used to encode it.
+    pub fn iris_with_prefix<'b>(
+        &'b self,
+        prefix: &'b str,
+    ) -> impl Iterator<Item = Result<NamedNode, StorageError>> + 'b {
+        let (mut iter, mut error) = match self.reader.iter(&self.storage.id2str_cf) {
+            Ok(iter) => (Some(iter), None),
+            Err(error) => (None, Some(error)),
+        };
+        let mut candidates = VecDeque::new();
+        from_fn(move || {
+            if let Some(error) = error.take() {
+                return Some(Err(error));
+            }
+            loop {
+                if let Some((iri, encoded)) = candidates.pop_front() {
+                    match self.is_used_as_named_node(&encoded) {
+                        Ok(true) => return Some(Ok(iri)),
+                        Ok(false) => continue,
+                        Err(error) => return Some(Err(error)),
+                    }
+                }
+                let current = iter.as_mut()?;
+                match current.value() {
+                    Some(value) => candidates.extend(self.iri_candidates(value, prefix)),
+                    None => {
+                        let status = current.status();
+                        iter = None;
+                        return status.err().map(Err);
+                    }
+                }
+                current.next();
+            }
+        })
+    }
+
+    // id2str 中的值可能对应的以 prefix 开头的 IRI：值本身，或者注册的前缀加上作为后缀的值
+    // 只保留与前缀表给出的编码相同的 IRI，同一个 IRI 不会出现两次
+    fn iri_candidates(&self, value: &[u8], prefix: &str) -> Vec<(NamedNode, EncodedTerm)> {
+        let value = match std::str::from_utf8(value) {
+            Ok(value) => value,
+            Err(_) => return Vec::new(),
+        };
+        let mut candidates = Vec::new();
+        if value.starts_with(prefix) {
+            candidates.push((value.to_owned(), None));
+        }
+        for (prefix_id, registered) in self.prefixes.prefixes().iter().enumerate() {
+            let matches = if registered.len() >= prefix.len() {
+                registered.starts_with(prefix)
+            } else {
+                prefix.starts_with(registered.as_str())
+                    && value.starts_with(&prefix[registered.len()..])
+            };
+            if matches {
+                candidates.push((format!("{}{}", registered, value), Some(prefix_id)));
+            }
+        }
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        let hash = StrHash::new(value);
+        candidates
+            .into_iter()
+            .filter_map(|(iri, prefix_id)| {
+                let iri = NamedNode::new(iri).ok()?;
+                let encoded = match prefix_id {
+                    Some(prefix_id) => EncodedTerm::PrefixedNamedNode {
+                        prefix_id: prefix_id.try_into().unwrap(),
+                        suffix_id: hash,
+                    },
+                    None => EncodedTerm::NamedNode { iri_id: hash },
+                };
+                (self.encode_named_node(iri.as_ref()) == encoded).then(|| (iri, encoded))
+            })
+            .collect()
+    }
+
+    // IRI 是否作为主语、谓语、宾语或者图名出现
+    fn is_used_as_named_node(&self, term: &EncodedTerm) -> Result<bool, StorageError> {
+        Ok(self.quads_for_subject(term).next().transpose()?.is_some()
+            || self.quads_for_predicate(term).next().transpose()?.is_some()
+            || self.quads_for_object(term).next().transpose()?.is_some()
+            || self.contains_named_graph(term)?)
+    }
+
+    /// Returns every named graph with its number of quads.
+    ///
+    /// The `gspo` index is scanned once, counting the keys of each graph prefix without decoding the quads,
+    /// and the counts are joined with the `graphs` column family so that empty named graphs are returned with a count of 0.
+    /// The graphs are in the order of [`named_graphs`](Self::named_graphs).
+    pub fn graph_names_with_counts(&self) -> Result<Vec<(NamedOrBlankNode, usize)>, StorageError> {
+        let mut counts = HashMap::new();
+        let mut current: Option<(Vec<u8>, usize)> = None;
+        let mut iter = self.reader.iter(&self.storage.gspo_cf)?;
+        while let Some(key) = iter.key() {
+            match &mut current {
+                Some((prefix, count)) if key.starts_with(prefix) => *count += 1,
+                _ => {
+                    if let Some((prefix, count)) = current.take() {
+                        counts.insert(prefix, count);
+                    }
+                    // 只解码 key 开头的图名
+                    let graph_name = decode_term(key)?;
+                    current = Some((encode_term(&graph_name), 1));
+                }
+            }
+            iter.next();
+        }
+        iter.status()?;
+        if let Some((prefix, count)) = current {
+            counts.insert(prefix, count);
+        }
+
+        let mut result = Vec::new();
+        for graph_name in self.named_graphs() {
+            let graph_name = graph_name?;
+            let count = counts.remove(&encode_term(&graph_name)).unwrap_or(0);
+            result.push((self.decode_named_or_blank_node(&graph_name)?, count));
+        }
+        // gspo 中有但没有登记在 graphs 中的图（不一致的存储）也返回
+        let mut unregistered = counts.into_iter().collect::<Vec<_>>();
+        unregistered.sort_unstable();
+        for (prefix, count) in unregistered {
+            result.push((self.decode_named_or_blank_node(&decode_term(&prefix)?)?, count));
+        }
+        Ok(result)
+    }
+
+    pub fn contains_named_graph(&self, graph_name: &EncodedTerm) -> Result<bool, StorageError> {
+        self.reader
+            .contains_key(&self.storage.graphs_cf, &encode_term(graph_name))
+    }
+
+    /// Same as [`contains_named_graph`](Self::contains_named_graph) but encodes the graph name itself.
+    ///
+    /// Blank nodes and named nodes are encoded with different type bytes:
+    /// a blank node graph is never mistaken for a named graph with the same label.
+    pub fn contains_named_graph_ref(
+        &self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<bool, StorageError> {
+        self.contains_named_graph(&self.encode_term(graph_name))
+    }
+
+    /// Returns the distinct subjects of `graph`, or of the whole dataset if `graph` is `None`.
+    ///
+    /// The `spo`/`gspo` indexes are walked by seeking past each subject, so the cost depends on the number of distinct subjects and not on the number of quads.
+    pub fn distinct_subjects(
+        &self,
+        graph: Option<GraphNameRef<'_>>,
+    ) -> impl Iterator<Item = Result<Subject, StorageError>> + '_ {
+        self.distinct_terms(
+            &self.storage.dspo_cf,
+            &self.storage.spog_cf,
+            &self.storage.gspo_cf,
+            graph,
+        )
+        .map(move |subject| self.decode_subject(&subject?))
+    }
+
+    /// Returns the distinct predicates of `graph`, or of the whole dataset if `graph` is `None`.
+    ///
+    /// The `pos`/`gpos` indexes are walked by seeking past each predicate, so the cost depends on the number of distinct predicates and not on the number of quads.
+    pub fn distinct_predicates(
+        &self,
+        graph: Option<GraphNameRef<'_>>,
+    ) -> impl Iterator<Item = Result<NamedNode, StorageError>> + '_ {
+        self.distinct_terms(
+            &self.storage.dpos_cf,
+            &self.storage.posg_cf,
+            &self.storage.gpos_cf,
+            graph,
+        )
+        .map(move |predicate| self.decode_named_node(&predicate?))
+    }
+
+    /// Returns the distinct objects of `graph`, or of the whole dataset if `graph` is `None`.
+    ///
+    /// The `osp`/`gosp` indexes are walked by seeking past each object.
+    pub fn distinct_objects(
+        &self,
+        graph: Option<GraphNameRef<'_>>,
+    ) -> impl Iterator<Item = Result<Term, StorageError>> + '_ {
+        self.distinct_terms(
+            &self.storage.dosp_cf,
+            &self.storage.ospg_cf,
+            &self.storage.gosp_cf,
+            graph,
+        )
+        .map(move |object| self.decode_term(&object?))
+    }
+
+    // 默认图用 dxxx 索引，命名图用带图前缀的 gxxx 索引，不指定图时合并 dxxx 与 xxxg 两个索引
+    fn distinct_terms(
+        &self,
+        default_cf: &ColumnFamily,
+        union_cf: &ColumnFamily,
+        named_cf: &ColumnFamily,
+        graph: Option<GraphNameRef<'_>>,
+    ) -> DistinctTermIterator {
+        let scans = match graph {
+            None => vec![(default_cf, Vec::new()), (union_cf, Vec::new())],
+            Some(graph) if graph.is_default_graph() => vec![(default_cf, Vec::new())],
+            Some(graph) => vec![(named_cf, encode_term(&self.encode_graph_name(graph)))],
+        };
+        let prefix_len = scans[0].1.len();
+        let mut iters = Vec::with_capacity(scans.len());
+        for (column_family, prefix) in scans {
+            match self.reader.scan_prefix(column_family, &prefix) {
+                Ok(iter) => iters.push(iter),
+                Err(error) => {
+                    return DistinctTermIterator {
+                        iters: Vec::new(),
+                        prefix_len,
+                        error: Some(error),
+                        seeks: 0,
+                    }
+                }
+            }
+        }
+        DistinctTermIterator {
+            iters,
+            prefix_len,
+            error: None,
+            seeks: 0,
+        }
+    }
+
+    /// Returns, for each predicate, the number of quads using it and the numbers of distinct subjects and objects.
+    ///
+    /// The `dpos` and `posg` indexes are walked once together in key order.
+    /// The objects of a predicate are sorted so they are counted on the fly,
+    /// the subjects are only sorted for a given object and are kept in a set while the predicate is walked.
+    /// A triple present in several graphs is counted once per graph in [`PredicateStats::triple_count`].
+    pub fn predicate_stats(&self) -> Result<HashMap<NamedNode, PredicateStats>, StorageError> {
+        let mut dpos = self.reader.scan_prefix(&self.storage.dpos_cf, &[])?;
+        let mut posg = self.reader.scan_prefix(&self.storage.posg_cf, &[])?;
+        let mut stats = Vec::<(EncodedTerm, PredicateStats)>::new();
+        let mut subjects = HashSet::new();
+        let mut last_object = None;
+        loop {
+            // 按 key 归并两个索引，dpos 的 key (p o s) 是相同三元组 posg key (p o s g) 的前缀
+            let (quad, from_dpos) = match (dpos.key(), posg.key()) {
+                (Some(d), Some(g)) if d <= g => (QuadEncoding::Dpos.decode(d)?, true),
+                (_, Some(g)) => (QuadEncoding::Posg.decode(g)?, false),
+                (Some(d), None) => (QuadEncoding::Dpos.decode(d)?, true),
+                (None, None) => break,
+            };
+            if from_dpos {
+                dpos.next();
+            } else {
+                posg.next();
+            }
+            if stats.last().map_or(true, |(predicate, _)| *predicate != quad.predicate) {
+                if let Some((_, current)) = stats.last_mut() {
+                    current.distinct_subjects = subjects.len();
+                }
+                subjects.clear();
+                last_object = None;
+                stats.push((quad.predicate.clone(), PredicateStats::default()));
+            }
+            let current = &mut stats.last_mut().unwrap().1;
+            current.triple_count += 1;
+            if last_object.as_ref() != Some(&quad.object) {
+                current.distinct_objects += 1;
+                last_object = Some(quad.object);
+            }
+            subjects.insert(quad.subject);
+        }
+        dpos.status()?;
+        posg.status()?;
+        if let Some((_, current)) = stats.last_mut() {
+            current.distinct_subjects = subjects.len();
+        }
+
+        stats
+            .into_iter()
+            .map(|(predicate, predicate_stats)| Ok((self.decode_named_node(&predicate)?, predicate_stats)))
+            .collect()
+    }
+
+
+
+    // 调用self.inner_quads，生成迭代器，在 validate方法里会调用到
+    fn spog_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.spog_cf, prefix, QuadEncoding::Spog)
+    }
+
+    fn posg_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.posg_cf, prefix, QuadEncoding::Posg)
+    }
+
+    fn ospg_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.ospg_cf, prefix, QuadEncoding::Ospg)
+    }
+
+    fn gspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.gspo_cf, prefix, QuadEncoding::Gspo)
+    }
+
+    fn gpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.gpos_cf, prefix, QuadEncoding::Gpos)
+    }
+
+    fn gosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.gosp_cf, prefix, QuadEncoding::Gosp)
+    }
+
+    fn dspo_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {    // prefix 实际上就是包含s p o的buffer编码字节序列
+        self.inner_quads(&self.storage.dspo_cf, prefix, QuadEncoding::Dspo)
+    }
+
+    fn dpos_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.dpos_cf, prefix, QuadEncoding::Dpos)
+    }
+
+    fn dosp_quads(&self, prefix: &[u8]) -> DecodingQuadIterator {
+        self.inner_quads(&self.storage.dosp_cf, prefix, QuadEncoding::Dosp)
+    }
+
+    fn index_quads(&self, index: QuadEncoding, prefix: &[u8]) -> DecodingQuadIterator {
+        match index {
+            QuadEncoding::Spog => self.spog_quads(prefix),
+            QuadEncoding::Posg => self.posg_quads(prefix),
+            QuadEncoding::Ospg => self.ospg_quads(prefix),
+            QuadEncoding::Gspo => self.gspo_quads(prefix),
+            QuadEncoding::Gpos => self.gpos_quads(prefix),
+            QuadEncoding::Gosp => self.gosp_quads(prefix),
+            QuadEncoding::Dspo => self.dspo_quads(prefix),
+            QuadEncoding::Dpos => self.dpos_quads(prefix),
+            QuadEncoding::Dosp => self.dosp_quads(prefix),
+        }
+    }
+
+    // 与 inner_quads 相同，但扫描 [start, end) 区间而不是一个前缀
+    fn inner_quads_in_range(
+        &self,
+        index: QuadEncoding,
+        start: &[u8],
+        end: Option<Vec<u8>>,
+    ) -> DecodingQuadIterator {
+        let column_family = self.storage.column_family(StorageColumnFamily::of_index(index));
+        match self.reader.scan_range(column_family, start, end) {
+            Ok(iter) => DecodingQuadIterator {
+                iter: Some(iter),
+                error: None,
+                encoding: index,
+                interval_prefix: None,
+            },
+            Err(error) => DecodingQuadIterator {
+                iter: None,
+                error: Some(error),
+                encoding: index,
+                interval_prefix: None,
+            },
+        }
+    }
+
+    fn inner_quads(
+        &self,
+        column_family: &ColumnFamily,
+        prefix: &[u8],   // spog的字节编码序列buffer（不定顺序）
+        encoding: QuadEncoding,
+    ) -> DecodingQuadIterator {
+        // IntervalKey 布局下默认图索引的 key 以区间编码开头，无法按 term 前缀扫描，扫描整个索引后再过滤
+        // 所以这时默认图上的每个模式都是 O(N) 的全索引扫描
+        let interval_prefix = (self.storage.interval_keys.load(Ordering::Relaxed)
+            && matches!(encoding, QuadEncoding::Dspo | QuadEncoding::Dpos | QuadEncoding::Dosp))
+        .then(|| (prefix.to_vec(), Arc::clone(&self.prefixes)));
+        let scanned_prefix = if interval_prefix.is_some() { &[] } else { prefix };
+        // 创建迭代器失败时不 panic，而是将错误作为迭代器的第一个元素返回
+        match self.reader.scan_prefix(column_family, scanned_prefix) {
+            Ok(iter) => DecodingQuadIterator {
+                iter: Some(iter),
+                error: None,
+                encoding,
+                interval_prefix,
+            },
+            Err(error) => DecodingQuadIterator {
+                iter: None,
+                error: Some(error),
+                encoding,
+                interval_prefix,
+            },
+        }
+    }
+
+    /// Returns the IRI prefixes registered with [`Storage::register_prefix`] when this reader was created.
+    pub fn prefix_table(&self) -> &PrefixTable {
+        &self.prefixes
+    }
+
+    /// Encodes a term with the registered IRI prefixes, the only encoding used for it in the storage.
+    pub fn encode_term<'b>(&self, term: impl Into<TermRef<'b>>) -> EncodedTerm {
+        self.prefixes.encode_term(term)
+    }
+
+    /// Same as [`encode_term`](Self::encode_term) for a named node.
+    pub fn encode_named_node(&self, node: NamedNodeRef<'_>) -> EncodedTerm {
+        self.prefixes.encode_named_node(node)
+    }
+
+    /// Same as [`encode_term`](Self::encode_term) for a graph name, the default graph included.
+    pub fn encode_graph_name(&self, graph_name: GraphNameRef<'_>) -> EncodedTerm {
+        self.prefixes.encode_graph_name(graph_name)
+    }
+
+    /// Encodes a quad like [`encode_term`](Self::encode_term) does for each of its terms.
+    pub fn encode_quad(&self, quad: QuadRef<'_>) -> EncodedQuad {
+        self.prefixes.encode_quad(quad)
+    }
+
+    /// Decodes a quad yielded by the [`EncodedQuad`] iterators like [`quads_for_pattern`](Self::quads_for_pattern).
+    ///
+    /// The strings behind the [`StrHash`]es of the terms are resolved through the `id2str` column family,
+    /// a [`CorruptionError`] is returned if one of them is missing.
+    pub fn decode_quad(&self, encoded: &EncodedQuad) -> Result<Quad, StorageError> {
+        Decoder::decode_quad(self, encoded)
+    }
+
+    /// Decodes a page of quads, like [`decode_quad`](Self::decode_quad) on each of them.
+    ///
+    /// All the strings referenced by the page are fetched with a single [`get_strs`](Self::get_strs)
+    /// instead of one `id2str` lookup per term.
+    pub fn decode_quads(&self, encoded: &[EncodedQuad]) -> Result<Vec<Quad>, StorageError> {
+        let cache = DecodeCache::new(self, encoded)?;
+        encoded
+            .iter()
+            .map(|quad| Decoder::decode_quad(&cache, quad))
+            .collect()
+    }
+
+    // 根据 StrHash 编码获得其对应存储的字符串
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
+        self.storage
+            .db
+            .get(&self.storage.id2str_cf, &self.storage.id2str_key(key))?
+            .map(|v| decode_id2str_value(key, v.into()))
+            .transpose()
+    }
+
+    /// Same as [`get_str`](Self::get_str) for several keys with a single `id2str` multi-get.
+    ///
+    /// There is one result per key, in the order of the keys, even if some keys are repeated.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn get_strs(&self, keys: &[StrHash]) -> Result<Vec<Option<String>>, StorageError> {
+        let raw_keys = keys.iter().map(|k| self.storage.id2str_key(k)).collect::<Vec<_>>();
+        self.storage
+            .db
+            .multi_get(
+                &self.storage.id2str_cf,
+                &raw_keys.iter().map(|k| k.as_slice()).collect::<Vec<_>>(),
+            )?
+            .into_iter()
+            .zip(keys)
+            .map(|(v, key)| v.map(|v| decode_id2str_value(key, v.into())).transpose())
+            .collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_strs(&self, keys: &[StrHash]) -> Result<Vec<Option<String>>, StorageError> {
+        keys.iter().map(|key| self.get_str(key)).collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
+        self.reader
+            .get(&self.storage.id2str_cf, &self.storage.id2str_key(key))?
+            .map(|v| decode_id2str_value(key, v.into()))
+            .transpose()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
+        self.storage
+            .db
+            .contains_key(&self.storage.id2str_cf, &self.storage.id2str_key(key))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
+        self.reader
+            .contains_key(&self.storage.id2str_cf, &self.storage.id2str_key(key))
+    }
+
+    /// Validates that all the storage invariants held in the data
+    // 验证存储的数据是否一致（spo、pos、osp中的元组数量是否一致，四元组也同样）
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn validate(&self) -> Result<(), StorageError> {
+        self.validate_index_sizes()?;
+        for spo in self.dspo_quads(&[]) {
+            self.validate_triple(&spo?)?;
+        }
+        for gspo in self.gspo_quads(&[]) {
+            self.validate_quad(&gspo?)?;
+        }
+        Ok(())
+    }
+
+    /// Same as [`validate`](Self::validate) but checks the quads on `num_threads` threads.
+    ///
+    /// The `dspo` and `gspo` scans are split by the first byte of their keys.
+    /// The reported error is the one [`validate`](Self::validate) would report, i.e. the first one in key order.
+    /// All the threads read the snapshot of this reader, so concurrent writes are not seen by the validation.
+    /// Fails if this reader reads a transaction: its pending writes cannot be shared with other threads.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn validate_parallel(&self, num_threads: usize) -> Result<(), StorageError> {
+        let snapshot = self.reader.shared_snapshot().ok_or_else(|| {
+            StorageError::Other(
+                "validate_parallel cannot be used with the reader of a transaction".into(),
+            )
+        })?;
+        self.validate_index_sizes()?;
+        let num_threads = num_threads.max(1);
+        for (encoding, check) in [
+            (
+                QuadEncoding::Dspo,
+                Self::validate_triple as fn(&Self, &EncodedQuad) -> Result<(), StorageError>,
+            ),
+            (QuadEncoding::Gspo, Self::validate_quad),
+        ] {
+            let threads = (0..num_threads)
+                .map(|thread| {
+                    let storage = self.storage.clone();
+                    let prefixes = Arc::clone(&self.prefixes);
+                    let snapshot = snapshot.clone();
+                    spawn(move || {
+                        // 每个线程负责第一个字节为 thread、thread + num_threads... 的 key，只保留每个前缀的第一个错误
+                        let reader = StorageReader {
+                            reader: snapshot.reader(),
+                            storage,
+                            prefixes,
+                        };
+                        let mut errors = Vec::new();
+                        for first_byte in (thread..=usize::from(u8::MAX)).step_by(num_threads) {
+                            let first_byte = u8::try_from(first_byte).unwrap();
+                            for quad in reader.index_quads(encoding, &[first_byte]) {
+                                if let Err(error) = quad.and_then(|quad| check(&reader, &quad)) {
+                                    errors.push((first_byte, error));
+                                    break;
+                                }
+                            }
+                        }
+                        errors
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut errors = Vec::new();
+            for thread in threads {
+                errors.extend(thread.join().map_err(|_| {
+                    StorageError::Other("A validation thread panicked".into())
+                })?);
+            }
+            if let Some((_, error)) = errors.into_iter().min_by_key(|(first_byte, _)| *first_byte) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn validate_index_sizes(&self) -> Result<(), StorageError> {
+        if let Some(mismatch) = self.index_size_mismatch()? {
+            return Err(CorruptionError::msg(format!(
+                "Not the same number of entries in the quad indexes ({})",
+                mismatch
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    // 同一组冗余索引（默认图的三个索引、命名图的六个索引）的条目数不一致时，返回各个索引的条目数
+    #[cfg(not(target_arch = "wasm32"))]
+    fn index_size_mismatch(&self) -> Result<Option<String>, StorageError> {
+        let groups: [&[QuadEncoding]; 2] = [
+            &[QuadEncoding::Dspo, QuadEncoding::Dpos, QuadEncoding::Dosp],
+            &[
+                QuadEncoding::Gspo,
+                QuadEncoding::Gpos,
+                QuadEncoding::Gosp,
+                QuadEncoding::Spog,
+                QuadEncoding::Posg,
+                QuadEncoding::Ospg,
+            ],
+        ];
+        for group in groups {
+            let mut sizes = Vec::with_capacity(group.len());
+            for index in group {
+                let column_family = StorageColumnFamily::of_index(*index);
+                sizes.push((
+                    column_family.name(),
+                    self.reader.len(self.storage.column_family(column_family))?,
+                ));
+            }
+            if sizes.iter().any(|(_, size)| *size != sizes[0].1) {
+                return Ok(Some(
+                    sizes
+                        .iter()
+                        .map(|(name, size)| format!("{}: {}", name, size))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ));
+            }
+        }
+        Ok(None)
+    }
+
+    // 检查 dspo 中的一个三元组在其他默认图索引中都存在
+    #[cfg(not(target_arch = "wasm32"))]
+    fn validate_triple(&self, spo: &EncodedQuad) -> Result<(), StorageError> {
+        self.decode_quad(spo)?; // We ensure that the quad is readable
+        if !self.storage.db.contains_key(
+            &self.storage.dpos_cf,
+            &encode_term_triple(&spo.predicate, &spo.object, &spo.subject),
+        )? {
+            return Err(CorruptionError::new("Quad in dspo and not in dpos").into());
+        }
+        if !self.storage.db.contains_key(
+            &self.storage.dosp_cf,
+            &encode_term_triple(&spo.object, &spo.subject, &spo.predicate),
+        )? {
+            return Err(CorruptionError::new("Quad in dspo and not in dosp").into());
+        }
+        Ok(())
+    }
+
+    // 检查 gspo 中的一个四元组在其他命名图索引以及 graphs 中都存在
+    #[cfg(not(target_arch = "wasm32"))]
+    fn validate_quad(&self, gspo: &EncodedQuad) -> Result<(), StorageError> {
+        self.decode_quad(gspo)?; // We ensure that the quad is readable
+        if !self.storage.db.contains_key(
+            &self.storage.gpos_cf,
+            &encode_term_quad(
+                &gspo.graph_name,
+                &gspo.predicate,
+                &gspo.object,
+                &gspo.subject,
+            ),
+        )? {
+            return Err(CorruptionError::new("Quad in gspo and not in gpos").into());
+        }
+        if !self.storage.db.contains_key(
+            &self.storage.gosp_cf,
+            &encode_term_quad(
+                &gspo.graph_name,
+                &gspo.object,
+                &gspo.subject,
+                &gspo.predicate,
+            ),
+        )? {
+            return Err(CorruptionError::new("Quad in gspo and not in gosp").into());
+        }
+        if !self.storage.db.contains_key(
+            &self.storage.spog_cf,
+            &encode_term_quad(
+                &gspo.subject,
+                &gspo.predicate,
+                &gspo.object,
+                &gspo.graph_name,
+            ),
+        )? {
+            return Err(CorruptionError::new("Quad in gspo and not in spog").into());
+        }
+        if !self.storage.db.contains_key(
+            &self.storage.posg_cf,
+            &encode_term_quad(
+                &gspo.predicate,
+                &gspo.object,
+                &gspo.subject,
+                &gspo.graph_name,
+            ),
+        )? {
+            return Err(CorruptionError::new("Quad in gspo and not in posg").into());
+        }
+        if !self.storage.db.contains_key(
+            &self.storage.ospg_cf,
+            &encode_term_quad(
+                &gspo.object,
+                &gspo.subject,
+                &gspo.predicate,
+                &gspo.graph_name,
+            ),
+        )? {
+            return Err(CorruptionError::new("Quad in gspo and not in ospg").into());
+        }
+        if !self
+            .storage
+            .db
+            .contains_key(&self.storage.graphs_cf, &encode_term(&gspo.graph_name))?
+        {
+            return Err(
+                CorruptionError::new("Quad graph name in gspo and not in graphs").into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+
+// ##########################################################################
+// 在查询时若没有指定图，则使用 pair()新建 dspo、gspo两个迭代器
+// 若指定了图，则只使用 new()新建对应图上的迭代器
+#[derive(Clone)]
+pub struct ChainedDecodingQuadIterator {
+    first: DecodingQuadIterator,
+    second: Option<DecodingQuadIterator>,
+    rest: VecDeque<DecodingQuadIterator>,   // second 之后依次迭代的迭代器
+    remaining: usize,   // 调用者保证至少还会返回的元素个数
+}
+
+
+impl ChainedDecodingQuadIterator {
+    fn new(first: DecodingQuadIterator) -> Self {
+        Self {
+            first,
+            second: None,
+            rest: VecDeque::new(),
+            remaining: 0,
+        }
+    }
+
+    fn pair(first: DecodingQuadIterator, second: DecodingQuadIterator) -> Self {
+        Self {
+            first,
+            second: Some(second),
+            rest: VecDeque::new(),
+            remaining: 0,
+        }
+    }
+
+    // 依次迭代任意个迭代器，至少需要一个
+    fn chain(iters: Vec<DecodingQuadIterator>) -> Self {
+        let mut iters = VecDeque::from(iters);
+        let first = iters.pop_front().expect("at least one iterator is needed");
+        let second = iters.pop_front();
+        Self {
+            first,
+            second,
+            rest: iters,
+            remaining: 0,
+        }
+    }
+
+    /// Sets a lower bound on the number of quads this iterator is going to return.
+    ///
+    /// It is reported by [`Iterator::size_hint`] so `collect` allocates once.
+    /// The caller must make sure the iterator really returns at least `count` elements.
+    pub fn with_lower_bound(mut self, count: usize) -> Self {
+        debug_assert!(
+            self.size_hint().1.map_or(true, |upper| count <= upper),
+            "The lower bound {} is above the upper bound of the iterator",
+            count
+        );
+        self.remaining = count;
+        self
+    }
+
+    /// Returns the approximate number of keys of the whole indexes scanned by this iterator, an upper bound of the quads left.
+    ///
+    /// It is the sum of the `rocksdb.estimate-num-keys` property of the not yet exhausted indexes:
+    /// the keys outside of the scanned prefixes are counted too, so for a selective pattern it is far above
+    /// the number of matching quads. It is also read from the live database and not from the snapshot,
+    /// and RocksDB only approximates it, so it is not a valid [`Iterator::size_hint`] bound.
+    /// It is never below the lower bound of `size_hint`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn index_len_upper_bound(&self, reader: &StorageReader) -> Result<usize, StorageError> {
+        let mut estimate = 0_u64;
+        for iter in once(&self.first).chain(&self.second).chain(&self.rest) {
+            // 已经结束的迭代器不再计入
+            if iter.size_hint().1 == Some(0) {
+                continue;
+            }
+            let column_family = reader.storage.column_family(StorageColumnFamily::of_index(iter.encoding));
+            estimate = estimate.saturating_add(
+                reader
+                    .storage
+                    .db
+                    .property_int(column_family, "rocksdb.estimate-num-keys")?
+                    .unwrap_or(0),
+            );
+        }
+        Ok(max(usize::try_from(estimate).unwrap_or(usize::MAX), self.size_hint().0))
+    }
+
+    // 依次在各个迭代器上跳过 count 个 key，不解码，返回实际跳过的个数
+    fn skip_keys(&mut self, count: usize) -> usize {
+        let mut skipped = self.first.skip_keys(count);
+        if let Some(second) = self.second.as_mut() {
+            skipped += second.skip_keys(count - skipped);
+        }
+        for iter in &mut self.rest {
+            skipped += iter.skip_keys(count - skipped);
+        }
+        self.remaining = self.remaining.saturating_sub(skipped);
+        skipped
+    }
+}
+
+impl Iterator for ChainedDecodingQuadIterator {
+    type Item = Result<EncodedQuad, StorageError>; // 被迭代的元素类型
+
+    fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {   // 推进迭代器并返回下一个值
+        let mut result = if let Some(result) = self.first.next() {
+            Some(result)
+        } else if let Some(second) = self.second.as_mut() {
+            second.next()
+        } else {
+            None
+        };
+        while result.is_none() {
+            if let Some(next) = self.rest.front_mut() {
+                result = next.next();
+                if result.is_none() {
+                    self.rest.pop_front();
+                }
+            } else {
+                break;
+            }
+        }
+        if result.is_some() {
+            self.remaining = self.remaining.saturating_sub(1);
+        }
+        result
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (first_min, first_max) = self.first.size_hint();
+        let (second_min, second_max) = self
+            .second
+            .as_ref()
+            .map_or((0, Some(0)), DecodingQuadIterator::size_hint);
+        let (rest_min, rest_max) = self.rest.iter().map(DecodingQuadIterator::size_hint).fold(
+            (0_usize, Some(0_usize)),
+            |(min_a, max_a), (min_b, max_b)| {
+                (min_a + min_b, max_a.zip(max_b).and_then(|(a, b)| a.checked_add(b)))
+            },
+        );
+        let upper = match (first_max, second_max, rest_max) {
+            (Some(a), Some(b), Some(c)) => a.checked_add(b).and_then(|ab| ab.checked_add(c)),
+            _ => None,
+        };
+        // with_lower_bound 给出的下界不能超过迭代器已知的上界
+        let lower = max(self.remaining, first_min + second_min + rest_min);
+        (upper.map_or(lower, |upper| min(lower, upper)), upper)
+    }
+}
+
+/// Joins two scans on their subject with a sort-merge and returns every pair of quads with the same subject.
+///
+/// Both iterators must be ordered by subject, i.e. each one is a single index scan whose key prefix fixes
+/// every term before the subject: for example [`StorageReader::quads_for_pattern`] with the predicate, the object
+/// and the graph bound, which scans `dpos` or `gpos`. Without the graph the default and the named graph indexes
+/// are chained, each one is ordered but not the whole, and the scan is not valid here.
+/// The order is the byte order of the encoded subjects, not the RDF term order.
+/// If the precondition does not hold some matching pairs are silently missing.
+///
+/// The quads of `right` sharing a subject are buffered, so memory grows with the largest group of `right`.
+/// The iteration stops after the first error.
+pub fn merge_join_on_subject(
+    left: ChainedDecodingQuadIterator,
+    right: ChainedDecodingQuadIterator,
+) -> impl Iterator<Item = Result<(EncodedQuad, EncodedQuad), StorageError>> {
+    SubjectMergeJoin {
+        left,
+        right,
+        next_right: None,
+        group: Vec::new(),
+        group_subject: Vec::new(),
+        current_left: None,
+        group_position: 0,
+        done: false,
+    }
+}
+
+struct SubjectMergeJoin {
+    left: ChainedDecodingQuadIterator,
+    right: ChainedDecodingQuadIterator,
+    next_right: Option<(Vec<u8>, EncodedQuad)>,   // 已经读出但还没有放入 group 的 right 元素
+    group: Vec<EncodedQuad>,   // right 中主语为 group_subject 的所有四元组
+    group_subject: Vec<u8>,
+    current_left: Option<EncodedQuad>,   // 正在与 group 逐个配对的 left 元素
+    group_position: usize,
+    done: bool,
+}
+
+impl SubjectMergeJoin {
+    fn next_right(&mut self) -> Result<Option<(Vec<u8>, EncodedQuad)>, StorageError> {
+        if let Some(next) = self.next_right.take() {
+            return Ok(Some(next));
+        }
+        Ok(match self.right.next().transpose()? {
+            Some(quad) => Some((encode_term(&quad.subject), quad)),
+            None => None,
+        })
+    }
+
+    // 读取 right 中主语不小于 subject 的第一组，主语等于 subject 时放入 group 并返回 true
+    fn fill_group(&mut self, subject: &[u8]) -> Result<bool, StorageError> {
+        self.group.clear();
+        while let Some((key, quad)) = self.next_right()? {
+            match key.as_slice().cmp(subject) {
+                std::cmp::Ordering::Less => (),
+                std::cmp::Ordering::Equal => self.group.push(quad),
+                std::cmp::Ordering::Greater => {
+                    self.next_right = Some((key, quad));
+                    break;
+                }
+            }
+        }
+        self.group_subject = subject.to_vec();
+        Ok(!self.group.is_empty())
+    }
+
+    fn next_pair(&mut self) -> Result<Option<(EncodedQuad, EncodedQuad)>, StorageError> {
+        loop {
+            if let Some(left) = &self.current_left {
+                if let Some(right) = self.group.get(self.group_position) {
+                    self.group_position += 1;
+                    return Ok(Some((left.clone(), right.clone())));
+                }
+                self.current_left = None;
+            }
+            let left = match self.left.next().transpose()? {
+                Some(left) => left,
+                None => return Ok(None),
+            };
+            let subject = encode_term(&left.subject);
+            // 同一个主语的 left 元素复用已经读出的 group
+            if subject != self.group_subject && !self.fill_group(&subject)? {
+                if self.next_right.is_none() {
+                    return Ok(None);
+                }
+                continue;
+            }
+            self.current_left = Some(left);
+            self.group_position = 0;
+        }
+    }
+}
+
+impl Iterator for SubjectMergeJoin {
+    type Item = Result<(EncodedQuad, EncodedQuad), StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_pair().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+// ----------------------------------------------------------
+pub struct DecodingQuadIterator {
+    iter: Option<Iter>,
+    error: Option<StorageError>,   // 创建迭代器时的错误，会作为第一个元素返回
+    encoding: QuadEncoding,   // 三元组和四元组的九种序列（gspo...）枚举
+    // IntervalKey 布局的默认图索引：key 以区间编码开头，只返回 term 部分以该前缀开头的 key
+    // 解析区间编码需要识别使用前缀编码的子类、子属性谓语，所以同时保存读取时的前缀表
+    interval_prefix: Option<(Vec<u8>, Arc<PrefixTable>)>,
+}
+
+impl Clone for DecodingQuadIterator {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+            error: self.error.as_ref().map(clone_storage_error),
+            encoding: self.encoding,
+            interval_prefix: self.interval_prefix.clone(),
+        }
+    }
+}
+
+impl DecodingQuadIterator {
+    // 在不解码的情况下跳过至多 count 个 key，创建时的错误不会被跳过
+    // 带区间编码的 key 需要解码后才能知道是否匹配前缀
+    fn skip_keys(&mut self, count: usize) -> usize {
+        if self.error.is_some() {
+            return 0;
+        }
+        if self.interval_prefix.is_some() {
+            return (0..count).take_while(|_| self.next_entry(false).is_some()).count();
+        }
+        let mut skipped = 0;
+        if let Some(iter) = self.iter.as_mut() {
+            while skipped < count && iter.key().is_some() {
+                iter.next();
+                skipped += 1;
+            }
+        }
+        skipped
+    }
+
+    // 解码当前 key 并将迭代器推进到下一个 key，with_value 为 true 时同时返回该 key 的 value
+    // 带区间编码的 key 先跳过区间编码再解码，term 部分不以 interval_prefix 开头的 key 被跳过
+    fn next_entry(&mut self, with_value: bool) -> Option<Result<(EncodedQuad, Vec<u8>), StorageError>> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        let iter = self.iter.as_mut()?;
+        loop {
+            if let Err(e) = iter.status() {
+                return Some(Err(e));
+            }
+            let key = iter.key()?;
+            let quad = if let Some((prefix, prefixes)) = &self.interval_prefix {
+                match self.encoding.decode_interval_key(key, prefixes) {
+                    Ok((quad, _)) => {
+                        let mut terms = Vec::new();
+                        self.encoding.encode_quad(&quad, &mut terms);
+                        if !terms.starts_with(prefix) {
+                            iter.next();
+                            continue;
+                        }
+                        Ok(quad)
+                    }
+                    Err(e) => Err(e),
+                }
+            } else {
+                // 索引中的 key 只包含 term，不应该有多余的字节
+                self.encoding.decode_with_len(key).and_then(|(quad, len)| {
+                    if len == key.len() {
+                        Ok(quad)
+                    } else {
+                        Err(CorruptionError::msg(format!(
+                            "The {:?} key has {} unexpected trailing bytes",
+                            self.encoding,
+                            key.len() - len
+                        ))
+                        .into())
+                    }
+                })
+            };
+            let value = if with_value {
+                iter.value().map(<[u8]>::to_vec).unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            iter.next();
+            return Some(quad.map(|quad| (quad, value)));
+        }
+    }
+}
+
+impl Iterator for DecodingQuadIterator {
+    type Item = Result<EncodedQuad, StorageError>;
+
+    fn next(&mut self) -> Option<Result<EncodedQuad, StorageError>> {   // 推进迭代器并返回下一个值
+        Some(self.next_entry(false)?.map(|(quad, _)| quad))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.error.is_some() {
+            return (1, Some(1));
+        }
+        match &self.iter {
+            // 带区间编码的 key 可能都不匹配前缀
+            Some(iter) if self.interval_prefix.is_some() && iter.key().is_some() => (0, None),
+            // 迭代器指向一个 key 时至少还有一个元素
+            Some(iter) if iter.key().is_some() || iter.status().is_err() => (1, None),
+            _ => (0, Some(0)),
+        }
+    }
+}
+
+/// Same as [`DecodingQuadIterator`] but also yields the value stored with each key.
+pub struct DecodingQuadValueIterator {
+    inner: DecodingQuadIterator,
+}
+
+impl Iterator for DecodingQuadValueIterator {
+    type Item = Result<(EncodedQuad, Vec<u8>), StorageError>;
+
+    fn next(&mut self) -> Option<Result<(EncodedQuad, Vec<u8>), StorageError>> {
+        self.inner.next_entry(true)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+pub struct DecodingGraphIterator {
+    iter: Option<Iter>,
+    error: Option<StorageError>,
+}
+
+impl Iterator for DecodingGraphIterator {
+    type Item = Result<EncodedTerm, StorageError>;   // 进行迭代的元素
+
+    fn next(&mut self) -> Option<Result<EncodedTerm, StorageError>> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        let iter = self.iter.as_mut()?;
+        if let Err(e) = iter.status() {
+            return Some(Err(e));
+        }
+        let term = decode_term(iter.key()?);   // 将内存里的 buffer 解码成 EncodedTerm
+        iter.next();
+        Some(term)
+    }
+}
+
+/// Iterates over the distinct terms found just after a fixed prefix in one or more indexes.
+///
+/// Instead of stepping over every key, the underlying iterators seek past the encoding of the returned term.
+pub struct DistinctTermIterator {
+    iters: Vec<Iter>,
+    prefix_len: usize,   // 所有索引共用的前缀长度（命名图的编码或者为空）
+    error: Option<StorageError>,
+    seeks: usize,   // 底层迭代器 seek 的次数
+}
+
+impl DistinctTermIterator {
+    // 返回各个迭代器当前位置中最小的 term 及其在 key 中的编码（包含前缀）
+    fn smallest_current(&self) -> Result<Option<(Vec<u8>, EncodedTerm)>, StorageError> {
+        let mut smallest: Option<(Vec<u8>, EncodedTerm)> = None;
+        for iter in &self.iters {
+            iter.status()?;
+            let key = if let Some(key) = iter.key() {
+                key
+            } else {
+                continue;
+            };
+            let mut cursor = key.get(self.prefix_len..).ok_or_else(|| {
+                CorruptionError::msg("An index key is shorter than its scanned prefix")
+            })?;
+            let term = cursor.read_term()?;
+            let term_key = &key[..key.len() - cursor.len()];
+            if smallest
+                .as_ref()
+                .map_or(true, |(smallest_key, _)| term_key < smallest_key.as_slice())
+            {
+                smallest = Some((term_key.to_vec(), term));
+            }
+        }
+        Ok(smallest)
+    }
+}
+
+impl Iterator for DistinctTermIterator {
+    type Item = Result<EncodedTerm, StorageError>;
+
+    fn next(&mut self) -> Option<Result<EncodedTerm, StorageError>> {
+        if let Some(e) = self.error.take() {
+            return Some(Err(e));
+        }
+        let (term_key, term) = match self.smallest_current() {
+            Ok(smallest) => smallest?,
+            Err(e) => {
+                self.iters.clear();
+                return Some(Err(e));
+            }
+        };
+        // term 的编码没有前缀冲突，跳到这个 term 之后的第一个 key 即可跳过它的所有 quad
+        if let Some(next_key) = next_prefix(&term_key) {
+            for iter in &mut self.iters {
+                if iter.key().map_or(false, |key| key.starts_with(&term_key)) {
+                    iter.seek(&next_key);
+                    self.seeks += 1;
+                }
+            }
+        } else {
+            self.iters.clear();
+        }
+        Some(Ok(term))
+    }
+}
+
+// StorageError 没有实现 Clone，复制迭代器时按照错误的类别重新构造
+fn clone_storage_error(error: &StorageError) -> StorageError {
+    match error {
+        StorageError::Io(e) => io::Error::new(e.kind(), e.to_string()).into(),
+        StorageError::Corruption(e) => CorruptionError::msg(e.to_string()).into(),
+        _ => StorageError::Other(error.to_string().into()),
+    }
+}
+
+impl StrLookup for StorageReader {
+    fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
+        self.get_str(key)
+    }
+
+    fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
+        self.contains_str(key)
+    }
+
+    fn get_prefix(&self, prefix_id: u8) -> Result<Option<String>, StorageError> {
+        Ok(self.prefixes.get(prefix_id).map(str::to_owned))
+    }
+}
+
+// 一页 EncodedQuad 引用的所有字符串，构造时一次性从 id2str 中批量读取
+// 不在缓存中的 key 回退到 StorageReader 逐个读取，fallbacks 记录回退的次数
+struct DecodeCache<'a> {
+    reader: &'a StorageReader,
+    strs: HashMap<StrHash, String>,
+    fallbacks: Cell<usize>,
+}
+
+impl<'a> DecodeCache<'a> {
+    fn new(reader: &'a StorageReader, quads: &[EncodedQuad]) -> Result<Self, StorageError> {
+        let mut keys = HashSet::new();
+        for quad in quads {
+            for term in [&quad.subject, &quad.predicate, &quad.object, &quad.graph_name] {
+                for_each_str_hash(term, &mut |key| {
+                    keys.insert(key);
+                });
+            }
+        }
+        let keys = keys.into_iter().collect::<Vec<_>>();
+        let strs = keys
+            .iter()
+            .copied()
+            .zip(reader.get_strs(&keys)?)
+            .filter_map(|(key, value)| Some((key, value?)))
+            .collect();
+        Ok(Self {
+            reader,
+            strs,
+            fallbacks: Cell::new(0),
+        })
+    }
+}
+
+impl StrLookup for DecodeCache<'_> {
+    fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError> {
+        if let Some(value) = self.strs.get(key) {
+            return Ok(Some(value.clone()));
+        }
+        self.fallbacks.set(self.fallbacks.get() + 1);
+        self.reader.get_str(key)
+    }
+
+    fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError> {
+        Ok(self.strs.contains_key(key) || self.reader.contains_str(key)?)
+    }
+
+    fn get_prefix(&self, prefix_id: u8) -> Result<Option<String>, StorageError> {
+        self.reader.get_prefix(prefix_id)
+    }
+}
+
+fn prefix_key(prefix_id: u8) -> Vec<u8> {
+    let mut key = PREFIX_KEY.to_vec();
+    key.push(prefix_id);
+    key
+}
+
+pub struct StorageWriter<'a> {
+    buffer: Vec<u8>,
+    transaction: Transaction<'a>,
+    storage: &'a Storage,
+    prefixes: Arc<PrefixTable>,
+}
+
+impl<'a> StorageWriter<'a> {
+    /// Returns a reader over the storage as seen by this transaction.
+    ///
+    /// The reader sees the insertions and removals already done by this transaction, even if they are not committed yet,
+    /// on top of the snapshot taken when the transaction started.
+    /// Writes committed by other transactions after that are not visible.
+    pub fn reader(&self) -> StorageReader {
+        StorageReader {
+            reader: self.transaction.reader(),
+            storage: self.storage.clone(),
+            prefixes: Arc::clone(&self.prefixes),
+        }
+    }
+
+    // 和 reader 一样能看到本事务的写入，但是看到的是最新提交的数据，而不是事务开始时的快照
+    // 垃圾回收在锁住候选字符串之后用它判断字符串是否仍被使用
+    fn latest_reader(&self) -> StorageReader {
+        StorageReader {
+            reader: self.transaction.latest_reader(),
+            storage: self.storage.clone(),
+            prefixes: Arc::clone(&self.prefixes),
+        }
+    }
+
+    // 重点看了一下insert方法
+    // 元组和Term的插入都使用的是 Transaction 里的insert方法
+    pub fn insert(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
+        let encoded = self.prefixes.encode_quad(quad);   // type: EncodedQuad，IRI 使用注册的前缀编码
+        self.insert_encoded(quad, &encoded)
+    }
+
+    fn insert_encoded(&mut self, quad: QuadRef<'_>, encoded: &EncodedQuad) -> Result<bool, StorageError> {
+        // 不写入位置不合法的 term（例如作为主语的字面量），否则索引中会出现无法解析的 key
+        encoded.validate()?;
+        self.buffer.clear();
+
+        let result = if quad.graph_name.is_default_graph() {    // 如果是写入default graph，则只要spo pos osp
+            if self.insert_default_graph_indexes(encoded)? {
+                self.insert_term(quad.subject.into(), &encoded.subject)?;   // TermRef   EncodedTerm
+                self.insert_term(quad.predicate.into(), &encoded.predicate)?;
+                self.insert_term(quad.object, &encoded.object)?;
+                true
+            } else {
+                false
+            }
+        } else if self.insert_named_graph_indexes(encoded)? {
+            self.insert_term(quad.subject.into(), &encoded.subject)?;
+            self.insert_term(quad.predicate.into(), &encoded.predicate)?;
+            self.insert_term(quad.object, &encoded.object)?;
+
+            // 开始插入graphTerm
+            self.buffer.clear();
+            write_term(&mut self.buffer, &encoded.graph_name);
+            if !self
+                .transaction
+                .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+            {
+                self.transaction
+                    .insert_empty(&self.storage.graphs_cf, &self.buffer)?;   // 在graph的cf中插入，只有键没有值
+                self.insert_graph_name(quad.graph_name, &encoded.graph_name)?;// 在id2str中插入
+            }
+            true
+        } else {
+            false
+        };
+        if result {
+            self.storage
+                .notify_change(ChangeKind::Insert, || QuadOrGraph::Quad(quad.into_owned()));
+        }
+        Ok(result)
+    }
+
+    /// Overwrites the value stored with a default graph triple in the `dspo`, `dpos` and `dosp` indexes
+    /// and returns `false` without writing anything if the triple is not in the storage.
+    ///
+    /// It is meant to update the interval encodings written by [`StorageBulkLoader::load_oxiuse_value`]
+    /// without removing and inserting the triple again: [`insert`](Self::insert) always writes empty values.
+    /// It fails on a store loaded with [`StorageBulkLoader::load_oxiuse_key`], whose triples have another key.
+    pub fn update_triple_value(&mut self, triple: TripleRef<'_>, value: &[u8]) -> Result<bool, StorageError> {
+        self.ensure_plain_default_graph()?;
+        let encoded = self
+            .prefixes
+            .encode_quad(triple.in_graph(GraphNameRef::DefaultGraph));
+        self.buffer.clear();
+        write_spo_quad(&mut self.buffer, &encoded);
+        if !self
+            .transaction
+            .contains_key_for_update(&self.storage.dspo_cf, &self.buffer)?
+        {
+            return Ok(false);
+        }
+        self.transaction
+            .insert(&self.storage.dspo_cf, &self.buffer, value)?;
+
+        self.buffer.clear();
+        write_pos_quad(&mut self.buffer, &encoded);
+        self.transaction
+            .insert(&self.storage.dpos_cf, &self.buffer, value)?;
+
+        self.buffer.clear();
+        write_osp_quad(&mut self.buffer, &encoded);
+        self.transaction
+            .insert(&self.storage.dosp_cf, &self.buffer, value)?;
+        Ok(true)
+    }
+
+    // IntervalKey 布局的默认图索引中 key 以区间编码开头，而事务按 term 写入和查找 key，
+    // 会再写入一个相同三元组的 key 或者找不到要删除的三元组，所以拒绝这些写入
+    fn ensure_plain_default_graph(&self) -> Result<(), StorageError> {
+        if self.storage.interval_keys.load(Ordering::Relaxed) {
+            Err(StorageError::Other(
+                "The default graph of a store loaded with LoadMode::IntervalKey can only be written by the bulk loader".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    // 在默认图的三个索引中写入三元组（不写入字符串），三元组之前不存在时返回 true
+    fn insert_default_graph_indexes(&mut self, encoded: &EncodedQuad) -> Result<bool, StorageError> {
+        self.ensure_plain_default_graph()?;
+        self.buffer.clear();
+        write_spo_quad(&mut self.buffer, encoded);    // 使用 EcodedQuad 才能进行字节序列的编码以及写入buffer
+        if self.transaction
+            .contains_key_for_update(&self.storage.dspo_cf, &self.buffer)?  // 如果之前包含这个三元组，则进行更新，当得到的是false时，说明是新插入的元组
+        {
+            return Ok(false);
+        }
+        self.transaction
+            .insert_empty(&self.storage.dspo_cf, &self.buffer)?;  // 一个 buffer 绑定到一个列族
+
+        self.buffer.clear();
+        write_pos_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.dpos_cf, &self.buffer)?;
+
+        self.buffer.clear();
+        write_osp_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.dosp_cf, &self.buffer)?;
+        // 以上的代码是在对应的cf上插入 spo（或者其它顺序的）buffer 字节序列
+        Ok(true)
+    }
+
+    // 在命名图的六个索引中写入四元组（不写入字符串以及 graphs_cf），四元组之前不存在时返回 true
+    fn insert_named_graph_indexes(&mut self, encoded: &EncodedQuad) -> Result<bool, StorageError> {
+        self.buffer.clear();
+        write_spog_quad(&mut self.buffer, encoded);
+
+        if self.transaction
+            .contains_key_for_update(&self.storage.spog_cf, &self.buffer)?
+        {
+            return Ok(false);
+        }
+        self.transaction
+            .insert_empty(&self.storage.spog_cf, &self.buffer)?;
+
+        self.buffer.clear();
+        write_posg_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.posg_cf, &self.buffer)?;
+
+        self.buffer.clear();
+        write_ospg_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.ospg_cf, &self.buffer)?;
+
+        self.buffer.clear();
+        write_gspo_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.gspo_cf, &self.buffer)?;
+
+        self.buffer.clear();
+        write_gpos_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.gpos_cf, &self.buffer)?;
+
+        self.buffer.clear();
+        write_gosp_quad(&mut self.buffer, encoded);
+        self.transaction
+            .insert_empty(&self.storage.gosp_cf, &self.buffer)?;
+        Ok(true)
+    }
+
+    // 闭包可以捕获上下文中的值，insert_term方法中第三个参数是一个闭包，包括两个参数、一行闭包体
+    // 当以闭包作为输入参数时，必须指出闭包的完整类型，它是通过使用这三种 trait中的一种来指定的：Fn、FnMut、FnOnce
+    // 闭包可以作为参数传递过去，向 numeric_encoder.rs 文件的 insert_term方法传递闭包，并捕获从其中获得的参数值，接着调用闭包，执行闭包体
+    fn insert_term(
+        &mut self,
+        term: TermRef<'_>,
+        encoded: &EncodedTerm,
+    ) -> Result<(), StorageError> {
+        let prefixes = Arc::clone(&self.prefixes);
+        insert_term(term, encoded, &prefixes, &mut |key, value| {
+            self.insert_str(key, value)
+        })
+    }
+
+    // 通过事务往 id2str 中插入，并用 get_for_update 锁住这个 key，
+    // 这样并发的垃圾回收（remove_with_gc、collect_unused_strings）不会删除本事务正在使用的字符串
+    // SmallString不会往id2str中存
+    #[cfg(not(target_arch = "wasm32"))]
+    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
+        if let Some(existing) = self
+            .transaction
+            .get_for_update(&self.storage.id2str_cf, &self.storage.id2str_key(key))?
+        {
+            return if self.storage.detects_hash_collisions() {
+                check_str_collision(key, &existing, value.as_bytes())
+            } else {
+                Ok(())
+            };
+        }
+        self.transaction.insert(
+            &self.storage.id2str_cf,
+            &self.storage.id2str_key(key),  // 字节序列,StrHash里只包含一个u128类型的成员
+            value.as_bytes(),  // 字节序列
+        )
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
+        if self.storage.detects_hash_collisions() {
+            if let Some(existing) = self.transaction.reader().get(&self.storage.id2str_cf, &self.storage.id2str_key(key))? {
+                return check_str_collision(key, &existing, value.as_bytes());
+            }
+        }
+        self.transaction.insert(
+            &self.storage.id2str_cf,
+            &self.storage.id2str_key(key),
+            value.as_bytes(),
+        )
+    }
+
+    // TODO：这两个方法有什么不同
+    // 对 graph 进行插入
+    // 在 is2str上会插入
+    // graph的cf上也插入
+    pub fn insert_named_graph(
+        &mut self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<bool, StorageError> {
+        let encoded_graph_name = self.prefixes.encode_term(graph_name); // EnodedTerm
+
+        self.buffer.clear();
+        write_term(&mut self.buffer, &encoded_graph_name);
+
+        let result = if self.transaction
+            .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+        {
+            false
+        } else {
+            self.transaction
+                .insert_empty(&self.storage.graphs_cf, &self.buffer)?;
+            self.insert_term(graph_name.into(), &encoded_graph_name)?;
+            true
+        };
+        if result {
+            self.storage.notify_change(ChangeKind::Insert, || {
+                QuadOrGraph::Graph(graph_name.into_owned())
+            });
+        }
+        Ok(result)
+    }
+
+    // 前缀表写在 default_cf 中，已经注册过的前缀直接返回原来的编号
+    // 只能在存储为空时注册新的前缀，否则已经写入的 IRI 会有两种编码
+    fn register_prefix(&mut self, prefix: &str) -> Result<u8, StorageError> {
+        let prefixes = self.storage.load_prefixes(&self.transaction.reader())?;
+        if let Some(id) = prefixes.iter().position(|p| p == prefix) {
+            return Ok(id.try_into().unwrap());
+        }
+        if prefix.is_empty() {
+            return Err(StorageError::Other("The empty IRI prefix could not be registered".into()));
+        }
+        if prefixes.len() >= MAX_CUSTOM_PREFIXES {
+            return Err(StorageError::Other(
+                format!("At most {} IRI prefixes could be registered", MAX_CUSTOM_PREFIXES).into(),
+            ));
+        }
+        let reader = self.reader();
+        if !reader.is_empty()? || reader.named_graphs().next().is_some() {
+            return Err(StorageError::Other(
+                format!(
+                    "The IRI prefix {} could not be registered: the storage already contains quads or named graphs",
+                    prefix
+                )
+                .into(),
+            ));
+        }
+        let id = prefixes.len().try_into().unwrap();
+        self.transaction
+            .insert(&self.storage.default_cf, &prefix_key(id), prefix.as_bytes())?;
+        Ok(id)
+    }
+
+    // 将 graph的名字进行StrHash编码后在id2str上插入
+    // 使用的是Db中的插入方法
+    fn insert_graph_name(
+        &mut self,
+        graph_name: GraphNameRef<'_>,
+        encoded: &EncodedTerm,
+    ) -> Result<(), StorageError> {
+        match graph_name {
+            GraphNameRef::NamedNode(graph_name) => self.insert_term(graph_name.into(), encoded),
+            GraphNameRef::BlankNode(graph_name) => self.insert_term(graph_name.into(), encoded),
+            GraphNameRef::DefaultGraph => Ok(()),
+        }
+    }
+
+
+    // 移除三元组（四元组）
+    pub fn remove(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
+        let result = self.remove_encoded(&self.prefixes.encode_quad(quad))?;
+        if result {
+            self.storage
+                .notify_change(ChangeKind::Remove, || QuadOrGraph::Quad(quad.into_owned()));
+        }
+        Ok(result)
+    }
+
+    // 批量删除，返回实际存在并被删除的元组个数
+    // 与 remove 一样，即使删除了某个命名图的最后一个元组，graphs_cf 中的图也会保留，需要使用 remove_named_graph 删除
+    pub fn remove_many<'b, I: IntoIterator<Item = QuadRef<'b>>>(
+        &mut self,
+        quads: I,
+    ) -> Result<usize, StorageError> {
+        // 先删除默认图中的三元组，再删除命名图中的四元组，使同一个列族上的操作连续进行
+        let (triples, quads): (Vec<EncodedQuad>, Vec<EncodedQuad>) = quads
+            .into_iter()
+            .map(|quad| self.prefixes.encode_quad(quad))
+            .partition(|quad| quad.graph_name.is_default_graph());
+        let mut removed = 0;
+        for quad in triples.iter().chain(&quads) {
+            if self.remove_encoded(quad)? {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    // id2str上的term并未被删除；以及删除图时，图的str编码也未被删除
+    fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<bool, StorageError> {
+        self.buffer.clear();
+
+        let result = if quad.graph_name.is_default_graph() {
+            self.ensure_plain_default_graph()?;
+            write_spo_quad(&mut self.buffer, quad);
 
-        let result = if quad.graph_name.is_default_graph() {    // 如果是写入default graph，则只要spo pos osp
-            write_spo_quad(&mut self.buffer, &encoded);    // 使用 EcodedQuad 才能进行字节序列的编码以及写入buffer
             if self.transaction
-                .contains_key_for_update(&self.storage.dspo_cf, &self.buffer)?  // 如果之前包含这个三元组，则进行更新，当得到的是false时，说明是新插入的元组
+                .contains_key_for_update(&self.storage.dspo_cf, &self.buffer)?
+            {   // 存在之前的元组
+                self.transaction
+                    .remove(&self.storage.dspo_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_pos_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.dpos_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_osp_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.dosp_cf, &self.buffer)?;
+                true
+            } else {
+                false
+            }
+        } else {
+            write_spog_quad(&mut self.buffer, quad);
+
+            if self
+                .transaction
+                .contains_key_for_update(&self.storage.spog_cf, &self.buffer)?
             {
+                self.transaction
+                    .remove(&self.storage.spog_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_posg_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.posg_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_ospg_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.ospg_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_gspo_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.gspo_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_gpos_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.gpos_cf, &self.buffer)?;
+
+                self.buffer.clear();
+                write_gosp_quad(&mut self.buffer, quad);
+                self.transaction
+                    .remove(&self.storage.gosp_cf, &self.buffer)?;
+                true
+            } else {
                 false
+            }
+        };
+        Ok(result)
+    }
+
+    /// Removes a quad and the `id2str` strings of its subject, predicate and object if they are not used anymore.
+    ///
+    /// All the strings of the three terms are candidates: IRIs, blank node ids, literal values, datatypes,
+    /// language tags and the strings of quoted triples. A string is kept as soon as it is still used anywhere.
+    /// The whole terms are looked up in the indexes, but the strings only used inside literals or quoted triples
+    /// are found by scanning all the language-tagged strings, the literals with a custom datatype
+    /// and the quoted triples of the store, so it gets slower on stores with many of them.
+    ///
+    /// It is safe to run at the same time as other transactions: the candidate strings are locked before checking
+    /// if they are still used, and the transactions inserting a quad lock the strings it uses.
+    /// If one of these strings has been written since this transaction started, the transaction conflicts and is retried.
+    /// The bulk loaders do not take these locks, so they must not run at the same time.
+    pub fn remove_with_gc(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
+        let encoded = self.prefixes.encode_quad(quad);
+        if !self.remove_encoded(&encoded)? {
+            return Ok(false);
+        }
+        let mut candidates = HashSet::new();
+        for term in [&encoded.subject, &encoded.predicate, &encoded.object] {
+            for_each_str_hash(term, &mut |hash| {
+                candidates.insert(hash);
+            });
+        }
+        for hash in &candidates {
+            self.transaction
+                .contains_key_for_update(&self.storage.id2str_cf, &self.storage.id2str_key(hash))?;
+        }
+        for hash in self.unused_strings(candidates)? {
+            self.transaction
+                .remove(&self.storage.id2str_cf, &self.storage.id2str_key(&hash))?;
+        }
+        Ok(true)
+    }
+
+    // candidates 中已经不再被任何四元组使用的字符串
+    // 候选字符串必须已经被锁住，这里读取最新提交的数据，从而能看到并发事务刚刚插入的四元组
+    fn unused_strings(&self, candidates: HashSet<StrHash>) -> Result<HashSet<StrHash>, StorageError> {
+        let reader = self.latest_reader();
+        let prefix_count = self.prefixes.prefixes().len();
+        let mut unused = HashSet::new();
+        for hash in candidates {
+            // 同一个字符串可能同时被 IRI（包括带前缀的后缀）、空白节点和字符串字面量使用
+            let mut terms = vec![
+                EncodedTerm::NamedNode { iri_id: hash },
+                EncodedTerm::BigBlankNode { id_id: hash },
+                EncodedTerm::BigStringLiteral { value_id: hash },
+            ];
+            for prefix_id in 0..prefix_count {
+                terms.push(EncodedTerm::PrefixedNamedNode {
+                    prefix_id: prefix_id.try_into().unwrap(),
+                    suffix_id: hash,
+                });
+            }
+            let mut used = false;
+            for term in &terms {
+                if self.is_term_used(term)? {
+                    used = true;
+                    break;
+                }
+            }
+            if !used {
+                unused.insert(hash);
+            }
+        }
+        // 字面量的值、类型和语言标签以及引用三元组中的字符串不能通过索引前缀查找，只能扫描这些 term
+        if !unused.is_empty() {
+            let mut iters = Vec::new();
+            for kind in [ObjectKind::LangString, ObjectKind::OtherTypedLiteral, ObjectKind::Triple] {
+                iters.push(reader.quads_with_object_of_type(kind, None));
+            }
+            iters.push(reader.quads_with_subject_of_type(ObjectKind::Triple));
+            'scan: for iter in iters {
+                for quad in iter {
+                    let quad = quad?;
+                    for term in [&quad.subject, &quad.object] {
+                        for_each_str_hash(term, &mut |hash| {
+                            unused.remove(&hash);
+                        });
+                    }
+                    if unused.is_empty() {
+                        break 'scan;
+                    }
+                }
+            }
+        }
+        Ok(unused)
+    }
+
+    // 判断 term 是否仍然作为一个完整的 term 出现在某个索引中（主语、谓语、宾语或者图名）
+    fn is_term_used(&self, term: &EncodedTerm) -> Result<bool, StorageError> {
+        let reader = self.latest_reader();
+        Ok(reader.quads_for_subject(term).next().transpose()?.is_some()
+            || reader.quads_for_predicate(term).next().transpose()?.is_some()
+            || reader.quads_for_object(term).next().transpose()?.is_some()
+            || reader.contains_named_graph(term)?)
+    }
+
+    /// Removes from `id2str` all the strings that are not referenced by any quad or named graph anymore.
+    ///
+    /// It scans all the indexes twice and returns the number of removed strings.
+    ///
+    /// Like [`remove_with_gc`](Self::remove_with_gc), it locks the unused strings before checking again that they are not used,
+    /// so it is safe to run at the same time as other transactions but not at the same time as the bulk loaders.
+    pub fn collect_unused_strings(&mut self) -> Result<usize, StorageError> {
+        let used = self.used_str_keys()?;
+        let mut unused = Vec::new();
+        let mut iter = self.latest_reader().reader.iter(&self.storage.id2str_cf)?;
+        while let Some(key) = iter.key() {
+            let key = <[u8; 16]>::try_from(key).map_err(|_| {
+                CorruptionError::msg("Invalid key length in the id2str column family")
+            })?;
+            if !used.contains(&key) {
+                unused.push(key);
+            }
+            iter.next();
+        }
+        iter.status()?;
+
+        // 锁住之后再扫描一次，排除在第一次扫描之后被并发事务用到的字符串
+        for key in &unused {
+            self.transaction
+                .contains_key_for_update(&self.storage.id2str_cf, key)?;
+        }
+        let used = self.used_str_keys()?;
+        unused.retain(|key| !used.contains(key));
+
+        for key in &unused {
+            self.transaction.remove(&self.storage.id2str_cf, key)?;
+        }
+        Ok(unused.len())
+    }
+
+    // 所有四元组和命名图用到的 id2str key
+    // 加盐后的 key 无法还原为 StrHash，所以直接比较 id2str 中的 key
+    fn used_str_keys(&self) -> Result<HashSet<[u8; 16]>, StorageError> {
+        let reader = self.latest_reader();
+        let storage = self.storage;
+        let mut used = HashSet::new();
+        for quad in reader.quads() {
+            let quad = quad?;
+            for term in [&quad.subject, &quad.predicate, &quad.object, &quad.graph_name] {
+                for_each_str_hash(term, &mut |hash| {
+                    used.insert(storage.id2str_key(&hash));
+                });
+            }
+        }
+        for graph_name in reader.named_graphs() {
+            for_each_str_hash(&graph_name?, &mut |hash| {
+                used.insert(storage.id2str_key(&hash));
+            });
+        }
+        Ok(used)
+    }
+
+    /// Removes all the quads of a graph one by one inside the transaction.
+    ///
+    /// On big graphs [`Storage::clear_graph_by_range`] is much faster but it bypasses the transactions and the change listener.
+    pub fn clear_graph(&mut self, graph_name: GraphNameRef<'_>) -> Result<(), StorageError> {
+        if graph_name.is_default_graph() {
+            for quad in self.reader().quads_for_graph(&EncodedTerm::DefaultGraph) {
+                self.remove_encoded(&quad?)?;
+            }
+        } else {
+            let encoded_graph_name = self.prefixes.encode_graph_name(graph_name);
+            self.buffer.clear();
+            write_term(&mut self.buffer, &encoded_graph_name);
+            if self.transaction
+                .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+            {
+                // The condition is useful to lock the graph itself and ensure no quad is inserted at the same time
+                for quad in self.reader().quads_for_graph(&encoded_graph_name) {
+                    self.remove_encoded(&quad?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 清除所有 named_graph（即图上的元组）
+    pub fn clear_all_named_graphs(&mut self) -> Result<(), StorageError> {
+        for quad in self.reader().quads_in_named_graph() {
+            self.remove_encoded(&quad?)?;
+        }
+        Ok(())
+    }
+
+    // 清除所有graph（即图上的元组）
+    pub fn clear_all_graphs(&mut self) -> Result<(), StorageError> {
+        for quad in self.reader().quads() {
+            self.remove_encoded(&quad?)?;
+        }
+        Ok(())
+    }
+
+    // 不仅删除图上的三元组，也将图在 graph_cf 上清除
+    pub fn remove_named_graph(
+        &mut self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<bool, StorageError> {
+        let result = self.remove_encoded_named_graph(&self.prefixes.encode_term(graph_name))?;
+        if result {
+            self.storage.notify_change(ChangeKind::Remove, || {
+                QuadOrGraph::Graph(graph_name.into_owned())
+            });
+        }
+        Ok(result)
+    }
+
+    /// Removes the named graph registration in `graphs` without touching any quad and returns `true` if it was registered.
+    ///
+    /// Fails if the graph still contains quads: use [`clear_graph`](Self::clear_graph) first
+    /// or [`remove_named_graph`](Self::remove_named_graph) to do both.
+    pub fn unregister_named_graph(
+        &mut self,
+        graph_name: NamedOrBlankNodeRef<'_>,
+    ) -> Result<bool, StorageError> {
+        let encoded_graph_name = self.prefixes.encode_term(graph_name);
+        self.buffer.clear();
+        write_term(&mut self.buffer, &encoded_graph_name);
+        if !self
+            .transaction
+            .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+        {
+            return Ok(false);
+        }
+        if !self.reader().graph_is_empty(graph_name)? {
+            return Err(StorageError::Other(
+                format!("The graph {} still contains quads and could not be unregistered", graph_name).into(),
+            ));
+        }
+        self.buffer.clear();
+        write_term(&mut self.buffer, &encoded_graph_name);
+        self.transaction
+            .remove(&self.storage.graphs_cf, &self.buffer)?;
+        self.storage.notify_change(ChangeKind::Remove, || {
+            QuadOrGraph::Graph(graph_name.into_owned())
+        });
+        Ok(true)
+    }
+
+    /// Moves all the quads of the named graph `from` into the named graph `to` and returns the number of moved quads.
+    ///
+    /// Quads already present in `to` are not duplicated. `to` is created if needed and `from` is removed.
+    /// Renaming a graph to itself does nothing.
+    /// The change listener is notified of each inserted and removed quad and of the removal of `from`.
+    pub fn rename_graph(
+        &mut self,
+        from: NamedOrBlankNodeRef<'_>,
+        to: NamedOrBlankNodeRef<'_>,
+    ) -> Result<usize, StorageError> {
+        if from == to {
+            return Ok(0);
+        }
+        let encoded_from = self.prefixes.encode_term(from);
+        let encoded_to = self.prefixes.encode_term(to);
+        self.insert_named_graph(to)?;
+
+        // 元组中的字符串已经在 id2str 中，只需要写入索引
+        let mut moved = 0;
+        for quad in self.reader().quads_for_graph(&encoded_from) {
+            let quad = quad?;
+            let renamed = EncodedQuad::new(
+                quad.subject.clone(),
+                quad.predicate.clone(),
+                quad.object.clone(),
+                encoded_to.clone(),
+            );
+            if self.insert_named_graph_indexes(&renamed)? {
+                self.notify_encoded_change(ChangeKind::Insert, &renamed)?;
+            }
+            self.remove_encoded(&quad)?;
+            self.notify_encoded_change(ChangeKind::Remove, &quad)?;
+            moved += 1;
+        }
+        if self.remove_encoded_named_graph(&encoded_from)? {
+            self.storage.notify_change(ChangeKind::Remove, || {
+                QuadOrGraph::Graph(from.into_owned())
+            });
+        }
+        Ok(moved)
+    }
+
+    /// Copies all the quads of the graph `from` into the graph `to` and returns the number of copied quads.
+    ///
+    /// `from` is left unchanged and `to` is created if it is a named graph that does not exist yet.
+    /// Quads already present in `to` are not duplicated nor counted.
+    /// The change listener is notified of each inserted quad.
+    pub fn copy_graph(
+        &mut self,
+        from: GraphNameRef<'_>,
+        to: GraphNameRef<'_>,
+    ) -> Result<usize, StorageError> {
+        if from == to {
+            return Ok(0);
+        }
+        let encoded_from = self.prefixes.encode_graph_name(from);
+        let encoded_to = self.prefixes.encode_graph_name(to);
+        match to {
+            GraphNameRef::NamedNode(to) => {
+                self.insert_named_graph(to.into())?;
+            }
+            GraphNameRef::BlankNode(to) => {
+                self.insert_named_graph(to.into())?;
+            }
+            GraphNameRef::DefaultGraph => (),
+        }
+
+        // 元组中的字符串已经在 id2str 中，只需要按目标图写入对应的索引
+        let mut copied = 0;
+        for quad in self.reader().quads_for_graph(&encoded_from) {
+            let quad = quad?;
+            let copy = EncodedQuad::new(quad.subject, quad.predicate, quad.object, encoded_to.clone());
+            let inserted = if copy.graph_name.is_default_graph() {
+                self.insert_default_graph_indexes(&copy)?
+            } else {
+                self.insert_named_graph_indexes(&copy)?
+            };
+            if inserted {
+                self.notify_encoded_change(ChangeKind::Insert, &copy)?;
+                copied += 1;
+            }
+        }
+        Ok(copied)
+    }
+
+    // 复制和重命名图时直接写索引，没有原来的 QuadRef，只有设置了监听器时才解码元组
+    fn notify_encoded_change(&self, kind: ChangeKind, quad: &EncodedQuad) -> Result<(), StorageError> {
+        if self.storage.has_change_listener() {
+            let quad = self.reader().decode_quad(quad)?;
+            self.storage
+                .notify_change(kind, || QuadOrGraph::Quad(quad));
+        }
+        Ok(())
+    }
+
+    /// Replaces all the triples of the graph `graph_name` with `triples` inside the current transaction.
+    ///
+    /// The old quads are removed inside the transaction,
+    /// so the other readers see either the old or the new content of the graph, never a partial one.
+    /// A named graph stays registered even if `triples` is empty.
+    pub fn replace_graph<'b>(
+        &mut self,
+        graph_name: GraphNameRef<'_>,
+        triples: impl IntoIterator<Item = TripleRef<'b>>,
+    ) -> Result<(), StorageError> {
+        let encoded_graph_name = self.prefixes.encode_graph_name(graph_name);
+        // 同时锁住 graphs 中的图名，避免其他事务并发写入该图
+        match graph_name {
+            GraphNameRef::NamedNode(graph_name) => {
+                self.insert_named_graph(graph_name.into())?;
+            }
+            GraphNameRef::BlankNode(graph_name) => {
+                self.insert_named_graph(graph_name.into())?;
+            }
+            GraphNameRef::DefaultGraph => (),
+        }
+        for quad in self.reader().quads_for_graph(&encoded_graph_name) {
+            self.remove_encoded(&quad?)?;
+        }
+        for triple in triples {
+            self.insert(triple.in_graph(graph_name))?;
+        }
+        Ok(())
+    }
+
+    // 移除给定的 named_graph
+    // 不仅删除图上的三元组，也将图在 graph_cf 上清除
+    fn remove_encoded_named_graph(
+        &mut self,
+        graph_name: &EncodedTerm,
+    ) -> Result<bool, StorageError> {
+        self.buffer.clear();
+        write_term(&mut self.buffer, graph_name);
+
+        let result = if self
+            .transaction
+            .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+        {
+            // The condition is done ASAP to lock the graph itself
+            for quad in self.reader().quads_for_graph(graph_name) {
+                self.remove_encoded(&quad?)?;
+            }
+            self.buffer.clear();
+            write_term(&mut self.buffer, graph_name);
+            self.transaction
+                .remove(&self.storage.graphs_cf, &self.buffer)?;
+            true
+        } else {
+            false
+        };
+        Ok(result)
+    }
+
+    // 移除所有的 named_graph
+    // 不仅删除图上的三元组，也将图在 graph_cf 上清除
+    pub fn remove_all_named_graphs(&mut self) -> Result<(), StorageError> {
+        for graph_name in self.reader().named_graphs() {
+            self.remove_encoded_named_graph(&graph_name?)?;
+        }
+        Ok(())
+    }
+
+    // 清除所有元组（包括默认图以及命名图）
+    // 并清除 graph_cf
+    pub fn clear(&mut self) -> Result<(), StorageError> {
+        for graph_name in self.reader().named_graphs() {
+            self.remove_encoded_named_graph(&graph_name?)?;
+        }
+        for quad in self.reader().quads() {
+            self.remove_encoded(&quad?)?;
+        }
+        Ok(())
+    }
+}
+
+
+/// How [`StorageBulkLoader`] writes the default graph indexes (`dspo`, `dpos` and `dosp`).
+///
+/// The interval modes read the class and property trees from the N-Triples file at the given path
+/// on the first load. The encoded trees and the path are then saved in the storage and the trees are reused
+/// by the later loads, so that all the stored interval encodings share the same numbering.
+/// A later load with a different path fails instead of ignoring the hierarchy of the new file.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LoadMode<'a> {
+    /// Keys only, with empty values.
+    Plain,
+    /// The interval encoding is stored as the value of each key.
+    IntervalValue(&'a str),
+    /// The interval encoding is prepended to each key.
+    ///
+    /// The keys then do not start with the terms anymore: every default graph pattern scans the whole `dspo`, `dpos`
+    /// or `dosp` index, so the cost of a lookup grows with the number of triples in the default graph.
+    /// The default graph of such a store can not be written by a [`StorageWriter`] anymore, only by the bulk loader.
+    IntervalKey(&'a str),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'a> LoadMode<'a> {
+    fn tree_path(self) -> Option<&'a str> {
+        match self {
+            Self::Plain => None,
+            Self::IntervalValue(path) | Self::IntervalKey(path) => Some(path),
+        }
+    }
+
+    fn encoding_mode(self) -> TripleEncodingMode {
+        match self {
+            Self::Plain => TripleEncodingMode::Plain,
+            Self::IntervalValue(_) => TripleEncodingMode::IntervalValue,
+            Self::IntervalKey(_) => TripleEncodingMode::IntervalKey,
+        }
+    }
+}
+
+/// The layout of the default graph indexes written by a [`LoadMode`], recorded in the store by its first bulk load.
+///
+/// The layouts can not be mixed in the same store: [`StorageBulkLoader`] rejects a load with another layout.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TripleEncodingMode {
+    /// See [`LoadMode::Plain`].
+    Plain,
+    /// See [`LoadMode::IntervalValue`].
+    IntervalValue,
+    /// See [`LoadMode::IntervalKey`].
+    IntervalKey,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TripleEncodingMode {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Plain => 0,
+            Self::IntervalValue => 1,
+            Self::IntervalKey => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Plain),
+            1 => Some(Self::IntervalValue),
+            2 => Some(Self::IntervalKey),
+            _ => None,
+        }
+    }
+}
+
+// 将四元组中的空白节点（包括引用三元组中的）替换为作用域内的空白节点
+#[cfg(not(target_arch = "wasm32"))]
+fn scope_quad_blank_nodes(quad: &Quad, scope: &str) -> Quad {
+    Quad {
+        subject: scope_subject_blank_nodes(&quad.subject, scope),
+        predicate: quad.predicate.clone(),
+        object: scope_term_blank_nodes(&quad.object, scope),
+        graph_name: match &quad.graph_name {
+            GraphName::BlankNode(graph_name) => scope_blank_node(graph_name, scope).into(),
+            graph_name => graph_name.clone(),
+        },
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn scope_triple_blank_nodes(triple: &Triple, scope: &str) -> Triple {
+    Triple {
+        subject: scope_subject_blank_nodes(&triple.subject, scope),
+        predicate: triple.predicate.clone(),
+        object: scope_term_blank_nodes(&triple.object, scope),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn scope_subject_blank_nodes(subject: &Subject, scope: &str) -> Subject {
+    match subject {
+        Subject::NamedNode(subject) => subject.clone().into(),
+        Subject::BlankNode(subject) => scope_blank_node(subject, scope).into(),
+        Subject::Triple(subject) => scope_triple_blank_nodes(subject, scope).into(),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn scope_term_blank_nodes(term: &Term, scope: &str) -> Term {
+    match term {
+        Term::NamedNode(term) => term.clone().into(),
+        Term::BlankNode(term) => scope_blank_node(term, scope).into(),
+        Term::Literal(term) => term.clone().into(),
+        Term::Triple(term) => scope_triple_blank_nodes(term, scope).into(),
+    }
+}
+
+// 新的空白节点由作用域和原标签的哈希得到，作用域的长度放在最前面，避免 ("a", "bc") 与 ("ab", "c") 冲突
+#[cfg(not(target_arch = "wasm32"))]
+fn scope_blank_node(node: &BlankNode, scope: &str) -> BlankNode {
+    BlankNode::new_from_unique_id(
+        StrHash::new(&format!("{}:{}{}", scope.len(), scope, node.as_str())).get_hash_u128(),
+    )
+}
+
+/// Progress of a [`StorageBulkLoader`], reported to [`StorageBulkLoader::on_progress_detailed`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct LoadProgress {
+    /// The number of loaded quads.
+    pub quads: u64,
+    /// The number of `id2str` entries written. A string used by several batches is counted once per batch.
+    pub id2str_entries: u64,
+    /// The size in bytes of the SST files ingested into the storage.
+    pub sst_bytes: u64,
+}
+
+// 加载线程共享的计数器
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct LoadCounters {
+    quads: AtomicU64,
+    id2str_entries: AtomicU64,
+    sst_bytes: AtomicU64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LoadCounters {
+    // quads 最后更新：读到新的 quads 时，其余两个计数器也已经包含了这个批次
+    fn add(&self, progress: LoadProgress) {
+        self.id2str_entries.fetch_add(progress.id2str_entries, Ordering::Relaxed);
+        self.sst_bytes.fetch_add(progress.sst_bytes, Ordering::Relaxed);
+        self.quads.fetch_add(progress.quads, Ordering::Release);
+    }
+}
+
+// 在 store.rs 中用到了
+#[cfg(not(target_arch = "wasm32"))]
+pub struct StorageBulkLoader {
+    storage: Storage,
+    hooks: Vec<Box<dyn Fn(u64)>>,
+    detailed_hooks: Vec<Box<dyn Fn(LoadProgress)>>,
+    num_threads: Option<usize>,
+    max_memory_size: Option<usize>,
+    batch_size: Option<usize>,
+    spill_id2str: bool,
+    blank_node_scope: Option<String>,
+    flush_threshold: Option<usize>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageBulkLoader {
+    pub fn new(storage: Storage) -> Self {
+        Self {
+            storage,
+            hooks: Vec::new(),
+            detailed_hooks: Vec::new(),
+            num_threads: None,
+            max_memory_size: None,
+            batch_size: None,
+            spill_id2str: false,
+            blank_node_scope: None,
+            flush_threshold: None,
+        }
+    }
+
+    pub fn set_num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    pub fn set_max_memory_size_in_megabytes(mut self, max_memory_size: usize) -> Self {
+        self.max_memory_size = Some(max_memory_size);
+        self
+    }
+
+    /// Sets the number of quads of each batch, overriding the size computed from the available memory.
+    ///
+    /// It is clamped to at most 100M quads.
+    pub fn set_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Writes the strings of a batch to sorted temporary files as soon as they exceed half of the memory
+    /// allowed to the batch, instead of keeping all of them in memory until the batch is saved.
+    ///
+    /// Useful for datasets with many long literals.
+    pub fn spill_id2str_to_disk(mut self) -> Self {
+        self.spill_id2str = true;
+        self
+    }
+
+    /// Writes the SST files of a batch as soon as it holds `flush_threshold` distinct quads,
+    /// then goes on encoding the rest of the batch.
+    ///
+    /// The peak memory of a batch is then bounded by the threshold and not by the batch size,
+    /// at the cost of more and smaller SST files. By default a batch is written at once.
+    pub fn set_flush_threshold(mut self, flush_threshold: usize) -> Self {
+        self.flush_threshold = Some(flush_threshold);
+        self
+    }
+
+    /// Makes the blank nodes of the loaded quads local to `scope`, e.g. the name of the loaded file.
+    ///
+    /// Each blank node is replaced by a blank node derived from `scope` and its label,
+    /// so the same label loaded with two different scopes gives two different blank nodes.
+    /// By default there is no scope: blank nodes with the same label are merged, even if they come from different loads.
+    pub fn set_blank_node_scope(mut self, scope: impl Into<String>) -> Self {
+        self.blank_node_scope = Some(scope.into());
+        self
+    }
+
+    pub fn on_progress(mut self, callback: impl Fn(u64) + 'static) -> Self {
+        self.hooks.push(Box::new(callback));
+        self
+    }
+
+    /// Same as [`on_progress`](Self::on_progress) but also reports the number of written strings
+    /// and the size of the ingested SST files.
+    pub fn on_progress_detailed(mut self, callback: impl Fn(LoadProgress) + 'static) -> Self {
+        self.detailed_hooks.push(Box::new(callback));
+        self
+    }
+
+    pub fn load<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
+        &self,
+        quads: I,
+    ) -> Result<(), EO> {
+        self.load_with_mode(quads, LoadMode::Plain)
+    }
+
+    // ############################## 将区间编码加入value中 ##############################
+    pub fn load_oxiuse_value<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
+        &self,
+        quads: I,
+        tree_path: &str,
+    ) -> Result<(), EO> {
+        self.load_with_mode(quads, LoadMode::IntervalValue(tree_path))
+    }
+
+    // ############################## 将区间编码加入key中 ##############################
+    pub fn load_oxiuse_key<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
+        &self,
+        quads: I,
+        tree_path: &str,
+    ) -> Result<(), EO> {
+        self.load_with_mode(quads, LoadMode::IntervalKey(tree_path))
+    }
+
+    /// Loads the quads using the given [`LoadMode`] for the default graph indexes.
+    pub fn load_with_mode<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
+        &self,
+        quads: I,
+        mode: LoadMode<'_>,
+    ) -> Result<(), EO> {
+        self.load_batches::<EI, EO, _>(quads, mode)?;
+        Ok(())
+    }
+
+    /// Loads quads that are already encoded, for example by a pipeline sharing its string dictionary between shards.
+    ///
+    /// The quads are written to the indexes without being decoded and re-encoded.
+    /// The strings referenced by the quads are read from `id2str` and merged into the storage strings.
+    /// A string missing from `id2str` makes the load fail before anything is written.
+    pub fn load_encoded(
+        &self,
+        quads: impl IntoIterator<Item = EncodedQuad>,
+        id2str: &HashMap<StrHash, Box<str>>,
+    ) -> Result<(), StorageError> {
+        self.storage.ensure_triple_encoding_mode(TripleEncodingMode::Plain)?;
+        let counter = AtomicU64::new(0);
+        FileBulkLoader::new(self.storage.clone()).load_encoded(quads, id2str, &counter)?;
+        let count = counter.load(Ordering::Relaxed);
+        for hook in &self.hooks {
+            hook(count);
+        }
+        Ok(())
+    }
+
+    // 返回启动的加载线程的统计（包括最后一个可能为空的批次）
+    fn load_batches<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
+        &self,
+        quads: I,
+        mode: LoadMode<'_>,
+    ) -> Result<LoadThreadStats, EO> {
+        let system = System::new_all();
+        let cpu_count = min(4, system.physical_core_count().unwrap_or(2));
+        let num_threads = max(
+            if let Some(num_threads) = self.num_threads {
+                num_threads
+            } else if let Some(max_memory_size) = self.max_memory_size {
+                min(
+                    cpu_count,
+                    max_memory_size * 1000 / DEFAULT_BULK_LOAD_BATCH_SIZE,
+                )
+            } else {
+                cpu_count
+            },
+            2,
+        );
+        let batch_size = self.batch_size(num_threads, system.free_memory());
+        // 每个批次的字符串最多占用该批次一半的内存
+        let id2str_spill_size = self.spill_id2str.then(|| {
+            self.max_memory_size.map_or(DEFAULT_ID2STR_SPILL_SIZE, |max_memory_size| {
+                max_memory_size * 1_000_000 / num_threads / 2
+            })
+        });
+        let mut threads = VecDeque::with_capacity(num_threads - 1);
+        let mut buffer = Vec::with_capacity(batch_size);
+        let done_counter = Arc::new(LoadCounters::default());
+        let mut done_and_displayed_counter = 0;
+        let mut stats = LoadThreadStats::default();
+        let encoding_mode = mode.encoding_mode();
+        self.storage.ensure_triple_encoding_mode(encoding_mode)?;
+        // 在启动加载线程之前准备好树，只读取一次，所有加载线程共享同一个 Arc
+        // 每个批次只在自己的集合中去重：所有批次使用同一组树，同一个三元组在不同批次中得到相同的 key 和 value，
+        // 写入两个 SST 之后读到哪一个都一样，compaction 时会合并
+        let trees = match mode.tree_path() {
+            Some(path) => Some(Arc::new(self.ensure_interval_trees(path)?)),
+            None => None,
+        };
+
+        for quad in quads {
+            let quad = quad?;
+            buffer.push(match &self.blank_node_scope {
+                Some(scope) => scope_quad_blank_nodes(&quad, scope),
+                None => quad,
+            });    // 其中是Quad
+            if buffer.len() >= batch_size {
+                self.spawn_load_thread(
+                    &mut buffer,
+                    &mut threads,
+                    &mut stats,
+                    &done_counter,
+                    &mut done_and_displayed_counter,
+                    num_threads,
+                    encoding_mode,
+                    trees.clone(),
+                    id2str_spill_size,
+                )?;
+            }
+        }
+        self.spawn_load_thread(
+            &mut buffer,
+            &mut threads,
+            &mut stats,
+            &done_counter,
+            &mut done_and_displayed_counter,
+            num_threads,
+            encoding_mode,
+            trees,
+            id2str_spill_size,
+        )?;
+        for thread in threads {
+            thread.join().unwrap()?;
+            self.on_possible_progress(&done_counter, &mut done_and_displayed_counter);
+        }
+        Ok(stats)
+    }
+
+    // 每个批次的四元组数量，不超过 MAX_BULK_LOAD_BATCH_SIZE
+    // 无法获得内存信息时（free_memory 为 0，例如在某些容器中）或者 free_memory 超出 usize 时使用默认批次大小
+    fn batch_size(&self, num_threads: usize, free_memory: u64) -> usize {
+        min(
+            if let Some(batch_size) = self.batch_size {
+                max(1, batch_size)
+            } else if let Some(max_memory_size) = self.max_memory_size {
+                max(1000, max_memory_size * 1000 / num_threads)
+            } else {
+                usize::try_from(free_memory).map_or(DEFAULT_BULK_LOAD_BATCH_SIZE, |free_memory| {
+                    max(free_memory / num_threads, DEFAULT_BULK_LOAD_BATCH_SIZE)
+                })
+            },
+            MAX_BULK_LOAD_BATCH_SIZE,
+        )
+    }
+
+    // 已经保存过由同一个文件构造的树时直接复用，没有保存过时从文件构造并保存树和路径
+    // 保存的树来自另一个文件时返回错误，否则新文件中的层次会被静默忽略
+    fn ensure_interval_trees(&self, path: &str) -> Result<(MultiTree, MultiTree), StorageError> {
+        let trees = match self.storage.interval_trees()? {
+            Some(trees) => trees,
+            None => {
+                let trees = FileBulkLoader::new(self.storage.clone()).construct_tree(path)?;
+                self.storage.set_interval_trees(&trees)?;
+                self.storage.set_interval_tree_path(path)?;
+                return Ok(trees);
+            }
+        };
+        match self.storage.interval_tree_path()? {
+            Some(saved_path) if saved_path != path => Err(StorageError::Other(
+                format!(
+                    "The interval trees of this store have been built from {}, they can not be rebuilt from {}",
+                    saved_path, path
+                )
+                .into(),
+            )),
+            Some(_) => Ok(trees),
+            // 路径记录之前保存的树：记录本次的路径，之后的加载都要使用同一个文件
+            None => {
+                self.storage.set_interval_tree_path(path)?;
+                Ok(trees)
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_load_thread(
+        &self,
+        buffer: &mut Vec<Quad>,
+        threads: &mut VecDeque<JoinHandle<Result<(), StorageError>>>,
+        stats: &mut LoadThreadStats,
+        done_counter: &Arc<LoadCounters>,
+        done_and_displayed_counter: &mut u64,
+        num_threads: usize,
+        mode: TripleEncodingMode,
+        trees: Option<Arc<(MultiTree, MultiTree)>>,
+        id2str_spill_size: Option<usize>,
+    ) -> Result<(), StorageError> {
+        self.on_possible_progress(done_counter, done_and_displayed_counter);
+        // We avoid to have too many threads
+        if threads.len() >= num_threads {
+            if let Some(thread) = threads.pop_front() {
+                thread.join().unwrap()?;
+                self.on_possible_progress(done_counter, done_and_displayed_counter);
+            }
+        }
+        // 为了在线程之中安全转移，之后传递的是vec
+        let buffer = take(buffer);
+        let mut loader = FileBulkLoader::new(self.storage.clone());
+        if let Some(id2str_spill_size) = id2str_spill_size {
+            loader = loader.with_id2str_spill(id2str_spill_size);
+        }
+        if let Some(flush_threshold) = self.flush_threshold {
+            loader = loader.with_flush_threshold(flush_threshold);
+        }
+        let done_counter_clone = done_counter.clone();
+        // move会将所有权丢给线程
+        threads.push_back(spawn(move || loader.load(buffer, &done_counter_clone, mode, trees.as_deref())));
+        stats.spawned += 1;
+        stats.max_in_flight = max(stats.max_in_flight, threads.len());
+        self.on_possible_progress(done_counter, done_and_displayed_counter);
+        Ok(())
+    }
+
+    fn on_possible_progress(&self, done: &LoadCounters, done_and_displayed: &mut u64) {
+        let new_counter = done.quads.fetch_max(*done_and_displayed, Ordering::AcqRel);
+        let display_step = u64::try_from(DEFAULT_BULK_LOAD_BATCH_SIZE).unwrap();
+        if new_counter % display_step > *done_and_displayed % display_step {
+            for hook in &self.hooks {
+                hook(new_counter);
+            }
+            let progress = LoadProgress {
+                quads: new_counter,
+                id2str_entries: done.id2str_entries.load(Ordering::Relaxed),
+                sst_bytes: done.sst_bytes.load(Ordering::Relaxed),
+            };
+            for hook in &self.detailed_hooks {
+                hook(progress);
+            }
+        }
+        *done_and_displayed = new_counter;
+    }
+}
+
+
+
+// 批次中缓存的 IRI 数，超过后清空缓存
+#[cfg(not(target_arch = "wasm32"))]
+const IRI_CACHE_SIZE: usize = 65_536;
+
+// 区间编码函数使用的 s/p/o 映射
+#[cfg(not(target_arch = "wasm32"))]
+fn triple_term_map(quad: &EncodedQuad) -> HashMap<&str, &EncodedTerm> {
+    let mut map = HashMap::new();
+    map.insert("s", &quad.subject);
+    map.insert("p", &quad.predicate);
+    map.insert("o", &quad.object);
+    map
+}
+
+// 一次加载启动的线程数，以及同时未结束（尚未 join）的线程数的最大值
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default, Debug, Clone, Copy)]
+struct LoadThreadStats {
+    spawned: usize,
+    max_in_flight: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct FileBulkLoader {
+    storage: Storage,
+    id2str: HashMap<StrHash, Box<str>>,
+    id2str_size: usize,   // id2str 中 key 与字符串的总字节数
+    id2str_spill_size: Option<usize>,
+    id2str_runs: SpilledRuns,
+    quads: HashSet<EncodedQuad>,
+    triples: HashSet<EncodedQuad>,
+    graphs: HashSet<EncodedTerm>,
+    prefixes: Arc<PrefixTable>,
+    iri_cache: Option<HashMap<StrHash, EncodedTerm>>,   // IRI 的 StrHash -> 使用前缀的编码，同一个 IRI 重复出现时不再查找前缀树
+    prefixed_iris: HashMap<EncodedTerm, StrHash>,   // 批次中使用前缀编码的 IRI -> 完整 IRI 的 StrHash，区间编码用它在树中查找
+    flush_threshold: Option<usize>,   // 批次内 triples 与 quads 的总数达到该值时先写入一组 SST
+}
+
+// 溢写到磁盘的有序 id2str 临时文件，drop 时删除
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct SpilledRuns(Vec<PathBuf>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for SpilledRuns {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+// 删除以 prefix 开头的所有 key：[prefix, prefix + 0xFF) 使用一次范围删除，剩下以 prefix + 0xFF 开头的 key 逐条删除
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_prefix(
+    batch: &mut WriteBatch,
+    reader: &Reader,
+    column_family: &ColumnFamily,
+    prefix: &[u8],
+) -> Result<(), StorageError> {
+    let mut end = prefix.to_vec();
+    end.push(u8::MAX);
+    batch.remove_range(column_family, prefix, &end);
+    let mut iter = reader.scan_prefix(column_family, &end)?;
+    while let Some(key) = iter.key() {
+        batch.remove(column_family, key);
+        iter.next();
+    }
+    iter.status()
+}
+
+// 命名图索引（gspo、gpos、gosp）中以 graph_name 和 terms 开头的 key 前缀
+// 默认图的编码为空，用作前缀会匹配所有命名图中的四元组，所以默认图只能使用 d* 索引
+fn named_graph_prefix(graph_name: &EncodedTerm, terms: &[&EncodedTerm]) -> Vec<u8> {
+    assert!(
+        !graph_name.is_default_graph(),
+        "The default graph has no key prefix in the named graph indexes"
+    );
+    let mut prefix = Vec::with_capacity((terms.len() + 1) * WRITTEN_TERM_MAX_SIZE);
+    write_term(&mut prefix, graph_name);
+    for term in terms {
+        write_term(&mut prefix, term);
+    }
+    prefix
+}
+
+// 所有以 prefix 开头的 key 之后的第一个 key，prefix 全为 0xFF 时不存在
+fn next_prefix(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut next = prefix.to_vec();
+    while let Some(last) = next.pop() {
+        if last < u8::MAX {
+            next.push(last + 1);
+            return Some(next);
+        }
+    }
+    None
+}
+
+// id2str 中的值不是合法的 UTF-8 时，错误信息中给出 key 和值的前几个字节，方便定位损坏的数据
+fn decode_id2str_value(key: &StrHash, value: Vec<u8>) -> Result<String, StorageError> {
+    String::from_utf8(value).map_err(|e| {
+        let bytes = e.as_bytes();
+        CorruptionError::msg(format!(
+            "The id2str value for key {} is not valid UTF-8 ({}), it starts with bytes {}",
+            hex::encode(key.to_be_bytes()),
+            e.utf8_error(),
+            hex::encode(&bytes[..min(bytes.len(), 16)])
+        ))
+        .into()
+    })
+}
+
+// 同一个 StrHash 下已存储的字符串与新字符串不同，说明发生了哈希冲突
+fn check_str_collision(key: &StrHash, existing: &[u8], value: &[u8]) -> Result<(), StorageError> {
+    if existing == value {
+        Ok(())
+    } else {
+        Err(CorruptionError::msg(format!(
+            "StrHash collision on {:?}: {:?} is already stored and {:?} has the same hash",
+            key,
+            String::from_utf8_lossy(existing),
+            String::from_utf8_lossy(value)
+        ))
+        .into())
+    }
+}
+
+// 读取溢写文件中的下一条记录：key(16 字节) + 字符串长度(u32) + 字符串
+#[cfg(not(target_arch = "wasm32"))]
+fn read_id2str_record(reader: &mut impl BufRead) -> io::Result<Option<([u8; 16], Vec<u8>)>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut key = [0; 16];
+    reader.read_exact(&mut key)?;
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let mut value = vec![0; u32::from_be_bytes(len).try_into().unwrap()];
+    reader.read_exact(&mut value)?;
+    Ok(Some((key, value)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileBulkLoader {
+    fn new(storage: Storage) -> Self {
+        Self {
+            prefixes: storage.prefix_table(),
+            storage,
+            id2str: HashMap::default(),
+            id2str_size: 0,
+            id2str_spill_size: None,
+            id2str_runs: SpilledRuns::default(),
+            quads: HashSet::default(),
+            triples: HashSet::default(),
+            graphs: HashSet::default(),
+            iri_cache: Some(HashMap::default()),
+            prefixed_iris: HashMap::default(),
+            flush_threshold: None,
+        }
+    }
+
+    // 限制批次在内存中的三元组与四元组数量，超过时先保存已编码的部分
+    fn with_flush_threshold(mut self, flush_threshold: usize) -> Self {
+        self.flush_threshold = Some(max(1, flush_threshold));
+        self
+    }
+
+    // id2str 超过给定字节数时排序并写入临时文件，save 时再归并成一个 SST
+    fn with_id2str_spill(mut self, max_size: usize) -> Self {
+        self.id2str_spill_size = Some(max_size);
+        self
+    }
+
+    // 每次都在前缀树中查找 IRI，用于检查缓存不改变编码
+    #[cfg(test)]
+    fn without_iri_cache(mut self) -> Self {
+        self.iri_cache = None;
+        self
+    }
+
+    fn load(
+        &mut self,
+        quads: impl IntoIterator<Item = Quad>,
+        counter: &LoadCounters,
+        mode: TripleEncodingMode,
+        trees: Option<&(MultiTree, MultiTree)>,
+    ) -> Result<(), StorageError> {
+        // 区间编码使用 StorageBulkLoader 准备好的树
+        if mode != TripleEncodingMode::Plain && trees.is_none() {
+            return Err(StorageError::Other("The interval trees have not been prepared before the load".into()));
+        }
+
+        // 设置了 flush_threshold 时一个批次可能分多次保存，每次都会写入一组 SST
+        let mut quads = quads.into_iter().peekable();
+        loop {
+            self.encode_until(&mut quads, self.flush_threshold)?;   // 该方法主要是获得self的id2str hashmap
+
+            let size = self.triples.len() + self.quads.len();
+
+            let mut progress = self.save(mode, trees)?;
+            progress.quads = size.try_into().unwrap();
+
+            counter.add(progress);
+            if quads.peek().is_none() {
+                return Ok(());
+            }
+        }
+    }
+
+    // 跳过编码，直接使用调用者提供的 EncodedQuad 和字符串字典构造 SST
+    fn load_encoded(
+        &mut self,
+        quads: impl IntoIterator<Item = EncodedQuad>,
+        id2str: &HashMap<StrHash, Box<str>>,
+        counter: &AtomicU64,
+    ) -> Result<(), StorageError> {
+        for quad in quads {
+            quad.validate()?;
+            let is_new = if quad.graph_name.is_default_graph() {
+                self.triples.insert(quad.clone())
             } else {
-                self.transaction
-                    .insert_empty(&self.storage.dspo_cf, &self.buffer)?;  // 一个 buffer 绑定到一个列族
+                self.graphs.insert(quad.graph_name.clone());
+                self.quads.insert(quad.clone())
+            };
+            if is_new {
+                // 只合并被引用的字符串，字典可能由多个分片共享
+                for term in [&quad.subject, &quad.predicate, &quad.object, &quad.graph_name] {
+                    let mut keys = Vec::new();
+                    for_each_str_hash(term, &mut |key| keys.push(key));
+                    for key in keys {
+                        let value = id2str.get(&key).ok_or_else(|| {
+                            StorageError::Other(
+                                format!("The string with hash {:?} is missing from the given dictionary", key)
+                                    .into(),
+                            )
+                        })?;
+                        self.insert_str(&key, value)?;
+                    }
+                }
+            }
+        }
+
+        let size = self.triples.len() + self.quads.len();
+        self.save(TripleEncodingMode::Plain, None)?;
+        counter.fetch_add(size.try_into().unwrap(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    // 该方法主要是获得self的id2str hashmap
+    fn encode(&mut self, quads: impl IntoIterator<Item = Quad>) -> Result<(), StorageError> {
+        self.encode_until(&mut quads.into_iter(), None)
+    }
+
+    // 编码直到三元组与四元组的总数达到 max_len（未编码的四元组留在迭代器中）或者迭代器耗尽
+    fn encode_until(
+        &mut self,
+        quads: &mut impl Iterator<Item = Quad>,
+        max_len: Option<usize>,
+    ) -> Result<(), StorageError> {
+        let mut previous: Option<EncodedQuad> = None;
+        while max_len.map_or(true, |max_len| self.triples.len() + self.quads.len() < max_len) {
+            let quad = match quads.next() {
+                Some(quad) => quad,
+                None => break,
+            };
+            let encoded = self.encode_quad(quad.as_ref());   // 转成EncodedQuad，由EcodedTerm组成
+            // 连续重复的四元组（排好序的数据中很常见）不需要再查询去重集合
+            if previous.as_ref() == Some(&encoded) {
+                continue;
+            }
+            encoded.validate()?;
+            if quad.graph_name.is_default_graph() {
+                if self.triples.insert(encoded.clone()) {   // 先在自己的triples中插入EncodedQuad，然后将spo传入insert_term方法（不会重复插入）
+                    self.insert_term(quad.subject.as_ref().into(), &encoded.subject)?;
+                    self.insert_term(quad.predicate.as_ref().into(), &encoded.predicate)?;
+                    self.insert_term(quad.object.as_ref(), &encoded.object)?;
+                }
+            } else if self.quads.insert(encoded.clone()) {
+                self.insert_term(quad.subject.as_ref().into(), &encoded.subject)?;
+                self.insert_term(quad.predicate.as_ref().into(), &encoded.predicate)?;
+                self.insert_term(quad.object.as_ref(), &encoded.object)?;
+
+                if self.graphs.insert(encoded.graph_name.clone()) {
+                    self.insert_term(
+                        match quad.graph_name.as_ref() {
+                            GraphNameRef::NamedNode(n) => n.into(),
+                            GraphNameRef::BlankNode(n) => n.into(),
+                            GraphNameRef::DefaultGraph => unreachable!(),
+                        },
+                        &encoded.graph_name,
+                    )?;
+                }
+            }
+            previous = Some(encoded);
+        }
+        Ok(())
+    }
+
+    // 与 PrefixTable::encode_quad 相同，只是 IRI 的编码从缓存中读取
+    fn encode_quad(&mut self, quad: QuadRef<'_>) -> EncodedQuad {
+        EncodedQuad::new(
+            match quad.subject {
+                SubjectRef::NamedNode(node) => self.encode_named_node(node),
+                subject => self.prefixes.encode_term(subject),
+            },
+            self.encode_named_node(quad.predicate),
+            match quad.object {
+                TermRef::NamedNode(node) => self.encode_named_node(node),
+                object => self.prefixes.encode_term(object),
+            },
+            match quad.graph_name {
+                GraphNameRef::NamedNode(node) => self.encode_named_node(node),
+                graph_name => graph_name.into(),
+            },
+        )
+    }
+
+    // 没有前缀时编码就是 IRI 的 StrHash，不需要缓存
+    fn encode_named_node(&mut self, node: NamedNodeRef<'_>) -> EncodedTerm {
+        if self.prefixes.is_empty() {
+            return self.prefixes.encode_named_node(node);
+        }
+        let iri_id = StrHash::new(node.as_str());
+        let encoded = match &mut self.iri_cache {
+            Some(cache) => {
+                if let Some(encoded) = cache.get(&iri_id) {
+                    encoded.clone()
+                } else {
+                    let encoded = self.prefixes.encode_named_node(node);
+                    if cache.len() >= IRI_CACHE_SIZE {
+                        cache.clear();
+                    }
+                    cache.insert(iri_id, encoded.clone());
+                    encoded
+                }
+            }
+            None => self.prefixes.encode_named_node(node),
+        };
+        if let EncodedTerm::PrefixedNamedNode { .. } = encoded {
+            self.prefixed_iris.insert(encoded.clone(), iri_id);
+        }
+        encoded
+    }
+
+    // 命名节点完整 IRI 的 StrHash，使用前缀编码的 IRI 在编码时已经记录
+    fn iri_hash(&self, term: &EncodedTerm) -> Result<Option<StrHash>, StorageError> {
+        match term {
+            EncodedTerm::NamedNode { iri_id } => Ok(Some(*iri_id)),
+            EncodedTerm::PrefixedNamedNode { .. } => self.prefixed_iris.get(term).copied().map(Some).ok_or_else(|| {
+                StorageError::Other(format!("The whole IRI of {:?} has not been recorded during the encoding", term).into())
+            }),
+            _ => Ok(None),
+        }
+    }
+
+    fn save(
+        &mut self,
+        mode: TripleEncodingMode,
+        trees: Option<&(MultiTree, MultiTree)>,
+    ) -> Result<LoadProgress, StorageError> {
+        let mut to_load = Vec::new();
+        let mut progress = LoadProgress::default();
+
+        // id2str
+        if !self.id2str_runs.0.is_empty() {
+            self.spill_id2str()?;
+            let (id2str_sst, id2str_entries) = self.merge_id2str_runs()?;
+            progress.id2str_entries = id2str_entries;
+            to_load.push((&self.storage.id2str_cf, id2str_sst));
+        } else if !self.id2str.is_empty() {
+            let mut id2str = take(&mut self.id2str)
+                .into_iter()
+                .map(|(k, v)| (self.storage.id2str_key(&k), v))
+                .collect::<Vec<_>>();
+            id2str.sort_unstable();
+            self.id2str_size = 0;
+            progress.id2str_entries = id2str.len().try_into().unwrap();
+            let mut id2str_sst = self.storage.db.new_sst_file()?;
+            for (k, v) in id2str {
+                id2str_sst.insert(&k, v.as_bytes())?;
+            }
+            to_load.push((&self.storage.id2str_cf, id2str_sst.finish()?));
+        }
+
+        // 默认图的三个索引，只有这里会因为加载模式不同而不同
+        if !self.triples.is_empty() {
+            to_load.push((
+                &self.storage.dspo_cf,
+                self.build_sst_for_triples(QuadEncoding::Dspo, mode, trees)?,
+            ));
+            to_load.push((
+                &self.storage.dpos_cf,
+                self.build_sst_for_triples(QuadEncoding::Dpos, mode, trees)?,
+            ));
+            to_load.push((
+                &self.storage.dosp_cf,
+                self.build_sst_for_triples(QuadEncoding::Dosp, mode, trees)?,
+            ));
+            self.triples.clear();
+        }
+        self.prefixed_iris.clear();
+
+        if !self.quads.is_empty() {
+            to_load.push((
+                &self.storage.graphs_cf,
+                self.build_sst_for_keys(self.graphs.iter().map(encode_term))?,
+            ));
+            self.graphs.clear();
+
+            to_load.push((
+                &self.storage.gspo_cf,
+                self.build_sst_for_keys(self.quads.iter().map(|quad| {
+                    encode_term_quad(
+                        &quad.graph_name,
+                        &quad.subject,
+                        &quad.predicate,
+                        &quad.object,
+                    )
+                }))?,
+            ));
+            to_load.push((
+                &self.storage.gpos_cf,
+                self.build_sst_for_keys(self.quads.iter().map(|quad| {
+                    encode_term_quad(
+                        &quad.graph_name,
+                        &quad.predicate,
+                        &quad.object,
+                        &quad.subject,
+                    )
+                }))?,
+            ));
+            to_load.push((
+                &self.storage.gosp_cf,
+                self.build_sst_for_keys(self.quads.iter().map(|quad| {
+                    encode_term_quad(
+                        &quad.graph_name,
+                        &quad.object,
+                        &quad.subject,
+                        &quad.predicate,
+                    )
+                }))?,
+            ));
+            to_load.push((
+                &self.storage.spog_cf,
+                self.build_sst_for_keys(self.quads.iter().map(|quad| {
+                    encode_term_quad(
+                        &quad.subject,
+                        &quad.predicate,
+                        &quad.object,
+                        &quad.graph_name,
+                    )
+                }))?,
+            ));
+            to_load.push((
+                &self.storage.posg_cf,
+                self.build_sst_for_keys(self.quads.iter().map(|quad| {
+                    encode_term_quad(
+                        &quad.predicate,
+                        &quad.object,
+                        &quad.subject,
+                        &quad.graph_name,
+                    )
+                }))?,
+            ));
+            to_load.push((
+                &self.storage.ospg_cf,
+                self.build_sst_for_keys(self.quads.iter().map(|quad| {
+                    encode_term_quad(
+                        &quad.object,
+                        &quad.subject,
+                        &quad.predicate,
+                        &quad.graph_name,
+                    )
+                }))?,
+            ));
+            self.quads.clear();
+        }
+
+        for (_, path) in &to_load {
+            progress.sst_bytes += path.metadata()?.len();
+        }
+        self.storage.db.insert_stt_files(&to_load)?;
+        Ok(progress)
+    }
+
+    // 按照加载模式编码默认图中的三元组：
+    // Plain 只写 key，IntervalValue 将区间编码写在 value 中，IntervalKey 将区间编码作为 key 的前缀
+    fn build_sst_for_triples(
+        &self,
+        encoding: QuadEncoding,
+        mode: TripleEncodingMode,
+        trees: Option<&(MultiTree, MultiTree)>,
+    ) -> Result<SstFile, StorageError> {
+        let iri_hash = |term: &EncodedTerm| self.iri_hash(term);
+        match (mode, trees) {
+            (TripleEncodingMode::IntervalValue, Some(trees)) => {
+                let entries = self
+                    .triples
+                    .iter()
+                    .map(|quad| {
+                        let map = triple_term_map(quad);
+                        match encoding {
+                            QuadEncoding::Dspo => encode_term_triple_oxiuse_value_spo(map, trees, &iri_hash),
+                            QuadEncoding::Dpos => encode_term_triple_oxiuse_value_pos(map, trees, &iri_hash),
+                            _ => encode_term_triple_oxiuse_value_osp(map, trees, &iri_hash),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.build_sst_for_key_values(entries.into_iter())
+            }
+            (TripleEncodingMode::IntervalKey, Some(trees)) => {
+                let keys = self
+                    .triples
+                    .iter()
+                    .map(|quad| {
+                        let map = triple_term_map(quad);
+                        match encoding {
+                            QuadEncoding::Dspo => encode_term_triple_oxiuse_key_spo(map, trees, &iri_hash),
+                            QuadEncoding::Dpos => encode_term_triple_oxiuse_key_pos(map, trees, &iri_hash),
+                            _ => encode_term_triple_oxiuse_key_osp(map, trees, &iri_hash),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.build_sst_for_keys(keys.into_iter())
+            }
+            (TripleEncodingMode::IntervalValue | TripleEncodingMode::IntervalKey, None) => Err(StorageError::Other(
+                "The interval trees are required to write the interval encodings".into(),
+            )),
+            (TripleEncodingMode::Plain, _) => self.build_sst_for_keys(self.triples.iter().map(|quad| match encoding {
+                QuadEncoding::Dspo => {
+                    encode_term_triple(&quad.subject, &quad.predicate, &quad.object)
+                }
+                QuadEncoding::Dpos => {
+                    encode_term_triple(&quad.predicate, &quad.object, &quad.subject)
+                }
+                _ => encode_term_triple(&quad.object, &quad.subject, &quad.predicate),
+            })),
+        }
+    }
+
+    fn build_sst_for_keys(
+        &self,
+        values: impl Iterator<Item = Vec<u8>>,
+    ) -> Result<SstFile, StorageError> {
+        let mut values = values.collect::<Vec<_>>();
+        values.sort_unstable();
+
+        let mut sst = self.storage.db.new_sst_file()?;
+
+        for value in values {  
+            sst.insert_empty(&value)?;
+        }
+
+
+        sst.finish()   // 不用看了
+    }
+
+    // 使用insert_key_value()，对key、value进行插入
+    fn build_sst_for_key_values(
+        &self,
+        values: impl Iterator<Item = (Vec<u8>, Vec<u8>)>,
+    ) -> Result<SstFile, StorageError> {
+        let mut values = values.collect::<Vec<_>>();
+        values.sort_unstable();
+
+        let mut sst = self.storage.db.new_sst_file()?;
+
+        for value in values {
+            sst.insert_key_value(&value.0, &value.1)?;
+        }
+
+        sst.finish()
+    }
+
+    fn insert_term(   // insert_term将获得NamedNode中的str以及对应的EncodedTerm中的StrHash，插入到自己的id2str hashmap中（这部分应该是不用修改的）
+        &mut self,
+        term: TermRef<'_>,
+        encoded: &EncodedTerm,
+    ) -> Result<(), StorageError> {
+        let prefixes = Arc::clone(&self.prefixes);
+        insert_term(term, encoded, &prefixes, &mut |key, value| {
+            self.insert_str(key, value)
+        })
+    }
+
+    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
+        let detect_collisions = self.storage.detects_hash_collisions();
+        match self.id2str.entry(*key) {
+            Entry::Vacant(entry) => {
+                if detect_collisions {
+                    if let Some(existing) = self.storage.db.get(&self.storage.id2str_cf, &self.storage.id2str_key(key))? {
+                        check_str_collision(key, &existing, value.as_bytes())?;
+                    }
+                }
+                entry.insert(value.into());
+                self.id2str_size += size_of::<StrHash>() + value.len();
+            }
+            Entry::Occupied(entry) => {
+                if detect_collisions {
+                    check_str_collision(key, entry.get().as_bytes(), value.as_bytes())?;
+                }
+            }
+        }
+        if self.id2str_spill_size.map_or(false, |max_size| self.id2str_size > max_size) {
+            self.spill_id2str()?;
+        }
+        Ok(())
+    }
+
+    // 将内存中的 id2str 按 key 排序后写入一个新的临时文件
+    // 同一个字符串可能出现在多个文件中，归并时去重
+    fn spill_id2str(&mut self) -> Result<(), StorageError> {
+        if self.id2str.is_empty() {
+            return Ok(());
+        }
+        let mut id2str = take(&mut self.id2str)
+            .into_iter()
+            .map(|(k, v)| (self.storage.id2str_key(&k), v))
+            .collect::<Vec<_>>();
+        id2str.sort_unstable();
+        self.id2str_size = 0;
+
+        let path = std::env::temp_dir().join(format!("oxigraph-id2str-{}", rand::random::<u128>()));
+        let mut file = BufWriter::new(File::create(&path)?);
+        self.id2str_runs.0.push(path);
+        for (k, v) in id2str {
+            let len = u32::try_from(v.len()).map_err(|_| {
+                StorageError::Other(format!("The string {} is too long to be stored", v).into())
+            })?;
+            file.write_all(&k)?;
+            file.write_all(&len.to_be_bytes())?;
+            file.write_all(v.as_bytes())?;
+        }
+        file.flush()?;
+        Ok(())
+    }
 
-                self.buffer.clear();
-                write_pos_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.dpos_cf, &self.buffer)?;
+    // 多路归并所有溢写文件，生成 id2str 的 SST，重复的 key 只写入一次
+    // 返回 SST 的路径以及写入的条目数
+    fn merge_id2str_runs(&mut self) -> Result<(SstFile, u64), StorageError> {
+        let mut runs = self
+            .id2str_runs
+            .0
+            .iter()
+            .map(|path| Ok(BufReader::new(File::open(path)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut heap = BinaryHeap::new();
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some((key, value)) = read_id2str_record(run)? {
+                heap.push(Reverse((key, i, value)));
+            }
+        }
 
-                self.buffer.clear();
-                write_osp_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.dosp_cf, &self.buffer)?;
-                // 以上的代码是在对应的cf上插入 spo（或者其它顺序的）buffer 字节序列
+        let mut sst = self.storage.db.new_sst_file()?;
+        let detect_collisions = self.storage.detects_hash_collisions();
+        let mut last: Option<([u8; 16], Vec<u8>)> = None;
+        let mut entries = 0;
+        while let Some(Reverse((key, i, value))) = heap.pop() {
+            match &last {
+                Some((last_key, last_value)) if *last_key == key => {
+                    if detect_collisions {
+                        check_str_collision(&StrHash::from_be_bytes(key), last_value, &value)?;
+                    }
+                }
+                _ => {
+                    sst.insert(&key, &value)?;
+                    last = Some((key, value));
+                    entries += 1;
+                }
+            }
+            if let Some((key, value)) = read_id2str_record(&mut runs[i])? {
+                heap.push(Reverse((key, i, value)));
+            }
+        }
+        self.id2str_runs = SpilledRuns::default();
+        Ok((sst.finish()?, entries))
+    }
 
-                self.insert_term(quad.subject.into(), &encoded.subject)?;   // TermRef   EncodedTerm
-                self.insert_term(quad.predicate.into(), &encoded.predicate)?;
-                self.insert_term(quad.object, &encoded.object)?;
-                true
+
+
+    // 构造Class树和属性树（已更新）
+    // 只解析谓词为 subClassOf、subOrganizationOf 或 subPropertyOf 的行，再交给 construct_tree_from_quads
+    pub fn construct_tree(&self, path: &str) -> Result<(MultiTree, MultiTree), StorageError> {
+        let mut quads = Vec::new();
+        for line in self.read_lines(path)? {
+            if let Ok(triple) = line {
+                let vec: Vec<&str> = triple.split(' ').collect();
+                if vec.len() < 3 {
+                    continue;
+                }
+                let p = &vec[1][1..vec[1].len()-1];
+                if p == rdfs::SUB_CLASS_OF || p == lubm::SUB_ORGANIZATION || p == rdfs::SUB_PROPERTY_OF {
+                    let s = &vec[0][1..vec[0].len()-1];
+                    let o = &vec[2][1..vec[2].len()-1];
+                    quads.push(Quad::new(
+                        NamedNode::new_unchecked(s),
+                        NamedNode::new_unchecked(p),
+                        NamedNode::new_unchecked(o),
+                        GraphName::DefaultGraph,
+                    ));
+                }
             }
-        } else {
-            write_spog_quad(&mut self.buffer, &encoded);
+        }
+        self.construct_tree_from_quads(quads)
+    }
 
-            if self.transaction
-                .contains_key_for_update(&self.storage.spog_cf, &self.buffer)?
-            {
-                false
+    // 从内存中的四元组构造Class树和属性树，不需要先写成 N-Triples 文件
+    pub fn construct_tree_from_quads<I: IntoIterator<Item = Quad>>(
+        &self,
+        quads: I,
+    ) -> Result<(MultiTree, MultiTree), StorageError> {
+        let classTree = MultiTree::new(owl::OWL_CLASS);
+        let propertyTree = MultiTree::new(rdf::PROPERTY);
+
+        for quad in quads {
+            let s = match &quad.subject {
+                Subject::NamedNode(s) => s.as_str(),
+                _ => continue,
+            };
+            let o = match &quad.object {
+                Term::NamedNode(o) => o.as_str(),
+                _ => continue,
+            };
+            let p = quad.predicate.as_str();
+            let tree = if p == rdfs::SUB_CLASS_OF || p == lubm::SUB_ORGANIZATION {
+                &classTree
+            } else if p == rdfs::SUB_PROPERTY_OF {
+                &propertyTree
             } else {
-                self.transaction
-                    .insert_empty(&self.storage.spog_cf, &self.buffer)?;
+                continue;
+            };
+            // 自反的 subClassOf / subPropertyOf 是 RDFS 推理的正常结果，不影响层次结构
+            if s == o {
+                continue;
+            }
+            if tree.creates_cycle(s, o) {
+                return Err(StorageError::Other(
+                    format!("<{}> <{}> <{}> introduces a cycle in the hierarchy", s, p, o).into(),
+                ));
+            }
+            tree.insert(s, o);
+        }
 
-                self.buffer.clear();
-                write_posg_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.posg_cf, &self.buffer)?;
+        classTree.encode();
+        propertyTree.encode();
 
-                self.buffer.clear();
-                write_ospg_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.ospg_cf, &self.buffer)?;
+        Ok((classTree, propertyTree))
+    }
 
-                self.buffer.clear();
-                write_gspo_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.gspo_cf, &self.buffer)?;
+    fn read_lines<P>(&self, filename: P) -> io::Result<io::Lines<io::BufReader<File>>> where P: AsRef<Path>, {
+        let file = File::open(filename)?;
+        Ok(io::BufReader::new(file).lines())
+    }
+}
 
-                self.buffer.clear();
-                write_gpos_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.gpos_cf, &self.buffer)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::DatasetParser;
+    use crate::model::*;
 
-                self.buffer.clear();
-                write_gosp_quad(&mut self.buffer, &encoded);
-                self.transaction
-                    .insert_empty(&self.storage.gosp_cf, &self.buffer)?;
+    fn ex(name: &str) -> NamedNode {
+        NamedNode::new_unchecked(format!("http://example.com/{}", name))
+    }
 
-                self.insert_term(quad.subject.into(), &encoded.subject)?;
-                self.insert_term(quad.predicate.into(), &encoded.predicate)?;
-                self.insert_term(quad.object, &encoded.object)?;
+    // 加载模式使用的层次结构文件，drop 时删除，断言失败时也不会留下临时文件
+    struct TreeFile(std::path::PathBuf);
 
-                // 开始插入graphTerm
-                self.buffer.clear();
-                write_term(&mut self.buffer, &encoded.graph_name);
-                if !self
-                    .transaction
-                    .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
-                {
-                    self.transaction
-                        .insert_empty(&self.storage.graphs_cf, &self.buffer)?;   // 在graph的cf中插入，只有键没有值
-                    self.insert_graph_name(quad.graph_name, &encoded.graph_name)?;// 在id2str中插入
-                }
-                true
+    impl TreeFile {
+        fn new(content: &str) -> io::Result<Self> {
+            let path = std::env::temp_dir().join(format!("oxigraph-tree-{}.nt", rand::random::<u128>()));
+            std::fs::write(&path, content)?;
+            Ok(Self(path))
+        }
+
+        // 每个类是前一个类的子类，第一个类是 owl:Class 的子类
+        fn with_classes(classes: &[&str]) -> io::Result<Self> {
+            let mut parent = owl::OWL_CLASS.to_owned();
+            let mut content = String::new();
+            for class in classes {
+                let class = ex(class);
+                content.push_str(&format!("{} <{}> <{}> .\n", class, rdfs::SUB_CLASS_OF, parent));
+                parent = class.into_string();
             }
-        };
-        Ok(result)
-    }
+            Self::new(&content)
+        }
 
-    // 闭包可以捕获上下文中的值，insert_term方法中第三个参数是一个闭包，包括两个参数、一行闭包体
-    // 当以闭包作为输入参数时，必须指出闭包的完整类型，它是通过使用这三种 trait中的一种来指定的：Fn、FnMut、FnOnce
-    // 闭包可以作为参数传递过去，向 numeric_encoder.rs 文件的 insert_term方法传递闭包，并捕获从其中获得的参数值，接着调用闭包，执行闭包体
-    fn insert_term(
-        &mut self,
-        term: TermRef<'_>,
-        encoded: &EncodedTerm,
-    ) -> Result<(), StorageError> {
-        insert_term(term, encoded, &mut |key, value| self.insert_str(key, value))
+        fn from_quads(quads: &[Quad]) -> io::Result<Self> {
+            Self::new(&quads.iter().map(|q| format!("{} .\n", Triple::from(q.clone()))).collect::<String>())
+        }
+
+        fn path(&self) -> &str {
+            self.0.to_str().unwrap()
+        }
     }
 
-    // 统一会调用 Db 中的insert方法，往 id2str 中插入
-    // SmallString不会往id2str中存
-    #[cfg(not(target_arch = "wasm32"))]
-    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
-        if self
-            .storage
-            .db
-            .contains_key(&self.storage.id2str_cf, &key.to_be_bytes())?
-        {
-            return Ok(());
+    impl Drop for TreeFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
         }
-        self.storage.db.insert(
-            &self.storage.id2str_cf,
-            &key.to_be_bytes(),  // 字节序列,StrHash里只包含一个u128类型的成员
-            value.as_bytes(),  // 字节序列
-        )
     }
 
-    #[cfg(target_arch = "wasm32")]
-    fn insert_str(&mut self, key: &StrHash, value: &str) -> Result<(), StorageError> {
-        self.transaction.insert(
-            &self.storage.id2str_cf,
-            &key.to_be_bytes(),
-            value.as_bytes(),
-        )
+    // 通过存储的前缀表和 id2str 得到命名节点完整 IRI 的 StrHash
+    fn storage_iri_hash(storage: &Storage) -> impl Fn(&EncodedTerm) -> Result<Option<StrHash>, StorageError> {
+        let reader = storage.snapshot();
+        move |term| iri_hash(term, &reader)
     }
 
-    // TODO：这两个方法有什么不同
-    // 对 graph 进行插入
-    // 在 is2str上会插入
-    // graph的cf上也插入
-    pub fn insert_named_graph(
-        &mut self,
-        graph_name: NamedOrBlankNodeRef<'_>,
-    ) -> Result<bool, StorageError> {
-        let encoded_graph_name = graph_name.into(); // EnodedTerm
+    fn insert_all(storage: &Storage, quads: &[Quad]) -> Result<(), StorageError> {
+        storage.transaction(|mut writer| {
+            for quad in quads {
+                writer.insert(quad.as_ref())?;
+            }
+            Ok::<_, StorageError>(())
+        })
+    }
 
-        self.buffer.clear();
-        write_term(&mut self.buffer, &encoded_graph_name);
+    #[test]
+    fn test_quads_for_pattern_in() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s"), ex("p2"), ex("o"), ex("g1")),
+                Quad::new(ex("s"), ex("p1"), ex("o"), ex("g2")),
+                Quad::new(ex("s"), ex("p1"), ex("o"), GraphName::DefaultGraph),
+                Quad::new(ex("s2"), ex("p1"), ex("o"), ex("g1")),
+            ],
+        )?;
+        let reader = storage.snapshot();
+        let subject = EncodedTerm::from(ex("s").as_ref());
+
+        let expected = reader
+            .quads_for_pattern(Some(&subject), None, None, None)
+            .collect::<Result<HashSet<_>, _>>()?;
+        let forced = reader
+            .quads_for_pattern_in(QuadEncoding::Spog, Some(&subject), None, None, None)?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(forced.len(), 3);
+        assert_eq!(forced.iter().cloned().collect::<HashSet<_>>(), expected);
+        let named_keys = forced
+            .iter()
+            .filter(|q| !q.graph_name.is_default_graph())
+            .map(|q| encode_term_quad(&q.subject, &q.predicate, &q.object, &q.graph_name))
+            .collect::<Vec<_>>();
+        let mut sorted_keys = named_keys.clone();
+        sorted_keys.sort();
+        assert_eq!(named_keys, sorted_keys);
+
+        assert!(reader
+            .quads_for_pattern_in(QuadEncoding::Gosp, Some(&subject), None, None, None)
+            .is_err());
+
+        // 默认图不在 spog 的 key 中，所以只绑定主语和默认图时扫描 dspo
+        let default_graph = reader
+            .quads_for_pattern_in(QuadEncoding::Spog, Some(&subject), None, None, Some(&EncodedTerm::DefaultGraph))?
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            default_graph,
+            vec![EncodedQuad::from(Quad::new(ex("s"), ex("p1"), ex("o"), GraphName::DefaultGraph).as_ref())]
+        );
+        Ok(())
+    }
 
-        let result = if self.transaction
-            .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
+    #[test]
+    fn test_flush_named_graph_quads() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
         {
-            false
-        } else {
-            self.transaction
-                .insert_empty(&self.storage.graphs_cf, &self.buffer)?;
-            self.insert_term(graph_name.into(), &encoded_graph_name)?;
-            true
+            let storage = Storage::open(&path)?;
+            insert_all(&storage, &[quad.clone()])?;
+            // 重新打开时会重放 WAL，只检查重新打开后的内容发现不了没有 flush 的列族，所以直接检查 SST 文件
+            storage.flush_cf(StorageColumnFamily::Gspo)?;
+            let usage = storage.disk_usage()?;
+            assert!(usage[GSPO_CF] > 0);
+            assert_eq!(usage[GPOS_CF], 0);
+            storage.flush()?;
+            let usage = storage.disk_usage()?;
+            for which in [
+                StorageColumnFamily::Id2str,
+                StorageColumnFamily::Spog,
+                StorageColumnFamily::Posg,
+                StorageColumnFamily::Ospg,
+                StorageColumnFamily::Gspo,
+                StorageColumnFamily::Gpos,
+                StorageColumnFamily::Gosp,
+                StorageColumnFamily::Graphs,
+            ] {
+                assert!(usage[which.name()] > 0, "{} has not been flushed", which.name());
+            }
+        }
+        {
+            let storage = Storage::open(&path)?;
+            let encoded = EncodedQuad::from(quad.as_ref());
+            let found = storage
+                .snapshot()
+                .gspo_quads(&[])
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(found, vec![encoded]);
+        }
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_backup_under_concurrent_writes() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let backup_path = std::env::temp_dir().join(format!("oxigraph-backup-{}", rand::random::<u128>()));
+        let storage = Storage::open(&path)?;
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let storage = storage.clone();
+            let done = Arc::clone(&done);
+            spawn(move || {
+                let mut i = 0;
+                while !done.load(Ordering::SeqCst) {
+                    insert_all(
+                        &storage,
+                        &[
+                            Quad::new(ex("s"), ex("p"), Literal::from(i), ex("g")),
+                            Quad::new(ex("s"), ex("p"), Literal::from(i), GraphName::DefaultGraph),
+                        ],
+                    )?;
+                    i += 1;
+                }
+                Ok::<_, StorageError>(i)
+            })
         };
-        Ok(result)
+        while storage.snapshot().len()? < 100 {
+            std::thread::yield_now();
+        }
+        storage.backup(&backup_path)?;
+        done.store(true, Ordering::SeqCst);
+        let written = writer.join().unwrap()?;
+        drop(storage);
+        {
+            let backup = Storage::open(&backup_path)?;
+            let reader = backup.snapshot();
+            reader.validate()?;
+            // 每个事务写入的两个四元组要么都在备份中，要么都不在
+            let len = reader.len()?;
+            assert!(len >= 100 && len % 2 == 0, "{}", len);
+            assert!(len <= 2 * usize::try_from(written).unwrap());
+            assert_eq!(
+                reader.len_for_graph(GraphNameRef::DefaultGraph)?,
+                reader.len_for_graph(ex("g").as_ref().into())?
+            );
+        }
+        for path in [path, backup_path] {
+            std::fs::remove_dir_all(&path)?;
+        }
+        Ok(())
     }
 
-    // 将 graph的名字进行StrHash编码后在id2str上插入
-    // 使用的是Db中的插入方法
-    fn insert_graph_name(
-        &mut self,
-        graph_name: GraphNameRef<'_>,
-        encoded: &EncodedTerm,
-    ) -> Result<(), StorageError> {
-        match graph_name {
-            GraphNameRef::NamedNode(graph_name) => self.insert_term(graph_name.into(), encoded),
-            GraphNameRef::BlankNode(graph_name) => self.insert_term(graph_name.into(), encoded),
-            GraphNameRef::DefaultGraph => Ok(()),
+    #[test]
+    #[cfg(not(target_family = "wasm"))]
+    fn test_backup_incremental() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let backup_path = std::env::temp_dir().join(format!("oxigraph-backup-{}", rand::random::<u128>()));
+        let restore_path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let quads = (0..1000)
+            .map(|i| Quad::new(ex("s"), ex("p"), Literal::from(format!("a literal that is not inlined {}", i)), ex("g")))
+            .collect::<Vec<_>>();
+        let last = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let (first, second) = {
+            let storage = Storage::open(&path)?;
+            insert_all(&storage, &quads)?;
+            let first = storage.backup_incremental(&backup_path)?;
+            insert_all(&storage, &[last.clone()])?;
+            let second = storage.backup_incremental(&backup_path)?;
+            (first, second)
+        };
+        assert!(second.backup_id > first.backup_id);
+        // 第二次备份只复制了新写入的文件
+        assert!(first.bytes_written > 0);
+        assert!(second.bytes_written < first.bytes_written);
+
+        Storage::restore_from_backup(&backup_path, &restore_path, second.backup_id)?;
+        {
+            let storage = Storage::open(&restore_path)?;
+            let reader = storage.snapshot();
+            assert_eq!(reader.len()?, 1001);
+            assert!(reader.contains_quad(last.as_ref())?);
+            reader.validate()?;
+        }
+        assert!(Storage::new()?.backup_incremental(&backup_path).is_err());
+        for path in [path, backup_path, restore_path] {
+            std::fs::remove_dir_all(&path)?;
         }
+        Ok(())
     }
 
 
-    // 移除三元组（四元组）
-    pub fn remove(&mut self, quad: QuadRef<'_>) -> Result<bool, StorageError> {
-        self.remove_encoded(&quad.into())
+    #[test]
+    fn test_open_read_only() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
+        {
+            let storage = Storage::open(&path)?;
+            insert_all(&storage, &[quad.clone()])?;
+            storage.flush()?;
+
+            // 读写句柄仍然打开时也可以以只读模式打开
+            let read_only = Storage::open_read_only(&path)?;
+            let reader = read_only.snapshot();
+            let subject = EncodedTerm::from(ex("s").as_ref());
+            let found = reader
+                .quads_for_pattern(Some(&subject), None, None, None)
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(found, vec![EncodedQuad::from(quad.as_ref())]);
+            assert_eq!(
+                reader.get_str(&StrHash::new("http://example.com/s"))?,
+                Some("http://example.com/s".to_owned())
+            );
+            assert!(insert_all(&read_only, &[quad.clone()]).is_err());
+            assert!(read_only.flush().is_err());
+        }
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
     }
 
-    // id2str上的term并未被删除；以及删除图时，图的str编码也未被删除
-    fn remove_encoded(&mut self, quad: &EncodedQuad) -> Result<bool, StorageError> {
-        self.buffer.clear();
 
-        let result = if quad.graph_name.is_default_graph() {
-            write_spo_quad(&mut self.buffer, quad);
+    #[test]
+    fn test_load_oxiuse_value_dedups_across_batches() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c"])?;
+        let tree_path = tree_file.path();
+
+        // 批次大小为 1000，重复的三元组分别落在不同的批次中，两个批次写入相同的 key 和 value
+        let duplicate = Quad::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("c"), GraphName::DefaultGraph);
+        let mut quads = vec![duplicate.clone()];
+        quads.extend((0..1500).map(|i| Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), GraphName::DefaultGraph)));
+        quads.push(duplicate.clone());
+
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone())
+            .set_num_threads(2)
+            .set_max_memory_size_in_megabytes(1)
+            .load_oxiuse_value::<StorageError, StorageError, _>(quads.into_iter().map(Ok), tree_path)?;
+
+        let encoded = EncodedQuad::from(duplicate.as_ref());
+        let mut map = HashMap::new();
+        map.insert("s", &encoded.subject);
+        map.insert("p", &encoded.predicate);
+        map.insert("o", &encoded.object);
+        let trees = FileBulkLoader::new(storage.clone()).construct_tree(tree_path)?;
+        let (key, value) = encode_term_triple_oxiuse_value_spo(map, &trees, &storage_iri_hash(&storage))?;
+
+        let reader = storage.snapshot();
+        assert_eq!(reader.reader.get(&storage.dspo_cf, &key)?.as_deref(), Some(value.as_slice()));
+        assert_eq!(reader.len()?, 1501);
+        Ok(())
+    }
 
-            if self.transaction
-                .contains_key_for_update(&self.storage.dspo_cf, &self.buffer)?
-            {   // 存在之前的元组
-                self.transaction
-                    .remove(&self.storage.dspo_cf, &self.buffer)?;
 
-                self.buffer.clear();
-                write_pos_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.dpos_cf, &self.buffer)?;
+    #[test]
+    fn test_load_with_flush_threshold() -> Result<(), StorageError> {
+        let quads = (0..2500)
+            .map(|i| {
+                Quad::new(
+                    ex(&format!("s{}", i)),
+                    ex("p"),
+                    Literal::from(i),
+                    if i % 3 == 0 {
+                        GraphName::DefaultGraph
+                    } else {
+                        ex(&format!("g{}", i % 7)).into()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let storage = Storage::new()?;
+        // 整个数据集在一个批次中，批次内每 100 个四元组写入一次
+        StorageBulkLoader::new(storage.clone())
+            .set_batch_size(10_000)
+            .set_flush_threshold(100)
+            .load::<StorageError, StorageError, _>(quads.iter().cloned().map(Ok))?;
+
+        let reader = storage.snapshot();
+        assert_eq!(reader.len()?, quads.len());
+        for quad in &quads {
+            assert!(reader.contains_quad(quad.as_ref())?);
+        }
+        assert_eq!(reader.named_graphs().count(), 6);
+        reader.validate()?;
+        Ok(())
+    }
 
-                self.buffer.clear();
-                write_osp_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.dosp_cf, &self.buffer)?;
-                true
-            } else {
-                false
+    #[test]
+    fn test_blank_node_scope() -> Result<(), StorageError> {
+        // 两个文件都使用了 _:b1 和 _:b2
+        let document = |name: &str| {
+            vec![
+                Quad::new(BlankNode::new_unchecked("b1"), ex("p"), ex(name), GraphName::DefaultGraph),
+                Quad::new(BlankNode::new_unchecked("b1"), ex("knows"), BlankNode::new_unchecked("b2"), GraphName::DefaultGraph),
+            ]
+        };
+        let blank_nodes = |storage: &Storage| -> Result<usize, StorageError> {
+            let mut blank_nodes = HashSet::new();
+            for quad in storage.snapshot().quads() {
+                let quad = quad?;
+                for term in [quad.subject, quad.object] {
+                    if term.is_blank_node() {
+                        blank_nodes.insert(term);
+                    }
+                }
             }
-        } else {
-            write_spog_quad(&mut self.buffer, quad);
+            Ok(blank_nodes.len())
+        };
 
-            if self
-                .transaction
-                .contains_key_for_update(&self.storage.spog_cf, &self.buffer)?
-            {
-                self.transaction
-                    .remove(&self.storage.spog_cf, &self.buffer)?;
+        let merged = Storage::new()?;
+        for name in ["first", "second"] {
+            StorageBulkLoader::new(merged.clone())
+                .load::<StorageError, StorageError, _>(document(name).into_iter().map(Ok))?;
+        }
+        assert_eq!(blank_nodes(&merged)?, 2);
+        assert_eq!(merged.snapshot().len()?, 3);
+
+        let scoped = Storage::new()?;
+        for name in ["first", "second"] {
+            StorageBulkLoader::new(scoped.clone())
+                .set_blank_node_scope(name)
+                .load::<StorageError, StorageError, _>(document(name).into_iter().map(Ok))?;
+        }
+        assert_eq!(blank_nodes(&scoped)?, 4);
+        assert_eq!(scoped.snapshot().len()?, 4);
+        Ok(())
+    }
 
-                self.buffer.clear();
-                write_posg_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.posg_cf, &self.buffer)?;
+    #[test]
+    fn test_update_triple_value() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c"])?;
+        let tree_path = tree_file.path();
+        let triple = Triple::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("c"));
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone()).load_oxiuse_value::<StorageError, StorageError, _>(
+            [Ok(triple.clone().in_graph(GraphName::DefaultGraph))],
+            tree_path,
+        )?;
+        let encoded = EncodedQuad::from(triple.as_ref().in_graph(GraphNameRef::DefaultGraph));
+        let values = |storage: &Storage| -> Result<Vec<Vec<u8>>, StorageError> {
+            let reader = storage.snapshot();
+            [
+                reader.triples_for_pattern_with_values(Some(&encoded.subject), None, None),
+                reader.triples_for_pattern_with_values(None, Some(&encoded.predicate), None),
+                reader.triples_for_pattern_with_values(None, None, Some(&encoded.object)),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|result| Ok(result?.1))
+            .collect()
+        };
+        let old_values = values(&storage)?;
+        assert_eq!(old_values.len(), 3);
+        assert!(old_values.iter().all(|value| !value.is_empty()));
 
-                self.buffer.clear();
-                write_ospg_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.ospg_cf, &self.buffer)?;
+        assert!(storage.transaction(|mut writer| writer.update_triple_value(triple.as_ref(), b"new value"))?);
+        assert_eq!(values(&storage)?, vec![b"new value".to_vec(); 3]);
+        assert_eq!(storage.snapshot().len()?, 1);
 
-                self.buffer.clear();
-                write_gspo_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.gspo_cf, &self.buffer)?;
+        let missing = Triple::new(ex("s"), ex("p"), ex("o"));
+        assert!(!storage.transaction(|mut writer| writer.update_triple_value(missing.as_ref(), b"value"))?);
+        assert_eq!(storage.snapshot().len()?, 1);
+        Ok(())
+    }
 
-                self.buffer.clear();
-                write_gpos_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.gpos_cf, &self.buffer)?;
+    #[test]
+    fn test_mixed_load_modes_are_rejected() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c"])?;
+        let tree_path = tree_file.path();
+        let first = Quad::new(ex("s1"), NamedNode::new_unchecked(rdf::TYPE), ex("c"), GraphName::DefaultGraph);
+        let second = Quad::new(ex("s2"), NamedNode::new_unchecked(rdf::TYPE), ex("c"), GraphName::DefaultGraph);
+
+        let storage = Storage::new()?;
+        assert_eq!(storage.triple_encoding_mode()?, None);
+        StorageBulkLoader::new(storage.clone()).load::<StorageError, StorageError, _>([Ok(first.clone())])?;
+        assert_eq!(storage.triple_encoding_mode()?, Some(TripleEncodingMode::Plain));
+        let keys = column_family_keys(&storage, &storage.dspo_cf)?;
+
+        let error = StorageBulkLoader::new(storage.clone())
+            .load_oxiuse_key::<StorageError, StorageError, _>([Ok(second.clone())], tree_path)
+            .unwrap_err();
+        assert!(error.to_string().contains("Plain layout"), "{}", error);
+        assert!(error.to_string().contains("IntervalKey layout"), "{}", error);
+        assert_eq!(column_family_keys(&storage, &storage.dspo_cf)?, keys);
+        assert_eq!(storage.triple_encoding_mode()?, Some(TripleEncodingMode::Plain));
+
+        // 相同的布局可以继续加载
+        StorageBulkLoader::new(storage.clone()).load::<StorageError, StorageError, _>([Ok(second)])?;
+        assert_eq!(storage.snapshot().len()?, 2);
+
+        // 事务写入的三元组也是 Plain 布局
+        let inserted = Storage::new()?;
+        insert_all(&inserted, &[first])?;
+        assert!(StorageBulkLoader::new(inserted)
+            .load_oxiuse_value::<StorageError, StorageError, _>(std::iter::empty(), tree_path)
+            .is_err());
+        Ok(())
+    }
 
-                self.buffer.clear();
-                write_gosp_quad(&mut self.buffer, quad);
-                self.transaction
-                    .remove(&self.storage.gosp_cf, &self.buffer)?;
-                true
-            } else {
-                false
+    #[test]
+    fn test_triples_for_pattern_with_values() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c"])?;
+        let tree_path = tree_file.path();
+
+        let typed = Quad::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("c"), GraphName::DefaultGraph);
+        let other = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone()).load_oxiuse_value::<StorageError, StorageError, _>(
+            [typed.clone(), other].into_iter().map(Ok),
+            tree_path,
+        )?;
+
+        let encoded = EncodedQuad::from(typed.as_ref());
+        let mut map = HashMap::new();
+        map.insert("s", &encoded.subject);
+        map.insert("p", &encoded.predicate);
+        map.insert("o", &encoded.object);
+        let trees = FileBulkLoader::new(storage.clone()).construct_tree(tree_path)?;
+        let (_, expected_value) = encode_term_triple_oxiuse_value_spo(map, &trees, &storage_iri_hash(&storage))?;
+        assert!(!expected_value.is_empty());
+
+        let reader = storage.snapshot();
+        let found = reader
+            .triples_for_pattern_with_values(Some(&encoded.subject), Some(&encoded.predicate), None)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(found, vec![(encoded.clone(), expected_value)]);
+        assert_eq!(
+            reader
+                .triples_for_pattern_with_values(Some(&encoded.subject), None, None)
+                .count(),
+            2
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_join_on_subject() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let mut quads = Vec::new();
+        for (subject, predicate, object) in [
+            ("s1", "p1", "o"),
+            ("s2", "p1", "o"),
+            ("s3", "p1", "o"),
+            ("s5", "p1", "o"),
+            ("s2", "p2", "o"),
+            ("s3", "p2", "o"),
+            ("s4", "p2", "o"),
+            ("s5", "p2", "other"),
+        ] {
+            quads.push(Quad::new(ex(subject), ex(predicate), ex(object), GraphName::DefaultGraph));
+        }
+        insert_all(&storage, &quads)?;
+
+        let reader = storage.snapshot();
+        let object = EncodedTerm::from(ex("o").as_ref());
+        let scan = |predicate: &str| {
+            reader.quads_for_pattern(
+                None,
+                Some(&ex(predicate).as_ref().into()),
+                Some(&object),
+                Some(&EncodedTerm::DefaultGraph),
+            )
+        };
+        let pairs = merge_join_on_subject(scan("p1"), scan("p2")).collect::<Result<Vec<_>, _>>()?;
+        let mut subjects = pairs
+            .iter()
+            .map(|(left, right)| {
+                assert_eq!(left.subject, right.subject);
+                assert_eq!(left.predicate, ex("p1").as_ref().into());
+                assert_eq!(right.predicate, ex("p2").as_ref().into());
+                reader.decode_term(&left.subject)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        subjects.sort_by_key(ToString::to_string);
+        assert_eq!(subjects, vec![Term::from(ex("s2")), Term::from(ex("s3"))]);
+
+        assert_eq!(merge_join_on_subject(scan("p1"), scan("p3")).count(), 0);
+        Ok(())
+    }
+
+    fn column_family_keys(storage: &Storage, column_family: &ColumnFamily) -> Result<Vec<Vec<u8>>, StorageError> {
+        let reader = storage.snapshot();
+        let mut iter = reader.reader.iter(column_family)?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.key() {
+            keys.push(key.to_vec());
+            iter.next();
+        }
+        iter.status()?;
+        Ok(keys)
+    }
+
+    #[test]
+    fn test_load_modes() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c", "d"])?;
+        let tree_path = tree_file.path();
+        let dataset = vec![
+            Quad::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("d"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), ex("o"), ex("g")),
+        ];
+        let triples = dataset[..2].iter().map(|q| EncodedQuad::from(q.as_ref())).collect::<Vec<_>>();
+        let named = EncodedQuad::from(dataset[2].as_ref());
+
+        for mode in [LoadMode::Plain, LoadMode::IntervalValue(tree_path), LoadMode::IntervalKey(tree_path)] {
+            let storage = Storage::new()?;
+            StorageBulkLoader::new(storage.clone())
+                .load_with_mode::<StorageError, StorageError, _>(dataset.iter().cloned().map(Ok), mode)?;
+            let trees = FileBulkLoader::new(storage.clone()).construct_tree(tree_path)?;
+
+            // 命名图的索引与加载模式无关
+            assert_eq!(
+                column_family_keys(&storage, &storage.gspo_cf)?,
+                vec![encode_term_quad(&named.graph_name, &named.subject, &named.predicate, &named.object)]
+            );
+            assert_eq!(column_family_keys(&storage, &storage.graphs_cf)?, vec![encode_term(&named.graph_name)]);
+
+            let mut expected_spo = Vec::new();
+            let mut expected_osp = Vec::new();
+            for quad in &triples {
+                match mode {
+                    LoadMode::Plain => {
+                        expected_spo.push(encode_term_triple(&quad.subject, &quad.predicate, &quad.object));
+                        expected_osp.push(encode_term_triple(&quad.object, &quad.subject, &quad.predicate));
+                    }
+                    LoadMode::IntervalValue(_) => {
+                        let (key, value) = encode_term_triple_oxiuse_value_spo(triple_term_map(quad), &trees, &storage_iri_hash(&storage))?;
+                        assert_eq!(
+                            storage.snapshot().reader.get(&storage.dspo_cf, &key)?.as_deref(),
+                            Some(value.as_slice())
+                        );
+                        expected_spo.push(key);
+                        expected_osp.push(encode_term_triple_oxiuse_value_osp(triple_term_map(quad), &trees, &storage_iri_hash(&storage))?.0);
+                    }
+                    LoadMode::IntervalKey(_) => {
+                        expected_spo.push(encode_term_triple_oxiuse_key_spo(triple_term_map(quad), &trees, &storage_iri_hash(&storage))?);
+                        expected_osp.push(encode_term_triple_oxiuse_key_osp(triple_term_map(quad), &trees, &storage_iri_hash(&storage))?);
+                    }
+                }
             }
-        };
-        Ok(result)
+            expected_spo.sort();
+            expected_osp.sort();
+            assert_eq!(column_family_keys(&storage, &storage.dspo_cf)?, expected_spo);
+            assert_eq!(column_family_keys(&storage, &storage.dosp_cf)?, expected_osp);
+
+            // 无论默认图的 key 是否以区间编码开头，按模式查询都能读出原来的三元组
+            let reader = storage.snapshot();
+            let default_graph = EncodedTerm::DefaultGraph;
+            for (subject, predicate, object, expected) in [
+                (None, None, None, &triples[..]),
+                (Some(&triples[0].subject), None, None, &triples[..]),
+                (None, Some(&triples[1].predicate), None, &triples[1..]),
+                (None, None, Some(&triples[0].object), &triples[..1]),
+                (Some(&triples[1].subject), Some(&triples[1].predicate), Some(&triples[1].object), &triples[1..]),
+            ] {
+                let found = reader
+                    .quads_for_pattern(subject, predicate, object, Some(&default_graph))
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(found.len(), expected.len(), "{:?}", mode);
+                assert!(expected.iter().all(|quad| found.contains(quad)), "{:?}", mode);
+            }
+            assert_eq!(reader.quads_for_pattern(None, None, None, None).count(), dataset.len());
+        }
+        Ok(())
     }
 
-    // 删除某一个图（即图上的元组）
-    pub fn clear_graph(&mut self, graph_name: GraphNameRef<'_>) -> Result<(), StorageError> {
-        if graph_name.is_default_graph() {
-            for quad in self.reader().quads_for_graph(&EncodedTerm::DefaultGraph) {
-                self.remove_encoded(&quad?)?;
-            }
-        } else {
-            self.buffer.clear();
-            write_term(&mut self.buffer, &graph_name.into());
-            if self.transaction
-                .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
-            {
-                // The condition is useful to lock the graph itself and ensure no quad is inserted at the same time
-                for quad in self.reader().quads_for_graph(&graph_name.into()) {
-                    self.remove_encoded(&quad?)?;
+    #[test]
+    fn test_interval_load_with_prefixes() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c", "d"])?;
+        let tree_path = tree_file.path();
+        let dataset = vec![
+            Quad::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("d"), GraphName::DefaultGraph),
+            Quad::new(ex("d"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("c"), GraphName::DefaultGraph),
+        ];
+
+        for mode in [LoadMode::IntervalValue(tree_path), LoadMode::IntervalKey(tree_path)] {
+            // 数据的命名空间和 rdfs 的命名空间都使用前缀编码
+            let storage = Storage::new()?;
+            storage.register_prefix("http://example.com/")?;
+            storage.register_prefix("http://www.w3.org/2000/01/rdf-schema#")?;
+            StorageBulkLoader::new(storage.clone())
+                .load_with_mode::<StorageError, StorageError, _>(dataset.iter().cloned().map(Ok), mode)?;
+            let trees = FileBulkLoader::new(storage.clone()).construct_tree(tree_path)?;
+
+            let reader = storage.snapshot();
+            let mut expected_spo = Vec::new();
+            for quad in &dataset {
+                let quad = reader.encode_quad(quad.as_ref());
+                assert!(matches!(quad.object, EncodedTerm::PrefixedNamedNode { .. }));
+                let (key, value) = encode_term_triple_oxiuse_value_spo(triple_term_map(&quad), &trees, &storage_iri_hash(&storage))?;
+                // 两个三元组都有区间编码
+                assert!(!value.is_empty(), "{:?}", mode);
+                match mode {
+                    LoadMode::IntervalKey(_) => {
+                        expected_spo.push(encode_term_triple_oxiuse_key_spo(triple_term_map(&quad), &trees, &storage_iri_hash(&storage))?);
+                    }
+                    _ => {
+                        assert_eq!(reader.reader.get(&storage.dspo_cf, &key)?.as_deref(), Some(value.as_slice()));
+                        expected_spo.push(key);
+                    }
                 }
             }
+            expected_spo.sort();
+            assert_eq!(column_family_keys(&storage, &storage.dspo_cf)?, expected_spo, "{:?}", mode);
+
+            let found = reader
+                .quads_for_pattern(None, None, None, None)
+                .map(|quad| reader.decode_quad(&quad?))
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(found.len(), dataset.len(), "{:?}", mode);
+            assert!(dataset.iter().all(|quad| found.contains(quad)), "{:?}", mode);
         }
         Ok(())
     }
 
-    // 清除所有 named_graph（即图上的元组）
-    pub fn clear_all_named_graphs(&mut self) -> Result<(), StorageError> {
-        for quad in self.reader().quads_in_named_graph() {
-            self.remove_encoded(&quad?)?;
-        }
+    #[test]
+    fn test_interval_key_store_rejects_transactional_default_graph_writes() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c", "d"])?;
+        let tree_path = tree_file.path();
+        let triple = Triple::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("d"));
+        let quad = triple.clone().in_graph(GraphName::DefaultGraph);
+
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone())
+            .load_oxiuse_key::<StorageError, StorageError, _>([Ok(quad.clone())], tree_path)?;
+        assert!(storage.snapshot().contains(&EncodedQuad::from(quad.as_ref()))?);
+
+        // 事务写入的 key 没有区间编码，会让同一个三元组出现两次，所以这些写入都被拒绝
+        assert!(storage.transaction(|mut writer| writer.insert(quad.as_ref())).is_err());
+        assert!(storage.transaction(|mut writer| writer.remove(quad.as_ref())).is_err());
+        assert!(storage
+            .transaction(|mut writer| writer.update_triple_value(triple.as_ref(), &[]))
+            .is_err());
+        // 命名图不受影响
+        let named = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
+        assert!(storage.transaction(|mut writer| writer.insert(named.as_ref()))?);
+
+        let reader = storage.snapshot();
+        assert_eq!(reader.len()?, 2);
+        assert_eq!(
+            reader
+                .quads_for_pattern(None, None, None, Some(&EncodedTerm::DefaultGraph))
+                .collect::<Result<Vec<_>, _>>()?,
+            vec![EncodedQuad::from(quad.as_ref())]
+        );
         Ok(())
     }
 
-    // 清除所有graph（即图上的元组）
-    pub fn clear_all_graphs(&mut self) -> Result<(), StorageError> {
-        for quad in self.reader().quads() {
-            self.remove_encoded(&quad?)?;
+    // 版本 1 的区间编码：subClassOf、subPropertyOf 没有区间数，子节点的区间没有 layer，只有父节点的第一个区间
+    #[cfg(not(target_arch = "wasm32"))]
+    fn legacy_interval_encoding(kind: u8, value: &IntervalValue) -> Vec<u8> {
+        let mut encoding = vec![kind];
+        match value {
+            IntervalValue::Hierarchy { child, parent } => {
+                for (start, end, _) in child {
+                    encoding.extend_from_slice(&start.to_be_bytes());
+                    encoding.extend_from_slice(&end.to_be_bytes());
+                }
+                let (start, end, layer) = parent[0];
+                encoding.extend_from_slice(&start.to_be_bytes());
+                encoding.extend_from_slice(&end.to_be_bytes());
+                encoding.extend_from_slice(&layer.to_be_bytes());
+            }
+            IntervalValue::Class(intervals) => {
+                encoding.push(intervals.len().try_into().unwrap());
+                for (start, end, layer) in intervals {
+                    encoding.extend_from_slice(&start.to_be_bytes());
+                    encoding.extend_from_slice(&end.to_be_bytes());
+                    encoding.extend_from_slice(&layer.to_be_bytes());
+                }
+            }
+        }
+        encoding
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_migrate_interval_encodings_from_version_1() -> Result<(), StorageError> {
+        let tree_file = TreeFile::with_classes(&["c", "d"])?;
+        let tree_path = tree_file.path();
+        let dataset = vec![
+            Quad::new(ex("d"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("c"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), NamedNode::new_unchecked(rdf::TYPE), ex("d"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+        ];
+        let column_family_entries = |storage: &Storage, column_family: &ColumnFamily| {
+            let reader = storage.snapshot();
+            let mut iter = reader.reader.iter(column_family)?;
+            let mut entries = Vec::new();
+            while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                entries.push((key.to_vec(), value.to_vec()));
+                iter.next();
+            }
+            iter.status()?;
+            Ok::<_, StorageError>(entries)
+        };
+        let entries = |storage: &Storage| {
+            let mut entries = Vec::new();
+            for column_family in [&storage.dspo_cf, &storage.dpos_cf, &storage.dosp_cf] {
+                entries.extend(column_family_entries(storage, column_family)?);
+            }
+            Ok::<_, StorageError>(entries)
+        };
+
+        for mode in [LoadMode::IntervalValue(tree_path), LoadMode::IntervalKey(tree_path)] {
+            let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+            let expected = {
+                let storage = Storage::open(&path)?;
+                StorageBulkLoader::new(storage.clone())
+                    .load_with_mode::<StorageError, StorageError, _>(dataset.iter().cloned().map(Ok), mode)?;
+                let expected = entries(&storage)?;
+
+                // 将三个索引中的区间编码改写为版本 1 的格式
+                let mut batch = storage.db.new_write_batch();
+                for (encoding, column_family) in [
+                    (QuadEncoding::Dspo, &storage.dspo_cf),
+                    (QuadEncoding::Dpos, &storage.dpos_cf),
+                    (QuadEncoding::Dosp, &storage.dosp_cf),
+                ] {
+                    for (key, value) in column_family_entries(&storage, column_family)? {
+                        if let Some(interval) = decode_interval_value(&value)? {
+                            batch.insert(column_family, &key, &legacy_interval_encoding(value[0], &interval));
+                        } else if let (quad, Some(interval)) = encoding.decode_interval_key(&key, &storage.prefix_table())? {
+                            let mut legacy_key = legacy_interval_encoding(key[0], &interval);
+                            encoding.encode_quad(&quad, &mut legacy_key);
+                            batch.remove(column_family, &key);
+                            batch.insert(column_family, &legacy_key, &value);
+                        }
+                    }
+                }
+                storage.db.write_batch(batch)?;
+                storage.update_version(1)?;
+                assert_ne!(entries(&storage)?, expected, "{:?}", mode);
+                expected
+            };
+
+            let storage = Storage::open(&path)?;
+            assert_eq!(storage.ensure_version()?, LATEST_STORAGE_VERSION);
+            assert_eq!(entries(&storage)?, expected, "{:?}", mode);
+            let found = storage
+                .snapshot()
+                .quads_for_pattern(None, None, None, None)
+                .collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(found.len(), dataset.len(), "{:?}", mode);
+            for quad in &dataset {
+                assert!(found.contains(&EncodedQuad::from(quad.as_ref())), "{:?}", mode);
+            }
         }
         Ok(())
     }
 
-    // 不仅删除图上的三元组，也将图在 graph_cf 上清除
-    pub fn remove_named_graph(
-        &mut self,
-        graph_name: NamedOrBlankNodeRef<'_>,
-    ) -> Result<bool, StorageError> {
-        self.remove_encoded_named_graph(&graph_name.into())
+
+    // 树的可观察状态：每个节点的区间与父节点路径
+    fn tree_layout(tree: &MultiTree, nodes: &[&str]) -> Vec<(Vec<(u32, u32, u16)>, Vec<Vec<StrHash>>)> {
+        nodes
+            .iter()
+            .map(|node| {
+                let hash = StrHash::new(node);
+                let intervals = tree
+                    .get_node_by_strhash(hash)
+                    .unwrap()
+                    .get_interval_nodes()
+                    .iter()
+                    .map(|i| (i.get_start(), i.get_end(), i.get_layer()))
+                    .collect();
+                (intervals, tree.get_parent_way_by_str(hash))
+            })
+            .collect()
     }
 
-    // 移除给定的 named_graph
-    // 不仅删除图上的三元组，也将图在 graph_cf 上清除
-    fn remove_encoded_named_graph(
-        &mut self,
-        graph_name: &EncodedTerm,
-    ) -> Result<bool, StorageError> {
-        self.buffer.clear();
-        write_term(&mut self.buffer, graph_name);
+    #[test]
+    fn test_construct_tree_from_quads() -> Result<(), StorageError> {
+        let ontology = vec![
+            Quad::new(ex("c"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("b"), GraphName::DefaultGraph),
+            Quad::new(ex("b"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("a"), GraphName::DefaultGraph),
+            Quad::new(ex("q"), NamedNode::new_unchecked(rdfs::SUB_PROPERTY_OF), ex("p"), GraphName::DefaultGraph),
+            Quad::new(ex("c"), ex("label"), Literal::from("ignored"), GraphName::DefaultGraph),
+        ];
+        let tree_file = TreeFile::from_quads(&ontology)?;
+
+        let loader = FileBulkLoader::new(Storage::new()?);
+        let (from_file_classes, from_file_properties) = loader.construct_tree(tree_file.path())?;
+        let (classes, properties) = loader.construct_tree_from_quads(ontology)?;
+
+        let class_nodes = ["http://example.com/a", "http://example.com/b", "http://example.com/c"];
+        assert_eq!(tree_layout(&classes, &class_nodes), tree_layout(&from_file_classes, &class_nodes));
+        let property_nodes = ["http://example.com/p", "http://example.com/q"];
+        assert_eq!(tree_layout(&properties, &property_nodes), tree_layout(&from_file_properties, &property_nodes));
+        assert!(!classes.if_exist("ignored"));
+        Ok(())
+    }
 
-        let result = if self
-            .transaction
-            .contains_key_for_update(&self.storage.graphs_cf, &self.buffer)?
-        {
-            // The condition is done ASAP to lock the graph itself
-            for quad in self.reader().quads_for_graph(graph_name) {
-                self.remove_encoded(&quad?)?;
-            }
-            self.buffer.clear();
-            write_term(&mut self.buffer, graph_name);
-            self.transaction
-                .remove(&self.storage.graphs_cf, &self.buffer)?;
-            true
-        } else {
-            false
-        };
-        Ok(result)
+
+    #[test]
+    fn test_construct_tree_reports_cycles() -> Result<(), StorageError> {
+        let sub_class_of = NamedNode::new_unchecked(rdfs::SUB_CLASS_OF);
+        let loader = FileBulkLoader::new(Storage::new()?);
+        let error = loader
+            .construct_tree_from_quads(vec![
+                Quad::new(ex("a"), sub_class_of.clone(), ex("b"), GraphName::DefaultGraph),
+                Quad::new(ex("b"), sub_class_of.clone(), ex("a"), GraphName::DefaultGraph),
+            ])
+            .unwrap_err();
+        assert!(error.to_string().contains("introduces a cycle"), "{}", error);
+
+        // 自反的边被忽略
+        let (classes, _) = loader.construct_tree_from_quads(vec![
+            Quad::new(ex("a"), sub_class_of.clone(), ex("a"), GraphName::DefaultGraph),
+            Quad::new(ex("b"), sub_class_of, ex("a"), GraphName::DefaultGraph),
+        ])?;
+        assert!(classes.is_ancestor("http://example.com/a", "http://example.com/b"));
+        Ok(())
     }
 
-    // 移除所有的 named_graph
-    // 不仅删除图上的三元组，也将图在 graph_cf 上清除
-    pub fn remove_all_named_graphs(&mut self) -> Result<(), StorageError> {
-        for graph_name in self.reader().named_graphs() {
-            self.remove_encoded_named_graph(&graph_name?)?;
-        }
+    #[test]
+    fn test_iterator_creation_error_is_returned() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), ex("g"))])?;
+        // 事务结束之后再使用它的 reader 创建迭代器会失败
+        let reader = storage.transaction(|writer| Ok::<_, StorageError>(writer.reader()))?;
+
+        let mut quads = reader.quads_for_pattern(None, None, None, None);
+        assert!(matches!(quads.next(), Some(Err(_))));
+        let mut graphs = reader.named_graphs();
+        assert!(matches!(graphs.next(), Some(Err(_))));
+        assert!(graphs.next().is_none());
         Ok(())
     }
 
-    // 清除所有元组（包括默认图以及命名图）
-    // 并清除 graph_cf
-    pub fn clear(&mut self) -> Result<(), StorageError> {
-        for graph_name in self.reader().named_graphs() {
-            self.remove_encoded_named_graph(&graph_name?)?;
-        }
-        for quad in self.reader().quads() {
-            self.remove_encoded(&quad?)?;
-        }
+
+    #[test]
+    fn test_contains_quad() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let triple = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
+        insert_all(&storage, &[triple.clone(), quad.clone()])?;
+
+        let reader = storage.snapshot();
+        assert!(reader.contains_quad(triple.as_ref())?);
+        assert!(reader.contains_quad(quad.as_ref())?);
+        assert!(!reader.contains_quad(QuadRef::new(ex("s").as_ref(), ex("p").as_ref(), ex("o").as_ref(), ex("h").as_ref()))?);
+        assert!(!reader.contains_quad(QuadRef::new(ex("o").as_ref(), ex("p").as_ref(), ex("s").as_ref(), GraphNameRef::DefaultGraph))?);
         Ok(())
     }
-}
 
 
-// 在 store.rs 中用到了
-#[cfg(not(target_arch = "wasm32"))]
-pub struct StorageBulkLoader {
-    storage: Storage,
-    hooks: Vec<Box<dyn Fn(u64)>>,
-    num_threads: Option<usize>,
-    max_memory_size: Option<usize>,
-}
+    #[test]
+    fn test_quads_for_pattern_size_hint() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+                Quad::new(ex("s"), ex("p"), ex("o2"), ex("g")),
+            ],
+        )?;
+        let reader = storage.snapshot();
+        let subject = EncodedTerm::from(ex("s").as_ref());
+        assert_eq!(reader.quads_for_pattern(Some(&subject), None, None, None).size_hint().0, 2);
+        let missing = EncodedTerm::from(ex("missing").as_ref());
+        assert_eq!(reader.quads_for_pattern(Some(&missing), None, None, None).size_hint(), (0, Some(0)));
 
-#[cfg(not(target_arch = "wasm32"))]
-impl StorageBulkLoader {
-    pub fn new(storage: Storage) -> Self {
-        Self {
-            storage,
-            hooks: Vec::new(),
-            num_threads: None,
-            max_memory_size: None,
-        }
+        #[cfg(not(target_arch = "wasm32"))]
+        assert!(reader.quads_for_pattern(Some(&subject), None, None, None).index_len_upper_bound(&reader)? >= 2);
+
+        let mut iter = reader.quads().with_lower_bound(2);
+        assert_eq!(iter.size_hint().0, 2);
+        iter.next();
+        assert_eq!(iter.size_hint().0, 1);
+        Ok(())
     }
 
-    pub fn set_num_threads(mut self, num_threads: usize) -> Self {
-        self.num_threads = Some(num_threads);
-        self
+
+    #[test]
+    fn test_remove_many() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let triple = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
+        let missing = Quad::new(ex("s"), ex("p"), ex("missing"), ex("g"));
+        insert_all(&storage, &[triple.clone(), quad.clone()])?;
+
+        let removed = storage.transaction(|mut writer| {
+            writer.remove_many([triple.as_ref(), quad.as_ref(), missing.as_ref(), triple.as_ref()])
+        })?;
+        assert_eq!(removed, 2);
+
+        let reader = storage.snapshot();
+        assert!(reader.is_empty()?);
+        // 删除最后一个元组之后，命名图本身仍然存在
+        assert!(reader.contains_named_graph(&ex("g").as_ref().into())?);
+        Ok(())
     }
 
-    pub fn set_max_memory_size_in_megabytes(mut self, max_memory_size: usize) -> Self {
-        self.max_memory_size = Some(max_memory_size);
-        self
+
+    #[test]
+    fn test_collect_unused_strings() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let unique = Literal::from("a literal that is too long to be inlined");
+        let removed = Quad::new(ex("s"), ex("p"), unique.clone(), GraphName::DefaultGraph);
+        let kept = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        insert_all(&storage, &[removed.clone(), kept])?;
+
+        let collected = storage.transaction(|mut writer| {
+            writer.remove(removed.as_ref())?;
+            writer.collect_unused_strings()
+        })?;
+        assert_eq!(collected, 1);
+        let reader = storage.snapshot();
+        assert!(!reader.contains_str(&StrHash::new(unique.value()))?);
+        assert!(reader.contains_str(&StrHash::new(ex("s").as_str()))?);
+        assert!(reader.contains_str(&StrHash::new(ex("p").as_str()))?);
+        Ok(())
     }
 
-    pub fn on_progress(mut self, callback: impl Fn(u64) + 'static) -> Self {
-        self.hooks.push(Box::new(callback));
-        self
+    #[test]
+    fn test_remove_with_gc() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let removed = Quad::new(ex("s"), ex("p"), ex("unique"), GraphName::DefaultGraph);
+        let kept = Quad::new(ex("s2"), ex("p"), ex("o"), ex("g"));
+        insert_all(&storage, &[removed.clone(), kept])?;
+
+        assert!(storage.transaction(|mut writer| writer.remove_with_gc(removed.as_ref()))?);
+        let reader = storage.snapshot();
+        assert!(!reader.contains_str(&StrHash::new(ex("s").as_str()))?);
+        assert!(!reader.contains_str(&StrHash::new(ex("unique").as_str()))?);
+        // 谓语仍然被另一个四元组使用
+        assert!(reader.contains_str(&StrHash::new(ex("p").as_str()))?);
+        Ok(())
     }
 
-    // 注意一下，这个方法也重写了
-    pub fn load<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
-        &self,
-        quads: I,
-    ) -> Result<(), EO> {
-        let system = System::new_all();
-        let cpu_count = min(4, system.physical_core_count().unwrap_or(2));
-        let num_threads = max(
-            if let Some(num_threads) = self.num_threads {
-                num_threads
-            } else if let Some(max_memory_size) = self.max_memory_size {
-                min(
-                    cpu_count,
-                    max_memory_size * 1000 / DEFAULT_BULK_LOAD_BATCH_SIZE,
-                )
-            } else {
-                cpu_count
-            },
-            2,
-        );
-        let batch_size = min(
-            if let Some(max_memory_size) = self.max_memory_size {
-                max(1000, max_memory_size * 1000 / num_threads)
-            } else {
-                max(
-                    usize::try_from(system.free_memory()).unwrap() / num_threads,
-                    DEFAULT_BULK_LOAD_BATCH_SIZE,
-                )
-            },
-            MAX_BULK_LOAD_BATCH_SIZE,
-        );
-        let mut threads = VecDeque::with_capacity(num_threads - 1);
-        let mut buffer = Vec::with_capacity(batch_size);
-        let done_counter = Arc::new(AtomicU64::new(0));
-        let mut done_and_displayed_counter = 0;
 
-        for quad in quads {
-            let quad = quad?;
-            buffer.push(quad);    // 其中是Quad
-            if buffer.len() >= batch_size {
-                self.spawn_load_thread(
-                    &mut buffer,
-                    &mut threads,
-                    &done_counter,
-                    &mut done_and_displayed_counter,
-                    num_threads,
-                )?;
-            }
+    #[test]
+    fn test_remove_with_gc_keeps_strings_used_inside_literals() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let datatype = ex("datatype");
+        let long = "a literal value that is too long to be inlined";
+        let removed_literal = Quad::new(
+            ex("s"),
+            ex("p"),
+            Literal::new_typed_literal(long, datatype.clone()),
+            GraphName::DefaultGraph,
+        );
+        let removed_datatype = Quad::new(ex("s"), ex("p"), datatype.clone(), GraphName::DefaultGraph);
+        let removed_quoted = Quad::new(ex("s"), ex("p"), ex("quoted"), GraphName::DefaultGraph);
+        let kept = vec![
+            Quad::new(ex("s2"), ex("p"), Literal::new_typed_literal("1", datatype.clone()), GraphName::DefaultGraph),
+            Quad::new(
+                Triple::new(ex("s3"), ex("p"), ex("quoted")),
+                ex("p"),
+                ex("o"),
+                GraphName::DefaultGraph,
+            ),
+        ];
+        insert_all(&storage, &[removed_literal.clone(), removed_datatype.clone(), removed_quoted.clone()])?;
+        insert_all(&storage, &kept)?;
+
+        for removed in [&removed_literal, &removed_datatype, &removed_quoted] {
+            assert!(storage.transaction(|mut writer| writer.remove_with_gc(removed.as_ref()))?);
         }
-        self.spawn_load_thread(
-            &mut buffer,
-            &mut threads,
-            &done_counter,
-            &mut done_and_displayed_counter,
-            num_threads,
-        )?;
-        for thread in threads {
-            thread.join().unwrap()?;
-            self.on_possible_progress(&done_counter, &mut done_and_displayed_counter);
+        let reader = storage.snapshot();
+        assert!(!reader.contains_str(&StrHash::new(long))?);
+        // 仍然是另一个字面量的类型
+        assert!(reader.contains_str(&StrHash::new(datatype.as_str()))?);
+        // 仍然在引用三元组中
+        assert!(reader.contains_str(&StrHash::new(ex("quoted").as_str()))?);
+        for quad in &kept {
+            assert!(reader.contains_quad(quad.as_ref())?);
+            assert_eq!(reader.decode_quad(&quad.as_ref().into())?, *quad);
         }
+        storage.snapshot().validate()?;
         Ok(())
     }
 
-    fn spawn_load_thread(
-        &self,
-        buffer: &mut Vec<Quad>,
-        threads: &mut VecDeque<JoinHandle<Result<(), StorageError>>>,
-        done_counter: &Arc<AtomicU64>,
-        done_and_displayed_counter: &mut u64,
-        num_threads: usize,
-    ) -> Result<(), StorageError> {
-        self.on_possible_progress(done_counter, done_and_displayed_counter);
-        // We avoid to have too many threads
-        if threads.len() >= num_threads {
-            if let Some(thread) = threads.pop_front() {
-                thread.join().unwrap()?;
-                self.on_possible_progress(done_counter, done_and_displayed_counter);
-            }
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_collect_unused_strings_sees_concurrent_insert() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let long = Literal::from("a literal that is too long to be inlined");
+        let removed = Quad::new(ex("s"), ex("p"), long.clone(), GraphName::DefaultGraph);
+        let reused = Quad::new(ex("s2"), ex("p"), long.clone(), GraphName::DefaultGraph);
+        insert_all(&storage, &[removed.clone()])?;
+        storage.transaction(|mut writer| writer.remove(removed.as_ref()))?;
+
+        // 另一个事务在垃圾回收的事务开始之后提交了一个重新使用这个字符串的四元组
+        let collected = storage.transaction(|mut writer| {
+            insert_all(&storage, &[reused.clone()])?;
+            writer.collect_unused_strings()
+        })?;
+        assert_eq!(collected, 0);
+        let reader = storage.snapshot();
+        assert!(reader.contains_str(&StrHash::new(long.value()))?);
+        assert_eq!(reader.decode_quad(&reused.as_ref().into())?, reused);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_remove_with_gc_under_concurrent_writes() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let long = Literal::from("a literal that is too long to be inlined");
+        let removed = Quad::new(ex("s"), ex("p"), long.clone(), GraphName::DefaultGraph);
+        let writer = {
+            let storage = storage.clone();
+            let long = long.clone();
+            spawn(move || {
+                for i in 0..100 {
+                    insert_all(
+                        &storage,
+                        &[Quad::new(ex(&format!("s{}", i)), ex("p"), long.clone(), ex("g"))],
+                    )?;
+                }
+                Ok::<_, StorageError>(())
+            })
+        };
+        while !writer.is_finished() {
+            insert_all(&storage, &[removed.clone()])?;
+            storage.transaction(|mut writer| writer.remove_with_gc(removed.as_ref()))?;
         }
-        let buffer = take(buffer);
-        let storage = self.storage.clone();
-        let done_counter_clone = done_counter.clone();
-        threads.push_back(spawn(move || {
-            FileBulkLoader::new(storage).load(buffer, &done_counter_clone)   // TODO:这里面有插入的方法了
-        }));
-        self.on_possible_progress(done_counter, done_and_displayed_counter);
+        writer.join().unwrap()?;
+
+        let reader = storage.snapshot();
+        assert_eq!(reader.len()?, 100);
+        assert!(reader.contains_str(&StrHash::new(long.value()))?);
+        assert!(reader.contains_str(&StrHash::new(ex("p").as_str()))?);
+        reader.validate()?;
         Ok(())
     }
 
 
+    fn id2str_size(storage: &Storage) -> Result<usize, StorageError> {
+        let reader = storage.snapshot();
+        let mut size = 0;
+        for key in column_family_keys(storage, &storage.id2str_cf)? {
+            size += reader.get_str(&StrHash::from_be_bytes(key.try_into().unwrap()))?.unwrap().len();
+        }
+        Ok(size)
+    }
 
-    // ############################## 将区间编码加入value中 ##############################
-    // 重写的方法
-    pub fn load_oxiuse_value<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
-        &self,
-        quads: I,
-        tree_path:&'static str
-    ) -> Result<(), EO> {
-        let system = System::new_all();
-        let cpu_count = min(4, system.physical_core_count().unwrap_or(2));
-        let num_threads = max(
-            if let Some(num_threads) = self.num_threads {
-                num_threads
-            } else if let Some(max_memory_size) = self.max_memory_size {
-                min(
-                    cpu_count,
-                    max_memory_size * 1000 / DEFAULT_BULK_LOAD_BATCH_SIZE,
-                )
-            } else {
-                cpu_count
-            },
-            2,
-        );
-        let batch_size = min(
-            if let Some(max_memory_size) = self.max_memory_size {
-                max(1000, max_memory_size * 1000 / num_threads)
-            } else {
-                max(
-                    usize::try_from(system.free_memory()).unwrap() / num_threads,
-                    DEFAULT_BULK_LOAD_BATCH_SIZE,
-                )
-            },
-            MAX_BULK_LOAD_BATCH_SIZE,
-        );
-        let mut threads = VecDeque::with_capacity(num_threads - 1);
-        let mut buffer = Vec::with_capacity(batch_size);
-        let done_counter = Arc::new(AtomicU64::new(0));
-        let mut done_and_displayed_counter = 0;
+    #[test]
+    fn test_prefixed_named_nodes() -> Result<(), StorageError> {
+        let namespace = "http://example.com/a/rather/long/namespace/";
+        let quads = (0..100)
+            .map(|i| {
+                let node = |name: &str| NamedNode::new_unchecked(format!("{}{}{}", namespace, name, i));
+                Quad::new(node("s"), node("p"), node("o"), GraphName::DefaultGraph)
+            })
+            .collect::<Vec<_>>();
+
+        let plain = Storage::new()?;
+        insert_all(&plain, &quads)?;
+
+        let prefixed = Storage::new()?;
+        assert_eq!(prefixed.register_prefix("http://example.com/")?, 0);
+        assert_eq!(prefixed.register_prefix(namespace)?, 1);
+        assert_eq!(prefixed.register_prefix("http://example.com/")?, 0);
+        insert_all(&prefixed, &quads)?;
+        // 存储不为空时不能再注册前缀，否则已经写入的 IRI 会有两种编码
+        assert!(prefixed.register_prefix("http://example.org/").is_err());
+
+        // 使用最长的前缀，解码之后得到原来的四元组
+        let reader = prefixed.snapshot();
+        let encoded = reader.encode_quad(quads[0].as_ref());
+        assert!(matches!(encoded.subject, EncodedTerm::PrefixedNamedNode { prefix_id: 1, .. }));
+        assert!(reader.contains(&encoded)?);
+        let mut decoded = reader
+            .quads()
+            .map(|q| reader.decode_quad(&q?))
+            .collect::<Result<Vec<_>, _>>()?;
+        decoded.sort_by_key(|q| q.to_string());
+        let mut expected = quads.clone();
+        expected.sort_by_key(|q| q.to_string());
+        assert_eq!(decoded, expected);
+
+        assert!(id2str_size(&prefixed)? + 3 * 100 * namespace.len() <= id2str_size(&plain)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefixed_named_nodes_have_a_single_encoding() -> Result<(), StorageError> {
+        let namespace = "http://example.com/ns/";
+        let node = |name: &str| NamedNode::new_unchecked(format!("{}{}", namespace, name));
+        let storage = Storage::new()?;
+        storage.register_prefix("http://example.com/")?;
+        storage.register_prefix(namespace)?;
+        let quoted = Triple::new(node("s"), node("p"), node("o"));
+        let quads = vec![
+            Quad::new(node("s"), node("p"), node("o"), GraphName::DefaultGraph),
+            Quad::new(quoted.clone(), ex("p"), ex("o"), ex("g")),
+            Quad::new(ex("s"), ex("p"), Literal::new_typed_literal("1", node("datatype")), GraphName::DefaultGraph),
+        ];
+        insert_all(&storage, &quads)?;
+
+        // 查询、删除与写入使用同一个编码，被引用三元组中的 IRI 也一样
+        let reader = storage.snapshot();
+        for quad in &quads {
+            assert!(reader.contains_quad(quad.as_ref())?);
+            assert_eq!(&reader.decode_quad(&reader.encode_quad(quad.as_ref()))?, quad);
+        }
+        assert!(matches!(
+            reader.encode_term(&quoted),
+            EncodedTerm::Triple(triple) if matches!(triple.subject, EncodedTerm::PrefixedNamedNode { prefix_id: 1, .. })
+        ));
+        assert_eq!(reader.predicate_stats()?.len(), 2);
+        let mut iris = reader.iris_with_prefix(namespace).collect::<Result<Vec<_>, _>>()?;
+        iris.sort();
+        assert_eq!(iris, vec![node("o"), node("p"), node("s")]);
+        assert_eq!(reader.iris_with_prefix("http://example.com/").count(), 7);
+        reader.validate()?;
+
+        assert!(storage.transaction(|mut writer| writer.remove(quads[1].as_ref()))?);
+        assert!(!storage.snapshot().contains_quad(quads[1].as_ref())?);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_multi_tree_bytes_round_trip() -> Result<(), StorageError> {
+        let loader = FileBulkLoader::new(Storage::new()?);
+        let (classes, properties) = loader.construct_tree_from_quads(vec![
+            Quad::new(ex("b"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("a"), GraphName::DefaultGraph),
+            Quad::new(ex("c"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("a"), GraphName::DefaultGraph),
+            Quad::new(ex("c"), NamedNode::new_unchecked(rdfs::SUB_CLASS_OF), ex("d"), GraphName::DefaultGraph),
+        ])?;
+        let class_nodes = ["http://example.com/a", "http://example.com/b", "http://example.com/c", "http://example.com/d"];
+        let decoded = MultiTree::from_bytes(&classes.to_bytes()?).unwrap();
+        assert_eq!(tree_layout(&decoded, &class_nodes), tree_layout(&classes, &class_nodes));
+        assert_eq!(decoded.to_bytes()?, classes.to_bytes()?);
+        assert_eq!(MultiTree::from_bytes(&properties.to_bytes()?).unwrap().to_bytes()?, properties.to_bytes()?);
+
+        let bytes = classes.to_bytes()?;
+        assert!(MultiTree::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        Ok(())
+    }
 
-        for quad in quads {
-            let quad = quad?;
-            buffer.push(quad);    // 其中是Quad
-            if buffer.len() >= batch_size {
-                self.spawn_load_thread_oxiuse_value(  // TODO：记得修改方法
-                    &mut buffer,
-                    &mut threads,
-                    &done_counter,
-                    &mut done_and_displayed_counter,
-                    num_threads,
-                    tree_path
-                )?;
+    #[test]
+    fn test_sequential_interval_loads_reuse_trees() -> Result<(), StorageError> {
+        let ontology = [
+            format!("<http://example.com/a> <{}> <{}> .\n", rdfs::SUB_CLASS_OF, owl::OWL_CLASS),
+            format!("<http://example.com/b> <{}> <http://example.com/a> .\n", rdfs::SUB_CLASS_OF),
+            format!("<http://example.com/c> <{}> <http://example.com/a> .\n", rdfs::SUB_CLASS_OF),
+        ];
+        // 第二个文件中兄弟节点的顺序相反，若重新构造树，b 与 c 的区间会互换，所以使用它的加载会失败
+        let tree_files = [
+            TreeFile::new(&ontology.concat())?,
+            TreeFile::new(&[ontology[0].clone(), ontology[2].clone(), ontology[1].clone()].concat())?,
+        ];
+        let paths = tree_files.iter().map(TreeFile::path).collect::<Vec<_>>();
+        let first = vec![Quad::new(ex("s1"), NamedNode::new_unchecked(rdf::TYPE), ex("b"), GraphName::DefaultGraph)];
+        let second = vec![Quad::new(ex("s2"), NamedNode::new_unchecked(rdf::TYPE), ex("c"), GraphName::DefaultGraph)];
+
+        for mode in [LoadMode::IntervalValue as fn(&str) -> LoadMode<'_>, LoadMode::IntervalKey] {
+            let sequential = Storage::new()?;
+            StorageBulkLoader::new(sequential.clone())
+                .load_with_mode::<StorageError, StorageError, _>(first.iter().cloned().map(Ok), mode(paths[0]))?;
+            StorageBulkLoader::new(sequential.clone())
+                .load_with_mode::<StorageError, StorageError, _>(second.iter().cloned().map(Ok), mode(paths[0]))?;
+            assert!(StorageBulkLoader::new(sequential.clone())
+                .load_with_mode::<StorageError, StorageError, _>(second.iter().cloned().map(Ok), mode(paths[1]))
+                .is_err());
+
+            let combined = Storage::new()?;
+            StorageBulkLoader::new(combined.clone()).load_with_mode::<StorageError, StorageError, _>(
+                first.iter().chain(&second).cloned().map(Ok),
+                mode(paths[0]),
+            )?;
+
+            for (sequential_cf, combined_cf) in [
+                (&sequential.dspo_cf, &combined.dspo_cf),
+                (&sequential.dpos_cf, &combined.dpos_cf),
+                (&sequential.dosp_cf, &combined.dosp_cf),
+            ] {
+                let keys = column_family_keys(&sequential, sequential_cf)?;
+                assert_eq!(keys, column_family_keys(&combined, combined_cf)?);
+                for key in keys {
+                    assert_eq!(
+                        sequential.snapshot().reader.get(sequential_cf, &key)?.as_deref(),
+                        combined.snapshot().reader.get(combined_cf, &key)?.as_deref()
+                    );
+                }
             }
         }
-        self.spawn_load_thread_oxiuse_value(    // TODO：记得修改方法
-            &mut buffer,
-            &mut threads,
-            &done_counter,
-            &mut done_and_displayed_counter,
-            num_threads,
-            tree_path
-        )?;
-        for thread in threads {
-            thread.join().unwrap()?;
-            self.on_possible_progress(&done_counter, &mut done_and_displayed_counter);
-        }
         Ok(())
     }
 
-    // 在这个版本中才加入tree的读取构造
-    fn spawn_load_thread_oxiuse_value(
-        &self,
-        buffer: &mut Vec<Quad>,
-        threads: &mut VecDeque<JoinHandle<Result<(), StorageError>>>,
-        done_counter: &Arc<AtomicU64>,
-        done_and_displayed_counter: &mut u64,
-        num_threads: usize,
-        tree_path: &'static str
-    ) -> Result<(), StorageError> {
-        self.on_possible_progress(done_counter, done_and_displayed_counter);
-        // We avoid to have too many threads
-        if threads.len() >= num_threads {
-            if let Some(thread) = threads.pop_front() {
-                thread.join().unwrap()?;
-                self.on_possible_progress(done_counter, done_and_displayed_counter);
+
+    #[test]
+    fn test_hierarchy_closure() -> Result<(), StorageError> {
+        let sub_class_of = NamedNode::new_unchecked(rdfs::SUB_CLASS_OF);
+        let class = NamedNode::new_unchecked(owl::OWL_CLASS);
+        let ontology = vec![
+            Quad::new(ex("a"), sub_class_of.clone(), class.clone(), GraphName::DefaultGraph),
+            Quad::new(ex("b"), sub_class_of.clone(), ex("a"), GraphName::DefaultGraph),
+            Quad::new(ex("c"), sub_class_of.clone(), ex("b"), GraphName::DefaultGraph),
+            Quad::new(ex("d"), sub_class_of, ex("a"), GraphName::DefaultGraph),
+        ];
+        let tree_file = TreeFile::from_quads(&ontology)?;
+        let tree_path = tree_file.path();
+
+        // 注册了 rdfs 的命名空间时 rdfs:subClassOf 也使用前缀编码
+        for prefixes in [&[][..], &["http://example.com/", "http://www.w3.org/2000/01/rdf-schema#"][..]] {
+            let storage = Storage::new()?;
+            for prefix in prefixes {
+                storage.register_prefix(prefix)?;
             }
+            StorageBulkLoader::new(storage.clone())
+                .load_oxiuse_value::<StorageError, StorageError, _>(ontology.iter().cloned().map(Ok), tree_path)?;
+            let reader = storage.snapshot();
+            let encoded = |node: &NamedNode| reader.encode_named_node(node.as_ref());
+
+            assert_eq!(
+                reader.descendants_of(&encoded(&ex("a")))?,
+                vec![encoded(&ex("b")), encoded(&ex("c")), encoded(&ex("d"))],
+                "{:?}",
+                prefixes
+            );
+            assert_eq!(reader.descendants_of(&encoded(&ex("b")))?, vec![encoded(&ex("c"))]);
+            assert!(reader.descendants_of(&encoded(&ex("c")))?.is_empty());
+            assert_eq!(
+                reader.ancestors_of(&encoded(&ex("c")))?,
+                vec![encoded(&class), encoded(&ex("a")), encoded(&ex("b"))],
+                "{:?}",
+                prefixes
+            );
+            assert!(reader.ancestors_of(&encoded(&ex("unknown")))?.is_empty());
         }
+        Ok(())
+    }
 
-        // 为了在线程之中安全转移，之后传递的是vec
-        let buffer = take(buffer);
-        let storage = self.storage.clone();
-        let done_counter_clone = done_counter.clone();
 
-        // TODO:多线程的问题还没解决
-        // 这大概是使用多线程插入数据，速度会加快，move会将所有权丢给线程
-        threads.push_back(spawn( move || {
-            FileBulkLoader::new(storage).load_oxiuse_value(buffer, &done_counter_clone, tree_path)   // TODO:记得修改方法
-        }));
+    #[test]
+    fn test_decoded_named_graphs() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let blank = BlankNode::new_unchecked("a-blank-node-id-longer-than-16-bytes");
+        storage.transaction(|mut writer| {
+            writer.insert_named_graph(ex("g1").as_ref().into())?;
+            writer.insert_named_graph(blank.as_ref().into())?;
+            Ok::<_, StorageError>(())
+        })?;
+
+        let graphs = storage.snapshot().decoded_named_graphs().collect::<Result<HashSet<_>, _>>()?;
+        assert_eq!(graphs, HashSet::from([NamedOrBlankNode::from(ex("g1")), NamedOrBlankNode::from(blank.clone())]));
+
+        // id2str 中缺少大空白节点的字符串时应该报错而不是跳过
+        storage.transaction(|mut writer| {
+            writer.transaction.remove(&storage.id2str_cf, &StrHash::new(blank.as_str()).to_be_bytes())
+        })?;
+        let results = storage.snapshot().decoded_named_graphs().collect::<Vec<_>>();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|r| matches!(r, Err(StorageError::Corruption(_)))));
+        Ok(())
+    }
 
-        self.on_possible_progress(done_counter, done_and_displayed_counter);
+
+    #[test]
+    fn test_rename_graph() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s1"), ex("p"), ex("o"), ex("g1")),
+                Quad::new(ex("s2"), ex("p"), ex("o"), ex("g1")),
+                Quad::new(ex("s3"), ex("p"), ex("o"), ex("g1")),
+                Quad::new(ex("s1"), ex("p"), ex("o"), ex("g2")),
+                Quad::new(ex("s4"), ex("p"), ex("o"), ex("g2")),
+            ],
+        )?;
+
+        let moved = storage.transaction(|mut writer| {
+            writer.rename_graph(ex("g1").as_ref().into(), ex("g2").as_ref().into())
+        })?;
+        assert_eq!(moved, 3);
+
+        let reader = storage.snapshot();
+        let g1 = EncodedTerm::from(ex("g1").as_ref());
+        let g2 = EncodedTerm::from(ex("g2").as_ref());
+        assert!(!reader.contains_named_graph(&g1)?);
+        assert!(reader.contains_named_graph(&g2)?);
+        assert_eq!(reader.quads_for_graph(&g1).count(), 0);
+        assert_eq!(reader.quads_for_graph(&g2).count(), 4);
+        assert_eq!(reader.len()?, 4);
+        assert!(reader.contains_quad(QuadRef::new(ex("s3").as_ref(), ex("p").as_ref(), ex("o").as_ref(), ex("g2").as_ref()))?);
+
+        let moved = storage.transaction(|mut writer| {
+            writer.rename_graph(ex("g2").as_ref().into(), ex("g2").as_ref().into())
+        })?;
+        assert_eq!(moved, 0);
+        assert_eq!(storage.snapshot().quads_for_graph(&g2).count(), 4);
         Ok(())
     }
 
-    // ############################## 将区间编码加入key中 ##############################
-    pub fn load_oxiuse_key<EI, EO: From<StorageError> + From<EI>, I: IntoIterator<Item = Result<Quad, EI>>>(
-        &self,
-        quads: I,
-        tree_path:&'static str
-    ) -> Result<(), EO> {
-        let system = System::new_all();
-        let cpu_count = min(4, system.physical_core_count().unwrap_or(2));
-        let num_threads = max(
-            if let Some(num_threads) = self.num_threads {
-                num_threads
-            } else if let Some(max_memory_size) = self.max_memory_size {
-                min(
-                    cpu_count,
-                    max_memory_size * 1000 / DEFAULT_BULK_LOAD_BATCH_SIZE,
-                )
-            } else {
-                cpu_count
-            },
-            2,
-        );
-        let batch_size = min(
-            if let Some(max_memory_size) = self.max_memory_size {
-                max(1000, max_memory_size * 1000 / num_threads)
-            } else {
-                max(
-                    usize::try_from(system.free_memory()).unwrap() / num_threads,
-                    DEFAULT_BULK_LOAD_BATCH_SIZE,
-                )
-            },
-            MAX_BULK_LOAD_BATCH_SIZE,
+
+    #[test]
+    fn test_copy_graph() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s1"), ex("p"), ex("o"), ex("g1")),
+                Quad::new(ex("s2"), ex("p"), ex("o"), ex("g1")),
+                Quad::new(ex("s3"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            ],
+        )?;
+        let g1 = EncodedTerm::from(ex("g1").as_ref());
+        let g2 = EncodedTerm::from(ex("g2").as_ref());
+        let g3 = EncodedTerm::from(ex("g3").as_ref());
+        let graph = |graph_name: &EncodedTerm| -> Result<Vec<EncodedQuad>, StorageError> {
+            storage.snapshot().quads_for_graph(graph_name).collect()
+        };
+        let g1_quads = graph(&g1)?;
+
+        // 命名图 → 默认图
+        let copied = storage.transaction(|mut writer| {
+            writer.copy_graph(ex("g1").as_ref().into(), GraphNameRef::DefaultGraph)
+        })?;
+        assert_eq!(copied, 2);
+        assert_eq!(graph(&g1)?, g1_quads);
+        assert_eq!(graph(&EncodedTerm::DefaultGraph)?.len(), 3);
+        let reader = storage.snapshot();
+        let s1 = EncodedTerm::from(ex("s1").as_ref());
+        let o = EncodedTerm::from(ex("o").as_ref());
+        assert_eq!(reader.quads_for_subject(&s1).filter(|q| q.as_ref().map_or(false, |q| q.graph_name.is_default_graph())).count(), 1);
+        assert_eq!(reader.quads_for_object(&o).count(), 5);
+
+        // 默认图 → 命名图
+        let default_quads = graph(&EncodedTerm::DefaultGraph)?;
+        let copied = storage.transaction(|mut writer| {
+            writer.copy_graph(GraphNameRef::DefaultGraph, ex("g2").as_ref().into())
+        })?;
+        assert_eq!(copied, 3);
+        assert_eq!(graph(&EncodedTerm::DefaultGraph)?, default_quads);
+        assert!(storage.snapshot().contains_named_graph(&g2)?);
+        assert_eq!(graph(&g2)?.len(), 3);
+
+        // 命名图 → 命名图
+        let copied = storage.transaction(|mut writer| {
+            writer.copy_graph(ex("g1").as_ref().into(), ex("g3").as_ref().into())
+        })?;
+        assert_eq!(copied, 2);
+        assert_eq!(graph(&g1)?, g1_quads);
+        assert!(storage.snapshot().contains_named_graph(&g3)?);
+        assert_eq!(
+            graph(&g3)?.into_iter().map(|q| q.subject).collect::<Vec<_>>(),
+            g1_quads.into_iter().map(|q| q.subject).collect::<Vec<_>>()
         );
-        let mut threads = VecDeque::with_capacity(num_threads - 1);
-        let mut buffer = Vec::with_capacity(batch_size);
-        let done_counter = Arc::new(AtomicU64::new(0));
-        let mut done_and_displayed_counter = 0;
+        assert_eq!(storage.snapshot().len()?, 10);
+
+        // 目标图中已有的元组不重复写入，也不计数
+        insert_all(&storage, &[Quad::new(ex("s4"), ex("p"), ex("o"), ex("g1"))])?;
+        let copied = storage.transaction(|mut writer| {
+            writer.copy_graph(ex("g1").as_ref().into(), ex("g3").as_ref().into())
+        })?;
+        assert_eq!(copied, 1);
+        assert_eq!(graph(&g3)?.len(), 3);
+        let copied = storage.transaction(|mut writer| {
+            writer.copy_graph(GraphNameRef::DefaultGraph, ex("g2").as_ref().into())
+        })?;
+        assert_eq!(copied, 0);
+        assert_eq!(storage.snapshot().len()?, 12);
+        Ok(())
+    }
 
-        for quad in quads {
-            let quad = quad?;
-            buffer.push(quad);    // 其中是Quad
-            if buffer.len() >= batch_size {
-                self.spawn_load_thread_oxiuse_key(  // TODO：记得修改方法
-                    &mut buffer,
-                    &mut threads,
-                    &done_counter,
-                    &mut done_and_displayed_counter,
-                    num_threads,
-                    tree_path
-                )?;
+
+    #[test]
+    fn test_transaction_with_retry() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+
+        // 第一次尝试时另一个事务在快照之后写入了同一个 key，提交前的检查会报告写冲突
+        let attempts = std::cell::Cell::new(0);
+        let inserted = storage.transaction_with_retry(3, |mut writer| {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() == 1 {
+                insert_all(&storage, &[quad.clone()])?;
             }
+            writer.insert(quad.as_ref())
+        })?;
+        assert_eq!(attempts.get(), 2);
+        assert!(!inserted);
+        assert!(storage.snapshot().contains_quad(quad.as_ref())?);
+
+        // 没有剩余的重试次数时返回冲突错误
+        let attempts = std::cell::Cell::new(0);
+        let result = storage.transaction_with_retry(0, |mut writer| {
+            attempts.set(attempts.get() + 1);
+            storage.transaction(|mut other| other.remove(quad.as_ref()))?;
+            writer.insert(quad.as_ref())
+        });
+        assert!(result.unwrap_err().is_transient());
+        assert_eq!(attempts.get(), 1);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_quads_for_predicate_graph_prefix() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let mut quads = (0..100)
+            .map(|i| Quad::new(ex(&format!("s{}", i)), ex("q"), ex("o"), ex("g")))
+            .collect::<Vec<_>>();
+        for i in 0..3 {
+            quads.push(Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), ex("g")));
+            quads.push(Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), ex("g2")));
         }
-        self.spawn_load_thread_oxiuse_key(    // TODO：记得修改方法
-            &mut buffer,
-            &mut threads,
-            &done_counter,
-            &mut done_and_displayed_counter,
-            num_threads,
-            tree_path
-        )?;
-        for thread in threads {
-            thread.join().unwrap()?;
-            self.on_possible_progress(&done_counter, &mut done_and_displayed_counter);
+        quads.push(Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph));
+        insert_all(&storage, &quads)?;
+
+        let p = EncodedTerm::from(ex("p").as_ref());
+        let g = EncodedTerm::from(ex("g").as_ref());
+        let reader = storage.snapshot();
+        let results = reader
+            .quads_for_pattern(None, Some(&p), None, Some(&g))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|q| q.predicate == p && q.graph_name == g));
+
+        // 底层迭代器访问的每一个 key 都以 (graph, predicate) 为前缀
+        let iter = reader.quads_for_pattern(None, Some(&p), None, Some(&g));
+        assert!(iter.second.is_none());
+        assert_eq!(iter.first.encoding, QuadEncoding::Gpos);
+        let mut raw = iter.first.iter.unwrap();
+        let prefix = encode_term_pair(&g, &p);
+        let mut visited = 0;
+        while let Some(key) = raw.key() {
+            assert!(key.starts_with(&prefix));
+            visited += 1;
+            raw.next();
         }
+        raw.status()?;
+        assert_eq!(visited, 3);
         Ok(())
     }
 
-    // 在这个版本中才加入tree的读取构造
-    fn spawn_load_thread_oxiuse_key(
-        &self,
-        buffer: &mut Vec<Quad>,
-        threads: &mut VecDeque<JoinHandle<Result<(), StorageError>>>,
-        done_counter: &Arc<AtomicU64>,
-        done_and_displayed_counter: &mut u64,
-        num_threads: usize,
-        tree_path: &'static str
-    ) -> Result<(), StorageError> {
-        self.on_possible_progress(done_counter, done_and_displayed_counter);
-        // We avoid to have too many threads
-        if threads.len() >= num_threads {
-            if let Some(thread) = threads.pop_front() {
-                thread.join().unwrap()?;
-                self.on_possible_progress(done_counter, done_and_displayed_counter);
-            }
+
+    #[test]
+    fn test_spill_id2str_to_disk() -> Result<(), StorageError> {
+        let value = |i: usize| format!("{}-{}", i, "x".repeat(1000));
+        let quads = (0..3000)
+            .map(|i| Quad::new(ex("s"), ex("p"), Literal::from(value(i)), GraphName::DefaultGraph))
+            .collect::<Vec<_>>();
+
+        // 单个批次在保存之前已经溢写了多个文件
+        let storage = Storage::new()?;
+        let mut loader = FileBulkLoader::new(storage.clone()).with_id2str_spill(100_000);
+        loader.encode(quads[..1000].iter().cloned())?;
+        assert!(loader.id2str_runs.0.len() > 1);
+        let runs = loader.id2str_runs.0.clone();
+        loader.save(TripleEncodingMode::Plain, None)?;
+        assert!(runs.iter().all(|path| !path.exists()));
+        let reader = storage.snapshot();
+        for i in 0..1000 {
+            assert_eq!(reader.get_str(&StrHash::new(&value(i)))?, Some(value(i)));
         }
 
-        // 为了在线程之中安全转移，之后传递的是vec
-        let buffer = take(buffer);
-        let storage = self.storage.clone();
-        let done_counter_clone = done_counter.clone();
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone())
+            .set_num_threads(2)
+            .set_max_memory_size_in_megabytes(1)
+            .spill_id2str_to_disk()
+            .load::<StorageError, StorageError, _>(quads.into_iter().map(Ok))?;
+        let reader = storage.snapshot();
+        assert_eq!(reader.len()?, 3000);
+        for i in 0..3000 {
+            assert_eq!(reader.get_str(&StrHash::new(&value(i)))?, Some(value(i)));
+        }
+        assert_eq!(reader.get_str(&StrHash::new("http://example.com/s"))?.as_deref(), Some("http://example.com/s"));
+        Ok(())
+    }
 
-        // TODO:多线程的问题还没解决
-        // 这大概是使用多线程插入数据，速度会加快，move会将所有权丢给线程
-        threads.push_back(spawn( move || {
-            FileBulkLoader::new(storage).load_oxiuse_key(buffer, &done_counter_clone, tree_path)   // TODO:记得修改方法
-        }));
 
-        self.on_possible_progress(done_counter, done_and_displayed_counter);
+    #[test]
+    fn test_iri_cache_keeps_id2str() -> Result<(), StorageError> {
+        // 同一批 IRI 反复出现，缓存会被清空多次
+        let quads = (0..3 * IRI_CACHE_SIZE)
+            .map(|i| {
+                Quad::new(
+                    ex(&format!("s{}", i % (IRI_CACHE_SIZE + 7))),
+                    ex(&format!("p{}", i % 10)),
+                    if i % 2 == 0 {
+                        Term::from(ex(&format!("o{}", i)))
+                    } else {
+                        Literal::from(format!("a literal that is not inlined {}", i % 100)).into()
+                    },
+                    if i % 3 == 0 {
+                        GraphName::DefaultGraph
+                    } else {
+                        ex(&format!("g{}", i % 5)).into()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let storage = Storage::new()?;
+        // 只有注册了前缀时才会使用缓存
+        storage.register_prefix("http://example.com/")?;
+        let mut cached = FileBulkLoader::new(storage.clone());
+        cached.encode(quads.iter().cloned())?;
+        let mut uncached = FileBulkLoader::new(storage.clone()).without_iri_cache();
+        uncached.encode(quads.iter().cloned())?;
+
+        assert_eq!(cached.id2str, uncached.id2str);
+        assert_eq!(cached.triples, uncached.triples);
+        assert_eq!(cached.quads, uncached.quads);
+        assert_eq!(cached.graphs, uncached.graphs);
+        assert_eq!(
+            cached.quads.iter().chain(&cached.triples).cloned().collect::<HashSet<_>>(),
+            quads.iter().map(|quad| storage.prefix_table().encode_quad(quad.as_ref())).collect()
+        );
         Ok(())
     }
 
 
-    fn on_possible_progress(&self, done: &AtomicU64, done_and_displayed: &mut u64) {
-        let new_counter = done.fetch_max(*done_and_displayed, Ordering::Relaxed);
-        let display_step = u64::try_from(DEFAULT_BULK_LOAD_BATCH_SIZE).unwrap();
-        if new_counter % display_step > *done_and_displayed % display_step {
-            for hook in &self.hooks {
-                hook(new_counter);
-            }
+    #[test]
+    fn test_on_progress_detailed() -> Result<(), StorageError> {
+        let long = Literal::from("a literal that is longer than sixteen bytes");
+        let quads = vec![
+            Quad::new(ex("s1"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            Quad::new(ex("s2"), ex("p"), long.clone(), GraphName::DefaultGraph),
+            // 短字面量直接内联在编码中，不会写入 id2str
+            Quad::new(ex("s1"), ex("p"), Literal::from("short"), ex("g")),
+        ];
+        // s1、s2、p、o、g 以及长字面量
+        let distinct_strings = 6;
+
+        let last = std::rc::Rc::new(std::cell::Cell::new(LoadProgress::default()));
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone())
+            .on_progress_detailed({
+                let last = last.clone();
+                move |progress| last.set(progress)
+            })
+            .load::<StorageError, StorageError, _>(quads.into_iter().map(Ok))?;
+
+        let progress = last.get();
+        assert_eq!(progress.quads, 3);
+        assert_eq!(progress.id2str_entries, distinct_strings);
+        assert!(progress.sst_bytes > 0);
+        assert_eq!(storage.snapshot().get_str(&StrHash::new(long.value()))?.as_deref(), Some(long.value()));
+        Ok(())
+    }
+
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_disk_usage() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone()).load::<StorageError, StorageError, _>(
+            [
+                Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+                Quad::new(ex("s"), ex("p"), Literal::from("a literal that is longer than sixteen bytes"), ex("g")),
+            ]
+            .into_iter()
+            .map(Ok),
+        )?;
+        storage.flush()?;
+
+        let usage = storage.disk_usage()?;
+        assert_eq!(usage.len(), StorageColumnFamily::ALL.len());
+        for which in [
+            StorageColumnFamily::Id2str,
+            StorageColumnFamily::Dspo,
+            StorageColumnFamily::Dpos,
+            StorageColumnFamily::Dosp,
+            StorageColumnFamily::Gspo,
+            StorageColumnFamily::Spog,
+        ] {
+            assert!(usage[which.name()] > 0, "{} should not be empty", which.name());
         }
-        *done_and_displayed = new_counter;
+        Ok(())
     }
-}
 
 
+    #[test]
+    fn test_compact_graph() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        StorageBulkLoader::new(storage.clone()).load::<StorageError, StorageError, _>(
+            (0..1000).flat_map(|i| {
+                [
+                    Quad::new(ex(&format!("s{}", i)), ex("p"), ex(&format!("o{}", i)), ex("g")),
+                    Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), ex("kept")),
+                ]
+            })
+            .map(Ok),
+        )?;
+        storage.flush()?;
+        let graph_prefixed_indexes = [
+            StorageColumnFamily::Gspo,
+            StorageColumnFamily::Gpos,
+            StorageColumnFamily::Gosp,
+        ];
+        let graph_last_indexes = [
+            StorageColumnFamily::Spog,
+            StorageColumnFamily::Posg,
+            StorageColumnFamily::Ospg,
+        ];
+        let before = storage.disk_usage()?;
+
+        storage.transaction(|mut writer| writer.clear_graph(ex("g").as_ref().into()))?;
+        storage.flush()?;
+        let flushed = storage.disk_usage()?;
+        storage.compact_graph(&ex("g").as_ref().into(), false)?;
+
+        // 默认只压缩以图为前缀的三个索引，其余索引保持不变
+        let after = storage.disk_usage()?;
+        for which in graph_prefixed_indexes {
+            assert!(
+                after[which.name()] < before[which.name()],
+                "{} should be smaller after compaction",
+                which.name()
+            );
+        }
+        for which in graph_last_indexes {
+            assert_eq!(
+                after[which.name()],
+                flushed[which.name()],
+                "{} should not be compacted",
+                which.name()
+            );
+        }
 
-#[cfg(not(target_arch = "wasm32"))]
-struct FileBulkLoader {
-    storage: Storage,
-    id2str: HashMap<StrHash, Box<str>>,
-    quads: HashSet<EncodedQuad>,
-    triples: HashSet<EncodedQuad>,
-    graphs: HashSet<EncodedTerm>,
-}
+        storage.compact_graph(&ex("g").as_ref().into(), true)?;
+        let after = storage.disk_usage()?;
+        for which in graph_last_indexes {
+            assert!(
+                after[which.name()] < before[which.name()],
+                "{} should be smaller after compaction",
+                which.name()
+            );
+        }
+        let reader = storage.snapshot();
+        assert_eq!(reader.quads_for_graph(&ex("g").as_ref().into()).count(), 0);
+        assert_eq!(reader.quads_for_graph(&ex("kept").as_ref().into()).count(), 1000);
 
-#[cfg(not(target_arch = "wasm32"))]
-impl FileBulkLoader {
-    fn new(storage: Storage) -> Self {
-        Self {
-            storage,
-            id2str: HashMap::default(),
-            quads: HashSet::default(),
-            triples: HashSet::default(),
-            graphs: HashSet::default(),
+        // 默认图没有图前缀，只有 full_indexes 时才压缩整个 d* 索引
+        storage.compact_graph(&EncodedTerm::DefaultGraph, false)?;
+        storage.compact_graph(&EncodedTerm::DefaultGraph, true)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        assert_eq!(storage.diagnostics().triple_encoding_mode, None);
+        StorageBulkLoader::new(storage.clone()).load::<StorageError, StorageError, _>(
+            (0..100).map(|i| Ok(Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), GraphName::DefaultGraph))),
+        )?;
+        storage.flush()?;
+
+        let diagnostics = storage.diagnostics();
+        assert!(diagnostics.errors.is_empty(), "{:?}", diagnostics.errors);
+        assert_eq!(diagnostics.version, Some(LATEST_STORAGE_VERSION));
+        assert_eq!(diagnostics.triple_encoding_mode, Some(TripleEncodingMode::Plain));
+        assert_eq!(diagnostics.column_families.len(), StorageColumnFamily::ALL.len());
+        for which in [StorageColumnFamily::Dspo, StorageColumnFamily::Dpos, StorageColumnFamily::Dosp] {
+            let column_family = diagnostics.column_families[which.name()];
+            assert!(column_family.sst_bytes.unwrap() > 0, "{} should not be empty", which.name());
+            assert!(column_family.estimated_keys.unwrap() > 0, "{} should not be empty", which.name());
         }
+        Ok(())
     }
 
-    
-    fn load(
-        &mut self,
-        quads: impl IntoIterator<Item = Quad>,
-        counter: &AtomicU64,
-        
-    ) -> Result<(), StorageError> {
-        self.encode(quads)?;   
+    #[test]
+    fn test_bloom_filter_round_trip() -> Result<(), StorageError> {
+        let column_families = Storage::initial_column_families()
+            .into_iter()
+            .map(|mut cf| {
+                cf.bloom_filter_bits = Some(10.);
+                cf
+            })
+            .collect();
+        let storage = Storage::setup(Db::new(column_families)?)?;
+        let long = Literal::from("a literal that is longer than sixteen bytes");
+        let quad = Quad::new(ex("s"), ex("p"), long.clone(), ex("g"));
+        storage.transaction(|mut writer| writer.insert(quad.as_ref()))?;
+        #[cfg(not(target_arch = "wasm32"))]
+        storage.flush()?;
 
-        let size = self.triples.len() + self.quads.len();
+        let reader = storage.snapshot();
+        assert!(reader.contains_quad(quad.as_ref())?);
+        assert_eq!(reader.get_str(&StrHash::new(long.value()))?.as_deref(), Some(long.value()));
+        assert!(!reader.contains_str(&StrHash::new("not stored"))?);
+        assert_eq!(reader.quads().collect::<Result<Vec<_>, _>>()?, vec![quad.as_ref().into()]);
+        Ok(())
+    }
 
-        self.save()?;    
-        
-        counter.fetch_add(size.try_into().unwrap(), Ordering::Relaxed);
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_validate_parallel() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let mut quads = (0..100)
+            .map(|i| Quad::new(ex(&format!("s{}", i)), ex("p"), Literal::from(i), ex(&format!("g{}", i % 7))))
+            .collect::<Vec<_>>();
+        quads.extend((0..100).map(|i| {
+            Quad::new(
+                BlankNode::default(),
+                ex("p"),
+                Literal::from(format!("a literal that is not inlined {}", i)),
+                GraphName::DefaultGraph,
+            )
+        }));
+        insert_all(&storage, &quads)?;
+        storage.snapshot().validate()?;
+        for num_threads in [0, 1, 3, 16] {
+            storage.snapshot().validate_parallel(num_threads)?;
+        }
+        // 所有线程读取的都是这个 reader 的快照，之后的写入不可见
+        let before = storage.snapshot();
+        // 事务的 reader 不能被其他线程共享
+        assert!(storage
+            .transaction(|writer| writer.reader().validate_parallel(4))
+            .is_err());
+
+        // 索引的大小不变，只有逐条检查才能发现
+        storage.transaction(|mut writer| {
+            writer
+                .transaction
+                .remove(&storage.graphs_cf, &encode_term(&ex("g3").as_ref().into()))?;
+            writer.transaction.remove(
+                &storage.id2str_cf,
+                &StrHash::new("a literal that is not inlined 42").to_be_bytes(),
+            )
+        })?;
+        let expected = storage.snapshot().validate().unwrap_err().to_string();
+        for num_threads in [0, 1, 3, 16] {
+            assert_eq!(
+                storage.snapshot().validate_parallel(num_threads).unwrap_err().to_string(),
+                expected
+            );
+        }
+        before.validate_parallel(4)?;
+
+        // 修复三元组之后两者都只剩下命名图的错误
+        let value = "a literal that is not inlined 42";
+        storage.transaction(|mut writer| writer.insert_str(&StrHash::new(value), value))?;
+        let expected = storage.snapshot().validate().unwrap_err().to_string();
+        assert!(expected.contains("not in graphs"));
+        assert_eq!(storage.snapshot().validate_parallel(4).unwrap_err().to_string(), expected);
         Ok(())
     }
 
-    // 该方法主要是获得self的id2str hashmap
-    fn encode(&mut self, quads: impl IntoIterator<Item = Quad>) -> Result<(), StorageError> {
-        for quad in quads {
-            let encoded = EncodedQuad::from(quad.as_ref());   // 转成EncodedQuad，由EcodedTerm组成
-            if quad.graph_name.is_default_graph() {
-                if self.triples.insert(encoded.clone()) {   // 先在自己的triples中插入EncodedQuad，然后将spo传入insert_term方法（不会重复插入）
-                    self.insert_term(quad.subject.as_ref().into(), &encoded.subject)?;
-                    self.insert_term(quad.predicate.as_ref().into(), &encoded.predicate)?;
-                    self.insert_term(quad.object.as_ref(), &encoded.object)?;
-                }
-            } else if self.quads.insert(encoded.clone()) {
-                self.insert_term(quad.subject.as_ref().into(), &encoded.subject)?;
-                self.insert_term(quad.predicate.as_ref().into(), &encoded.predicate)?;
-                self.insert_term(quad.object.as_ref(), &encoded.object)?;
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_validate_names_dosp() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quad = EncodedQuad::from(Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph).as_ref());
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph)])?;
+        // 用另一个 key 替换 dosp 中的条目，索引的大小不变
+        storage.transaction(|mut writer| {
+            writer.transaction.remove(
+                &storage.dosp_cf,
+                &encode_term_triple(&quad.object, &quad.subject, &quad.predicate),
+            )?;
+            writer.transaction.insert_empty(
+                &storage.dosp_cf,
+                &encode_term_triple(&quad.subject, &quad.object, &quad.predicate),
+            )
+        })?;
+        let error = storage.snapshot().validate().unwrap_err().to_string();
+        assert!(error.contains("not in dosp"), "{}", error);
+        Ok(())
+    }
 
-                if self.graphs.insert(encoded.graph_name.clone()) {
-                    self.insert_term(
-                        match quad.graph_name.as_ref() {
-                            GraphNameRef::NamedNode(n) => n.into(),
-                            GraphNameRef::BlankNode(n) => n.into(),
-                            GraphNameRef::DefaultGraph => unreachable!(),
-                        },
-                        &encoded.graph_name,
-                    )?;
-                }
-            }
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_repair() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let triple = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
+        storage.transaction(|mut writer| {
+            writer.insert(triple.as_ref())?;
+            writer.insert(quad.as_ref())
+        })?;
+        assert!(storage.repair()?.is_empty());
+
+        let encoded_triple: EncodedQuad = triple.as_ref().into();
+        let encoded_quad: EncodedQuad = quad.as_ref().into();
+        storage.transaction(|mut writer| {
+            let mut buffer = Vec::new();
+            write_pos_quad(&mut buffer, &encoded_triple);
+            writer.transaction.remove(&storage.dpos_cf, &buffer)?;
+            buffer.clear();
+            write_gosp_quad(&mut buffer, &encoded_quad);
+            writer.transaction.remove(&storage.gosp_cf, &buffer)?;
+            writer
+                .transaction
+                .remove(&storage.graphs_cf, &encode_term(&encoded_quad.graph_name))
+        })?;
+        assert!(storage.snapshot().validate().is_err());
+
+        let report = storage.repair()?;
+        assert_eq!(
+            report.added,
+            [(DPOS_CF, 1), (GOSP_CF, 1), (GRAPHS_CF, 1)].into_iter().collect()
+        );
+        storage.snapshot().validate()?;
+        assert!(storage.snapshot().contains_quad(triple.as_ref())?);
+        assert!(storage.snapshot().contains_named_graph(&encoded_quad.graph_name)?);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_open_with_recovery() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let triple = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        {
+            let storage = Storage::open(&path)?;
+            insert_all(&storage, &[triple.clone()])?;
+            // 模拟 dpos 的 SST 没有被导入
+            storage.transaction(|mut writer| {
+                let mut buffer = Vec::new();
+                write_pos_quad(&mut buffer, &triple.as_ref().into());
+                writer.transaction.remove(&storage.dpos_cf, &buffer)
+            })?;
+        }
+
+        let error = Storage::open_with_recovery(&path, RecoveryMode::Refuse)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("dpos: 0"), "{}", error);
+        {
+            let storage = Storage::open_with_recovery(&path, RecoveryMode::Repair)?;
+            storage.snapshot().validate()?;
+            assert!(storage.snapshot().contains_quad(triple.as_ref())?);
         }
+        Storage::open_with_recovery(&path, RecoveryMode::Refuse)?;
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_unregister_named_graph() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let empty = ex("empty");
+        let full = ex("full");
+        storage.transaction(|mut writer| {
+            writer.insert_named_graph(empty.as_ref().into())?;
+            writer.insert(QuadRef::new(&ex("s"), &ex("p"), &ex("o"), &full))
+        })?;
+
+        assert!(storage.transaction(|mut writer| writer.unregister_named_graph(empty.as_ref().into()))?);
+        assert!(!storage.transaction(|mut writer| writer.unregister_named_graph(empty.as_ref().into()))?);
+        assert!(!storage.snapshot().contains_named_graph(&empty.as_ref().into())?);
+
+        let error = storage
+            .transaction(|mut writer| writer.unregister_named_graph(full.as_ref().into()))
+            .unwrap_err();
+        assert!(error.to_string().contains("still contains quads"));
+        let reader = storage.snapshot();
+        assert!(reader.contains_named_graph(&full.as_ref().into())?);
+        assert_eq!(reader.len_for_graph(full.as_ref().into())?, 1);
+
+        // 先清空再取消注册
+        storage.transaction(|mut writer| writer.clear_graph(full.as_ref().into()))?;
+        assert!(storage.transaction(|mut writer| writer.unregister_named_graph(full.as_ref().into()))?);
+        let reader = storage.snapshot();
+        assert!(!reader.contains_named_graph(&full.as_ref().into())?);
+        reader.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_len_for_graph() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let g1 = ex("g1");
+        let g2 = ex("g2");
+        storage.transaction(|mut writer| {
+            writer.insert(QuadRef::new(&ex("s"), &ex("p"), &ex("o"), GraphNameRef::DefaultGraph))?;
+            for object in ["o1", "o2", "o3"] {
+                writer.insert(QuadRef::new(&ex("s"), &ex("p"), &ex(object), &g1))?;
+            }
+            writer.insert(QuadRef::new(&ex("s"), &ex("p"), &ex("o"), &g2))
+        })?;
+
+        let reader = storage.snapshot();
+        let default_len = reader.len_for_graph(GraphNameRef::DefaultGraph)?;
+        assert_eq!(default_len, 1);
+        assert_eq!(reader.len_for_graph(g1.as_ref().into())?, 3);
+        assert_eq!(reader.len_for_graph(g2.as_ref().into())?, 1);
+        assert_eq!(reader.len_for_graph(ex("missing").as_ref().into())?, 0);
+        assert_eq!(reader.named_graph_len()?, 4);
+        let named_len = reader
+            .decoded_named_graphs()
+            .map(|graph| reader.len_for_graph(graph?.as_ref().into()))
+            .sum::<Result<usize, _>>()?;
+        assert_eq!(reader.len()?, default_len + named_len);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_change_listener() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        storage.set_change_listener(Box::new({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.clone())
+        }));
+        let first = Quad::new(ex("s"), ex("p"), ex("o1"), GraphName::DefaultGraph);
+        let second = Quad::new(ex("s"), ex("p"), ex("o2"), ex("g"));
+        storage.transaction(|mut writer| {
+            writer.insert(first.as_ref())?;
+            writer.insert(second.as_ref())?;
+            writer.insert(first.as_ref())
+        })?;
+
+        let inserted = events.lock().unwrap().clone();
+        assert_eq!(
+            inserted,
+            [first, second]
+                .into_iter()
+                .map(|quad| ChangeEvent {
+                    kind: ChangeKind::Insert,
+                    quad_or_graph: QuadOrGraph::Quad(quad),
+                    pre_commit: true,
+                })
+                .collect::<Vec<_>>()
+        );
+
+        // 复制和重命名图也会通知监听器
+        let copy = Quad::new(ex("s"), ex("p"), ex("o2"), ex("copy"));
+        let renamed = Quad::new(ex("s"), ex("p"), ex("o2"), ex("renamed"));
+        events.lock().unwrap().clear();
+        storage.transaction(|mut writer| {
+            writer.copy_graph(ex("g").as_ref().into(), ex("copy").as_ref().into())
+        })?;
+        storage.transaction(|mut writer| {
+            writer.rename_graph(ex("copy").as_ref().into(), ex("renamed").as_ref().into())
+        })?;
+        let events = events.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            [
+                (ChangeKind::Insert, QuadOrGraph::Graph(ex("copy").into())),
+                (ChangeKind::Insert, QuadOrGraph::Quad(copy.clone())),
+                (ChangeKind::Insert, QuadOrGraph::Graph(ex("renamed").into())),
+                (ChangeKind::Insert, QuadOrGraph::Quad(renamed)),
+                (ChangeKind::Remove, QuadOrGraph::Quad(copy)),
+                (ChangeKind::Remove, QuadOrGraph::Graph(ex("copy").into())),
+            ]
+            .into_iter()
+            .map(|(kind, quad_or_graph)| ChangeEvent {
+                kind,
+                quad_or_graph,
+                pre_commit: true,
+            })
+            .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_hash_collision_detection() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let key = StrHash::new("http://example.com/a");
+        storage.transaction(|mut writer| writer.insert_str(&key, "http://example.com/a"))?;
+        // 关闭检测时冲突的字符串被静默忽略
+        storage.transaction(|mut writer| writer.insert_str(&key, "http://example.com/b"))?;
+
+        storage.set_hash_collision_detection(true);
+        storage.transaction(|mut writer| writer.insert_str(&key, "http://example.com/a"))?;
+        assert!(matches!(
+            storage.transaction(|mut writer| writer.insert_str(&key, "http://example.com/b")),
+            Err(StorageError::Corruption(_))
+        ));
+        assert_eq!(storage.snapshot().get_str(&key)?.as_deref(), Some("http://example.com/a"));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_bulk_load_hash_collision_detection() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        storage.set_hash_collision_detection(true);
+        let encoded = EncodedTerm::NamedNode {
+            iri_id: StrHash::new("http://example.com/a"),
+        };
+        let mut loader = FileBulkLoader::new(storage);
+        loader.insert_term(ex("a").as_ref().into(), &encoded)?;
+        assert!(matches!(
+            loader.insert_term(ex("b").as_ref().into(), &encoded),
+            Err(StorageError::Corruption(_))
+        ));
         Ok(())
     }
 
 
-    fn save(&mut self) -> Result<(), StorageError> {
-        let mut to_load = Vec::new();
-
-        if !self.id2str.is_empty() {
-            let mut id2str = take(&mut self.id2str)
-                .into_iter()
-                .map(|(k, v)| (k.to_be_bytes(), v))
-                .collect::<Vec<_>>();
-            id2str.sort_unstable();
-            let mut id2str_sst = self.storage.db.new_sst_file()?;
-            for (k, v) in id2str {
-                id2str_sst.insert(&k, v.as_bytes())?;
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_open_with_tiny_block_cache() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let options = StorageOptions {
+            block_cache_size: Some(1024),
+            id2str_bloom_filter_bits: Some(16),
+            max_background_jobs: Some(1),
+            write_buffer_size: Some(64 * 1024),
+            max_open_files: Some(100),
+            ..StorageOptions::default()
+        };
+        let quads = (0..100)
+            .map(|i| Quad::new(ex("s"), ex("p"), ex(&format!("o{}", i)), ex("g")))
+            .collect::<Vec<_>>();
+        {
+            let storage = Storage::open_with_options(&path, &options)?;
+            insert_all(&storage, &quads)?;
+            storage.flush()?;
+            assert_eq!(storage.snapshot().len()?, quads.len());
+        }
+        {
+            let storage = Storage::open_with_options(&path, &options)?;
+            let reader = storage.snapshot();
+            for quad in &quads {
+                assert!(reader.contains_quad(quad.as_ref())?);
             }
-            to_load.push((&self.storage.id2str_cf, id2str_sst.finish()?));
+            assert_eq!(reader.decode_quad(&reader.quads().next().unwrap()?)?.graph_name, ex("g").into());
+            assert!(reader.contains_str(&StrHash::new(ex("o1").as_str()))?);
+            assert!(!reader.contains_str(&StrHash::new("not stored"))?);
         }
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
 
-        if !self.triples.is_empty() {
-            to_load.push((
-                &self.storage.dspo_cf,
-                self.build_sst_for_keys(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {  // 在每个元素上调用该闭包，获取三元组的字节序列，只能返回一个元素
-                        encode_term_triple(&quad.subject, &quad.predicate, &quad.object)
-                    }),
-                )?,
-            ));
-            to_load.push((
-                &self.storage.dpos_cf,
-                self.build_sst_for_keys(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {  // 在每个元素上调用该闭包，获取三元组的字节序列，只能返回一个元素
-                        encode_term_triple(&quad.predicate, &quad.object, &quad.subject)
-
-                    }),
-                )?,
-            ));
-            to_load.push((
-                &self.storage.dosp_cf,
-                self.build_sst_for_keys(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {  // 在每个元素上调用该闭包，获取三元组的字节序列，只能返回一个元素
-                        encode_term_triple(&quad.object, &quad.subject, &quad.predicate)
 
-                    }),
-                )?,
-            ));
-            self.triples.clear();
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_open_with_salt() -> Result<(), StorageError> {
+        let quad = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let hash = StrHash::new(ex("s").as_str());
+        let mut keys = Vec::new();
+        for salt in [1, 2] {
+            let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+            {
+                let storage = Storage::open_with_salt(&path, salt)?;
+                insert_all(&storage, &[quad.clone()])?;
+                storage.flush()?;
+                let id2str_keys = column_family_keys(&storage, &storage.id2str_cf)?;
+                let key = hash.salted(salt).to_be_bytes().to_vec();
+                assert!(id2str_keys.contains(&key));
+                assert!(!id2str_keys.contains(&hash.to_be_bytes().to_vec()));
+                keys.push(key);
+            }
+            for storage in [Storage::open(&path)?, Storage::open_with_salt(&path, salt)?] {
+                assert_eq!(storage.str_salt(), salt);
+                let reader = storage.snapshot();
+                assert!(reader.contains_quad(quad.as_ref())?);
+                let decoded = reader
+                    .quads()
+                    .map(|q| reader.decode_quad(&q?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert_eq!(decoded, vec![quad.clone()]);
+            }
+            assert!(Storage::open_with_salt(&path, salt + 10).is_err());
+            assert!(Storage::open_with_salt(&path, 0).is_err());
+            std::fs::remove_dir_all(&path)?;
         }
+        assert_ne!(keys[0], keys[1]);
 
-        if !self.quads.is_empty() {
-            to_load.push((
-                &self.storage.graphs_cf,
-                self.build_sst_for_keys(self.graphs.iter().map(encode_term))?,
-            ));
-            self.graphs.clear();
+        // 已经写入了未加盐字符串的存储不能再加盐
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        {
+            let storage = Storage::open(&path)?;
+            insert_all(&storage, &[quad])?;
+            storage.flush()?;
+        }
+        assert!(Storage::open_with_salt(&path, 1).is_err());
+        assert_eq!(Storage::open_with_salt(&path, 0)?.str_salt(), 0);
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
+    }
 
-            to_load.push((
-                &self.storage.gspo_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.object,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.gpos_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.subject,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.gosp_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.object,
-                        &quad.subject,
-                        &quad.predicate,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.spog_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.posg_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.subject,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.ospg_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.object,
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.graph_name,
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_open_with_custom_prefix_sizes() -> Result<(), StorageError> {
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let options = StorageOptions {
+            min_prefix_sizes: vec![
+                (StorageColumnFamily::Spog, 1),
+                (StorageColumnFamily::Dspo, 9),
+                (StorageColumnFamily::Dosp, 1),
+            ],
+            ..StorageOptions::default()
+        };
+        let quads = (0..100)
+            .map(|i| {
+                Quad::new(
+                    ex(&format!("s{}", i % 10)),
+                    ex("p"),
+                    Literal::from(i),
+                    if i % 2 == 0 {
+                        GraphName::DefaultGraph
+                    } else {
+                        ex("g").into()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        {
+            let storage = Storage::open_with_options(&path, &options)?;
+            insert_all(&storage, &quads)?;
+            storage.flush()?;
+        }
+        {
+            let storage = Storage::open_with_options(&path, &options)?;
+            let reader = storage.snapshot();
+            assert_eq!(reader.len()?, quads.len());
+            for quad in &quads {
+                assert!(reader.contains_quad(quad.as_ref())?);
+            }
+            let subject = EncodedTerm::from(ex("s3").as_ref());
+            assert_eq!(
+                reader
+                    .quads_for_pattern(Some(&subject), None, None, None)
+                    .count(),
+                10
+            );
+            let object = EncodedTerm::from(Literal::from(4).as_ref());
+            assert_eq!(
+                reader
+                    .quads_for_pattern(
+                        None,
+                        None,
+                        Some(&object),
+                        Some(&EncodedTerm::DefaultGraph)
                     )
-                }))?,
-            ));
-            self.quads.clear();
+                    .count(),
+                1
+            );
         }
+        std::fs::remove_dir_all(&path)?;
+
+        for min_prefix_sizes in [
+            vec![(StorageColumnFamily::Spog, 18)],
+            vec![(StorageColumnFamily::Ospg, 17)],
+            vec![(StorageColumnFamily::Id2str, 16)],
+        ] {
+            let options = StorageOptions {
+                min_prefix_sizes,
+                ..StorageOptions::default()
+            };
+            assert!(Storage::open_with_options(&path, &options).is_err());
+        }
+        Ok(())
+    }
 
-        self.storage.db.insert_stt_files(&to_load)
+    #[test]
+    fn test_dump_dataset_round_trip() -> Result<(), Box<dyn Error>> {
+        let data = "<http://example.com/s> <http://example.com/p> <http://example.com/o> .
+<http://example.com/s> <http://example.com/p> \"foo\"@en <http://example.com/g> .
+<http://example.com/s> <http://example.com/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> <http://example.com/g> .
+<http://example.com/s> <http://example.com/p> \"a literal that is longer than sixteen bytes\" <http://example.com/g2> .
+";
+        let parse = |data: &[u8]| {
+            DatasetParser::from_format(DatasetFormat::NQuads)
+                .read_quads(data)?
+                .collect::<Result<HashSet<_>, _>>()
+        };
+        let quads = parse(data.as_bytes())?;
+        let storage = Storage::new()?;
+        insert_all(&storage, &quads.iter().cloned().collect::<Vec<_>>())?;
+
+        let mut buffer = Vec::new();
+        storage.snapshot().dump_dataset(&mut buffer, DatasetFormat::NQuads)?;
+        // 默认图中的三元组不输出第四个元素
+        assert!(String::from_utf8(buffer.clone())?
+            .lines()
+            .any(|line| line == "<http://example.com/s> <http://example.com/p> <http://example.com/o> ."));
+        assert_eq!(parse(&buffer)?, quads);
+        Ok(())
     }
 
-    fn build_sst_for_keys(
-        &self,
-        values: impl Iterator<Item = Vec<u8>>,
-    ) -> Result<PathBuf, StorageError> {
-        let mut values = values.collect::<Vec<_>>();
-        values.sort_unstable();
 
-        let mut sst = self.storage.db.new_sst_file()?;
+    #[test]
+    fn test_dump_graph() -> Result<(), Box<dyn Error>> {
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s"), ex("p"), ex("o1"), GraphName::DefaultGraph),
+                Quad::new(ex("s"), ex("p"), ex("o2"), ex("g")),
+            ],
+        )?;
+        storage.transaction(|mut writer| writer.insert_named_graph(ex("empty").as_ref().into()))?;
+        let reader = storage.snapshot();
+
+        let mut buffer = Vec::new();
+        reader.dump_graph(ex("g").as_ref().into(), &mut buffer, GraphFormat::NTriples)?;
+        assert_eq!(
+            String::from_utf8(buffer)?,
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o2> .\n"
+        );
 
-        for value in values {  
-            sst.insert_empty(&value)?;
-        }
+        let mut buffer = Vec::new();
+        reader.dump_graph(GraphNameRef::DefaultGraph, &mut buffer, GraphFormat::NTriples)?;
+        assert_eq!(
+            String::from_utf8(buffer)?,
+            "<http://example.com/s> <http://example.com/p> <http://example.com/o1> .\n"
+        );
 
+        let mut buffer = Vec::new();
+        reader.dump_graph(ex("empty").as_ref().into(), &mut buffer, GraphFormat::NTriples)?;
+        assert!(buffer.is_empty());
 
-        sst.finish()   // 不用看了
+        let mut buffer = Vec::new();
+        match reader.dump_graph(ex("missing").as_ref().into(), &mut buffer, GraphFormat::NTriples) {
+            Err(SerializerError::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::NotFound),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(buffer.is_empty());
+        Ok(())
     }
 
 
-
-    // ############################## 将区间编码加入value中 ##############################
-    // 插入在这！！！！！！！！！！！！！
-    // 接下来的方法也要重新复制一份形成 oxiuse
-    fn load_oxiuse_value(
-        &mut self,
-        quads: impl IntoIterator<Item = Quad>,
-        counter: &AtomicU64,
-        path: &str
-    ) -> Result<(), StorageError> {
-        let trees =self.construct_tree(path).unwrap();
-
-        self.encode(quads)?;   // 该方法主要是获得self的id2str hashmap
-
-        let size = self.triples.len() + self.quads.len();
-
-        self.save_oxiuse_value(trees)?;    // TODO:记得修改方法
-        
-        counter.fetch_add(size.try_into().unwrap(), Ordering::Relaxed);
+    #[test]
+    fn test_get_strs() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let long = "a literal that is longer than sixteen bytes";
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s"), ex("p"), Literal::from(long), ex("g")),
+                Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            ],
+        )?;
+        let reader = storage.snapshot();
+        let keys = [
+            StrHash::new(long),
+            StrHash::new("http://example.com/missing"),
+            StrHash::new("http://example.com/s"),
+            StrHash::new(long),
+            StrHash::new("http://example.com/o"),
+        ];
+        let expected = keys
+            .iter()
+            .map(|key| reader.get_str(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(reader.get_strs(&keys)?, expected);
+        assert_eq!(expected[1], None);
+        assert_eq!(expected[3].as_deref(), Some(long));
+        assert!(reader.get_strs(&[])?.is_empty());
         Ok(())
     }
 
 
+    #[test]
+    fn test_encoded_term_order_matches_index_order() -> Result<(), StorageError> {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
 
-    // 三元组的插入在这个方法中，这个方法不可以公用
-    fn save_oxiuse_value(&mut self, trees: (MultiTree, MultiTree)) -> Result<(), StorageError> {
-        let mut to_load = Vec::new();
-
-        // id2str
-        if !self.id2str.is_empty() {
-            let mut id2str = take(&mut self.id2str)
-                .into_iter()
-                .map(|(k, v)| (k.to_be_bytes(), v))
-                .collect::<Vec<_>>();
-            id2str.sort_unstable();
-            let mut id2str_sst = self.storage.db.new_sst_file()?;
-            for (k, v) in id2str {
-                id2str_sst.insert(&k, v.as_bytes())?;
-            }
-            to_load.push((&self.storage.id2str_cf, id2str_sst.finish()?));
-        }
+        let mut rng = StdRng::seed_from_u64(42);
+        let random_string = |rng: &mut StdRng| {
+            let len = rng.gen_range(0..30);
+            (0..len).map(|_| rng.gen_range('a'..='z')).collect::<String>()
+        };
+        let terms = (0..500)
+            .map(|_| -> Term {
+                match rng.gen_range(0..7) {
+                    0 => ex(&random_string(&mut rng)).into(),
+                    1 => BlankNode::new_from_unique_id(rng.gen()).into(),
+                    2 => Literal::from(rng.gen::<i64>()).into(),
+                    3 => Literal::from(rng.gen::<bool>()).into(),
+                    4 => Literal::from(rng.gen::<f64>()).into(),
+                    5 => Literal::new_simple_literal(random_string(&mut rng)).into(),
+                    _ => Literal::new_language_tagged_literal_unchecked(random_string(&mut rng), "en")
+                        .into(),
+                }
+            })
+            .collect::<Vec<_>>();
 
-        // triple（集中在这里）
-        // TODO:考虑写一个新方法将（key，value）作为元组返回代替encode_term_triple()
-        if !self.triples.is_empty() {
-            to_load.push((
-                &self.storage.dspo_cf,
-                self.build_sst_for_oxiuse_value(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {  // 在每个元素上调用该闭包，获取三元组的字节序列，只能返回一个元素
-                        let mut map = HashMap::new();
-                        map.insert("s", &quad.subject);
-                        map.insert("p", &quad.predicate);
-                        map.insert("o", &quad.object);
-
-                        encode_term_triple_oxiuse_value_spo(map, trees.clone())   // TODO:记得修改方法
-                    }),
-                )?,
-            ));
-            to_load.push((
-                &self.storage.dpos_cf,
-                self.build_sst_for_oxiuse_value(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {
-                        let mut map = HashMap::new();
-                        map.insert("s", &quad.subject);
-                        map.insert("p", &quad.predicate);
-                        map.insert("o", &quad.object);
-
-                        encode_term_triple_oxiuse_value_pos(map, trees.clone())   // TODO:记得修改方法   
-                    }),
-                )?,
-            ));
-            to_load.push((
-                &self.storage.dosp_cf,
-                self.build_sst_for_oxiuse_value(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {
-                        let mut map = HashMap::new();
-                        map.insert("s", &quad.subject);
-                        map.insert("p", &quad.predicate);
-                        map.insert("o", &quad.object);
-
-                        encode_term_triple_oxiuse_value_osp(map, trees.clone())   // TODO:记得修改方法
-                    }),
-                )?,
-            ));
-            self.triples.clear();
+        let encoded = terms.iter().map(|t| EncodedTerm::from(t.as_ref())).collect::<Vec<_>>();
+        for (a, b) in encoded.iter().zip(encoded.iter().rev()) {
+            assert_eq!(a.byte_order_key(), encode_term(a));
+            assert_eq!(a.byte_order_key() == b.byte_order_key(), a == b);
         }
 
-        if !self.quads.is_empty() {
-            to_load.push((
-                &self.storage.graphs_cf,
-                self.build_sst_for_keys(self.graphs.iter().map(encode_term))?,
-            ));
-            self.graphs.clear();
+        // dspo 中主语与谓语相同的三元组按宾语的字节序排列
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &terms
+                .iter()
+                .map(|t| Quad::new(ex("s"), ex("p"), t.clone(), GraphName::DefaultGraph))
+                .collect::<Vec<_>>(),
+        )?;
+        let objects = storage
+            .snapshot()
+            .dspo_quads(&[])
+            .map(|q| Ok(q?.object))
+            .collect::<Result<Vec<_>, StorageError>>()?;
+        let mut sorted = encoded;
+        sorted.sort_by_key(EncodedTerm::byte_order_key);
+        sorted.dedup();
+        assert_eq!(objects, sorted);
+        Ok(())
+    }
 
-            to_load.push((
-                &self.storage.gspo_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.object,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.gpos_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.subject,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.gosp_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.object,
-                        &quad.subject,
-                        &quad.predicate,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.spog_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.posg_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.subject,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.ospg_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.object,
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            self.quads.clear();
-        }
 
-        self.storage.db.insert_stt_files(&to_load)
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_merge_from() -> Result<(), StorageError> {
+        let long = Literal::from("a literal that is longer than sixteen bytes");
+        let shared = Quad::new(ex("s"), ex("p"), ex("o"), ex("g"));
+        let target_only = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        let source_only = [
+            Quad::new(ex("s"), ex("p"), long, ex("g2")),
+            Quad::new(ex("s2"), ex("p"), ex("o"), GraphName::DefaultGraph),
+        ];
+        let target = Storage::new()?;
+        insert_all(&target, &[shared.clone(), target_only.clone()])?;
+        let source = Storage::new()?;
+        insert_all(&source, &[shared.clone(), source_only[0].clone(), source_only[1].clone()])?;
+        source.transaction(|mut writer| writer.insert_named_graph(ex("empty").as_ref().into()))?;
+
+        assert_eq!(target.merge_from(&source.snapshot())?, 2);
+
+        let reader = target.snapshot();
+        let quads = reader
+            .quads()
+            .map(|q| reader.decode_quad(&q?))
+            .collect::<Result<HashSet<_>, _>>()?;
+        let expected = [shared, target_only, source_only[0].clone(), source_only[1].clone()]
+            .into_iter()
+            .collect::<HashSet<_>>();
+        assert_eq!(quads, expected);
+        let graphs = reader.decoded_named_graphs().collect::<Result<HashSet<_>, _>>()?;
+        assert_eq!(
+            graphs,
+            [ex("g"), ex("g2"), ex("empty")].into_iter().map(NamedOrBlankNode::from).collect()
+        );
+        // 再次合并不会增加任何四元组
+        assert_eq!(target.merge_from(&source.snapshot())?, 0);
+        Ok(())
     }
 
 
-    // TODO：使用insert_key_value()，对key、value进行插入
-    fn build_sst_for_oxiuse_value(
-        &self,
-        values: impl Iterator<Item = (Vec<u8>, Vec<u8>)>,
-    ) -> Result<PathBuf, StorageError> {
-        let mut values = values.collect::<Vec<_>>();
-        values.sort_unstable();
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_clear_graph_by_range() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let g1 = ex("g1");
+        let g2 = ex("g2");
+        let mut quads = (0..10_000)
+            .map(|i| Quad::new(ex("s"), ex("p"), ex(&format!("o{}", i)), g1.clone()))
+            .collect::<Vec<_>>();
+        quads.push(Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph));
+        quads.push(Quad::new(ex("s"), ex("p"), ex("o"), g2.clone()));
+        insert_all(&storage, &quads)?;
+        assert_eq!(storage.snapshot().len()?, 10_002);
+
+        storage.clear_graph_by_range(g1.as_ref().into())?;
+        let reader = storage.snapshot();
+        assert_eq!(reader.len()?, 2);
+        assert_eq!(reader.len_for_graph(g1.as_ref().into())?, 0);
+        // 图本身仍然存在
+        assert!(reader.contains_named_graph(&g1.as_ref().into())?);
+        reader.validate()?;
+
+        // 图名以已注册的前缀编码时，也要删除同样编码的 key
+        let prefixed = Storage::new()?;
+        prefixed.register_prefix("http://example.com/")?;
+        insert_all(&prefixed, &quads[..100])?;
+        prefixed.clear_graph_by_range(g1.as_ref().into())?;
+        assert_eq!(prefixed.snapshot().len()?, 0);
+        prefixed.snapshot().validate()?;
+
+        storage.clear_graph_by_range(GraphNameRef::DefaultGraph)?;
+        assert!(storage.remove_named_graph_by_range(g2.as_ref().into())?);
+        assert!(!storage.remove_named_graph_by_range(g2.as_ref().into())?);
+        let reader = storage.snapshot();
+        assert_eq!(reader.len()?, 0);
+        assert!(!reader.contains_named_graph(&g2.as_ref().into())?);
+        reader.validate()?;
+        Ok(())
+    }
 
-        let mut sst = self.storage.db.new_sst_file()?;
 
-        for value in values {    
-            sst.insert_key_value(&value.0, &value.1)?;      // TODO:记得修改方法
+    #[test]
+    fn test_xsd_string_literal_dedup() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let value = "a value too long to be inlined";
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s"), ex("p"), Literal::new_typed_literal(value, vocab::xsd::STRING), ex("g")),
+                Quad::new(ex("s"), ex("p"), Literal::new_simple_literal(value), ex("g")),
+            ],
+        )?;
+        assert_eq!(storage.snapshot().len()?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_storage_clear() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        storage.register_prefix("http://example.com/")?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ex("s"), ex("p"), Literal::from("a string that is not inlined"), GraphName::DefaultGraph),
+                Quad::new(ex("s"), ex("p"), ex("o"), ex("g")),
+            ],
+        )?;
+        storage.transaction(|mut writer| writer.insert_named_graph(ex("empty").as_ref().into()))?;
+        assert!(!storage.snapshot().is_empty()?);
+
+        storage.clear()?;
+        let reader = storage.snapshot();
+        assert!(reader.is_empty()?);
+        assert_eq!(reader.named_graphs().count(), 0);
+        assert!(reader.prefix_table().is_empty());
+        assert!(!reader.contains_str(&StrHash::new("a string that is not inlined"))?);
+        assert_eq!(storage.ensure_version()?, LATEST_STORAGE_VERSION);
+        reader.validate()?;
+
+        // 清空之后仍然可以正常写入
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), ex("g"))])?;
+        assert_eq!(storage.snapshot().len()?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_is_stable_under_concurrent_writes() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), ex("g"))])?;
+        let reader = storage.snapshot();
+
+        let writer = {
+            let storage = storage.clone();
+            spawn(move || {
+                for i in 0..1000 {
+                    insert_all(
+                        &storage,
+                        &[Quad::new(ex("s"), ex("p"), ex(&format!("o{}", i)), ex("g"))],
+                    )?;
+                }
+                Ok::<_, StorageError>(())
+            })
+        };
+        while !writer.is_finished() {
+            assert_eq!(reader.len()?, 1);
+            assert_eq!(reader.len_for_graph(ex("g").as_ref().into())?, 1);
         }
+        writer.join().unwrap()?;
+        assert_eq!(reader.len()?, 1);
+        assert_eq!(storage.snapshot().len()?, 1001);
+        Ok(())
+    }
 
-        
-        sst.finish()
+    #[test]
+    fn test_decode_quad() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quad = Quad::new(
+            ex("s"),
+            ex("p"),
+            Literal::new_language_tagged_literal_unchecked("a long enough string", "en"),
+            ex("g"),
+        );
+        insert_all(&storage, &[quad.clone()])?;
+
+        let reader = storage.snapshot();
+        let subject = EncodedTerm::from(quad.subject.as_ref());
+        let encoded = reader
+            .quads_for_pattern(Some(&subject), None, None, None)
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(encoded.len(), 1);
+        assert_eq!(reader.decode_quad(&encoded[0])?, quad);
+        Ok(())
     }
 
 
-    // ############### 将区间编码加入key中  ###############
-    // 插入在这！！！！！！！！！！！！！
-    // 接下来的方法也要重新复制一份形成 oxiuse
-    fn load_oxiuse_key(
-        &mut self,
-        quads: impl IntoIterator<Item = Quad>,
-        counter: &AtomicU64,
-        path: &str
-    ) -> Result<(), StorageError> {
-        // 构造 tree
-        let trees =self.construct_tree(path).unwrap();
+    #[test]
+    #[cfg(target_arch = "wasm32")]
+    fn test_wasm_maintenance_stubs() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph)])?;
+        storage.flush()?;
+        storage.compact()?;
+        assert!(storage.backup(Path::new("backup")).is_err());
+        assert_eq!(storage.snapshot().len()?, 1);
+        Ok(())
+    }
 
-        self.encode(quads)?;   // 该方法主要是获得self的id2str hashmap
 
-        let size = self.triples.len() + self.quads.len();
+    #[test]
+    fn test_graph_is_empty() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let empty = ex("empty");
+        let populated = ex("populated");
+        storage.transaction(|mut writer| {
+            writer.insert_named_graph(empty.as_ref().into())?;
+            writer.insert(QuadRef::new(&ex("s"), &ex("p"), &ex("o"), &populated))
+        })?;
+
+        let reader = storage.snapshot();
+        assert!(reader.contains_named_graph(&empty.as_ref().into())?);
+        assert!(reader.graph_is_empty(empty.as_ref().into())?);
+        assert!(!reader.graph_is_empty(populated.as_ref().into())?);
+        assert!(reader.graph_is_empty(ex("missing").as_ref().into())?);
+        Ok(())
+    }
 
-        self.save_oxiuse_key(trees)?;    // TODO:记得修改方法
-        
-        counter.fetch_add(size.try_into().unwrap(), Ordering::Relaxed);
+
+    #[test]
+    fn test_plan_pattern() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let g = ex("g");
+        let mut quads = Vec::new();
+        for i in 0..10 {
+            quads.push(Quad::new(ex("s"), ex("p"), ex(&format!("o{}", i)), g.clone()));
+            quads.push(Quad::new(ex("s"), ex("p"), ex(&format!("o{}", i)), GraphName::DefaultGraph));
+        }
+        insert_all(&storage, &quads)?;
+        let reader = storage.snapshot();
+        let s = EncodedTerm::from(ex("s").as_ref());
+        let p = EncodedTerm::from(ex("p").as_ref());
+        let o = EncodedTerm::from(ex("o3").as_ref());
+        let g = EncodedTerm::from(g.as_ref());
+
+        let named = reader.plan_pattern(Some(&s), Some(&p), Some(&o), Some(&g))?;
+        assert_eq!(named.chosen_cf, GSPO_CF);
+        assert_eq!(named.prefix_len_bytes, encode_term_quad(&g, &s, &p, &o).len());
+        assert_eq!(named.estimated_rows, 1);
+        let default = reader.plan_pattern(Some(&s), Some(&p), Some(&o), Some(&EncodedTerm::DefaultGraph))?;
+        assert_eq!(default.chosen_cf, DSPO_CF);
+        assert_eq!(default.prefix_len_bytes, encode_term_triple(&s, &p, &o).len());
+        assert_eq!(default.estimated_rows, 1);
+
+        // 图名未绑定时同时统计默认图
+        let object = reader.plan_pattern(None, None, Some(&o), None)?;
+        assert_eq!(object.chosen_cf, OSPG_CF);
+        assert_eq!(object.estimated_rows, 2);
+        let predicate = reader.plan_pattern(None, Some(&p), None, Some(&g))?;
+        assert_eq!(predicate.chosen_cf, GPOS_CF);
+        assert_eq!(predicate.estimated_rows, 10);
+        assert!(predicate.prefix_len_bytes < named.prefix_len_bytes);
+        assert_eq!(reader.plan_pattern(None, None, None, None)?.estimated_rows, 20);
         Ok(())
     }
 
 
+    #[test]
+    fn test_bulk_load_batch_size() -> Result<(), StorageError> {
+        let quads = (0..100)
+            .map(|i| Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), GraphName::DefaultGraph))
+            .collect::<Vec<_>>();
+
+        let storage = Storage::new()?;
+        let default_threads = StorageBulkLoader::new(storage.clone())
+            .set_num_threads(2)
+            .load_batches::<StorageError, StorageError, _>(quads.iter().cloned().map(Ok), LoadMode::Plain)?;
+        assert_eq!(default_threads.spawned, 1);
+        assert_eq!(default_threads.max_in_flight, 1);
+
+        let storage = Storage::new()?;
+        // 优先于内存大小的估计
+        let small_threads = StorageBulkLoader::new(storage.clone())
+            .set_num_threads(2)
+            .set_max_memory_size_in_megabytes(1000)
+            .set_batch_size(10)
+            .load_batches::<StorageError, StorageError, _>(quads.iter().cloned().map(Ok), LoadMode::Plain)?;
+        // 每 10 个四元组启动一个线程，最后还有一个空批次；同时最多有 num_threads 个线程
+        assert_eq!(small_threads.spawned, 11);
+        assert_eq!(small_threads.max_in_flight, 2);
+        assert_eq!(storage.snapshot().len()?, 100);
+        storage.snapshot().validate()?;
+        Ok(())
+    }
 
-    // 三元组的插入在这个方法中，这个方法不可以公用
-    fn save_oxiuse_key(&mut self, trees: (MultiTree, MultiTree)) -> Result<(), StorageError> {
-        let mut to_load = Vec::new();
 
-        // id2str
-        if !self.id2str.is_empty() {
-            let mut id2str = take(&mut self.id2str)
-                .into_iter()
-                .map(|(k, v)| (k.to_be_bytes(), v))
-                .collect::<Vec<_>>();
-            id2str.sort_unstable();
-            let mut id2str_sst = self.storage.db.new_sst_file()?;
-            for (k, v) in id2str {
-                id2str_sst.insert(&k, v.as_bytes())?;
+    #[test]
+    fn test_bulk_load_batch_size_without_memory_info() -> Result<(), StorageError> {
+        let loader = StorageBulkLoader::new(Storage::new()?);
+        assert_eq!(loader.batch_size(4, 0), DEFAULT_BULK_LOAD_BATCH_SIZE);
+        // 不会 panic，在 32 位平台上无法转换时使用默认值
+        assert!(loader.batch_size(4, u64::MAX) <= MAX_BULK_LOAD_BATCH_SIZE);
+        assert_eq!(loader.batch_size(2, 400_000_000), MAX_BULK_LOAD_BATCH_SIZE);
+        assert_eq!(loader.batch_size(2, 4_000_000), 2_000_000);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_new_in_memory_is_not_persisted() -> Result<(), StorageError> {
+        let storage = Storage::new_in_memory()?;
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), ex("g"))])?;
+        assert_eq!(storage.snapshot().len()?, 1);
+        assert!(storage
+            .backup(&std::env::temp_dir().join(format!("oxigraph-backup-{}", rand::random::<u128>())))
+            .is_err());
+        drop(storage);
+
+        let storage = Storage::new_in_memory()?;
+        let reader = storage.snapshot();
+        assert!(reader.is_empty()?);
+        assert_eq!(reader.named_graphs().count(), 0);
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_iter_decoded_quads() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quads = vec![
+            Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::from("a string that is not inlined"), ex("g1")),
+            Quad::new(BlankNode::default(), ex("p"), ex("o"), ex("g2")),
+        ];
+        insert_all(&storage, &quads)?;
+
+        let decoded = storage.snapshot().iter().collect::<Result<HashSet<_>, _>>()?;
+        assert_eq!(decoded, quads.into_iter().collect());
+
+        // 缺失的字符串只让对应的四元组出错
+        storage.transaction(|mut writer| {
+            writer
+                .transaction
+                .remove(&storage.id2str_cf, &StrHash::new("a string that is not inlined").to_be_bytes())
+        })?;
+        let results = storage.snapshot().iter().collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_distinct_predicates() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let predicates = [ex("p0"), ex("p1"), ex("p2")];
+        let quads = (0..1000)
+            .map(|i| {
+                Quad::new(
+                    ex(&format!("s{}", i % 100)),
+                    predicates[i % 3].clone(),
+                    ex(&format!("o{}", i)),
+                    GraphName::DefaultGraph,
+                )
+            })
+            .chain([Quad::new(ex("s0"), ex("q"), ex("o"), ex("g"))])
+            .collect::<Vec<_>>();
+        insert_all(&storage, &quads)?;
+        let reader = storage.snapshot();
+
+        // 每个不同的谓词只需要一次 seek
+        let mut iter = reader.distinct_terms(
+            &storage.dpos_cf,
+            &storage.posg_cf,
+            &storage.gpos_cf,
+            Some(GraphNameRef::DefaultGraph),
+        );
+        assert_eq!(iter.by_ref().count(), 3);
+        assert!(iter.seeks <= 3);
+
+        let found = reader
+            .distinct_predicates(Some(GraphNameRef::DefaultGraph))
+            .collect::<Result<HashSet<_>, _>>()?;
+        assert_eq!(found, predicates.iter().cloned().collect());
+        assert_eq!(
+            reader
+                .distinct_predicates(Some(ex("g").as_ref().into()))
+                .collect::<Result<Vec<_>, _>>()?,
+            vec![ex("q")]
+        );
+        assert_eq!(reader.distinct_predicates(None).count(), 4);
+        assert_eq!(reader.distinct_subjects(Some(GraphNameRef::DefaultGraph)).count(), 100);
+        assert_eq!(reader.distinct_subjects(None).count(), 100);
+        assert_eq!(reader.distinct_objects(None).count(), 1001);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_encoded() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quads = vec![
+            Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::from("a string that is not inlined"), ex("g")),
+            Quad::new(ex("s"), ex("p"), Literal::new_language_tagged_literal_unchecked("chat", "fr"), ex("g")),
+        ];
+        let mut id2str = HashMap::new();
+        let mut encoded = Vec::new();
+        for quad in &quads {
+            let encoded_quad = EncodedQuad::from(quad.as_ref());
+            insert_term(quad.subject.as_ref().into(), &encoded_quad.subject, &mut |key, value| {
+                id2str.insert(*key, value.into());
+                Ok(())
+            })?;
+            insert_term(quad.predicate.as_ref().into(), &encoded_quad.predicate, &mut |key, value| {
+                id2str.insert(*key, value.into());
+                Ok(())
+            })?;
+            insert_term(quad.object.as_ref(), &encoded_quad.object, &mut |key, value| {
+                id2str.insert(*key, value.into());
+                Ok(())
+            })?;
+            if let GraphName::NamedNode(graph_name) = &quad.graph_name {
+                insert_term(graph_name.as_ref().into(), &encoded_quad.graph_name, &mut |key, value| {
+                    id2str.insert(*key, value.into());
+                    Ok(())
+                })?;
             }
-            to_load.push((&self.storage.id2str_cf, id2str_sst.finish()?));
+            encoded.push(encoded_quad);
         }
+        // 字典中多余的字符串不会被写入
+        id2str.insert(StrHash::new("unused"), "unused".into());
+
+        StorageBulkLoader::new(storage.clone()).load_encoded(encoded.clone(), &id2str)?;
+        let reader = storage.snapshot();
+        let decoded = reader.iter().collect::<Result<HashSet<_>, _>>()?;
+        assert_eq!(decoded, quads.into_iter().collect());
+        assert!(reader.contains_named_graph(&ex("g").as_ref().into())?);
+        assert!(!reader.contains_str(&StrHash::new("unused"))?);
+        reader.validate()?;
+
+        // 缺少字符串时不写入任何内容
+        let other = Storage::new()?;
+        id2str.remove(&StrHash::new("a string that is not inlined"));
+        assert!(StorageBulkLoader::new(other.clone()).load_encoded(encoded, &id2str).is_err());
+        assert!(other.snapshot().is_empty()?);
+        Ok(())
+    }
 
-        // triple（集中在这里）
-        // TODO:考虑写一个新方法将（key，value）作为元组返回代替encode_term_triple()
-        if !self.triples.is_empty() {
-            to_load.push((
-                &self.storage.dspo_cf,
-                self.build_sst_for_oxiuse_key(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {  // 在每个元素上调用该闭包，获取三元组的字节序列，只能返回一个元素
-                        let mut map = HashMap::new();
-                        map.insert("s", &quad.subject);
-                        map.insert("p", &quad.predicate);
-                        map.insert("o", &quad.object);
-
-                        encode_term_triple_oxiuse_key_spo(map, trees.clone())   // TODO:记得修改方法
-                    }),
-                )?,
-            ));
-            to_load.push((
-                &self.storage.dpos_cf,
-                self.build_sst_for_oxiuse_key(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {
-                        let mut map = HashMap::new();
-                        map.insert("s", &quad.subject);
-                        map.insert("p", &quad.predicate);
-                        map.insert("o", &quad.object);
-
-                        encode_term_triple_oxiuse_key_pos(map, trees.clone())   // TODO:记得修改方法   
-                    }),
-                )?,
-            ));
-            to_load.push((
-                &self.storage.dosp_cf,
-                self.build_sst_for_oxiuse_key(   // TODO:记得修改方法
-                    self.triples.iter().map(|quad| {
-                        let mut map = HashMap::new();
-                        map.insert("s", &quad.subject);
-                        map.insert("p", &quad.predicate);
-                        map.insert("o", &quad.object);
-
-                        encode_term_triple_oxiuse_key_osp(map, trees.clone())   // TODO:记得修改方法
-                    }),
-                )?,
-            ));
-            self.triples.clear();
-        }
+    #[test]
+    fn test_insert_rejects_invalid_positions() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quad = QuadRef::new(&ex("s"), &ex("p"), &ex("o"), GraphNameRef::DefaultGraph);
+        let mut encoded = EncodedQuad::from(quad);
+        encoded.subject = LiteralRef::new_simple_literal("foo").into();
+        let error = storage
+            .transaction(|mut writer| writer.insert_encoded(quad, &encoded))
+            .unwrap_err();
+        assert!(error.to_string().contains("is not allowed as subject"));
+        assert!(storage.snapshot().is_empty()?);
+
+        let error = StorageBulkLoader::new(storage.clone())
+            .load_encoded([encoded], &HashMap::new())
+            .unwrap_err();
+        assert!(error.to_string().contains("is not allowed as subject"));
+        assert!(storage.snapshot().is_empty()?);
+        Ok(())
+    }
 
-        if !self.quads.is_empty() {
-            to_load.push((
-                &self.storage.graphs_cf,
-                self.build_sst_for_keys(self.graphs.iter().map(encode_term))?,
-            ));
-            self.graphs.clear();
+    #[test]
+    fn test_transaction_reads_its_own_writes() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let g = ex("g");
+        let default_quad = QuadRef::new(&ex("s"), &ex("p"), &ex("o"), GraphNameRef::DefaultGraph);
+        let named_quad = QuadRef::new(&ex("s"), &ex("p"), &ex("o"), &g);
+        storage.transaction(|mut writer| {
+            writer.insert(default_quad)?;
+            writer.insert(named_quad)?;
+            let reader = writer.reader();
+            assert!(reader.contains_quad(default_quad)?);
+            assert!(reader.contains_quad(named_quad)?);
+            assert!(reader.contains_named_graph(&g.as_ref().into())?);
+            assert_eq!(reader.len()?, 2);
+            assert_eq!(
+                reader
+                    .quads_for_pattern(None, Some(&ex("p").as_ref().into()), None, None)
+                    .count(),
+                2
+            );
+
+            writer.remove(default_quad)?;
+            assert!(!writer.reader().contains_quad(default_quad)?);
+            // 事务中已有写入，clear_graph 逐条删除，删除对 reader 可见
+            writer.clear_graph(g.as_ref().into())?;
+            assert!(!writer.reader().contains_quad(named_quad)?);
+            assert!(writer.reader().is_empty()?);
+            Result::<_, StorageError>::Ok(())
+        })?;
+        assert!(storage.snapshot().is_empty()?);
+        Ok(())
+    }
 
-            to_load.push((
-                &self.storage.gspo_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.object,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.gpos_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.subject,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.gosp_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.graph_name,
-                        &quad.object,
-                        &quad.subject,
-                        &quad.predicate,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.spog_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.posg_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.predicate,
-                        &quad.object,
-                        &quad.subject,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            to_load.push((
-                &self.storage.ospg_cf,
-                self.build_sst_for_keys(self.quads.iter().map(|quad| {
-                    encode_term_quad(
-                        &quad.object,
-                        &quad.subject,
-                        &quad.predicate,
-                        &quad.graph_name,
-                    )
-                }))?,
-            ));
-            self.quads.clear();
-        }
+    #[test]
+    fn test_get_str_invalid_utf8_error() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let key = StrHash::new("corrupted");
+        storage.transaction(|mut writer| {
+            writer
+                .transaction
+                .insert(&storage.id2str_cf, &key.to_be_bytes(), &[b'a', 0xFF, 0xFE])
+        })?;
+        let reader = storage.snapshot();
+        let error = reader.get_str(&key).unwrap_err().to_string();
+        assert!(error.contains(&hex::encode(key.to_be_bytes())), "{}", error);
+        assert!(error.contains("61fffe"), "{}", error);
+        let error = reader.get_strs(&[StrHash::new("other"), key]).unwrap_err().to_string();
+        assert!(error.contains(&hex::encode(key.to_be_bytes())), "{}", error);
+        Ok(())
+    }
+
+    #[test]
+    fn test_quads_with_object_of_type() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quads = vec![
+            Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), BlankNode::default(), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::from("foo"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::new_language_tagged_literal_unchecked("chat", "fr"), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::from(true), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::from(1), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::new_typed_literal("1.5", vocab::xsd::DECIMAL), GraphName::DefaultGraph),
+            Quad::new(ex("s"), ex("p"), Literal::from(2.5_f64), ex("g")),
+            Quad::new(ex("s"), ex("p"), Literal::new_typed_literal("x", ex("custom")), ex("g")),
+        ];
+        insert_all(&storage, &quads)?;
+        let reader = storage.snapshot();
+        let objects = |kind, graph_name: Option<&EncodedTerm>| {
+            reader
+                .quads_with_object_of_type(kind, graph_name)
+                .map(|quad| reader.decode_quad(&quad?).map(|quad| quad.object))
+                .collect::<Result<HashSet<_>, _>>()
+        };
 
-        self.storage.db.insert_stt_files(&to_load)
+        assert_eq!(
+            objects(ObjectKind::Numeric, None)?,
+            [Literal::from(1), Literal::new_typed_literal("1.5", vocab::xsd::DECIMAL), Literal::from(2.5_f64)]
+                .into_iter()
+                .map(Term::from)
+                .collect()
+        );
+        assert_eq!(objects(ObjectKind::Numeric, Some(&EncodedTerm::DefaultGraph))?.len(), 2);
+        assert_eq!(objects(ObjectKind::Numeric, Some(&ex("g").as_ref().into()))?.len(), 1);
+        assert_eq!(objects(ObjectKind::NamedNode, None)?, [Term::from(ex("o"))].into_iter().collect());
+        assert_eq!(objects(ObjectKind::String, None)?, [Term::from(Literal::from("foo"))].into_iter().collect());
+        assert_eq!(objects(ObjectKind::LangString, None)?.len(), 1);
+        assert_eq!(objects(ObjectKind::BlankNode, None)?.len(), 1);
+        assert_eq!(objects(ObjectKind::Boolean, None)?.len(), 1);
+        assert_eq!(objects(ObjectKind::OtherTypedLiteral, None)?.len(), 1);
+        assert_eq!(objects(ObjectKind::Triple, None)?.len(), 0);
+        Ok(())
     }
 
+    #[test]
+    fn test_default_graph_patterns_do_not_match_named_graphs() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let triple = Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph);
+        insert_all(
+            &storage,
+            &[
+                triple.clone(),
+                Quad::new(ex("s"), ex("p"), ex("o"), ex("g")),
+                Quad::new(ex("s"), ex("p"), ex("o2"), ex("g")),
+            ],
+        )?;
+        let reader = storage.snapshot();
+        let expected = vec![EncodedQuad::from(triple.as_ref())];
+        let default_graph = EncodedTerm::DefaultGraph;
+        let s = EncodedTerm::from(ex("s").as_ref());
+        let p = EncodedTerm::from(ex("p").as_ref());
+        let o = EncodedTerm::from(ex("o").as_ref());
+
+        assert_eq!(
+            reader
+                .quads_for_graph(&default_graph)
+                .collect::<Result<Vec<_>, _>>()?,
+            expected
+        );
+        for (subject, predicate, object) in [
+            (None, None, None),
+            (Some(&s), None, None),
+            (Some(&s), Some(&p), None),
+            (Some(&s), Some(&p), Some(&o)),
+            (None, Some(&p), None),
+            (None, Some(&p), Some(&o)),
+            (None, None, Some(&o)),
+            (Some(&s), None, Some(&o)),
+        ] {
+            assert_eq!(
+                reader
+                    .quads_for_pattern(subject, predicate, object, Some(&default_graph))
+                    .collect::<Result<Vec<_>, _>>()?,
+                expected
+            );
+        }
+        assert_eq!(
+            reader
+                .quads_for_pattern_in(
+                    QuadEncoding::Gspo,
+                    Some(&s),
+                    None,
+                    None,
+                    Some(&default_graph)
+                )?
+                .collect::<Result<Vec<_>, _>>()?,
+            expected
+        );
+        Ok(())
+    }
 
-    // TODO：使用insert_key_value()，对key、value进行插入
-    fn build_sst_for_oxiuse_key(
-        &self,
-        values: impl Iterator<Item = (Vec<u8>)>,
-    ) -> Result<PathBuf, StorageError> {
-        let mut values = values.collect::<Vec<_>>();
-        values.sort_unstable();
+    #[test]
+    #[should_panic]
+    fn test_named_graph_prefix_rejects_default_graph() {
+        named_graph_prefix(&EncodedTerm::DefaultGraph, &[]);
+    }
 
-        let mut sst = self.storage.db.new_sst_file()?;
+    #[test]
+    fn test_quads_for_pattern_paged() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quads = (0..95)
+            .map(|i| {
+                Quad::new(
+                    ex(&format!("s{}", i % 7)),
+                    ex("p"),
+                    Literal::from(i),
+                    match i % 3 {
+                        0 => GraphName::DefaultGraph,
+                        1 => ex("g1").into(),
+                        _ => ex("g2").into(),
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        insert_all(&storage, &quads)?;
+        let reader = storage.snapshot();
+        let subject = EncodedTerm::from(ex("s3").as_ref());
+        let predicate = EncodedTerm::from(ex("p").as_ref());
+        for (subject, predicate) in [
+            (None, Some(&predicate)),
+            (Some(&subject), None),
+            (None, None),
+        ] {
+            let all = reader
+                .quads_for_pattern(subject, predicate, None, None)
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut paged = Vec::new();
+            for offset in (0..all.len() + 10).step_by(10) {
+                let page = reader
+                    .quads_for_pattern_paged(subject, predicate, None, None, offset, 10)
+                    .collect::<Result<Vec<_>, _>>()?;
+                assert!(page.len() <= 10);
+                paged.extend(page);
+            }
+            assert_eq!(paged, all);
+        }
+        assert_eq!(
+            reader
+                .quads_for_pattern_paged(None, None, None, None, 95, 10)
+                .count(),
+            0
+        );
+        Ok(())
+    }
 
-        for value in values {    
-            sst.insert_empty(&value)?;      // TODO:记得修改方法
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_bulk_load_removes_sst_files() -> Result<(), StorageError> {
+        // 临时 SST 文件写在数据库目录中，文件名是一个随机数
+        fn leftover_sst_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+            let mut files = Vec::new();
+            for entry in std::fs::read_dir(path)? {
+                let entry = entry?;
+                if entry
+                    .file_name()
+                    .to_str()
+                    .map_or(false, |name| name.bytes().all(|b| b.is_ascii_digit()))
+                {
+                    files.push(entry.path());
+                }
+            }
+            Ok(files)
         }
 
-        
-        sst.finish()
+        let path = std::env::temp_dir().join(format!("oxigraph-test-{}", rand::random::<u128>()));
+        let quads = (0..100)
+            .map(|i| Quad::new(ex("s"), ex("p"), ex(&format!("o{}", i)), ex("g")))
+            .collect::<Vec<_>>();
+        {
+            let storage = Storage::open(&path)?;
+            StorageBulkLoader::new(storage.clone())
+                .load::<StorageError, StorageError, _>(quads.iter().cloned().map(Ok))?;
+            assert_eq!(storage.snapshot().len()?, quads.len());
+        }
+        assert_eq!(leftover_sst_files(&path)?, Vec::<PathBuf>::new());
+        {
+            // 只读数据库上的导入会失败
+            let storage = Storage::open_read_only(&path)?;
+            assert!(StorageBulkLoader::new(storage)
+                .load::<StorageError, StorageError, _>(
+                    vec![Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph)]
+                        .into_iter()
+                        .map(Ok)
+                )
+                .is_err());
+        }
+        assert_eq!(leftover_sst_files(&path)?, Vec::<PathBuf>::new());
+        std::fs::remove_dir_all(&path)?;
+        Ok(())
     }
 
+    #[test]
+    fn test_decode_quads() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quads = (0..50)
+            .map(|i| {
+                Quad::new(
+                    ex(&format!("s{}", i % 5)),
+                    ex("p"),
+                    Literal::new_language_tagged_literal_unchecked(
+                        format!("a long enough string {}", i),
+                        "en",
+                    ),
+                    if i % 2 == 0 {
+                        GraphName::DefaultGraph
+                    } else {
+                        ex("g").into()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        insert_all(&storage, &quads)?;
+
+        let reader = storage.snapshot();
+        let encoded = reader.quads().collect::<Result<Vec<_>, _>>()?;
+        let decoded = reader.decode_quads(&encoded)?;
+        assert_eq!(
+            decoded,
+            encoded
+                .iter()
+                .map(|quad| reader.decode_quad(quad))
+                .collect::<Result<Vec<_>, _>>()?
+        );
+        assert_eq!(decoded.len(), quads.len());
+
+        // 批量读取之后解码不再访问 id2str
+        let cache = DecodeCache::new(&reader, &encoded)?;
+        for quad in &encoded {
+            Decoder::decode_quad(&cache, quad)?;
+        }
+        assert_eq!(cache.fallbacks.get(), 0);
+        Ok(())
+    }
 
+    #[test]
+    fn test_iris_with_prefix() -> Result<(), StorageError> {
+        let ns1 = |name: &str| NamedNode::new_unchecked(format!("http://example.org/ns#{}", name));
+        let ns2 = |name: &str| NamedNode::new_unchecked(format!("http://example.org/other#{}", name));
+        let storage = Storage::new()?;
+        insert_all(
+            &storage,
+            &[
+                Quad::new(ns1("a"), ns2("p"), ns1("b"), GraphName::DefaultGraph),
+                Quad::new(ns2("c"), ns2("p"), ns2("d"), ns1("g")),
+                // 与 IRI 文本相同的字面量不会被返回
+                Quad::new(ns2("c"), ns2("p"), Literal::new_simple_literal("http://example.org/ns#literal"), GraphName::DefaultGraph),
+            ],
+        )?;
 
+        let reader = storage.snapshot();
+        let mut iris = reader.iris_with_prefix("http://example.org/ns#").collect::<Result<Vec<_>, _>>()?;
+        iris.sort();
+        assert_eq!(iris, vec![ns1("a"), ns1("b"), ns1("g")]);
+        let mut iris = reader.iris_with_prefix("http://example.org/other#").collect::<Result<Vec<_>, _>>()?;
+        iris.sort();
+        assert_eq!(iris, vec![ns2("c"), ns2("d"), ns2("p")]);
+        assert_eq!(reader.iris_with_prefix("http://example.com/").count(), 0);
+        Ok(())
+    }
 
-    fn insert_term(   // insert_term将获得NamedNode中的str以及对应的EncodedTerm中的StrHash，插入到自己的id2str hashmap中（这部分应该是不用修改的）
-        &mut self,
-        term: TermRef<'_>,
-        encoded: &EncodedTerm,
-    ) -> Result<(), StorageError> {
-        insert_term(term, encoded, &mut |key, value| {
-            self.id2str.entry(*key).or_insert_with(|| value.into());
-            Ok(())
-        })
+    #[test]
+    fn test_graph_names_with_counts() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let mut quads = vec![Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph)];
+        quads.extend((0..3).map(|i| Quad::new(ex(&format!("s{}", i)), ex("p"), ex("o"), ex("g1"))));
+        quads.push(Quad::new(ex("s"), ex("p"), ex("o"), BlankNode::new_unchecked("g2")));
+        insert_all(&storage, &quads)?;
+        storage.transaction(|mut writer| writer.insert_named_graph(ex("empty").as_ref().into()))?;
+
+        let mut counts = storage.snapshot().graph_names_with_counts()?;
+        counts.sort_by_key(|(graph_name, _)| graph_name.to_string());
+        assert_eq!(
+            counts,
+            vec![
+                (ex("empty").into(), 0),
+                (ex("g1").into(), 3),
+                (BlankNode::new_unchecked("g2").into(), 1),
+            ]
+        );
+        Ok(())
     }
 
+    #[test]
+    fn test_contains_named_graph_ref() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let graph = NamedNode::new_unchecked("http://example.com/g");
+        insert_all(&storage, &[Quad::new(ex("s"), ex("p"), ex("o"), graph.clone())])?;
+
+        let reader = storage.snapshot();
+        assert!(reader.contains_named_graph_ref(graph.as_ref().into())?);
+        assert!(!reader.contains_named_graph_ref(ex("g2").as_ref().into())?);
+        for blank_node in [
+            BlankNode::new_unchecked("g"),
+            BlankNode::new_unchecked("http://example.com/g"),
+        ] {
+            assert!(!reader.contains_named_graph_ref(blank_node.as_ref().into())?);
+        }
+        Ok(())
+    }
 
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_replace_graph() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let graph = ex("g");
+        let old = (0..5)
+            .map(|i| Triple::new(ex("s"), ex("p"), ex(&format!("old{}", i))))
+            .collect::<Vec<_>>();
+        let new = (0..3)
+            .map(|i| Triple::new(ex("s"), ex("p"), ex(&format!("new{}", i))))
+            .collect::<Vec<_>>();
+        insert_all(
+            &storage,
+            &old.iter()
+                .map(|t| t.as_ref().in_graph(graph.as_ref().into()).into_owned())
+                .collect::<Vec<_>>(),
+        )?;
 
-    // 构造Class树和属性树（已更新）
-    pub fn construct_tree(&self, path: &str) -> Result<(MultiTree, MultiTree), ()>{
-        if let Ok(lines) = self.read_lines(path) {
-            let classTree = MultiTree::new(owl::OWL_CLASS);
-            let propertyTree = MultiTree::new(rdf::PROPERTY); 
-    
-            for line in lines {
-                if let Ok(triple) = line {
-                    let vec:Vec<&str> = triple.split(' ').collect();
-    
-                    let p = &vec[1][1..vec[1].len()-1];
-                    if p == rdfs::SUB_CLASS_OF || p == lubm::SUB_ORGANIZATION{
-                        let s = &vec[0][1..vec[0].len()-1];
-                        let o = &vec[2][1..vec[2].len()-1];
-                        
-                        classTree.insert(s, o);
-                    } else if p == rdfs::SUB_PROPERTY_OF {
-                        let s = &vec[0][1..vec[0].len()-1];
-                        let o = &vec[2][1..vec[2].len()-1];
-                        
-                        propertyTree.insert(s, o);
-                    }
-                }      
-            }   
-    
-            classTree.encode();
-            propertyTree.encode();
-    
-            return Ok((classTree, propertyTree))
+        let done = Arc::new(AtomicBool::new(false));
+        let observer = {
+            let storage = storage.clone();
+            let done = Arc::clone(&done);
+            let graph = graph.clone();
+            spawn(move || {
+                let mut sizes = HashSet::new();
+                while !done.load(Ordering::SeqCst) {
+                    sizes.insert(storage.snapshot().len_for_graph(graph.as_ref().into())?);
+                }
+                Ok::<_, StorageError>(sizes)
+            })
+        };
+        for _ in 0..10 {
+            for triples in [&new, &old] {
+                storage.transaction(|mut writer| {
+                    writer.replace_graph(graph.as_ref().into(), triples.iter().map(Triple::as_ref))
+                })?;
+            }
+        }
+        storage.transaction(|mut writer| {
+            writer.replace_graph(graph.as_ref().into(), new.iter().map(Triple::as_ref))
+        })?;
+        done.store(true, Ordering::SeqCst);
+        let sizes = observer.join().unwrap()?;
+        assert!(sizes.iter().all(|size| *size == 5 || *size == 3), "{:?}", sizes);
+
+        let reader = storage.snapshot();
+        assert_eq!(reader.len_for_graph(graph.as_ref().into())?, 3);
+        for triple in &new {
+            assert!(reader.contains_quad(triple.as_ref().in_graph(graph.as_ref().into()))?);
         }
-        Err(())
+
+        // 替换为空集合之后图仍然存在
+        storage.transaction(|mut writer| writer.replace_graph(graph.as_ref().into(), []))?;
+        let reader = storage.snapshot();
+        assert_eq!(reader.len_for_graph(graph.as_ref().into())?, 0);
+        assert!(reader.contains_named_graph_ref(graph.as_ref().into())?);
+        Ok(())
     }
 
-    fn read_lines<P>(&self, filename: P) -> io::Result<io::Lines<io::BufReader<File>>> where P: AsRef<Path>, {
-        let file = File::open(filename)?;
-        Ok(io::BufReader::new(file).lines())
+    #[test]
+    fn test_quads_for_quoted_triple_subject() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let quoted = Triple::new(ex("s"), ex("p"), ex("o"));
+        let nested = Triple::new(quoted.clone(), ex("p"), ex("o"));
+        let quads = vec![
+            Quad::new(quoted.clone(), ex("source"), ex("a"), GraphName::DefaultGraph),
+            Quad::new(quoted.clone(), ex("source"), ex("b"), ex("g")),
+            Quad::new(nested.clone(), ex("source"), ex("c"), GraphName::DefaultGraph),
+            Quad::new(
+                Triple::new(ex("s"), ex("p"), ex("o2")),
+                ex("source"),
+                ex("d"),
+                GraphName::DefaultGraph,
+            ),
+            Quad::new(ex("s"), ex("p"), ex("o"), GraphName::DefaultGraph),
+        ];
+        insert_all(&storage, &quads)?;
+
+        let reader = storage.snapshot();
+        // 词项的编码是无前缀的，这个前缀只匹配这个三元组，不会匹配引用它的三元组
+        let subject = EncodedTerm::from(quoted.as_ref());
+        let found = reader
+            .quads_for_pattern(Some(&subject), None, None, None)
+            .map(|quad| reader.decode_quad(&quad?))
+            .collect::<Result<HashSet<_>, _>>()?;
+        assert_eq!(found, quads[..2].iter().cloned().collect());
+
+        let predicate = EncodedTerm::from(ex("source").as_ref());
+        let found = reader
+            .quads_for_pattern(
+                Some(&subject),
+                Some(&predicate),
+                None,
+                Some(&EncodedTerm::DefaultGraph),
+            )
+            .map(|quad| reader.decode_quad(&quad?))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(found, vec![quads[0].clone()]);
+
+        let subject = EncodedTerm::from(nested.as_ref());
+        assert_eq!(
+            reader
+                .quads_for_pattern(Some(&subject), None, None, None)
+                .count(),
+            1
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_predicate_stats() -> Result<(), StorageError> {
+        let storage = Storage::new()?;
+        let mut quads = Vec::new();
+        // p1: 10 个主语，每个主语 3 个宾语，宾语之间共享
+        for s in 0..10 {
+            for o in 0..3 {
+                quads.push(Quad::new(
+                    ex(&format!("s{}", s)),
+                    ex("p1"),
+                    Literal::from(o),
+                    GraphName::DefaultGraph,
+                ));
+            }
+        }
+        // p2: 一个主语，5 个宾语，同一个三元组同时在默认图和命名图中
+        for o in 0..5 {
+            let triple = Triple::new(ex("s0"), ex("p2"), ex(&format!("o{}", o)));
+            quads.push(triple.clone().in_graph(GraphName::DefaultGraph));
+            quads.push(triple.in_graph(ex("g")));
+        }
+        // p3: 只在命名图中
+        quads.push(Quad::new(ex("s1"), ex("p3"), ex("o0"), ex("g")));
+        quads.push(Quad::new(ex("s2"), ex("p3"), ex("o0"), ex("g2")));
+        insert_all(&storage, &quads)?;
+
+        let stats = storage.snapshot().predicate_stats()?;
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            stats[&ex("p1")],
+            PredicateStats {
+                triple_count: 30,
+                distinct_objects: 3,
+                distinct_subjects: 10,
+            }
+        );
+        assert_eq!(
+            stats[&ex("p2")],
+            PredicateStats {
+                triple_count: 10,
+                distinct_objects: 5,
+                distinct_subjects: 1,
+            }
+        );
+        assert_eq!(
+            stats[&ex("p3")],
+            PredicateStats {
+                triple_count: 2,
+                distinct_objects: 1,
+                distinct_subjects: 2,
+            }
+        );
+        Ok(())
     }
 }
@@ -1,16 +1,24 @@
 #![allow(clippy::unreadable_literal)]
 
 use crate::model::*;
+use crate::storage::binary_encoder::encode_term;
 use crate::storage::small_string::SmallString;
 use crate::store::{CorruptionError, StorageError};
 use crate::xsd::*;
+use lazy_static::lazy_static;
 use siphasher::sip128::{Hasher128, SipHasher24};
+use std::borrow::Cow;
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::rc::Rc;
 use std::str;
 
+lazy_static! {
+    // xsd:string 的 StrHash，带有这个数据类型的字面量与普通字符串相同
+    pub static ref XSD_STRING_ID: StrHash = StrHash::new(vocab::xsd::STRING.as_str());
+}
+
 #[derive(Eq, PartialEq, Debug, Clone, Copy, Hash)]
 #[repr(transparent)]
 // u128，实现了Clone trait可以直接使用==赋值给别人
@@ -46,6 +54,23 @@ impl StrHash {
     pub fn get_hash_u128(&self) -> u128{
         self.hash
     }
+
+    /// Derives the hash used as `id2str` key by a store salted with `salt`.
+    ///
+    /// The salt `0` means no salt and returns the hash unchanged.
+    /// Other salts key a SipHash of the unsalted hash: the salted key is derived from the unsalted hash
+    /// and anyone who knows a string can still compute this unsalted hash.
+    // 以 salt 的高低 64 位作为 SipHash 的密钥，对原始哈希再做一次哈希
+    pub fn salted(self, salt: u128) -> Self {
+        if salt == 0 {
+            return self;
+        }
+        let mut hasher = SipHasher24::new_with_keys((salt >> 64) as u64, salt as u64);
+        hasher.write(&self.hash.to_be_bytes());
+        Self {
+            hash: hasher.finish128().into(),
+        }
+    }
 }
 
 
@@ -55,6 +80,11 @@ pub enum EncodedTerm {
     NamedNode {
         iri_id: StrHash,
     },
+    // 使用已注册的前缀压缩的 IRI，id2str 中只保存后缀
+    PrefixedNamedNode {
+        prefix_id: u8,
+        suffix_id: StrHash,
+    },
 
     NumericalBlankNode {
         id: u128,
@@ -119,6 +149,16 @@ impl PartialEq for EncodedTerm {
             (Self::NamedNode { iri_id: iri_id_a }, Self::NamedNode { iri_id: iri_id_b }) => {
                 iri_id_a == iri_id_b
             }
+            (
+                Self::PrefixedNamedNode {
+                    prefix_id: prefix_id_a,
+                    suffix_id: suffix_id_a,
+                },
+                Self::PrefixedNamedNode {
+                    prefix_id: prefix_id_b,
+                    suffix_id: suffix_id_b,
+                },
+            ) => prefix_id_a == prefix_id_b && suffix_id_a == suffix_id_b,
             (Self::NumericalBlankNode { id: id_a }, Self::NumericalBlankNode { id: id_b }) => {
                 id_a == id_b
             }
@@ -219,10 +259,27 @@ impl PartialEq for EncodedTerm {
 
 impl Eq for EncodedTerm {}
 
+impl EncodedTerm {
+    /// The bytes written for this term in the index keys.
+    ///
+    /// Comparing these bytes gives the order of the terms in the indexes.
+    /// It is not the value order: negative integers sort after the positive ones and hashed strings in hash order.
+    pub fn byte_order_key(&self) -> Vec<u8> {
+        encode_term(self)
+    }
+}
+
 impl Hash for EncodedTerm {
     fn hash<H: Hasher>(&self, state: &mut H) {  // 将该值输入给定的 Hasher(在方法参数里，是类型 H)
         match self {
             Self::NamedNode { iri_id } => iri_id.hash(state),
+            Self::PrefixedNamedNode {
+                prefix_id,
+                suffix_id,
+            } => {
+                prefix_id.hash(state);
+                suffix_id.hash(state);
+            }
             Self::NumericalBlankNode { id } => id.hash(state),
             Self::SmallBlankNode(id) => id.hash(state),
             Self::BigBlankNode { id_id } => id_id.hash(state),
@@ -283,7 +340,7 @@ impl Hash for EncodedTerm {
 // EncodedTerm is_named_node() / is_blank_node() / is_literal()
 impl EncodedTerm {
     pub fn is_named_node(&self) -> bool {
-        matches!(self, Self::NamedNode { .. })
+        matches!(self, Self::NamedNode { .. } | Self::PrefixedNamedNode { .. })
     }
 
     pub fn is_blank_node(&self) -> bool {
@@ -478,6 +535,8 @@ impl From<LiteralRef<'_>> for EncodedTerm {
         let native_encoding = match datatype {
             "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString" => {
                 literal.language().map(|language| {
+                    let language = normalize_language(language);
+                    let language = language.as_ref();
                     if let Ok(value) = SmallString::try_from(value) {
                         if let Ok(language) = SmallString::try_from(language) {
                             Self::SmallSmallLangStringLiteral { value, language }
@@ -564,6 +623,16 @@ impl From<LiteralRef<'_>> for EncodedTerm {
     }
 }
 
+// BCP47 的语言标签不区分大小写，编码时统一为小写，所以 "foo"@EN 与 "foo"@en 是同一个词项
+// 这个转换是有损的：解码得到的总是小写的标签
+fn normalize_language(language: &str) -> Cow<'_, str> {
+    if language.bytes().any(|b| b.is_ascii_uppercase()) {
+        language.to_ascii_lowercase().into()
+    } else {
+        language.into()
+    }
+}
+
 impl From<NamedOrBlankNodeRef<'_>> for EncodedTerm {
     fn from(term: NamedOrBlankNodeRef<'_>) -> Self {
         match term {
@@ -626,6 +695,38 @@ impl EncodedTriple {
             object,
         }
     }
+
+    /// Checks that the terms of this quoted triple are allowed at their positions.
+    ///
+    /// The rules are the same as for the subject, predicate and object of [`EncodedQuad::validate`].
+    pub fn validate(&self) -> Result<(), StorageError> {
+        validate_triple_terms(&self.subject, &self.predicate, &self.object)
+    }
+}
+
+// 主语：IRI、空白节点或者被引用的三元组；谓语：IRI；宾语：除默认图之外的任意 term
+fn validate_triple_terms(
+    subject: &EncodedTerm,
+    predicate: &EncodedTerm,
+    object: &EncodedTerm,
+) -> Result<(), StorageError> {
+    match subject {
+        EncodedTerm::Triple(triple) => triple.validate()?,
+        subject if subject.is_named_node() || subject.is_blank_node() => (),
+        subject => return Err(invalid_position_error(subject, "subject")),
+    }
+    if !predicate.is_named_node() {
+        return Err(invalid_position_error(predicate, "predicate"));
+    }
+    match object {
+        EncodedTerm::Triple(triple) => triple.validate(),
+        EncodedTerm::DefaultGraph => Err(invalid_position_error(object, "object")),
+        _ => Ok(()),
+    }
+}
+
+fn invalid_position_error(term: &EncodedTerm, position: &str) -> StorageError {
+    StorageError::Other(format!("{:?} is not allowed as {}", term, position).into())
 }
 
 impl From<TripleRef<'_>> for EncodedTriple {
@@ -660,6 +761,28 @@ impl EncodedQuad {
             graph_name,
         }
     }
+
+    /// Checks that each term is allowed at its position:
+    /// * the subject is an IRI, a blank node or a quoted triple,
+    /// * the predicate is an IRI,
+    /// * the object is an IRI, a blank node, a literal or a quoted triple,
+    /// * the graph name is an IRI, a blank node or the default graph.
+    ///
+    /// Quoted triples ([`EncodedTerm::Triple`]) follow RDF-star: they are allowed as subject and object,
+    /// never as predicate or graph name, and their own terms are checked with the same rules.
+    ///
+    /// The model types already enforce these rules, so this only fails on quads built directly from [`EncodedTerm`]s.
+    pub fn validate(&self) -> Result<(), StorageError> {
+        validate_triple_terms(&self.subject, &self.predicate, &self.object)?;
+        if self.graph_name.is_named_node()
+            || self.graph_name.is_blank_node()
+            || self.graph_name.is_default_graph()
+        {
+            Ok(())
+        } else {
+            Err(invalid_position_error(&self.graph_name, "graph name"))
+        }
+    }
 }
 
 impl From<QuadRef<'_>> for EncodedQuad {
@@ -673,21 +796,161 @@ impl From<QuadRef<'_>> for EncodedQuad {
     }
 }
 
+/// The IRI prefixes registered in a store with [`Storage::register_prefix`](super::Storage::register_prefix).
+///
+/// It gives each named node a single encoding: an [`EncodedTerm::PrefixedNamedNode`] using the longest registered prefix
+/// the IRI starts with, or an [`EncodedTerm::NamedNode`] if none matches.
+/// The longest prefix is found by walking a trie of the prefixes, in the length of the IRI.
+#[derive(Debug, Clone)]
+pub struct PrefixTable {
+    prefixes: Vec<String>,
+    // 前缀树，nodes[0] 为根节点
+    nodes: Vec<PrefixTrieNode>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PrefixTrieNode {
+    // 按字节排序的子节点
+    children: Vec<(u8, usize)>,
+    // 在这个节点结束的前缀
+    prefix_id: Option<u8>,
+}
+
+impl PrefixTable {
+    /// Builds the table from the prefixes ordered by id.
+    pub fn new(prefixes: Vec<String>) -> Self {
+        let mut nodes = vec![PrefixTrieNode::default()];
+        for (prefix_id, prefix) in prefixes.iter().enumerate() {
+            let mut node = 0;
+            for byte in prefix.bytes() {
+                node = match nodes[node]
+                    .children
+                    .binary_search_by_key(&byte, |(b, _)| *b)
+                {
+                    Ok(i) => nodes[node].children[i].1,
+                    Err(i) => {
+                        nodes.push(PrefixTrieNode::default());
+                        let child = nodes.len() - 1;
+                        nodes[node].children.insert(i, (byte, child));
+                        child
+                    }
+                };
+            }
+            nodes[node].prefix_id = Some(prefix_id.try_into().unwrap());
+        }
+        Self { prefixes, nodes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// The registered prefixes ordered by id.
+    pub fn prefixes(&self) -> &[String] {
+        &self.prefixes
+    }
+
+    pub fn get(&self, prefix_id: u8) -> Option<&str> {
+        self.prefixes.get(usize::from(prefix_id)).map(String::as_str)
+    }
+
+    // 最长前缀匹配，返回前缀编号和前缀的字节长度
+    fn longest_match(&self, iri: &str) -> Option<(u8, usize)> {
+        let mut node = 0;
+        let mut found = None;
+        for (depth, byte) in iri.bytes().enumerate() {
+            node = match self.nodes[node]
+                .children
+                .binary_search_by_key(&byte, |(b, _)| *b)
+            {
+                Ok(i) => self.nodes[node].children[i].1,
+                Err(_) => break,
+            };
+            if let Some(prefix_id) = self.nodes[node].prefix_id {
+                found = Some((prefix_id, depth + 1));
+            }
+        }
+        found
+    }
+
+    pub fn encode_named_node(&self, node: NamedNodeRef<'_>) -> EncodedTerm {
+        let iri = node.as_str();
+        match self.longest_match(iri) {
+            Some((prefix_id, len)) => EncodedTerm::PrefixedNamedNode {
+                prefix_id,
+                suffix_id: StrHash::new(&iri[len..]),
+            },
+            None => node.into(),
+        }
+    }
+
+    /// Encodes a term like [`EncodedTerm::from`], with the named nodes, including the ones in quoted triples, using the prefixes.
+    pub fn encode_term<'a>(&self, term: impl Into<TermRef<'a>>) -> EncodedTerm {
+        let term = term.into();
+        if self.is_empty() {
+            return term.into();
+        }
+        match term {
+            TermRef::NamedNode(node) => self.encode_named_node(node),
+            TermRef::Triple(triple) => self.encode_triple(triple.as_ref()),
+            term => term.into(),
+        }
+    }
+
+    pub fn encode_triple(&self, triple: TripleRef<'_>) -> EncodedTerm {
+        EncodedTerm::Triple(Rc::new(EncodedTriple::new(
+            self.encode_term(triple.subject),
+            self.encode_named_node(triple.predicate),
+            self.encode_term(triple.object),
+        )))
+    }
+
+    pub fn encode_graph_name(&self, graph_name: GraphNameRef<'_>) -> EncodedTerm {
+        match graph_name {
+            GraphNameRef::NamedNode(node) => self.encode_named_node(node),
+            graph_name => graph_name.into(),
+        }
+    }
+
+    pub fn encode_quad(&self, quad: QuadRef<'_>) -> EncodedQuad {
+        EncodedQuad::new(
+            self.encode_term(quad.subject),
+            self.encode_named_node(quad.predicate),
+            self.encode_term(quad.object),
+            self.encode_graph_name(quad.graph_name),
+        )
+    }
+}
+
+impl Default for PrefixTable {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
 
 // EncodedTerm 中的类型有 StrHash 的插入
+// 带前缀的 IRI 只插入后缀，prefixes 需要是编码这个 term 时使用的前缀表
 pub fn insert_term<F: FnMut(&StrHash, &str) -> Result<(), StorageError>>(
     term: TermRef<'_>,
     encoded: &EncodedTerm,
+    prefixes: &PrefixTable,
     insert_str: &mut F,
 ) -> Result<(), StorageError> {
     match term {
-        TermRef::NamedNode(node) => {
-            if let EncodedTerm::NamedNode { iri_id } = encoded {
-                insert_str(iri_id, node.as_str())    // iri_id：StrHash，as_str()方法返回 NamedNode 里的 &str
-            } else {
-                unreachable!("Invalid term encoding {:?} for {}", encoded, term)
-            }
-        }
+        TermRef::NamedNode(node) => match encoded {
+            EncodedTerm::NamedNode { iri_id } => insert_str(iri_id, node.as_str()),   // iri_id：StrHash，as_str()方法返回 NamedNode 里的 &str
+            EncodedTerm::PrefixedNamedNode {
+                prefix_id,
+                suffix_id,
+            } => match prefixes.get(*prefix_id) {
+                Some(prefix) if node.as_str().starts_with(prefix) => {
+                    insert_str(suffix_id, &node.as_str()[prefix.len()..])
+                }
+                _ => unreachable!("Invalid term encoding {:?} for {}", encoded, term),
+            },
+            _ => unreachable!("Invalid term encoding {:?} for {}", encoded, term),
+        },
         TermRef::BlankNode(node) => match encoded {
             EncodedTerm::BigBlankNode { id_id } => insert_str(id_id, node.as_str()),
             EncodedTerm::SmallBlankNode(..) | EncodedTerm::NumericalBlankNode { .. } => Ok(()),
@@ -700,7 +963,7 @@ pub fn insert_term<F: FnMut(&StrHash, &str) -> Result<(), StorageError>>(
             }
             EncodedTerm::SmallBigLangStringLiteral { language_id, .. } => {
                 if let Some(language) = literal.language() {
-                    insert_str(language_id, language)
+                    insert_str(language_id, &normalize_language(language))
                 } else {
                     unreachable!("Invalid term encoding {:?} for {}", encoded, term)
                 }
@@ -711,7 +974,7 @@ pub fn insert_term<F: FnMut(&StrHash, &str) -> Result<(), StorageError>>(
             } => {
                 insert_str(value_id, literal.value())?;
                 if let Some(language) = literal.language() {
-                    insert_str(language_id, language)
+                    insert_str(language_id, &normalize_language(language))
                 } else {
                     unreachable!("Invalid term encoding {:?} for {}", encoded, term)
                 }
@@ -748,13 +1011,19 @@ pub fn insert_term<F: FnMut(&StrHash, &str) -> Result<(), StorageError>>(
         },
         TermRef::Triple(triple) => {   // 如果是三元组的话，把主谓宾再调用该方法一遍
             if let EncodedTerm::Triple(encoded) = encoded {
-                insert_term(triple.subject.as_ref().into(), &encoded.subject, insert_str)?;
+                insert_term(
+                    triple.subject.as_ref().into(),
+                    &encoded.subject,
+                    prefixes,
+                    insert_str,
+                )?;
                 insert_term(
                     triple.predicate.as_ref().into(),
                     &encoded.predicate,
+                    prefixes,
                     insert_str,
                 )?;
-                insert_term(triple.object.as_ref(), &encoded.object, insert_str)
+                insert_term(triple.object.as_ref(), &encoded.object, prefixes, insert_str)
             } else {
                 unreachable!("Invalid term encoding {:?} for {}", encoded, term)
             }
@@ -762,6 +1031,39 @@ pub fn insert_term<F: FnMut(&StrHash, &str) -> Result<(), StorageError>>(
     }
 }
 
+// 遍历 EncodedTerm 在 id2str 中引用的所有 StrHash，与 insert_term 插入的 key 一一对应
+pub fn for_each_str_hash(encoded: &EncodedTerm, callback: &mut impl FnMut(StrHash)) {
+    match encoded {
+        EncodedTerm::NamedNode { iri_id } => callback(*iri_id),
+        EncodedTerm::PrefixedNamedNode { suffix_id, .. } => callback(*suffix_id),
+        EncodedTerm::BigBlankNode { id_id } => callback(*id_id),
+        EncodedTerm::BigStringLiteral { value_id }
+        | EncodedTerm::BigSmallLangStringLiteral { value_id, .. } => callback(*value_id),
+        EncodedTerm::SmallBigLangStringLiteral { language_id, .. } => callback(*language_id),
+        EncodedTerm::BigBigLangStringLiteral {
+            value_id,
+            language_id,
+        } => {
+            callback(*value_id);
+            callback(*language_id);
+        }
+        EncodedTerm::SmallTypedLiteral { datatype_id, .. } => callback(*datatype_id),
+        EncodedTerm::BigTypedLiteral {
+            value_id,
+            datatype_id,
+        } => {
+            callback(*value_id);
+            callback(*datatype_id);
+        }
+        EncodedTerm::Triple(triple) => {
+            for_each_str_hash(&triple.subject, callback);
+            for_each_str_hash(&triple.predicate, callback);
+            for_each_str_hash(&triple.object, callback);
+        }
+        _ => (),
+    }
+}
+
 // TODO：还没理解
 pub fn parse_boolean_str(value: &str) -> Option<EncodedTerm> {
     match value {
@@ -841,6 +1143,11 @@ pub trait StrLookup {
     fn get_str(&self, key: &StrHash) -> Result<Option<String>, StorageError>;
 
     fn contains_str(&self, key: &StrHash) -> Result<bool, StorageError>;
+
+    // 根据前缀编号获得注册的 IRI 前缀，没有前缀表的实现返回 None
+    fn get_prefix(&self, _prefix_id: u8) -> Result<Option<String>, StorageError> {
+        Ok(None)
+    }
 }
 
 // Decoder 继承 StrLoop
@@ -939,6 +1246,16 @@ impl<S: StrLookup> Decoder for S {
             EncodedTerm::NamedNode { iri_id } => {  // iri_id: StrHash
                 Ok(NamedNode::new_unchecked(get_required_str(self, iri_id)?).into())
             }
+            EncodedTerm::PrefixedNamedNode {
+                prefix_id,
+                suffix_id,
+            } => {
+                let mut iri = self.get_prefix(*prefix_id)?.ok_or_else(|| {
+                    CorruptionError::msg(format!("The IRI prefix {} is not registered", prefix_id))
+                })?;
+                iri.push_str(&get_required_str(self, suffix_id)?);
+                Ok(NamedNode::new_unchecked(iri).into())
+            }
             EncodedTerm::NumericalBlankNode { id } => Ok(BlankNode::new_from_unique_id(*id).into()),   // 创建匿名的blanknode
             EncodedTerm::SmallBlankNode(id) => Ok(BlankNode::new_unchecked(id.as_str()).into()),
             EncodedTerm::BigBlankNode { id_id } => {
@@ -1020,4 +1337,156 @@ fn get_required_str<L: StrLookup>(lookup: &L, id: &StrHash) -> Result<String, St
             id
         ))
     })?)
-}
\ No newline at end of file
+}
+
+/// Returns the hash of the whole IRI of a named node, whichever way it is encoded, or `None` if the term is not a named node.
+///
+/// The interval trees identify their nodes by this hash, while the stores with registered prefixes encode the matching IRIs
+/// as [`EncodedTerm::PrefixedNamedNode`], whose prefix and suffix are read with `lookup`.
+pub fn iri_hash<L: StrLookup>(term: &EncodedTerm, lookup: &L) -> Result<Option<StrHash>, StorageError> {
+    match term {
+        EncodedTerm::NamedNode { iri_id } => Ok(Some(*iri_id)),
+        EncodedTerm::PrefixedNamedNode {
+            prefix_id,
+            suffix_id,
+        } => {
+            let mut iri = lookup.get_prefix(*prefix_id)?.ok_or_else(|| {
+                CorruptionError::msg(format!("The IRI prefix {} is not registered", prefix_id))
+            })?;
+            iri.push_str(&get_required_str(lookup, suffix_id)?);
+            Ok(Some(StrHash::new(&iri)))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_big_literal_equality() {
+        let value = "a string literal long enough not to be inlined";
+        let a = EncodedTerm::from(LiteralRef::new_simple_literal(value));
+        let b = EncodedTerm::from(Literal::new_simple_literal(value.to_owned()).as_ref());
+        assert!(matches!(a, EncodedTerm::BigStringLiteral { .. }));
+        // PartialEq 只比较 StrHash，不需要 StrLookup，不会读取 id2str
+        assert_eq!(a, b);
+        assert_ne!(a, EncodedTerm::from(LiteralRef::new_simple_literal(
+            "another string literal long enough not to be inlined"
+        )));
+        assert_ne!(a, EncodedTerm::NamedNode { iri_id: StrHash::new(value) });
+
+        let nan = EncodedTerm::from(Literal::from(f64::NAN).as_ref());
+        assert_eq!(nan, nan);
+        assert_ne!(EncodedTerm::from(Literal::from(0.).as_ref()), EncodedTerm::from(Literal::from(-0.).as_ref()));
+    }
+
+    #[test]
+    fn test_language_tag_is_normalized() {
+        for value in ["foo", "a value too long to be inlined"] {
+            for language in ["en", "en-us-x-a-very-long-private-use-tag"] {
+                let upper = Literal::new_language_tagged_literal_unchecked(value, language.to_ascii_uppercase());
+                let lower = Literal::new_language_tagged_literal_unchecked(value, language);
+                assert_eq!(EncodedTerm::from(upper.as_ref()), EncodedTerm::from(lower.as_ref()));
+            }
+        }
+        assert_ne!(
+            EncodedTerm::from(LiteralRef::new_language_tagged_literal_unchecked("foo", "en")),
+            EncodedTerm::from(LiteralRef::new_language_tagged_literal_unchecked("foo", "fr"))
+        );
+    }
+
+    #[test]
+    fn test_normalized_language_is_inserted() {
+        let literal = Literal::new_language_tagged_literal_unchecked("foo", "EN-US-X-A-VERY-LONG-PRIVATE-USE-TAG");
+        let encoded = EncodedTerm::from(literal.as_ref());
+        let mut inserted = Vec::new();
+        insert_term(
+            literal.as_ref().into(),
+            &encoded,
+            &PrefixTable::default(),
+            &mut |_, value| {
+                inserted.push(value.to_owned());
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(inserted, vec!["en-us-x-a-very-long-private-use-tag".to_owned()]);
+    }
+
+    #[test]
+    fn test_validate_positions() {
+        let iri = EncodedTerm::from(NamedNodeRef::new_unchecked("http://example.com/s"));
+        let literal = EncodedTerm::from(LiteralRef::new_simple_literal("foo"));
+        let valid = EncodedTriple::new(iri.clone(), iri.clone(), literal.clone());
+        let quoted = EncodedTerm::Triple(Rc::new(valid.clone()));
+        assert!(valid.validate().is_ok());
+        assert!(EncodedQuad::new(quoted.clone(), iri.clone(), quoted.clone(), EncodedTerm::DefaultGraph)
+            .validate()
+            .is_ok());
+
+        assert!(EncodedQuad::new(literal.clone(), iri.clone(), iri.clone(), EncodedTerm::DefaultGraph)
+            .validate()
+            .is_err());
+        assert!(EncodedQuad::new(iri.clone(), quoted.clone(), iri.clone(), EncodedTerm::DefaultGraph)
+            .validate()
+            .is_err());
+        assert!(EncodedQuad::new(iri.clone(), iri.clone(), EncodedTerm::DefaultGraph, EncodedTerm::DefaultGraph)
+            .validate()
+            .is_err());
+        assert!(EncodedQuad::new(iri.clone(), iri.clone(), iri.clone(), literal.clone())
+            .validate()
+            .is_err());
+        // 被引用的三元组中的主语同样不能是字面量
+        let invalid_quoted = EncodedTerm::Triple(Rc::new(EncodedTriple::new(literal, iri.clone(), iri.clone())));
+        assert!(EncodedQuad::new(iri.clone(), iri, invalid_quoted, EncodedTerm::DefaultGraph)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_prefix_table_longest_match() {
+        let prefixes = PrefixTable::new(vec![
+            "http://example.com/".to_owned(),
+            "http://example.com/ns/".to_owned(),
+            "http://example.org/".to_owned(),
+        ]);
+        let encode = |iri: &str| prefixes.encode_named_node(NamedNodeRef::new_unchecked(iri));
+        assert_eq!(
+            encode("http://example.com/ns/s"),
+            EncodedTerm::PrefixedNamedNode {
+                prefix_id: 1,
+                suffix_id: StrHash::new("s")
+            }
+        );
+        assert_eq!(
+            encode("http://example.com/n"),
+            EncodedTerm::PrefixedNamedNode {
+                prefix_id: 0,
+                suffix_id: StrHash::new("n")
+            }
+        );
+        assert_eq!(
+            encode("http://example.org/"),
+            EncodedTerm::PrefixedNamedNode {
+                prefix_id: 2,
+                suffix_id: StrHash::new("")
+            }
+        );
+        assert_eq!(
+            encode("http://example.net/s"),
+            EncodedTerm::from(NamedNodeRef::new_unchecked("http://example.net/s"))
+        );
+
+        // 插入的是前缀之后的后缀
+        let node = NamedNodeRef::new_unchecked("http://example.com/ns/s");
+        let mut inserted = Vec::new();
+        insert_term(node.into(), &encode(node.as_str()), &prefixes, &mut |_, value| {
+            inserted.push(value.to_owned());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(inserted, vec!["s".to_owned()]);
+    }
+}
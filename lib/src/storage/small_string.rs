@@ -15,6 +15,9 @@ pub struct SmallString {
 }
 
 impl SmallString {
+    /// The maximal number of UTF-8 bytes stored inline, the last byte holds the length.
+    pub const CAPACITY: usize = 15;
+
     #[inline]
     pub const fn new() -> Self {
         Self { inner: [0; 16] }
@@ -45,6 +48,26 @@ impl SmallString {
         self.len() == 0
     }
 
+    /// The number of bytes that could still be pushed.
+    #[inline]
+    pub fn remaining_capacity(&self) -> usize {
+        Self::CAPACITY - self.len()
+    }
+
+    /// Appends a char if its UTF-8 encoding fits in the remaining capacity, the string is left unchanged otherwise.
+    #[inline]
+    pub fn try_push(&mut self, c: char) -> Result<(), CapacityError> {
+        let len = self.len();
+        let char_len = c.len_utf8();
+        if char_len > self.remaining_capacity() {
+            return Err(CapacityError(c));
+        }
+        let new_len = u8::try_from(len + char_len).map_err(|_| CapacityError(c))?;
+        c.encode_utf8(&mut self.inner[len..len + char_len]);
+        self.inner[15] = new_len;
+        Ok(())
+    }
+
     #[inline]
     #[allow(unsafe_code)]
     pub fn as_str(&self) -> &str {
@@ -151,7 +174,7 @@ impl FromStr for SmallString {
 
     #[inline]
     fn from_str(value: &str) -> Result<Self, BadSmallStringError> {
-        if value.len() <= 15 {
+        if value.len() <= Self::CAPACITY {
             let mut inner = [0; 16];
             inner[..value.len()].copy_from_slice(value.as_bytes());  // copy_from_slice: 使用 memcpy 将所有元素从 src 复制到 self。
             inner[15] = value.len().try_into()
@@ -202,3 +225,62 @@ impl Error for BadSmallStringError {
         }
     }
 }
+
+/// Error returned by [`SmallString::try_push`] when the char does not fit.
+#[derive(Debug, Clone, Copy)]
+pub struct CapacityError(char);
+
+impl CapacityError {
+    /// The char that could not be pushed.
+    #[inline]
+    pub fn element(self) -> char {
+        self.0
+    }
+}
+
+impl fmt::Display for CapacityError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "small strings could only contain at most {} bytes, {:?} does not fit",
+            SmallString::CAPACITY,
+            self.0
+        )
+    }
+}
+
+impl Error for CapacityError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_push_ascii_up_to_capacity() {
+        let mut string = SmallString::new();
+        for i in 0..SmallString::CAPACITY {
+            assert_eq!(string.remaining_capacity(), SmallString::CAPACITY - i);
+            string.try_push('a').unwrap();
+        }
+        assert_eq!(string.as_str(), "a".repeat(SmallString::CAPACITY));
+        assert_eq!(string.remaining_capacity(), 0);
+        assert_eq!(string.try_push('b').unwrap_err().element(), 'b');
+        assert_eq!(string.as_str(), "a".repeat(SmallString::CAPACITY));
+    }
+
+    #[test]
+    fn test_try_push_multi_byte_overflow() {
+        let mut string = SmallString::from_str(&"a".repeat(SmallString::CAPACITY - 2)).unwrap();
+        // 3 个字节的字符放不下，且不能写入任何部分字节
+        assert!(string.try_push('€').is_err());
+        assert_eq!(string.len(), SmallString::CAPACITY - 2);
+        assert_eq!(string.to_be_bytes()[SmallString::CAPACITY - 2], 0);
+        string.try_push('é').unwrap();
+        assert_eq!(string.remaining_capacity(), 0);
+        assert_eq!(
+            SmallString::from_be_bytes(string.to_be_bytes()).unwrap().as_str(),
+            format!("{}é", "a".repeat(SmallString::CAPACITY - 2))
+        );
+    }
+}
@@ -32,7 +32,7 @@ use crate::sparql::{
     evaluate_query, evaluate_update, EvaluationError, Query, QueryOptions, QueryResults, Update,
     UpdateOptions,
 };
-use crate::storage::numeric_encoder::{Decoder, EncodedQuad, EncodedTerm, StrHash};
+use crate::storage::numeric_encoder::{Decoder, StrHash};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage::StorageBulkLoader;
 use crate::storage::{
@@ -42,7 +42,6 @@ pub use crate::storage::{CorruptionError, LoaderError, SerializerError, StorageE
 use std::error::Error;
 use std::io::{self, BufRead, Write, Read};
 use std::ops::MulAssign;
-#[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
 use std::{fmt, str};
 
@@ -195,10 +194,10 @@ impl Store {
 
         QuadIter {
             iter: reader.quads_for_pattern(
-                subject.map(EncodedTerm::from).as_ref(),
-                predicate.map(EncodedTerm::from).as_ref(),
-                object.map(EncodedTerm::from).as_ref(),
-                graph_name.map(EncodedTerm::from).as_ref(),
+                subject.map(|s| reader.encode_term(s)).as_ref(),
+                predicate.map(|p| reader.encode_named_node(p)).as_ref(),
+                object.map(|o| reader.encode_term(o)).as_ref(),
+                graph_name.map(|g| reader.encode_graph_name(g)).as_ref(),
             ),
             reader,
         }
@@ -245,8 +244,7 @@ impl Store {
     /// # Result::<_, Box<dyn std::error::Error>>::Ok(())
     /// ```
     pub fn contains<'a>(&self, quad: impl Into<QuadRef<'a>>) -> Result<bool, StorageError> {
-        let quad = EncodedQuad::from(quad.into());
-        self.storage.snapshot().contains(&quad)
+        self.storage.snapshot().contains_quad(quad.into())
     }
 
     /// Returns the number of quads in the store.
@@ -624,8 +622,9 @@ impl Store {
         &self,
         graph_name: impl Into<NamedOrBlankNodeRef<'a>>,
     ) -> Result<bool, StorageError> {
-        let graph_name = EncodedTerm::from(graph_name.into());
-        self.storage.snapshot().contains_named_graph(&graph_name)
+        self.storage
+            .snapshot()
+            .contains_named_graph_ref(graph_name.into())
     }
 
     /// Inserts a graph into this store.
@@ -730,7 +729,8 @@ impl Store {
     /// Flushes all buffers and ensures that all writes are saved on disk.
     ///
     /// Flushes are automatically done using background threads but might lag a little bit.
-    #[cfg(not(target_arch = "wasm32"))]
+    ///
+    /// On `wasm32` the storage is in memory and there is nothing to flush.
     pub fn flush(&self) -> Result<(), StorageError> {
         self.storage.flush()
     }
@@ -740,7 +740,8 @@ impl Store {
     /// Useful to call after a batch upload or another similar operation.
     ///
     /// Warning: Can take hours on huge databases.
-    #[cfg(not(target_arch = "wasm32"))]
+    ///
+    /// On `wasm32` the storage is in memory and this is a no-op.
     pub fn optimize(&self) -> Result<(), StorageError> {
         self.storage.compact()
     }
@@ -761,7 +762,8 @@ impl Store {
     /// This allows cheap regular backups.
     ///
     /// If you want to move your data to another RDF storage system, you should have a look at the [`Store::dump_dataset`] function instead.
-    #[cfg(not(target_arch = "wasm32"))]
+    ///
+    /// Warning: Backups are not supported on `wasm32` and an error is always returned there.
     pub fn backup(&self, target_directory: impl AsRef<Path>) -> Result<(), StorageError> {
         self.storage.backup(target_directory.as_ref())
     }
@@ -914,10 +916,10 @@ impl<'a> Transaction<'a> {
         let reader = self.writer.reader();
         QuadIter {
             iter: reader.quads_for_pattern(
-                subject.map(EncodedTerm::from).as_ref(),
-                predicate.map(EncodedTerm::from).as_ref(),
-                object.map(EncodedTerm::from).as_ref(),
-                graph_name.map(EncodedTerm::from).as_ref(),
+                subject.map(|s| reader.encode_term(s)).as_ref(),
+                predicate.map(|p| reader.encode_named_node(p)).as_ref(),
+                object.map(|o| reader.encode_term(o)).as_ref(),
+                graph_name.map(|g| reader.encode_graph_name(g)).as_ref(),
             ),
             reader,
         }
@@ -930,8 +932,7 @@ impl<'a> Transaction<'a> {
 
     /// Checks if this store contains a given quad.
     pub fn contains<'b>(&self, quad: impl Into<QuadRef<'b>>) -> Result<bool, StorageError> {
-        let quad = EncodedQuad::from(quad.into());
-        self.writer.reader().contains(&quad)
+        self.writer.reader().contains_quad(quad.into())
     }
 
     /// Returns the number of quads in the store.
@@ -1140,7 +1141,7 @@ impl<'a> Transaction<'a> {
     ) -> Result<bool, StorageError> {
         self.writer
             .reader()
-            .contains_named_graph(&EncodedTerm::from(graph_name.into()))
+            .contains_named_graph_ref(graph_name.into())
     }
 
     /// Inserts a graph into this store.
@@ -1352,6 +1353,15 @@ impl BulkLoader {
         self
     }
 
+    /// Sets the number of quads loaded by each thread at once.
+    ///
+    /// It takes precedence over the size computed from [`BulkLoader::set_max_memory_size_in_megabytes`]
+    /// and the available memory, and is clamped to at most 100M quads.
+    pub fn set_batch_size(mut self, batch_size: usize) -> Self {
+        self.storage = self.storage.set_batch_size(batch_size);
+        self
+    }
+
     /// Adds a `callback` evaluated from time to time with the number of loaded triples.
     pub fn on_progress(mut self, callback: impl Fn(u64) + 'static) -> Self {
         self.storage = self.storage.on_progress(callback);
@@ -1497,7 +1507,7 @@ impl BulkLoader {
         format: GraphFormat,
         to_graph_name: impl Into<GraphNameRef<'a>>,
         base_iri: Option<&str>,
-        tree_path: &'static str
+        tree_path: &str
     ) -> Result<(), LoaderError> {
         let mut parser = GraphParser::from_format(format);
         
@@ -1534,10 +1544,8 @@ impl BulkLoader {
         format: GraphFormat,
         to_graph_name: impl Into<GraphNameRef<'a>>,
         base_iri: Option<&str>,
-        tree_path: &'static str
+        tree_path: &str
     ) -> Result<(), LoaderError> {
-        // let trees = self.construct_tree(path).unwrap();   // 传入数据集获得 tree
-
         let mut parser = GraphParser::from_format(format);
         
         if let Some(base_iri) = base_iri {
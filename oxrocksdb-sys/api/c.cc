@@ -16,6 +16,30 @@ static bool SaveStatus(rocksdb_status_t* target, const Status source) {
     return !source.ok();
 }
 
+// Missing keys get a null value and an ok status
+static void MultiGetPinned(DB* db, const rocksdb_readoptions_t* options,
+                           rocksdb_column_family_handle_t* column_family, size_t num_keys,
+                           const char* const* keys_list, const size_t* keys_list_sizes,
+                           rocksdb_pinnableslice_t** values, rocksdb_status_t* statuses) {
+    std::vector<Slice> keys(num_keys);
+    for (size_t i = 0; i < num_keys; ++i) {
+        keys[i] = Slice(keys_list[i], keys_list_sizes[i]);
+    }
+    std::vector<PinnableSlice> slices(num_keys);
+    std::vector<Status> results(num_keys);
+    db->MultiGet(options->rep, column_family->rep, num_keys, keys.data(), slices.data(),
+                 results.data());
+    for (size_t i = 0; i < num_keys; ++i) {
+        values[i] = nullptr;
+        if (results[i].ok()) {
+            values[i] = new rocksdb_pinnableslice_t;
+            values[i]->rep = std::move(slices[i]);
+        } else if (!results[i].IsNotFound()) {
+            SaveStatus(&statuses[i], results[i]);
+        }
+    }
+}
+
 extern "C" {
 
 
@@ -51,6 +75,64 @@ rocksdb_transactiondb_t* rocksdb_transactiondb_open_column_families_with_status(
     return result;
 }
 
+rocksdb_t* rocksdb_open_for_read_only_column_families_with_status(
+        const rocksdb_options_t* db_options, const char* name,
+        int num_column_families, const char* const* column_family_names,
+        const rocksdb_options_t* const* column_family_options,
+        rocksdb_column_family_handle_t** column_family_handles,
+        unsigned char error_if_wal_file_exists, rocksdb_status_t* statusptr) {
+    std::vector<ColumnFamilyDescriptor> column_families;
+    for (int i = 0; i < num_column_families; i++) {
+        column_families.push_back(ColumnFamilyDescriptor(
+                std::string(column_family_names[i]),
+                ColumnFamilyOptions(column_family_options[i]->rep)));
+    }
+
+    DB* db;
+    std::vector<ColumnFamilyHandle*> handles;
+    if (SaveStatus(statusptr, DB::OpenForReadOnly(DBOptions(db_options->rep),
+                                                 std::string(name), column_families,
+                                                 &handles, &db, error_if_wal_file_exists))) {
+        return nullptr;
+    }
+
+    for (size_t i = 0; i < handles.size(); i++) {
+        rocksdb_column_family_handle_t* c_handle =
+                new rocksdb_column_family_handle_t;
+        c_handle->rep = handles[i];
+        column_family_handles[i] = c_handle;
+    }
+    rocksdb_t* result = new rocksdb_t;
+    result->rep = db;
+    return result;
+}
+
+rocksdb_pinnableslice_t* rocksdb_get_pinned_cf_with_status(
+        rocksdb_t* db, const rocksdb_readoptions_t* options,
+        rocksdb_column_family_handle_t* column_family, const char* key,
+        size_t keylen, rocksdb_status_t* statusptr) {
+    rocksdb_pinnableslice_t* v = new rocksdb_pinnableslice_t;
+    Status s = db->rep->Get(options->rep, column_family->rep, Slice(key, keylen),
+                            &v->rep);
+    if (!s.ok()) {
+        delete v;
+        if (!s.IsNotFound()) {
+            SaveStatus(statusptr, s);
+        }
+        return nullptr;
+    }
+    return v;
+}
+
+void rocksdb_multi_get_pinned_cf_with_status(
+        rocksdb_t* db, const rocksdb_readoptions_t* options,
+        rocksdb_column_family_handle_t* column_family, size_t num_keys,
+        const char* const* keys_list, const size_t* keys_list_sizes,
+        rocksdb_pinnableslice_t** values, rocksdb_status_t* statuses) {
+    MultiGetPinned(db->rep, options, column_family, num_keys, keys_list, keys_list_sizes,
+                   values, statuses);
+}
+
 rocksdb_pinnableslice_t* rocksdb_transactiondb_get_pinned_cf_with_status(
         rocksdb_transactiondb_t* db, const rocksdb_readoptions_t* options,
         rocksdb_column_family_handle_t* column_family, const char* key,
@@ -68,6 +150,15 @@ rocksdb_pinnableslice_t* rocksdb_transactiondb_get_pinned_cf_with_status(
     return v;
 }
 
+void rocksdb_transactiondb_multi_get_pinned_cf_with_status(
+        rocksdb_transactiondb_t* db, const rocksdb_readoptions_t* options,
+        rocksdb_column_family_handle_t* column_family, size_t num_keys,
+        const char* const* keys_list, const size_t* keys_list_sizes,
+        rocksdb_pinnableslice_t** values, rocksdb_status_t* statuses) {
+    MultiGetPinned(db->rep, options, column_family, num_keys, keys_list, keys_list_sizes,
+                   values, statuses);
+}
+
 void rocksdb_transactiondb_put_cf_with_status(rocksdb_transactiondb_t* txn_db,
                                   const rocksdb_writeoptions_t* options,
                                   rocksdb_column_family_handle_t* column_family,
@@ -100,6 +191,15 @@ void rocksdb_transactiondb_compact_range_cf_opt_with_status(rocksdb_transactiond
             (limit_key ? (b = Slice(limit_key, limit_key_len), &b) : nullptr)));
 }
 
+void rocksdb_transactiondb_write_without_concurrency_control_with_status(
+        rocksdb_transactiondb_t* db, const rocksdb_writeoptions_t* options,
+        rocksdb_writebatch_t* batch, rocksdb_status_t* statusptr) {
+    // Required to write range deletions, see TransactionDB::DeleteRange
+    ROCKSDB_NAMESPACE::TransactionDBWriteOptimizations optimizations;
+    optimizations.skip_concurrency_control = true;
+    SaveStatus(statusptr, db->rep->Write(options->rep, optimizations, &batch->rep));
+}
+
 void rocksdb_transactiondb_ingest_external_files_with_status(
         rocksdb_transactiondb_t* db, const rocksdb_ingestexternalfilearg_t* list,
         const size_t list_len, rocksdb_status_t* statusptr) {
@@ -129,6 +229,47 @@ void rocksdb_transactiondb_create_checkpoint_with_status(
     delete checkpoint;
 }
 
+uint32_t rocksdb_transactiondb_create_new_backup_with_status(
+        rocksdb_transactiondb_t* db, const char* backup_dir,
+        rocksdb_status_t* statusptr) {
+    // Table files are shared between the backups of the same directory: only the new ones are copied
+    BackupEngine* backup_engine;
+    Status s = BackupEngine::Open(db->rep->GetEnv(), BackupEngineOptions(std::string(backup_dir)), &backup_engine);
+    if (!s.ok()) {
+        SaveStatus(statusptr, s);
+        return 0;
+    }
+    ROCKSDB_NAMESPACE::CreateBackupOptions options;
+    options.flush_before_backup = true;
+    BackupID backup_id = 0;
+    SaveStatus(statusptr, backup_engine->CreateNewBackup(options, db->rep, &backup_id));
+    delete backup_engine;
+    return backup_id;
+}
+
+void rocksdb_restore_db_from_backup_with_status(
+        const char* backup_dir, const char* db_dir, uint32_t backup_id,
+        rocksdb_status_t* statusptr) {
+    ROCKSDB_NAMESPACE::BackupEngineReadOnly* backup_engine;
+    Status s = ROCKSDB_NAMESPACE::BackupEngineReadOnly::Open(Env::Default(), BackupEngineOptions(std::string(backup_dir)), &backup_engine);
+    if (!s.ok()) {
+        SaveStatus(statusptr, s);
+        return;
+    }
+    SaveStatus(statusptr, backup_engine->RestoreDBFromBackup(backup_id, std::string(db_dir), std::string(db_dir)));
+    delete backup_engine;
+}
+
+int rocksdb_transactiondb_property_int_cf(
+        rocksdb_transactiondb_t* db, rocksdb_column_family_handle_t* column_family,
+        const char* propname, uint64_t* out_val) {
+    if (db->rep->GetIntProperty(column_family->rep, Slice(propname), out_val)) {
+        return 0;
+    } else {
+        return -1;
+    }
+}
+
 
 void rocksdb_transaction_commit_with_status(rocksdb_transaction_t* txn, rocksdb_status_t* statusptr) {
     SaveStatus(statusptr, txn->rep->Commit());
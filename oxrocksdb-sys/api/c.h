@@ -72,12 +72,36 @@ extern ROCKSDB_LIBRARY_API rocksdb_transactiondb_t* rocksdb_transactiondb_open_c
         const rocksdb_options_t* const* column_family_options,
         rocksdb_column_family_handle_t** column_family_handles, rocksdb_status_t* statusptr);
 
+extern ROCKSDB_LIBRARY_API rocksdb_t* rocksdb_open_for_read_only_column_families_with_status(
+        const rocksdb_options_t* options, const char* name,
+        int num_column_families, const char* const* column_family_names,
+        const rocksdb_options_t* const* column_family_options,
+        rocksdb_column_family_handle_t** column_family_handles,
+        unsigned char error_if_wal_file_exists, rocksdb_status_t* statusptr);
+
+extern ROCKSDB_LIBRARY_API rocksdb_pinnableslice_t* rocksdb_get_pinned_cf_with_status(
+        rocksdb_t* db, const rocksdb_readoptions_t* options,
+        rocksdb_column_family_handle_t* column_family, const char* key,
+        size_t keylen, rocksdb_status_t* statusptr);
+
+
+extern ROCKSDB_LIBRARY_API void rocksdb_multi_get_pinned_cf_with_status(
+        rocksdb_t* db, const rocksdb_readoptions_t* options,
+        rocksdb_column_family_handle_t* column_family, size_t num_keys,
+        const char* const* keys_list, const size_t* keys_list_sizes,
+        rocksdb_pinnableslice_t** values, rocksdb_status_t* statuses);
 
 extern ROCKSDB_LIBRARY_API rocksdb_pinnableslice_t* rocksdb_transactiondb_get_pinned_cf_with_status(
         rocksdb_transactiondb_t* db, const rocksdb_readoptions_t* options,
         rocksdb_column_family_handle_t* column_family, const char* key,
         size_t keylen, rocksdb_status_t* statusptr);
 
+extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_multi_get_pinned_cf_with_status(
+        rocksdb_transactiondb_t* db, const rocksdb_readoptions_t* options,
+        rocksdb_column_family_handle_t* column_family, size_t num_keys,
+        const char* const* keys_list, const size_t* keys_list_sizes,
+        rocksdb_pinnableslice_t** values, rocksdb_status_t* statuses);
+
 extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_put_cf_with_status(
         rocksdb_transactiondb_t* txn_db, const rocksdb_writeoptions_t* options,
         rocksdb_column_family_handle_t* column_family, const char* key,
@@ -92,6 +116,10 @@ extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_compact_range_cf_opt_with_
         rocksdb_compactoptions_t* opt, const char* start_key, size_t start_key_len,
         const char* limit_key, size_t limit_key_len, rocksdb_status_t* statusptr);
 
+extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_write_without_concurrency_control_with_status(
+        rocksdb_transactiondb_t* db, const rocksdb_writeoptions_t* options,
+        rocksdb_writebatch_t* batch, rocksdb_status_t* statusptr);
+
 extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_ingest_external_files_with_status(
         rocksdb_transactiondb_t* db, const rocksdb_ingestexternalfilearg_t* list,
         const size_t list_len, rocksdb_status_t* statusptr);
@@ -99,6 +127,16 @@ extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_ingest_external_files_with
 extern ROCKSDB_LIBRARY_API void rocksdb_transactiondb_create_checkpoint_with_status(
         rocksdb_transactiondb_t* db, const char* checkpoint_dir, rocksdb_status_t* statusptr);
 
+extern ROCKSDB_LIBRARY_API uint32_t rocksdb_transactiondb_create_new_backup_with_status(
+        rocksdb_transactiondb_t* db, const char* backup_dir, rocksdb_status_t* statusptr);
+
+extern ROCKSDB_LIBRARY_API void rocksdb_restore_db_from_backup_with_status(
+        const char* backup_dir, const char* db_dir, uint32_t backup_id, rocksdb_status_t* statusptr);
+
+extern ROCKSDB_LIBRARY_API int rocksdb_transactiondb_property_int_cf(
+        rocksdb_transactiondb_t* db, rocksdb_column_family_handle_t* column_family,
+        const char* propname, uint64_t* out_val);
+
 
 extern ROCKSDB_LIBRARY_API void rocksdb_transaction_commit_with_status(
         rocksdb_transaction_t* txn, rocksdb_status_t* statusptr);